@@ -1,4 +1,4 @@
-use rusqlite::{Connection, OpenFlags, Result, params};
+use rusqlite::{Connection, OpenFlags, Result, ffi, params};
 
 use crate::helpers::{TestDir, TestRunner};
 
@@ -124,6 +124,82 @@ pub(crate) fn run_evfs_vfs_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
         t.fail("ciphertext check", &"plaintext row data found in raw file");
     }
 
+    t.section("EVFS Reserve Format Version");
+
+    // Page 2 is the first encrypted page; its version byte follows the
+    // 16-byte tag, 6-byte marker and 12-byte nonce in the reserve.
+    let page_size = u16::from_be_bytes([raw[16], raw[17]]) as usize;
+    let reserve = raw[20] as usize;
+    let version_at = 2 * page_size - reserve + 16 + 6 + 12;
+    t.assert_eq("page 2 written as format v1", &raw[version_at], &1u8);
+    let future_db = tmp.path("future.db");
+    let mut future = raw.clone();
+    future[version_at] = 2;
+    std::fs::write(&future_db, &future).expect("write future-format DB");
+    std::fs::copy(
+        tmp.path("test.evfs-keyring"),
+        tmp.path("future.evfs-keyring"),
+    )
+    .expect("copy keyring");
+    match Connection::open_with_flags_and_vfs(&future_db, OpenFlags::SQLITE_OPEN_READ_WRITE, "evfs")
+        .and_then(|c| c.query_row("SELECT COUNT(*) FROM widgets", [], |r| r.get::<_, i64>(0)))
+    {
+        Ok(_) => t.fail("newer format version refused", &"opened without error"),
+        Err(e) => t.assert_eq(
+            "newer format version refused on open",
+            &e.sqlite_error_code(),
+            &Some(rusqlite::ErrorCode::CannotOpen),
+        ),
+    }
+
+    // A page with its trailer wiped is refused, not passed through as plaintext.
+    let markerless_db = tmp.path("markerless.db");
+    let mut markerless = raw.clone();
+    markerless[2 * page_size - reserve..2 * page_size].fill(0);
+    std::fs::write(&markerless_db, &markerless).expect("write markerless DB");
+    std::fs::copy(
+        tmp.path("test.evfs-keyring"),
+        tmp.path("markerless.evfs-keyring"),
+    )
+    .expect("copy keyring");
+    match Connection::open_with_flags_and_vfs(
+        &markerless_db,
+        OpenFlags::SQLITE_OPEN_READ_ONLY,
+        "evfs",
+    )
+    .and_then(|c| c.query_row("SELECT COUNT(*) FROM widgets", [], |r| r.get::<_, i64>(0)))
+    {
+        Ok(_) => t.fail("page without a marker refused", &"read without error"),
+        Err(e) => t.assert_eq(
+            "page without a marker refused",
+            &e.sqlite_error_code(),
+            &Some(rusqlite::ErrorCode::SystemIoFailure),
+        ),
+    }
+
+    t.section("EVFS Memory Mapping Disabled");
+
+    let conn =
+        Connection::open_with_flags_and_vfs(&db_path, OpenFlags::SQLITE_OPEN_READ_ONLY, "evfs")?;
+    let mmap_size: i64 = conn.query_row("PRAGMA mmap_size = 268435456", [], |r| r.get(0))?;
+    t.assert_eq("PRAGMA mmap_size reports mmap off", &mmap_size, &0i64);
+
+    let names: Vec<String> = conn
+        .prepare("SELECT name FROM widgets ORDER BY id")?
+        .query_map([], |r| r.get(0))?
+        .collect::<Result<_>>()?;
+    t.assert_eq(
+        "reads return plaintext with mmap_size set",
+        &names,
+        &vec![
+            "Sprocket".to_string(),
+            "Gizmo".to_string(),
+            "Doohickey".to_string(),
+        ],
+    );
+
+    drop(conn);
+
     t.section("EVFS Multi-Table Operations");
 
     let conn =
@@ -187,5 +263,496 @@ pub(crate) fn run_evfs_vfs_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
 
     drop(conn);
 
+    t.section("EVFS Hot Journal Recovery");
+
+    let open_evfs = |path: &std::path::Path| {
+        Connection::open_with_flags_and_vfs(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            "evfs",
+        )
+    };
+    let crash_db = tmp.path("crash.db");
+    let conn = open_evfs(&crash_db)?;
+    conn.execute_batch(
+        "PRAGMA journal_mode = DELETE;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, body TEXT);",
+    )?;
+    for id in 0..64 {
+        conn.execute(
+            "INSERT INTO t (id, body) VALUES (?1, ?2)",
+            params![id, format!("before-row-{id}-").repeat(100)],
+        )?;
+    }
+    let before: Vec<String> = conn
+        .prepare("SELECT body FROM t ORDER BY id")?
+        .query_map([], |r| r.get(0))?
+        .collect::<Result<_>>()?;
+
+    // A tiny cache makes SQLite spill changed pages into the database
+    // mid-transaction, so only the journal can undo them.
+    conn.execute_batch(
+        "PRAGMA cache_size = 2;
+         BEGIN;
+         UPDATE t SET body = 'after';",
+    )?;
+
+    // "Crash": snapshot the files while the transaction is still open.
+    let recovered_db = tmp.path("recovered.db");
+    let journal = tmp.path("crash.db-journal");
+    let copied = std::fs::copy(&crash_db, &recovered_db)
+        .and_then(|_| std::fs::copy(&journal, tmp.path("recovered.db-journal")))
+        .and_then(|_| {
+            std::fs::copy(
+                tmp.path("crash.evfs-keyring"),
+                tmp.path("recovered.evfs-keyring"),
+            )
+        });
+    if let Err(e) = copied {
+        t.fail("snapshot database with hot journal", &e);
+        return Ok(());
+    }
+    let journal_bytes = std::fs::read(&journal).unwrap_or_default();
+    t.assert_eq(
+        "journal page images are encrypted",
+        &journal_bytes.windows(10).any(|w| w == b"before-row"),
+        &false,
+    );
+    conn.execute_batch("ROLLBACK;")?;
+    drop(conn);
+
+    match open_evfs(&recovered_db).and_then(|c| {
+        c.prepare("SELECT body FROM t ORDER BY id")?
+            .query_map([], |r| r.get(0))?
+            .collect::<Result<Vec<String>>>()
+    }) {
+        Ok(after) => t.assert_eq("hot journal rolled back on open", &after, &before),
+        Err(e) => t.fail("read database recovered from hot journal", &e),
+    }
+
+    t.section("EVFS WAL Checkpoint");
+
+    let wal_db = tmp.path("wal.db");
+    let wal_file = tmp.path("wal.db-wal");
+    let conn = open_evfs(&wal_db)?;
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA wal_autocheckpoint = 0;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, body TEXT);",
+    )?;
+    for id in 0..32 {
+        conn.execute(
+            "INSERT INTO t (id, body) VALUES (?1, ?2)",
+            params![id, format!("wal-row-{id}-").repeat(50)],
+        )?;
+    }
+    let wal_bytes = std::fs::read(&wal_file).unwrap_or_default();
+    t.assert_eq(
+        "WAL frames are encrypted",
+        &(wal_bytes.len() > 32 && !wal_bytes.windows(7).any(|w| w == b"wal-row")),
+        &true,
+    );
+
+    // Recovery replays only frames whose checksums still match.
+    let copied = std::fs::copy(&wal_db, tmp.path("wal-copy.db"))
+        .and_then(|_| std::fs::write(tmp.path("wal-copy.db-wal"), &wal_bytes))
+        .and_then(|_| {
+            std::fs::copy(
+                tmp.path("wal.evfs-keyring"),
+                tmp.path("wal-copy.evfs-keyring"),
+            )
+        });
+    if let Err(e) = copied {
+        t.fail("snapshot database with its WAL", &e);
+        return Ok(());
+    }
+
+    let busy: i64 = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |r| r.get(0))?;
+    t.assert_eq("checkpoint completes", &busy, &0);
+    t.assert_eq(
+        "WAL truncated by the checkpoint",
+        &std::fs::metadata(&wal_file).map(|m| m.len()).ok(),
+        &Some(0),
+    );
+    let raw = std::fs::read(&wal_db).unwrap_or_default();
+    t.assert_eq(
+        "checkpointed pages are encrypted",
+        &raw.windows(7).any(|w| w == b"wal-row"),
+        &false,
+    );
+    drop(conn);
+
+    for (name, path) in [
+        ("checkpointed", wal_db),
+        ("recovered from WAL", tmp.path("wal-copy.db")),
+    ] {
+        match open_evfs(&path).and_then(|c| {
+            c.query_row("SELECT count(*), max(body) FROM t", [], |r| {
+                Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?))
+            })
+        }) {
+            Ok(got) => t.assert_eq(
+                &format!("{name} database reads back"),
+                &got,
+                &(32, "wal-row-9-".repeat(50)),
+            ),
+            Err(e) => t.fail(&format!("read {name} database"), &e),
+        }
+    }
+
+    t.section("EVFS Secure Delete");
+
+    let shred_path = tmp.path("shred.db");
+    let sidecar = shred_path.with_extension("evfs-keyring");
+    let conn = Connection::open_with_flags_and_vfs(
+        &shred_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs",
+    )?;
+    conn.execute_batch(
+        "PRAGMA secure_delete = ON;
+         CREATE TABLE t (body TEXT);
+         INSERT INTO t VALUES ('gone soon');",
+    )?;
+    t.assert_eq("sidecar written", &sidecar.exists(), &true);
+    conn.execute_batch(
+        "INSERT INTO t SELECT hex(randomblob(2000)) FROM t, t AS a, t AS b, t AS c;
+         INSERT INTO t SELECT body FROM t;
+         DELETE FROM t WHERE rowid > 1;
+         VACUUM;",
+    )?;
+    let body: String = conn.query_row("SELECT body FROM t", [], |r| r.get(0))?;
+    t.assert_eq(
+        "VACUUM truncates under secure_delete",
+        &body,
+        &"gone soon".to_string(),
+    );
+    std::fs::remove_file(&shred_path).expect("remove shred.db");
+    drop(conn);
+    t.assert_eq(
+        "sidecar shredded after the db is deleted",
+        &sidecar.exists(),
+        &false,
+    );
+
+    // The pragma belongs to the connection that ran it.
+    let kept_path = tmp.path("kept.db");
+    let kept_sidecar = kept_path.with_extension("evfs-keyring");
+    let open_kept = || {
+        Connection::open_with_flags_and_vfs(
+            &kept_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            "evfs",
+        )
+    };
+    let scrubbing = open_kept()?;
+    scrubbing.execute_batch(
+        "PRAGMA secure_delete = ON;
+         CREATE TABLE t (body TEXT);
+         INSERT INTO t VALUES ('kept');",
+    )?;
+    drop(scrubbing);
+    let conn = open_kept()?;
+    conn.execute_batch("INSERT INTO t VALUES ('also kept');")?;
+    std::fs::remove_file(&kept_path).expect("remove kept.db");
+    drop(conn);
+    t.assert_eq(
+        "secure_delete of a closed connection does not carry over",
+        &kept_sidecar.exists(),
+        &true,
+    );
+
+    t.section("EVFS Foreign Database");
+
+    // Stand-in for a SQLCipher file: no plaintext header, no EVFSv1 marker.
+    let foreign_db = tmp.path("foreign.db");
+    let foreign: Vec<u8> = (0..8192u32)
+        .map(|i| (i.wrapping_mul(31) ^ 0x5a) as u8)
+        .collect();
+    std::fs::write(&foreign_db, &foreign).expect("write foreign DB file");
+    match open_evfs(&foreign_db).and_then(|c| {
+        c.query_row("SELECT COUNT(*) FROM sqlite_master", [], |r| {
+            r.get::<_, i64>(0)
+        })
+    }) {
+        Ok(_) => t.fail("foreign database rejected", &"opened without error"),
+        Err(e) => t.assert_eq(
+            "foreign database rejected as not a database",
+            &e.sqlite_error_code(),
+            &Some(rusqlite::ErrorCode::NotADatabase),
+        ),
+    }
+
+    t.section("EVFS Reserve Mismatch");
+
+    // Same file, but its header claims another reserve size than the VFS uses.
+    let drifted_db = tmp.path("drifted.db");
+    open_evfs(&drifted_db)?.execute_batch("CREATE TABLE t (body TEXT);")?;
+    let mut drifted = std::fs::read(&drifted_db).expect("read drifted DB file");
+    drifted[20] = 32;
+    std::fs::write(&drifted_db, &drifted).expect("write drifted DB file");
+    match open_evfs(&drifted_db) {
+        Ok(_) => t.fail("reserve mismatch rejected", &"opened without error"),
+        Err(e) => t.assert_eq(
+            "reserve mismatch rejected on open",
+            &e.sqlite_error_code(),
+            &Some(rusqlite::ErrorCode::CannotOpen),
+        ),
+    }
+
+    t.section("EVFS Encryption Bypass");
+
+    let open_bypassed = |path: &std::path::Path| {
+        Connection::open_with_flags_and_vfs(
+            format!("file:{}?evfs_encrypt=off", path.display()),
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_URI,
+            "evfs",
+        )
+    };
+    let plain_db = tmp.path("plain.db");
+    Connection::open(&plain_db)?.execute_batch(
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, body TEXT);
+         INSERT INTO t (body) VALUES ('written plaintext');",
+    )?;
+    match open_bypassed(&plain_db).and_then(|c| {
+        c.execute("INSERT INTO t (body) VALUES ('written through evfs')", [])?;
+        c.query_row("SELECT body FROM t WHERE id = 1", [], |r| {
+            r.get::<_, String>(0)
+        })
+    }) {
+        Ok(body) => t.assert_eq(
+            "plaintext database readable with evfs_encrypt=off",
+            &body,
+            &"written plaintext".to_string(),
+        ),
+        Err(e) => t.fail("open plaintext database with evfs_encrypt=off", &e),
+    }
+    match Connection::open(&plain_db).and_then(|c| {
+        c.query_row("SELECT body FROM t WHERE id = 2", [], |r| {
+            r.get::<_, String>(0)
+        })
+    }) {
+        Ok(body) => t.assert_eq(
+            "evfs_encrypt=off writes stay plaintext",
+            &body,
+            &"written through evfs".to_string(),
+        ),
+        Err(e) => t.fail("read bypassed write with the default VFS", &e),
+    }
+    match open_bypassed(&db_path).and_then(|c| {
+        c.query_row("SELECT COUNT(*) FROM sqlite_master", [], |r| {
+            r.get::<_, i64>(0)
+        })
+    }) {
+        Ok(_) => t.fail(
+            "encrypted database refused",
+            &"opened with evfs_encrypt=off",
+        ),
+        Err(e) => t.assert_eq(
+            "encrypted database refused with evfs_encrypt=off",
+            &e.sqlite_error_code(),
+            &Some(rusqlite::ErrorCode::CannotOpen),
+        ),
+    }
+
+    t.section("EVFS Sub-Page I/O");
+
+    // Drive the VFS's xRead/xWrite directly with ranges SQLite itself
+    // rarely issues against the main database.
+    let io_db = tmp.path("subpage.db");
+    let conn = Connection::open_with_flags_and_vfs(
+        &io_db,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs",
+    )?;
+    conn.execute_batch(
+        "CREATE TABLE t (body TEXT);
+         WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 200)
+         INSERT INTO t SELECT hex(randomblob(64)) FROM n;",
+    )?;
+    let page_size: i64 = conn.query_row("PRAGMA page_size", [], |r| r.get(0))?;
+    let mut file: *mut ffi::sqlite3_file = std::ptr::null_mut();
+    let rc = unsafe {
+        ffi::sqlite3_file_control(
+            conn.handle(),
+            c"main".as_ptr(),
+            ffi::SQLITE_FCNTL_FILE_POINTER,
+            (&raw mut file).cast(),
+        )
+    };
+    let read = |amt: usize, ofst: i64| {
+        let mut buf = vec![0u8; amt];
+        let rc = unsafe {
+            ((*(*file).pMethods).xRead.unwrap())(file, buf.as_mut_ptr().cast(), amt as _, ofst)
+        };
+        (rc, buf)
+    };
+    if rc != ffi::SQLITE_OK || file.is_null() {
+        t.fail("get the main database file", &format!("rc {rc}"));
+    } else {
+        let (rc, header) = read(100, 0);
+        t.assert_eq("100-byte header read succeeds", &rc, &ffi::SQLITE_OK);
+        t.assert_eq(
+            "header read returns the plaintext magic",
+            &&header[..16],
+            &&b"SQLite format 3\0"[..],
+        );
+
+        let (rc, _) = read(0, page_size + 7);
+        t.assert_eq("zero-length read succeeds", &rc, &ffi::SQLITE_OK);
+
+        // Straddles the page 2/3 boundary at an unaligned offset.
+        let patch = b"evfs-sub-page-patch";
+        let ofst = 2 * page_size - 7;
+        let rc = unsafe {
+            ((*(*file).pMethods).xWrite.unwrap())(
+                file,
+                patch.as_ptr().cast(),
+                patch.len() as _,
+                ofst,
+            )
+        };
+        t.assert_eq("unaligned partial write succeeds", &rc, &ffi::SQLITE_OK);
+        let (rc, back) = read(patch.len(), ofst);
+        t.assert_eq(
+            "unaligned partial write reads back",
+            &(rc, back),
+            &(ffi::SQLITE_OK, patch.to_vec()),
+        );
+        let (rc, _) = read(page_size as usize, 2 * page_size);
+        t.assert_eq(
+            "neighbouring page still decrypts as a whole",
+            &rc,
+            &ffi::SQLITE_OK,
+        );
+        let raw = std::fs::read(&io_db).expect("read raw DB file");
+        t.assert_eq(
+            "partial write is encrypted on disk",
+            &raw.windows(patch.len()).any(|w| w == patch),
+            &false,
+        );
+
+        // Page 2^30 + 1 is past the default max_pages.
+        let absurd = page_size << 30;
+        let page = vec![0u8; page_size as usize];
+        let rc = unsafe {
+            ((*(*file).pMethods).xWrite.unwrap())(
+                file,
+                page.as_ptr().cast(),
+                page_size as _,
+                absurd,
+            )
+        };
+        t.assert_eq("write past max_pages is refused", &rc, &ffi::SQLITE_FULL);
+        let (rc, _) = read(page_size as usize, absurd);
+        t.assert_eq(
+            "read past max_pages is refused",
+            &rc,
+            &ffi::SQLITE_IOERR_READ,
+        );
+    }
+    drop(conn);
+
+    t.section("EVFS Page Size Detection");
+
+    // Page 1 written at 8192 bytes by plain SQLite, the rest through evfs,
+    // which was never told the page size. Pages evfs did not write would
+    // be refused for their missing marker.
+    let large_db = tmp.path("large_pages.db");
+    {
+        let conn = Connection::open(&large_db)?;
+        conn.execute_batch("PRAGMA page_size = 8192;")?;
+        let mut reserve: std::ffi::c_int = 48;
+        unsafe {
+            ffi::sqlite3_file_control(
+                conn.handle(),
+                c"main".as_ptr(),
+                ffi::SQLITE_FCNTL_RESERVE_BYTES,
+                (&raw mut reserve).cast(),
+            )
+        };
+        conn.execute_batch("PRAGMA user_version = 1;")?;
+    }
+    open_evfs(&large_db)?.execute_batch(
+        "CREATE TABLE t (body TEXT);
+         INSERT INTO t VALUES ('created at 8k'), (hex(zeroblob(6000)));",
+    )?;
+    let conn = open_evfs(&large_db)?;
+    let page_size: i64 = conn.query_row("PRAGMA page_size", [], |r| r.get(0))?;
+    t.assert_eq("page size read from the header", &page_size, &8192);
+    let body: String = conn.query_row("SELECT body FROM t WHERE rowid = 1", [], |r| r.get(0))?;
+    t.assert_eq(
+        "8k database readable without a page size",
+        &body,
+        &"created at 8k".to_string(),
+    );
+    conn.execute_batch(
+        "INSERT INTO t VALUES ('written through evfs');
+         UPDATE t SET body = hex(zeroblob(7000)) WHERE rowid = 2;",
+    )?;
+    drop(conn);
+
+    let conn = open_evfs(&large_db)?;
+    let rows: Vec<(i64, i64)> = conn
+        .prepare("SELECT rowid, length(body) FROM t ORDER BY rowid")?
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+        .collect::<Result<_>>()?;
+    t.assert_eq(
+        "8k pages written through evfs read back",
+        &rows,
+        &vec![(1, 13), (2, 14000), (3, 20)],
+    );
+    let check: String = conn.query_row("PRAGMA integrity_check", [], |r| r.get(0))?;
+    t.assert_eq(
+        "8k database passes integrity_check",
+        &check,
+        &"ok".to_string(),
+    );
+    drop(conn);
+    let raw = std::fs::read(&large_db).expect("read raw DB file");
+    t.assert_eq(
+        "page 2 encrypted at the detected page size",
+        &&raw[2 * 8192 - 48 + 16..2 * 8192 - 48 + 22],
+        &&b"EVFSv1"[..],
+    );
+
+    t.section("EVFS Unversioned Reserve");
+
+    // A 34-byte reserve predates the format version byte: tag, marker and
+    // nonce only. Such databases are format v0 and must keep opening.
+    let v0_db = tmp.path("v0.db");
+    {
+        let conn = Connection::open(&v0_db)?;
+        let mut reserve: std::ffi::c_int = 34;
+        unsafe {
+            ffi::sqlite3_file_control(
+                conn.handle(),
+                c"main".as_ptr(),
+                ffi::SQLITE_FCNTL_RESERVE_BYTES,
+                (&raw mut reserve).cast(),
+            )
+        };
+        conn.execute_batch("PRAGMA user_version = 1;")?;
+    }
+    open_evfs(&v0_db)?.execute_batch(
+        "CREATE TABLE t (body TEXT);
+         INSERT INTO t VALUES ('format v0');",
+    )?;
+    let conn = open_evfs(&v0_db)?;
+    let body: String = conn.query_row("SELECT body FROM t", [], |r| r.get(0))?;
+    t.assert_eq(
+        "reserve-34 database reads back",
+        &body,
+        &"format v0".to_string(),
+    );
+    drop(conn);
+    let raw = std::fs::read(&v0_db).expect("read raw DB file");
+    t.assert_eq("reserve-34 header kept", &raw[20], &34u8);
+    t.assert_eq(
+        "page 2 encrypted in the unversioned layout",
+        &&raw[2 * 4096 - 34 + 16..2 * 4096 - 34 + 22],
+        &&b"EVFSv1"[..],
+    );
+
     Ok(())
 }
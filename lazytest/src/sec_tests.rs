@@ -1,6 +1,6 @@
 use rusqlite::{Connection, Result, params};
 
-use crate::helpers::TestRunner;
+use crate::helpers::{TestDir, TestRunner};
 
 pub(crate) fn run_sqlsec_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
     t.section("sqlsec Direct Function-Call Tests");
@@ -18,6 +18,241 @@ pub(crate) fn run_sqlsec_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
         conn.load_extension_disable()?;
     }
 
+    t.section("sqlsec Schema Init on a Blank Database");
+    let blank = Connection::open(":memory:")?;
+    unsafe {
+        blank.load_extension_enable()?;
+        // Loading twice must be harmless: the schema setup is idempotent.
+        for _ in 0..2 {
+            blank.load_extension(format!("../sqlsec/target/{mode}/libsqlsec"), None::<&str>)?;
+        }
+        blank.load_extension_disable()?;
+    }
+    blank.execute_batch(
+        "CREATE TABLE __sec_notes (id INTEGER PRIMARY KEY, body TEXT, row_label_id INTEGER);
+         INSERT INTO __sec_notes VALUES (1, 'hello', sec_define_label('true'));",
+    )?;
+    match blank.query_row(
+        "SELECT sec_register_table('notes', '__sec_notes', 'row_label_id', NULL, NULL)",
+        [],
+        |r| r.get::<_, i64>(0),
+    ) {
+        Ok(_) => t.ok("register table without manual DDL"),
+        Err(e) => t.fail("register table without manual DDL", &e),
+    }
+    let meta_rows: i64 = blank.query_row("SELECT COUNT(*) FROM sec_meta", [], |r| r.get(0))?;
+    t.assert_eq("sec_meta seeded once", &meta_rows, &5i64);
+    let _: i64 = blank.query_row("SELECT sec_refresh_views()", [], |r| r.get(0))?;
+    let notes: i64 = blank.query_row("SELECT COUNT(*) FROM notes", [], |r| r.get(0))?;
+    t.assert_eq("registered table readable", &notes, &1i64);
+    drop(blank);
+
+    t.section("sqlsec Composite Row Labels");
+    let multi = Connection::open(":memory:")?;
+    unsafe {
+        multi.load_extension_enable()?;
+        multi.load_extension(format!("../sqlsec/target/{mode}/libsqlsec"), None::<&str>)?;
+        multi.load_extension_disable()?;
+    }
+    multi.execute_batch(
+        "CREATE TABLE __sec_staff (
+            id INTEGER PRIMARY KEY,
+            name TEXT,
+            dept_label INTEGER,
+            clearance_label INTEGER
+         );
+         INSERT INTO __sec_staff VALUES
+            (1, 'eng-admin', sec_define_label('team=eng'), sec_define_label('role=admin')),
+            (2, 'eng-public', sec_define_label('team=eng'), sec_define_label('true'));
+         SELECT sec_register_table('staff', '__sec_staff', 'dept_label,clearance_label', NULL, NULL);",
+    )?;
+    let staff_as = |attrs: &[(&str, &str)]| -> Result<Vec<String>> {
+        multi.query_row("SELECT sec_clear_context()", [], |r| r.get::<_, i64>(0))?;
+        for (attr, value) in attrs {
+            multi.query_row("SELECT sec_set_attr(?1, ?2)", [attr, value], |r| {
+                r.get::<_, i64>(0)
+            })?;
+        }
+        multi.query_row("SELECT sec_refresh_views()", [], |r| r.get::<_, i64>(0))?;
+        multi
+            .prepare("SELECT name FROM staff ORDER BY id")?
+            .query_map([], |r| r.get(0))?
+            .collect()
+    };
+    t.assert_eq(
+        "team=eng sees rows whose other label is public",
+        &staff_as(&[("team", "eng")])?,
+        &vec!["eng-public".to_string()],
+    );
+    t.assert_eq(
+        "team=eng + role=admin sees both rows",
+        &staff_as(&[("team", "eng"), ("role", "admin")])?,
+        &vec!["eng-admin".to_string(), "eng-public".to_string()],
+    );
+    t.assert_eq(
+        "role=admin alone sees no rows",
+        &staff_as(&[("role", "admin")])?,
+        &Vec::<String>::new(),
+    );
+    // A label column whose name holds a comma is quoted in the list.
+    multi.execute_batch(
+        r#"CREATE TABLE __sec_crew (
+            id INTEGER PRIMARY KEY,
+            "dept,label" INTEGER,
+            clearance_label INTEGER
+         );
+         INSERT INTO __sec_crew VALUES
+            (1, sec_define_label('team=eng'), sec_define_label('role=admin'));
+         SELECT sec_register_table('crew', '__sec_crew', '"dept,label",clearance_label', NULL, NULL);"#,
+    )?;
+    let crew_as = |attrs: &[(&str, &str)]| -> Result<i64> {
+        staff_as(attrs)?;
+        multi.query_row("SELECT COUNT(*) FROM crew", [], |r| r.get(0))
+    };
+    t.assert_eq(
+        "quoted label column with a comma still gates its row",
+        &(
+            crew_as(&[("role", "admin")])?,
+            crew_as(&[("team", "eng"), ("role", "admin")])?,
+        ),
+        &(0, 1),
+    );
+    drop(multi);
+
+    t.section("sqlsec Strict Mode");
+    let strict_tmp = TestDir::new("sqlsec-strict-");
+    let strict_db = strict_tmp.path("strict.db");
+    let open_strict_at = |path: &std::path::Path| -> Result<Connection> {
+        let c = Connection::open(path)?;
+        unsafe {
+            // Only consulted while the extension loads.
+            std::env::set_var("SQLSEC_STRICT", "1");
+            c.load_extension_enable()?;
+            let loaded =
+                c.load_extension(format!("../sqlsec/target/{mode}/libsqlsec"), None::<&str>);
+            std::env::remove_var("SQLSEC_STRICT");
+            loaded?;
+            c.load_extension_disable()?;
+        }
+        Ok(c)
+    };
+    let open_strict = || open_strict_at(&strict_db);
+    let read_vault = |c: &Connection| {
+        c.query_row("SELECT secret FROM __sec_vault", [], |r| {
+            r.get::<_, String>(0)
+        })
+    };
+    let expect_denied = |t: &mut TestRunner, msg: &str, res: Result<String>| match res {
+        Err(e) if e.to_string().contains("prohibited") => t.ok(msg),
+        other => t.fail(msg, &format!("expected access prohibited, got {other:?}")),
+    };
+    let strict = open_strict()?;
+    strict.execute_batch(
+        "CREATE TABLE __sec_vault (id INTEGER PRIMARY KEY, secret TEXT, row_label_id INTEGER);
+         INSERT INTO __sec_vault VALUES (1, 'gold', sec_define_label('true'));
+         SELECT sec_register_table('vault', '__sec_vault', 'row_label_id', NULL, NULL);",
+    )?;
+    t.assert_eq(
+        "intact metadata leaves the physical table readable",
+        &read_vault(&strict).ok(),
+        &Some("gold".to_string()),
+    );
+    strict.execute_batch("DROP TABLE sec_tables")?;
+    expect_denied(
+        t,
+        "physical table denied once sec_tables is dropped",
+        read_vault(&strict),
+    );
+    drop(strict);
+    let reopened = open_strict()?;
+    expect_denied(
+        t,
+        "reopening without sec_tables fails closed",
+        read_vault(&reopened),
+    );
+    drop(reopened);
+
+    // Every table of the metadata dropped through another connection, as a
+    // blank database would have none of them.
+    let wiped_db = strict_tmp.path("wiped.db");
+    open_strict_at(&wiped_db)?.execute_batch(
+        "CREATE TABLE __sec_vault (id INTEGER PRIMARY KEY, secret TEXT, row_label_id INTEGER);
+         INSERT INTO __sec_vault VALUES (1, 'gold', sec_define_label('true'));
+         SELECT sec_register_table('vault', '__sec_vault', 'row_label_id', NULL, NULL);",
+    )?;
+    let wiped = Connection::open(&wiped_db)?;
+    for table in [
+        "sec_labels",
+        "sec_label_names",
+        "sec_levels",
+        "sec_tables",
+        "sec_columns",
+        "sec_meta",
+    ] {
+        wiped.execute_batch(&format!("DROP TABLE {table}"))?;
+    }
+    drop(wiped);
+    expect_denied(
+        t,
+        "reopening with every sec_* table dropped fails closed",
+        read_vault(&open_strict_at(&wiped_db)?),
+    );
+    let fresh_db = strict_tmp.path("fresh.db");
+    Connection::open(&fresh_db)?.execute_batch("CREATE TABLE notes (body TEXT)")?;
+    t.assert_eq(
+        "a never-initialized database opens normally",
+        &open_strict_at(&fresh_db)?
+            .query_row("SELECT COUNT(*) FROM notes", [], |r| r.get::<_, i64>(0))
+            .ok(),
+        &Some(0),
+    );
+
+    t.section("sqlsec Per-Connection Context");
+    let tmp = TestDir::new("sqlsec-ctx-");
+    let shared_db = tmp.path("shared.db");
+    let open_sec = || -> Result<Connection> {
+        let c = Connection::open(&shared_db)?;
+        unsafe {
+            c.load_extension_enable()?;
+            c.load_extension(format!("../sqlsec/target/{mode}/libsqlsec"), None::<&str>)?;
+            c.load_extension_disable()?;
+        }
+        Ok(c)
+    };
+    let admin = open_sec()?;
+    admin.execute_batch(
+        "CREATE TABLE __sec_docs (id INTEGER PRIMARY KEY, body TEXT, row_label_id INTEGER);
+         INSERT INTO __sec_docs VALUES
+            (1, 'public', sec_define_label('true')),
+            (2, 'admin only', sec_define_label('role=admin'));
+         SELECT sec_register_table('docs', '__sec_docs', 'row_label_id', NULL, NULL);",
+    )?;
+    let guest = open_sec()?;
+    let visible = |c: &Connection| -> Result<i64> {
+        c.query_row("SELECT sec_refresh_views()", [], |r| r.get::<_, i64>(0))?;
+        c.query_row("SELECT COUNT(*) FROM docs", [], |r| r.get(0))
+    };
+    admin.query_row("SELECT sec_set_attr('role', 'admin')", [], |r| {
+        r.get::<_, i64>(0)
+    })?;
+    t.assert_eq("admin connection sees both rows", &visible(&admin)?, &2i64);
+    t.assert_eq(
+        "guest connection to the same file sees only the public row",
+        &visible(&guest)?,
+        &1i64,
+    );
+    drop(admin);
+    let reopened = open_sec()?;
+    let role: Option<String> =
+        reopened.query_row("SELECT sec_get_attr('role')", [], |r| r.get(0))?;
+    t.assert_eq(
+        "a new connection starts with an empty context",
+        &role,
+        &None,
+    );
+    drop(reopened);
+    drop(guest);
+
     t.section("sqlsec Labels, Levels, and Visibility");
     let public_label: i64 = conn.query_row("SELECT sec_define_label('true')", [], |r| r.get(0))?;
     let admin_label: i64 =
@@ -73,6 +308,24 @@ pub(crate) fn run_sqlsec_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
         &1i64,
     );
 
+    t.section("sqlsec Label Validation");
+    for (expr, expected) in [
+        (
+            "role=admin&(team=finance",
+            "unbalanced parenthesis at column 12",
+        ),
+        ("=admin", "missing attribute name at column 1"),
+        ("role!admin", "unknown operator '!' at column 5"),
+    ] {
+        match conn.query_row("SELECT sec_define_label(?1)", [expr], |r| {
+            r.get::<_, i64>(0)
+        }) {
+            Ok(id) => t.fail(&format!("reject label {expr}"), &format!("got id {id}")),
+            Err(e) if e.to_string().contains(expected) => t.ok(&format!("rejected label {expr}")),
+            Err(e) => t.fail(&format!("reject label {expr}"), &e),
+        }
+    }
+
     t.section("sqlsec Register + Refresh + Staleness");
     conn.execute_batch(
         "CREATE TABLE __sec_docs (
@@ -112,17 +365,12 @@ pub(crate) fn run_sqlsec_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
         params!["role", "user"],
         |r| r.get(0),
     )?;
-    match conn.query_row("SELECT sec_assert_fresh()", [], |r| r.get::<_, i64>(0)) {
-        Ok(_) => t.fail(
-            "sec_assert_fresh should fail when stale",
-            &"expected SQLITE error",
-        ),
-        Err(_) => t.ok("sec_assert_fresh fails when generation changed"),
-    }
-    match conn.query_row("SELECT COUNT(*) FROM docs", [], |r| r.get::<_, i64>(0)) {
-        Ok(_) => t.fail("stale docs read should fail", &"expected SQLITE error"),
-        Err(_) => t.ok("docs view rejects stale reads before refresh"),
-    }
+    let docs_after_change: i64 = conn.query_row("SELECT COUNT(*) FROM docs", [], |r| r.get(0))?;
+    t.assert_eq(
+        "docs view refreshes lazily on first read after context change",
+        &docs_after_change,
+        &1i64,
+    );
 
     let refreshed: i64 = conn.query_row("SELECT sec_refresh_views()", [], |r| r.get(0))?;
     t.assert_eq("refresh after context change", &refreshed, &1i64);
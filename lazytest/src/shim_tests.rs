@@ -1,6 +1,27 @@
-use rusqlite::{Connection, Result};
+use std::ffi::{CStr, CString};
 
-use crate::helpers::TestRunner;
+use rusqlite::{Connection, Result, ffi};
+
+use crate::helpers::{TestDir, TestRunner};
+
+unsafe extern "C" {
+    // Deprecated or UTF-16, so libsqlite3-sys doesn't bind them.
+    fn sqlite3_prepare(
+        db: *mut ffi::sqlite3,
+        z_sql: *const std::ffi::c_char,
+        n_byte: std::ffi::c_int,
+        pp_stmt: *mut *mut ffi::sqlite3_stmt,
+        pz_tail: *mut *const std::ffi::c_char,
+    ) -> std::ffi::c_int;
+
+    fn sqlite3_prepare16_v2(
+        db: *mut ffi::sqlite3,
+        z_sql: *const std::ffi::c_void,
+        n_byte: std::ffi::c_int,
+        pp_stmt: *mut *mut ffi::sqlite3_stmt,
+        pz_tail: *mut *const std::ffi::c_void,
+    ) -> std::ffi::c_int;
+}
 
 pub(crate) fn run_sqlshim_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
     t.section("sqlshim + sqlsec Extension Loading");
@@ -46,6 +67,35 @@ pub(crate) fn run_sqlshim_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
         }
     }
 
+    match conn.execute_batch("DEFINE LEVEL clearance 'restricted' = 1;") {
+        Err(e) if e.to_string().contains("already defined as 'confidential'") => {
+            t.ok("DEFINE LEVEL rejects a value already taken by another name")
+        }
+        other => t.fail(
+            "DEFINE LEVEL rejects a value already taken by another name",
+            &format!("{other:?}"),
+        ),
+    }
+
+    match conn.prepare("SHOW LEVELS clearance;").and_then(|mut stmt| {
+        stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(1)?, row.get::<_, i64>(2)?))
+        })?
+        .collect::<Result<Vec<_>>>()
+    }) {
+        Ok(levels) => t.assert_eq(
+            "SHOW LEVELS lists an attribute's levels in order",
+            &levels,
+            &vec![
+                ("public".to_string(), 0),
+                ("confidential".to_string(), 1),
+                ("secret".to_string(), 2),
+                ("top_secret".to_string(), 3),
+            ],
+        ),
+        Err(e) => t.fail("SHOW LEVELS", &e),
+    }
+
     t.section("CREATE POLICY");
     let policies = [
         (
@@ -73,12 +123,144 @@ pub(crate) fn run_sqlshim_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
         }
     }
 
+    match conn
+        .execute_batch(
+            "CREATE POLICY notes_rw ON notes FOR SELECT, UPDATE USING (has_role('editor'));",
+        )
+        .and_then(|()| {
+            conn.prepare(
+                "SELECT operation FROM __sqlshim_policies WHERE name = 'notes_rw' ORDER BY operation",
+            )?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>>>()
+        }) {
+        Ok(ops) => t.assert_eq(
+            "CREATE POLICY ... FOR SELECT, UPDATE stores one row per operation",
+            &ops,
+            &vec!["SELECT".to_string(), "UPDATE".to_string()],
+        ),
+        Err(e) => t.fail("CREATE POLICY (operation list)", &e),
+    }
+
+    match conn
+        .execute_batch(
+            "CREATE POLICY IF NOT EXISTS notes_rw ON notes FOR DELETE USING (has_role('janitor'));",
+        )
+        .and_then(|()| {
+            conn.prepare(
+                "SELECT operation || ':' || expr FROM __sqlshim_policies
+                 WHERE name = 'notes_rw' ORDER BY operation",
+            )?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>>>()
+        }) {
+        Ok(rows) => t.assert_eq(
+            "CREATE POLICY IF NOT EXISTS keeps the first definition",
+            &rows,
+            &vec![
+                "SELECT:has_role ( 'editor' )".to_string(),
+                "UPDATE:has_role ( 'editor' )".to_string(),
+            ],
+        ),
+        Err(e) => t.fail("CREATE POLICY IF NOT EXISTS", &e),
+    }
+    match conn.execute_batch("CREATE POLICY notes_rw ON notes USING (1);") {
+        Err(e) if e.to_string().contains("policy already exists") => {
+            t.ok("CREATE POLICY on an existing name is rejected")
+        }
+        Err(e) => t.fail("CREATE POLICY on an existing name is rejected", &e),
+        Ok(()) => t.fail(
+            "CREATE POLICY on an existing name is rejected",
+            &"duplicate CREATE POLICY succeeded",
+        ),
+    }
+    match conn.query_row(
+        "SELECT count(*) FROM sqlite_master
+         WHERE type IN ('view', 'trigger') AND sql LIKE '%__sqlshim_policies%'",
+        [],
+        |row| row.get::<_, i64>(0),
+    ) {
+        Ok(n) => t.assert_eq(
+            "CREATE POLICY adds no schema objects beside __sqlshim_policies",
+            &n,
+            &0,
+        ),
+        Err(e) => t.fail("CREATE POLICY schema objects", &e),
+    }
+
+    // A database from before operation lists: one row per policy.
+    let legacy = Connection::open(":memory:")?;
+    match legacy
+        .execute_batch(
+            "CREATE TABLE __sqlshim_policies (
+                 name TEXT NOT NULL,
+                 table_name TEXT NOT NULL,
+                 operation TEXT NOT NULL,
+                 label_id INTEGER,
+                 expr TEXT NOT NULL,
+                 PRIMARY KEY (name, table_name)
+             );
+             INSERT INTO __sqlshim_policies VALUES ('old', 'notes', 'ALL', NULL, '1');
+             CREATE POLICY new ON notes FOR SELECT, UPDATE USING (1);",
+        )
+        .and_then(|()| {
+            legacy
+                .prepare(
+                    "SELECT name || ':' || operation FROM __sqlshim_policies
+                     ORDER BY name, operation",
+                )?
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>>>()
+        }) {
+        Ok(rows) => t.assert_eq(
+            "CREATE POLICY migrates a table keyed by (name, table_name)",
+            &rows,
+            &vec![
+                "new:SELECT".to_string(),
+                "new:UPDATE".to_string(),
+                "old:ALL".to_string(),
+            ],
+        ),
+        Err(e) => t.fail("CREATE POLICY (legacy policy table)", &e),
+    }
+
     t.section("DROP POLICY");
     match conn.execute_batch("DROP POLICY invoices_write ON invoices;") {
         Ok(()) => t.ok("DROP POLICY"),
         Err(e) => t.fail("DROP POLICY", &e),
     }
 
+    t.section("SHOW CONTEXT");
+    match conn
+        .execute_batch(
+            "CLEAR CONTEXT;
+             SET CONTEXT role = 'admin';
+             SET CONTEXT team = 'finance';
+             SET CONTEXT clearance = 'secret';",
+        )
+        .and_then(|()| {
+            conn.prepare("SHOW CONTEXT;")?
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, Option<i64>>(2)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>>>()
+        }) {
+        Ok(rows) => t.assert_eq(
+            "SHOW CONTEXT lists exactly the attributes set, with resolved levels",
+            &rows,
+            &vec![
+                ("clearance".to_string(), "secret".to_string(), Some(2)),
+                ("role".to_string(), "admin".to_string(), None),
+                ("team".to_string(), "finance".to_string(), None),
+            ],
+        ),
+        Err(e) => t.fail("SHOW CONTEXT", &e),
+    }
+
     t.section("Context Management");
     for stmt in [
         "SET CONTEXT role = 'admin';",
@@ -89,6 +271,7 @@ pub(crate) fn run_sqlshim_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
         "PUSH CONTEXT;",
         "SET CONTEXT role = 'admin';",
         "POP CONTEXT;",
+        r#"SET CONTEXT FROM '{"role":"admin","team":"finance"}';"#,
     ] {
         match conn.execute_batch(stmt) {
             Ok(()) => t.ok(stmt),
@@ -96,10 +279,51 @@ pub(crate) fn run_sqlshim_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
         }
     }
 
+    t.section("PUSH / POP CONTEXT Bounds");
+    let expect_err =
+        |t: &mut TestRunner, name: &str, sql: &str, needle: &str| match conn.execute_batch(sql) {
+            Err(e) if e.to_string().contains(needle) => t.ok(name),
+            Err(e) => t.fail(name, &e),
+            Ok(()) => t.fail(name, &format!("{sql} succeeded")),
+        };
+    match conn.execute_batch(
+        "UPDATE sec_meta SET value = 3 WHERE key = 'max_context_depth';
+         PUSH CONTEXT; PUSH CONTEXT; PUSH CONTEXT;",
+    ) {
+        Ok(()) => t.ok("PUSH CONTEXT up to max_context_depth"),
+        Err(e) => t.fail("PUSH CONTEXT up to max_context_depth", &e),
+    }
+    expect_err(
+        t,
+        "PUSH CONTEXT past max_context_depth is rejected",
+        "PUSH CONTEXT;",
+        "context stack overflow",
+    );
+    match conn.execute_batch("POP CONTEXT; POP CONTEXT; POP CONTEXT;") {
+        Ok(()) => t.ok("POP CONTEXT back to the base context"),
+        Err(e) => t.fail("POP CONTEXT back to the base context", &e),
+    }
+    expect_err(
+        t,
+        "POP CONTEXT on the base context reports underflow",
+        "POP CONTEXT;",
+        "context stack underflow",
+    );
+    conn.execute_batch("UPDATE sec_meta SET value = 64 WHERE key = 'max_context_depth';")?;
+
     t.section("REFRESH SECURE VIEWS");
-    match conn.execute_batch("REFRESH SECURE VIEWS;") {
-        Ok(()) => t.ok("REFRESH SECURE VIEWS"),
-        Err(e) => t.fail("REFRESH SECURE VIEWS", &e),
+    for sql in ["REFRESH SECURE VIEWS;", "REFRESH SECURITY VIEWS;"] {
+        match conn.execute_batch(sql) {
+            Ok(()) => t.ok(&format!("{sql} (exec)")),
+            Err(e) => t.fail(&format!("{sql} (exec)"), &e),
+        }
+        match conn
+            .prepare(sql)
+            .and_then(|mut stmt| stmt.query_row([], |r| r.get::<_, i64>(0)))
+        {
+            Ok(_) => t.ok(&format!("{sql} (prepare)")),
+            Err(e) => t.fail(&format!("{sql} (prepare)"), &e),
+        }
     }
 
     t.section("REGISTER SECURE TABLE");
@@ -140,6 +364,170 @@ pub(crate) fn run_sqlshim_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
         Err(e) => t.fail("REGISTER SECURE TABLE (with labels)", &e),
     }
 
+    t.section("REGISTER SECURE TABLE ... ALLOW IMPLICIT LABEL");
+    match conn.execute_batch(
+        r#"
+        CREATE TABLE __sec_stickies (id INTEGER PRIMARY KEY, body TEXT, row_label_id INTEGER);
+        REGISTER SECURE TABLE stickies
+        ON __sec_stickies
+        WITH ROW LABEL row_label_id
+        ALLOW IMPLICIT LABEL;
+        CREATE TABLE __sec_memos (id INTEGER PRIMARY KEY, body TEXT, row_label_id INTEGER);
+        REGISTER SECURE TABLE memos
+        ON __sec_memos
+        WITH ROW LABEL row_label_id;
+        REFRESH SECURE VIEWS;
+        "#,
+    ) {
+        Ok(()) => t.ok("registered stickies (implicit allowed) and memos (implicit disallowed)"),
+        Err(e) => t.fail("register implicit-label tables", &e),
+    }
+    match conn.execute("INSERT INTO stickies (id, body) VALUES (1, 'no label')", []) {
+        Ok(_) => t.ok("insert without row label allowed"),
+        Err(e) => t.fail("insert without row label allowed", &e),
+    }
+    match conn.execute("INSERT INTO memos (id, body) VALUES (1, 'no label')", []) {
+        Ok(_) => t.fail("insert without row label rejected", &"insert succeeded"),
+        Err(e)
+            if e.to_string()
+                .contains("implicit row_label_col row_label_id not allowed") =>
+        {
+            t.ok("insert without row label rejected")
+        }
+        Err(e) => t.fail("insert without row label rejected", &e),
+    }
+    // A write straight after SET CONTEXT must not meet the previous
+    // context's triggers.
+    match conn.execute_batch(
+        "PUSH CONTEXT;
+         SET CONTEXT role = 'writer';
+         INSERT INTO stickies (id, body) VALUES (2, 'after SET CONTEXT');
+         POP CONTEXT;",
+    ) {
+        Ok(()) => t.ok("insert straight after SET CONTEXT"),
+        Err(e) => t.fail("insert straight after SET CONTEXT", &e),
+    }
+
+    t.section("LIST SECURE TABLES");
+    type Listed = (String, String, String, bool, bool, i64);
+    match conn.prepare("LIST SECURE TABLES;").and_then(|mut stmt| {
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<i64>>(3)?.is_some(),
+                    row.get::<_, Option<i64>>(4)?.is_some(),
+                    row.get::<_, i64>(5)?,
+                ))
+            })?
+            .collect::<Result<Vec<Listed>>>()?;
+        Ok((columns, rows))
+    }) {
+        Ok((columns, rows)) => {
+            t.assert_eq(
+                "LIST SECURE TABLES columns",
+                &columns,
+                &[
+                    "logical_name",
+                    "physical_name",
+                    "row_label_col",
+                    "table_label_id",
+                    "insert_label_id",
+                    "allow_implicit_label",
+                ]
+                .map(String::from)
+                .to_vec(),
+            );
+            let listed = |name: &str| rows.iter().find(|r| r.0 == name).cloned();
+            t.assert_eq(
+                "documents listed with its table and insert labels",
+                &listed("documents"),
+                &Some((
+                    "documents".to_string(),
+                    "__sec_documents".to_string(),
+                    "row_label_id".to_string(),
+                    true,
+                    true,
+                    0,
+                )),
+            );
+            t.assert_eq(
+                "stickies listed as allowing implicit labels",
+                &listed("stickies"),
+                &Some((
+                    "stickies".to_string(),
+                    "__sec_stickies".to_string(),
+                    "row_label_id".to_string(),
+                    false,
+                    false,
+                    1,
+                )),
+            );
+        }
+        Err(e) => t.fail("LIST SECURE TABLES", &e),
+    }
+
+    t.section("DEFINE LABEL ... AS name");
+    match conn.execute_batch(
+        r#"
+        DEFINE LABEL 'role=finance' AS finance_read;
+        CREATE TABLE __sec_payments (id INTEGER PRIMARY KEY, amount INTEGER, row_label_id INTEGER);
+        REGISTER SECURE TABLE payments
+        ON __sec_payments
+        WITH ROW LABEL row_label_id
+        TABLE LABEL finance_read;
+        CREATE TABLE __sec_invoices (id INTEGER PRIMARY KEY, amount INTEGER, row_label_id INTEGER);
+        REGISTER SECURE TABLE invoices
+        ON __sec_invoices
+        WITH ROW LABEL row_label_id
+        TABLE LABEL finance_read
+        INSERT LABEL finance_read;
+        "#,
+    ) {
+        Ok(()) => t.ok("tables registered against a named label"),
+        Err(e) => t.fail("tables registered against a named label", &e),
+    }
+    match conn
+        .prepare(
+            "SELECT t.logical_name, l.expr, t.insert_label_id IS NOT NULL
+             FROM sec_tables_info t JOIN sec_labels l ON l.id = t.table_label_id
+             WHERE t.logical_name IN ('payments', 'invoices')
+             ORDER BY t.logical_name",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+                .collect::<Result<Vec<(String, String, bool)>>>()
+        }) {
+        Ok(rows) => t.assert_eq(
+            "both tables carry the named label",
+            &rows,
+            &vec![
+                ("invoices".to_string(), "role=finance".to_string(), true),
+                ("payments".to_string(), "role=finance".to_string(), false),
+            ],
+        ),
+        Err(e) => t.fail("read back named label registrations", &e),
+    }
+    match conn.execute_batch("DEFINE LABEL 'role=admin' AS finance_read;") {
+        Err(e) if e.to_string().contains("already defined as 'role=finance'") => {
+            t.ok("a label name cannot be rebound")
+        }
+        other => t.fail("a label name cannot be rebound", &format!("{other:?}")),
+    }
+    match conn.execute_batch(
+        "CREATE TABLE __sec_typo (id INTEGER PRIMARY KEY, row_label_id INTEGER);
+         REGISTER SECURE TABLE typo ON __sec_typo WITH ROW LABEL row_label_id
+         TABLE LABEL finance_raed;",
+    ) {
+        Err(e) if e.to_string().contains("unknown label finance_raed") => {
+            t.ok("an unknown label name is refused")
+        }
+        other => t.fail("an unknown label name is refused", &format!("{other:?}")),
+    }
+
     t.section("CREATE SECURE VIEW");
     match conn.execute_batch(
         r#"
@@ -152,6 +540,20 @@ pub(crate) fn run_sqlshim_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
         Ok(()) => t.ok("CREATE SECURE VIEW"),
         Err(e) => t.fail("CREATE SECURE VIEW", &e),
     }
+    match conn.execute_batch(
+        r#"
+        CREATE SECURE VIEW last_names AS
+        SELECT id, name FROM __sec_employees ORDER BY name DESC LIMIT 5 -- last five
+        ;
+        "#,
+    ) {
+        Ok(()) => t.assert_eq(
+            "CREATE SECURE VIEW (ORDER BY ... LIMIT) is queryable",
+            &conn.prepare("SELECT id, name FROM last_names").is_ok(),
+            &true,
+        ),
+        Err(e) => t.fail("CREATE SECURE VIEW (ORDER BY ... LIMIT)", &e),
+    }
 
     t.section("SET COLUMN SECURITY");
     for stmt in [
@@ -165,6 +567,147 @@ pub(crate) fn run_sqlshim_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
         }
     }
 
+    t.section("SET COLUMN SECURITY ... NONE");
+    let salary_visible = |conn: &Connection| conn.prepare("SELECT salary FROM employees").is_ok();
+    match conn.execute_batch("REFRESH SECURE VIEWS;") {
+        Ok(()) => t.assert_eq(
+            "salary hidden while labelled",
+            &salary_visible(&conn),
+            &false,
+        ),
+        Err(e) => t.fail("REFRESH SECURE VIEWS", &e),
+    }
+    match conn
+        .execute_batch("SET COLUMN SECURITY employees.salary READ NONE;")
+        .and_then(|()| conn.execute_batch("REFRESH SECURE VIEWS;"))
+    {
+        Ok(()) => t.assert_eq(
+            "salary visible after READ NONE",
+            &salary_visible(&conn),
+            &true,
+        ),
+        Err(e) => t.fail("SET COLUMN SECURITY employees.salary READ NONE", &e),
+    }
+
+    t.section("INSERT into a write-protected column");
+    // `title` carries UPDATE 'role=hr', which the admin context lacks.
+    let insert = conn.execute_batch(
+        "INSERT INTO employees (id, name, title, row_label_id)
+         VALUES (100, 'Dana', 'CTO', sec_define_label('role=admin'));",
+    );
+    match insert {
+        Err(e) if e.to_string().contains("not writable in current context") => {
+            t.ok("insert naming an unwritable column aborts")
+        }
+        other => t.fail(
+            "insert naming an unwritable column aborts",
+            &format!("{other:?}"),
+        ),
+    }
+    match conn.query_row(
+        "SELECT COUNT(*) FROM __sec_employees WHERE id = 100",
+        [],
+        |row| row.get::<_, i64>(0),
+    ) {
+        Ok(n) => t.assert_eq("no row is written", &n, &0),
+        Err(e) => t.fail("read __sec_employees", &e),
+    }
+
+    t.section("UNREGISTER SECURE TABLE / DROP SECURE VIEW");
+    let table_exists = |conn: &Connection, name: &str| {
+        conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name = ?1",
+            [name],
+            |row| row.get::<_, i64>(0),
+        )
+        .map(|n| n > 0)
+        .unwrap_or(false)
+    };
+    let temp_object_exists = |conn: &Connection, kind: &str, name: &str| {
+        conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_temp_master WHERE type = ?1 AND name = ?2",
+            [kind, name],
+            |row| row.get::<_, i64>(0),
+        )
+        .map(|n| n > 0)
+        .unwrap_or(false)
+    };
+    let registered_rows = |conn: &Connection, logical: &str| -> Result<i64> {
+        conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM sec_tables WHERE logical_name = ?1)
+                  + (SELECT COUNT(*) FROM sec_columns WHERE logical_table = ?1)",
+            [logical],
+            |row| row.get(0),
+        )
+    };
+    match conn.execute_batch(
+        r#"
+        CREATE TABLE __sec_scratch (
+            id INTEGER PRIMARY KEY,
+            note TEXT,
+            row_label_id INTEGER
+        );
+        REGISTER SECURE TABLE scratch
+        ON __sec_scratch
+        WITH ROW LABEL row_label_id;
+        SET COLUMN SECURITY scratch.note READ 'true';
+        REFRESH SECURE VIEWS;
+        "#,
+    ) {
+        Ok(()) => {
+            t.assert_eq(
+                "scratch view exists after REFRESH",
+                &temp_object_exists(&conn, "view", "scratch"),
+                &true,
+            );
+            t.assert_eq(
+                "scratch insert trigger exists after REFRESH",
+                &temp_object_exists(&conn, "trigger", "scratch_sec_ins"),
+                &true,
+            );
+        }
+        Err(e) => t.fail("REGISTER SECURE TABLE scratch", &e),
+    }
+    for attempt in ["first", "repeated"] {
+        match conn.execute_batch("UNREGISTER SECURE TABLE scratch;") {
+            Ok(()) => {
+                t.assert_eq(
+                    &format!("{attempt} UNREGISTER drops the logical view"),
+                    &temp_object_exists(&conn, "view", "scratch"),
+                    &false,
+                );
+                let triggers = ["ins", "upd", "del"]
+                    .iter()
+                    .filter(|s| temp_object_exists(&conn, "trigger", &format!("scratch_sec_{s}")))
+                    .count();
+                t.assert_eq(
+                    &format!("{attempt} UNREGISTER drops the write triggers"),
+                    &triggers,
+                    &0,
+                );
+                match registered_rows(&conn, "scratch") {
+                    Ok(n) => t.assert_eq(
+                        &format!("{attempt} UNREGISTER deletes metadata rows"),
+                        &n,
+                        &0,
+                    ),
+                    Err(e) => t.fail("read sec_tables/sec_columns", &e),
+                }
+            }
+            Err(e) => t.fail(&format!("{attempt} UNREGISTER SECURE TABLE"), &e),
+        }
+    }
+    for attempt in ["first", "repeated"] {
+        match conn.execute_batch("DROP SECURE VIEW employee_view;") {
+            Ok(()) => t.assert_eq(
+                &format!("{attempt} DROP SECURE VIEW removes the view"),
+                &table_exists(&conn, "employee_view"),
+                &false,
+            ),
+            Err(e) => t.fail(&format!("{attempt} DROP SECURE VIEW"), &e),
+        }
+    }
+
     t.section("Stub Features (audit / explain policy)");
     for stmt in [
         "ENABLE AUDIT ON users;",
@@ -177,6 +720,606 @@ pub(crate) fn run_sqlshim_tests(t: &mut TestRunner, mode: &str) -> Result<()> {
         }
     }
 
+    t.section("CHANGEFEED");
+    conn.execute_batch(
+        "CREATE TABLE orders (id INTEGER PRIMARY KEY, status TEXT, total INTEGER);",
+    )?;
+    match conn
+        .execute_batch("CREATE CHANGEFEED open_orders ON orders (id, total) WHERE status = 'open';")
+    {
+        Ok(()) => t.ok("CREATE CHANGEFEED with filter"),
+        Err(e) => t.fail("CREATE CHANGEFEED with filter", &e),
+    }
+    for stmt in [
+        "INSERT INTO orders (id, status, total) VALUES (1, 'open', 10);",
+        "INSERT INTO orders (id, status, total) VALUES (2, 'closed', 20);",
+        "UPDATE orders SET total = 11 WHERE id = 1;",
+        "UPDATE orders SET total = 21 WHERE id = 2;",
+        "DELETE FROM orders WHERE id = 2;",
+        "DELETE FROM orders WHERE id = 1;",
+    ] {
+        conn.execute_batch(stmt)?;
+    }
+    let events = conn
+        .prepare("SELECT op, row_id, payload FROM open_orders_outbox ORDER BY seq")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| {
+                Ok(format!(
+                    "{} {} {}",
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()
+        });
+    match events {
+        Ok(events) => t.assert_eq(
+            "outbox only records rows matching the filter",
+            &events,
+            &vec![
+                r#"INSERT 1 {"id":1,"total":10}"#.to_string(),
+                r#"UPDATE 1 {"id":1,"total":11}"#.to_string(),
+                r#"DELETE 1 {"id":1,"total":11}"#.to_string(),
+            ],
+        ),
+        Err(e) => t.fail("read open_orders_outbox", &e),
+    }
+    match conn.execute_batch("DROP CHANGEFEED open_orders KEEP OUTBOX;") {
+        Ok(()) => {
+            conn.execute_batch("INSERT INTO orders (id, status, total) VALUES (3, 'open', 30);")?;
+            let count: i64 =
+                conn.query_row("SELECT COUNT(*) FROM open_orders_outbox", [], |row| {
+                    row.get(0)
+                })?;
+            t.assert_eq("no events after DROP CHANGEFEED", &count, &3);
+        }
+        Err(e) => t.fail("DROP CHANGEFEED ... KEEP OUTBOX", &e),
+    }
+    match conn.execute_batch("DROP CHANGEFEED open_orders;") {
+        Ok(()) => t.assert_eq(
+            "DROP CHANGEFEED removes the outbox",
+            &table_exists(&conn, "open_orders_outbox"),
+            &false,
+        ),
+        Err(e) => t.fail("DROP CHANGEFEED", &e),
+    }
+
+    // A rewrite's leading statements wait for the first step.
+    match conn.prepare("CREATE CHANGEFEED unstepped ON orders (id);") {
+        Ok(stmt) => {
+            drop(stmt);
+            t.assert_eq(
+                "preparing CREATE CHANGEFEED without stepping creates nothing",
+                &table_exists(&conn, "unstepped_outbox"),
+                &false,
+            );
+        }
+        Err(e) => t.fail("prepare CREATE CHANGEFEED", &e),
+    }
+    match conn
+        .prepare("CREATE CHANGEFEED stepped ON orders (id);")
+        .and_then(|mut stmt| stmt.execute([]))
+        .and_then(|_| {
+            conn.execute_batch("INSERT INTO orders (id, status, total) VALUES (4, 'open', 40);")?;
+            conn.query_row("SELECT COUNT(*) FROM stepped_outbox", [], |row| {
+                row.get::<_, i64>(0)
+            })
+        }) {
+        Ok(count) => t.assert_eq("stepping CREATE CHANGEFEED creates it", &count, &1),
+        Err(e) => t.fail("step CREATE CHANGEFEED", &e),
+    }
+    conn.execute_batch("DROP CHANGEFEED stepped; CLEAR CONTEXT;")?;
+    let role = conn
+        .prepare("SET CONTEXT role = 'unstepped', team = 'unstepped';")
+        .map(drop)
+        .and_then(|()| {
+            conn.query_row(
+                "SELECT COUNT(*) FROM sec_context WHERE value = 'unstepped'",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+        });
+    match role {
+        Ok(count) => t.assert_eq(
+            "preparing SET CONTEXT without stepping sets nothing",
+            &count,
+            &0,
+        ),
+        Err(e) => t.fail("prepare SET CONTEXT", &e),
+    }
+
+    t.section("Tenants");
+    match conn
+        .execute_batch("CREATE TENANT TABLE notes (id INTEGER PRIMARY KEY, body TEXT NOT NULL);")
+    {
+        Ok(()) => t.ok("CREATE TENANT TABLE"),
+        Err(e) => t.fail("CREATE TENANT TABLE", &e),
+    }
+    // Keys are scoped by tenant, so both tenants may use id 1.
+    for (tenant, body) in [("acme", "acme plan"), ("globex", "globex plan")] {
+        let inserted = conn
+            .execute_batch(&format!("SET TENANT = '{tenant}';"))
+            .and_then(|()| conn.execute("INSERT INTO notes (id, body) VALUES (1, ?1)", [body]));
+        if let Err(e) = inserted {
+            t.fail(&format!("insert note as {tenant}"), &e);
+        }
+    }
+    match conn.query_row(
+        "SELECT physical_name FROM sec_tables WHERE logical_name = 'notes'",
+        [],
+        |row| row.get::<_, String>(0),
+    ) {
+        Ok(physical) => t.assert_eq(
+            "tenant table is registered as a secure table",
+            &physical,
+            &"__tenant_notes".to_string(),
+        ),
+        Err(e) => t.fail("tenant table is registered as a secure table", &e),
+    }
+    match conn.execute(
+        "INSERT INTO notes (id, body, tenant_id) VALUES (2, 'planted', 'acme')",
+        [],
+    ) {
+        Err(e)
+            if e.to_string()
+                .contains("tenant_id must be the current tenant") =>
+        {
+            t.ok("a tenant cannot insert rows for another tenant")
+        }
+        other => t.fail(
+            "a tenant cannot insert rows for another tenant",
+            &format!("{other:?}"),
+        ),
+    }
+    let notes = |conn: &Connection| -> Result<Vec<String>> {
+        conn.prepare("SELECT body FROM notes ORDER BY id")?
+            .query_map([], |row| row.get(0))?
+            .collect()
+    };
+    for (tenant, body) in [("acme", "acme plan"), ("globex", "globex plan")] {
+        match conn
+            .execute_batch(&format!("SET TENANT = '{tenant}';"))
+            .and_then(|()| notes(&conn))
+        {
+            Ok(seen) => t.assert_eq(
+                &format!("{tenant} sees only its own notes"),
+                &seen,
+                &vec![body.to_string()],
+            ),
+            Err(e) => t.fail(&format!("read notes as {tenant}"), &e),
+        }
+    }
+
+    t.section("IMPORT TENANT ... ON CONFLICT");
+    // Another deployment's tenant tables: one note acme already has, one
+    // it lacks, and one belonging to globex. Its columns are in another
+    // order, which IMPORT TENANT copies by name.
+    let tmp = TestDir::new("sqlshim-import-");
+    let source = tmp.path("source.db");
+    Connection::open(&source)?.execute_batch(
+        "CREATE TABLE __tenant_notes (
+             tenant_id TEXT NOT NULL, body TEXT NOT NULL, id INTEGER,
+             PRIMARY KEY (tenant_id, id)
+         );
+         INSERT INTO __tenant_notes VALUES
+             ('acme', 'acme plan v2', 1),
+             ('acme', 'acme archive', 10),
+             ('globex', 'globex archive', 11);
+         CREATE TABLE __tenant_ledger (id INTEGER PRIMARY KEY, entry TEXT, tenant_id TEXT NOT NULL);
+         INSERT INTO __tenant_ledger VALUES (2, 'acme entry', 'acme');",
+    )?;
+    let import = |strategy: &str| {
+        conn.execute_batch(&format!(
+            "IMPORT TENANT 'acme' FROM '{}' INTO notes (id, body) ON CONFLICT {strategy};",
+            source.display()
+        ))
+    };
+    let acme_notes = |conn: &Connection| {
+        conn.execute_batch("SET TENANT = 'acme';")
+            .and_then(|()| notes(conn))
+    };
+    for (strategy, expected) in [
+        ("SKIP", ["acme plan", "acme archive"]),
+        ("REPLACE", ["acme plan v2", "acme archive"]),
+    ] {
+        for attempt in ["first", "repeated"] {
+            match import(strategy).and_then(|()| acme_notes(&conn)) {
+                Ok(seen) => t.assert_eq(
+                    &format!("{attempt} import ON CONFLICT {strategy}"),
+                    &seen,
+                    &expected.map(String::from).to_vec(),
+                ),
+                Err(e) => t.fail(&format!("{attempt} import ON CONFLICT {strategy}"), &e),
+            }
+        }
+    }
+    for attempt in ["first", "repeated"] {
+        match import("ERROR") {
+            Err(e) if e.to_string().contains("UNIQUE constraint failed") => {
+                t.ok(&format!("{attempt} import ON CONFLICT ERROR fails"))
+            }
+            other => t.fail(
+                &format!("{attempt} import ON CONFLICT ERROR fails"),
+                &format!("{other:?}"),
+            ),
+        }
+        t.assert_eq(
+            &format!("{attempt} failed import leaves no transaction open"),
+            &conn.is_autocommit(),
+            &true,
+        );
+    }
+    // The import switches to its tenant only while it runs, whether it
+    // succeeds or a conflict rolls it back.
+    conn.execute_batch("SET TENANT = 'globex';")?;
+    let session_tenant = |conn: &Connection| {
+        conn.query_row(
+            "SELECT group_concat(value) FROM sec_context WHERE key = 'tenant'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+    };
+    for strategy in ["SKIP", "ERROR"] {
+        let _ = import(strategy);
+        match session_tenant(&conn) {
+            Ok(tenant) => t.assert_eq(
+                &format!("import ON CONFLICT {strategy} restores the caller's tenant"),
+                &tenant,
+                &"globex".to_string(),
+            ),
+            Err(e) => t.fail("read the session's tenant", &e),
+        }
+    }
+    let sql = CString::new(format!(
+        "IMPORT TENANT 'acme' FROM '{}' INTO notes (id, body);",
+        source.display()
+    ))
+    .expect("no NUL in the path");
+    let (rc, errmsg) = unsafe {
+        let rc = ffi::sqlite3_exec(
+            conn.handle(),
+            sql.as_ptr(),
+            None,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        );
+        let errmsg = CStr::from_ptr(ffi::sqlite3_errmsg(conn.handle()));
+        (rc, errmsg.to_string_lossy().into_owned())
+    };
+    t.assert_eq(
+        "a failed import through sqlite3_exec keeps its error",
+        &(rc != ffi::SQLITE_OK && errmsg.contains("UNIQUE constraint failed")),
+        &true,
+    );
+    match session_tenant(&conn) {
+        Ok(tenant) => t.assert_eq(
+            "a failed import through sqlite3_exec restores the caller's tenant",
+            &tenant,
+            &"globex".to_string(),
+        ),
+        Err(e) => t.fail("read the session's tenant", &e),
+    }
+    match conn
+        .execute_batch("SET TENANT = 'globex';")
+        .and_then(|()| notes(&conn))
+    {
+        Ok(seen) => t.assert_eq(
+            "other tenants' rows are not imported",
+            &seen,
+            &vec!["globex plan".to_string()],
+        ),
+        Err(e) => t.fail("read notes as globex", &e),
+    }
+
+    // A table whose keys predate scoping by tenant, where globex already
+    // uses the id 2 of the source's acme entry.
+    conn.execute_batch(
+        "CREATE TABLE __tenant_ledger (id INTEGER PRIMARY KEY, entry TEXT, tenant_id TEXT NOT NULL);
+         INSERT INTO __tenant_ledger VALUES (2, 'globex entry', 'globex');",
+    )?;
+    for strategy in ["SKIP", "REPLACE"] {
+        let imported = conn.execute_batch(&format!(
+            "IMPORT TENANT 'acme' FROM '{}' INTO notes (id, body), ledger (id, entry)
+             ON CONFLICT {strategy};",
+            source.display()
+        ));
+        match imported {
+            Err(e) if e.to_string().contains("UNIQUE constraint failed") => t.ok(&format!(
+                "ON CONFLICT {strategy} fails on another tenant's key"
+            )),
+            other => t.fail(
+                &format!("ON CONFLICT {strategy} fails on another tenant's key"),
+                &format!("{other:?}"),
+            ),
+        }
+        match conn.query_row(
+            "SELECT group_concat(entry || ':' || tenant_id) FROM __tenant_ledger",
+            [],
+            |row| row.get::<_, String>(0),
+        ) {
+            Ok(ledger) => t.assert_eq(
+                &format!("ON CONFLICT {strategy} leaves globex's row alone"),
+                &ledger,
+                &"globex entry:globex".to_string(),
+            ),
+            Err(e) => t.fail("read __tenant_ledger", &e),
+        }
+    }
+    conn.execute_batch("DELETE FROM __tenant_notes WHERE tenant_id = 'acme' AND id = 10;")?;
+    let _ = conn.execute_batch(&format!(
+        "IMPORT TENANT 'acme' FROM '{}' INTO notes (id, body), ledger (id, entry)
+         ON CONFLICT REPLACE;",
+        source.display()
+    ));
+    match acme_notes(&conn) {
+        Ok(seen) => t.assert_eq(
+            "a failed import copies none of its tables",
+            &seen,
+            &vec!["acme plan v2".to_string()],
+        ),
+        Err(e) => t.fail("read notes as acme", &e),
+    }
+    conn.execute_batch("DROP TABLE __tenant_ledger;")?;
+
+    t.section("SQLSHIM_ALLOW");
+    // SAFETY: lazytest is single-threaded; the shim reads the variable per statement.
+    unsafe { std::env::set_var("SQLSHIM_ALLOW", "SET CONTEXT, CREATE POLICY") };
+    match conn.execute_batch("SET CONTEXT role = 'admin';") {
+        Ok(()) => t.ok("allowed SET CONTEXT is rewritten"),
+        Err(e) => t.fail("allowed SET CONTEXT", &e),
+    }
+    match conn.execute_batch("DEFINE LABEL 'role=auditor';") {
+        Ok(()) => t.fail("disallowed DEFINE LABEL", &"was rewritten"),
+        Err(e) => t.assert_eq(
+            "disallowed DEFINE LABEL passes through and SQLite rejects it",
+            &e.to_string().contains("syntax error"),
+            &true,
+        ),
+    }
+    unsafe { std::env::remove_var("SQLSHIM_ALLOW") };
+
+    t.section("Legacy sqlite3_prepare");
+    // rusqlite only uses prepare_v2/v3; older bindings still call v1.
+    conn.execute_batch("CLEAR CONTEXT;")?;
+    let rc = unsafe {
+        let db = conn.handle();
+        let sql = c"SET CONTEXT role = 'legacy';";
+        let mut stmt = std::ptr::null_mut();
+        let mut rc = sqlite3_prepare(db, sql.as_ptr(), -1, &mut stmt, std::ptr::null_mut());
+        if rc == ffi::SQLITE_OK {
+            while ffi::sqlite3_step(stmt) == ffi::SQLITE_ROW {}
+            rc = ffi::sqlite3_finalize(stmt);
+        }
+        rc
+    };
+    if rc == ffi::SQLITE_OK {
+        let role: String = conn.query_row(
+            "SELECT value FROM sec_context WHERE key = 'role'",
+            [],
+            |row| row.get(0),
+        )?;
+        t.assert_eq(
+            "SET CONTEXT prepared through sqlite3_prepare is rewritten",
+            &role,
+            &"legacy".to_string(),
+        );
+    } else {
+        t.fail("sqlite3_prepare SET CONTEXT", &format!("rc={rc}"));
+    }
+
+    t.section("sqlite3_exec with several statements");
+    // Statements after a leading custom one still run, custom or not.
+    conn.execute_batch("CLEAR CONTEXT;")?;
+    let rc = unsafe {
+        let sql = c"SET CONTEXT role = 'exec'; \
+                    INSERT INTO orders (id, status, total) VALUES (50, 'open', 5); \
+                    SET CONTEXT team = 'exec';";
+        ffi::sqlite3_exec(
+            conn.handle(),
+            sql.as_ptr(),
+            None,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if rc == ffi::SQLITE_OK {
+        let inserted: i64 =
+            conn.query_row("SELECT COUNT(*) FROM orders WHERE id = 50", [], |row| {
+                row.get(0)
+            })?;
+        t.assert_eq(
+            "plain SQL after a custom statement in sqlite3_exec runs",
+            &inserted,
+            &1,
+        );
+        let team: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sec_context WHERE key = 'team' AND value = 'exec'",
+            [],
+            |row| row.get(0),
+        )?;
+        t.assert_eq(
+            "a later custom statement in sqlite3_exec is rewritten",
+            &team,
+            &1,
+        );
+    } else {
+        t.fail("sqlite3_exec several statements", &format!("rc={rc}"));
+    }
+
+    t.section("UTF-16 sqlite3_prepare16_v2");
+    let sql: Vec<u16> = "DEFINE LABEL 'team=utf16';\nSELECT '🚀';"
+        .encode_utf16()
+        .collect();
+    let (rc, tail_units) = unsafe {
+        let mut stmt = std::ptr::null_mut();
+        let mut tail = std::ptr::null();
+        let mut rc = sqlite3_prepare16_v2(
+            conn.handle(),
+            sql.as_ptr().cast(),
+            (2 * sql.len()) as i32,
+            &mut stmt,
+            &mut tail,
+        );
+        if rc == ffi::SQLITE_OK {
+            while ffi::sqlite3_step(stmt) == ffi::SQLITE_ROW {}
+            rc = ffi::sqlite3_finalize(stmt);
+        }
+        (rc, tail.cast::<u16>().offset_from(sql.as_ptr()))
+    };
+    if rc == ffi::SQLITE_OK {
+        let defined: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sec_labels WHERE expr = 'team=utf16')",
+            [],
+            |row| row.get(0),
+        )?;
+        t.assert_eq("UTF-16 DEFINE LABEL is rewritten", &defined, &true);
+        t.assert_eq(
+            "tail points past the rewritten statement in UTF-16 units",
+            &String::from_utf16_lossy(&sql[tail_units as usize..]),
+            &"\nSELECT '🚀';".to_string(),
+        );
+    } else {
+        t.fail("sqlite3_prepare16_v2 DEFINE LABEL", &format!("rc={rc}"));
+    }
+
+    t.section("sqlshim::rewrite_sql");
+    match sqlshim::rewrite_sql("SET CONTEXT role = 'in_process';") {
+        Some(sql) => {
+            conn.execute_batch(&format!("CLEAR CONTEXT; {sql}"))?;
+            let role: String = conn.query_row(
+                "SELECT value FROM sec_context WHERE key = 'role'",
+                [],
+                |row| row.get(0),
+            )?;
+            t.assert_eq(
+                "rewrite_sql output runs without the preloaded shim's help",
+                &role,
+                &"in_process".to_string(),
+            );
+        }
+        None => t.fail("rewrite_sql SET CONTEXT", &"not rewritten"),
+    }
+    t.assert_eq(
+        "rewrite_sql leaves plain SQL alone",
+        &sqlshim::rewrite_sql("SELECT 1;"),
+        &None,
+    );
+
+    t.section("CREATE POLICY enforced in secure views");
+    let ledger_rows = |conn: &Connection| -> Result<Vec<String>> {
+        conn.execute_batch("REFRESH SECURE VIEWS;")?;
+        conn.prepare("SELECT department FROM ledger ORDER BY id")?
+            .query_map([], |row| row.get(0))?
+            .collect()
+    };
+    match conn
+        .execute_batch(
+            r#"
+            CREATE TABLE __sec_ledger (
+                id INTEGER PRIMARY KEY,
+                department TEXT,
+                row_label_id INTEGER
+            );
+            INSERT INTO __sec_ledger VALUES (1, 'finance', NULL), (2, 'sales', NULL);
+            REGISTER SECURE TABLE ledger
+            ON __sec_ledger
+            WITH ROW LABEL row_label_id;
+            CREATE POLICY ledger_finance ON ledger FOR SELECT USING (department='finance');
+            "#,
+        )
+        .and_then(|()| ledger_rows(&conn))
+    {
+        Ok(rows) => t.assert_eq(
+            "policies are only stored until enforce_policies is set",
+            &rows,
+            &vec!["finance".to_string(), "sales".to_string()],
+        ),
+        Err(e) => t.fail("CREATE POLICY on a secure table", &e),
+    }
+    match conn
+        .execute_batch("UPDATE sec_meta SET value = 1 WHERE key = 'enforce_policies';")
+        .and_then(|()| ledger_rows(&conn))
+    {
+        Ok(rows) => t.assert_eq(
+            "USING (department='finance') restricts the view's rows",
+            &rows,
+            &vec!["finance".to_string()],
+        ),
+        Err(e) => t.fail("enforced CREATE POLICY", &e),
+    }
+    conn.execute_batch(
+        "UPDATE sec_meta SET value = 0 WHERE key = 'enforce_policies';
+         DROP POLICY ledger_finance ON ledger;
+         REFRESH SECURE VIEWS;",
+    )?;
+
+    t.section("UPDATE / DELETE policies guard visible rows");
+    conn.execute_batch(
+        "UPDATE sec_meta SET value = 1 WHERE key = 'enforce_policies';
+         CREATE POLICY ledger_delete ON ledger FOR DELETE
+             USING (sec_get_attr('role') = 'admin');
+         CREATE POLICY ledger_update ON ledger FOR UPDATE USING (department <> 'finance');
+         CLEAR CONTEXT;
+         SET CONTEXT role = 'clerk';
+         REFRESH SECURE VIEWS;",
+    )?;
+    let ledger_count = |conn: &Connection| -> Result<i64> {
+        conn.query_row("SELECT COUNT(*) FROM ledger", [], |r| r.get(0))
+    };
+    t.assert_eq("clerk sees both ledger rows", &ledger_count(&conn)?, &2);
+    match conn.execute("DELETE FROM ledger WHERE id = 2", []) {
+        Err(e)
+            if e.to_string()
+                .contains("DELETE denied by policy ledger_delete") =>
+        {
+            t.ok("DELETE policy blocks deleting a visible row")
+        }
+        Err(e) => t.fail("DELETE policy blocks deleting a visible row", &e),
+        Ok(_) => t.fail(
+            "DELETE policy blocks deleting a visible row",
+            &"row deleted",
+        ),
+    }
+    match conn.execute("UPDATE ledger SET department = 'audit' WHERE id = 1", []) {
+        Err(e)
+            if e.to_string()
+                .contains("UPDATE denied by policy ledger_update") =>
+        {
+            t.ok("UPDATE policy blocks updating a visible row")
+        }
+        Err(e) => t.fail("UPDATE policy blocks updating a visible row", &e),
+        Ok(_) => t.fail(
+            "UPDATE policy blocks updating a visible row",
+            &"row updated",
+        ),
+    }
+    match conn
+        .execute(
+            "UPDATE ledger SET department = 'marketing' WHERE id = 2",
+            [],
+        )
+        .and_then(|_| {
+            conn.execute_batch("CLEAR CONTEXT; SET CONTEXT role = 'admin'; REFRESH SECURE VIEWS;")
+        })
+        .and_then(|()| conn.execute("DELETE FROM ledger WHERE id = 2", []))
+        .and_then(|_| ledger_count(&conn))
+    {
+        Ok(count) => t.assert_eq(
+            "rows the policies allow are still updated and deleted",
+            &count,
+            &1,
+        ),
+        Err(e) => t.fail("UPDATE / DELETE allowed by policy", &e),
+    }
+    conn.execute_batch(
+        "UPDATE sec_meta SET value = 0 WHERE key = 'enforce_policies';
+         DROP POLICY ledger_delete ON ledger;
+         DROP POLICY ledger_update ON ledger;
+         CLEAR CONTEXT;
+         REFRESH SECURE VIEWS;",
+    )?;
+
     t.section("Normal SQL Passthrough");
     conn.execute_batch("CREATE TABLE test_table (id INTEGER PRIMARY KEY, name TEXT);")?;
     conn.execute_batch("INSERT INTO test_table (id, name) VALUES (1, 'test');")?;
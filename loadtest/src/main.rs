@@ -195,9 +195,10 @@ impl Oracle {
     fn new() -> Self {
         let mut balances = Vec::with_capacity(TENANTS * ACCOUNTS_PER_TENANT + 1);
         balances.push(0);
-        for _ in 0..TENANTS * ACCOUNTS_PER_TENANT {
-            balances.push(INITIAL_BALANCE);
-        }
+        balances.extend(std::iter::repeat_n(
+            INITIAL_BALANCE,
+            TENANTS * ACCOUNTS_PER_TENANT,
+        ));
 
         Self {
             balances,
@@ -1735,6 +1736,7 @@ fn apply_context(
                 tenant_name(tenant_idx)
             ))?;
         }
+        conn.execute_batch("REFRESH SECURE VIEWS;")?;
     } else {
         conn.query_row::<i64, _, _>("SELECT sec_clear_context()", [], |r| r.get(0))?;
         conn.query_row::<i64, _, _>(
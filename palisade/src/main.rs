@@ -0,0 +1,220 @@
+use std::{
+    env,
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use rusqlite::{
+    Batch,
+    Connection,
+    OpenFlags,
+    fallible_iterator::FallibleIterator,
+    types::ValueRef,
+};
+
+type AppResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Debug, Default)]
+struct Config {
+    keyfile: Option<PathBuf>,
+    passphrase: Option<String>,
+    kms_key_id: Option<String>,
+    kms_endpoint: Option<String>,
+    evfs_lib: Option<PathBuf>,
+    sql_file: Option<PathBuf>,
+    verbose: bool,
+    db_path: Option<PathBuf>,
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("palisade: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> AppResult<()> {
+    let cfg = parse_args(env::args().skip(1).collect())?;
+    let db_path = cfg.db_path.clone().ok_or("missing database path")?;
+
+    let sql = match &cfg.sql_file {
+        Some(path) => fs::read_to_string(path)?,
+        None => {
+            let mut sql = String::new();
+            io::stdin().read_to_string(&mut sql)?;
+            sql
+        }
+    };
+
+    configure_key_source(&cfg)?;
+    let evfs_lib = cfg
+        .evfs_lib
+        .clone()
+        .or_else(|| env::var_os("PALISADE_EVFS_LIB").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("libsqlevfs"));
+
+    // The extension registers the `evfs` VFS when it is first loaded; a
+    // throwaway in-memory connection is enough to get it in place before
+    // the database itself is opened through it.
+    let loader = Connection::open_in_memory()?;
+    load_sqlevfs_on_conn(&loader, &evfs_lib)?;
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs",
+    )?;
+    // Loading again only adds the SQL functions to this connection.
+    load_sqlevfs_on_conn(&conn, &evfs_lib)?;
+
+    let report: String = conn.query_row("SELECT evfs_storage_policy()", [], |row| row.get(0))?;
+    if cfg.verbose {
+        eprintln!("{report}");
+    }
+
+    execute_sql(&conn, &sql)
+}
+
+/// Hand the key source to the extension, which reads it from the
+/// environment when it is loaded.
+fn configure_key_source(cfg: &Config) -> AppResult<()> {
+    let vars = [
+        (
+            "EVFS_KEYFILE",
+            cfg.keyfile.as_ref().map(|p| p.display().to_string()),
+        ),
+        ("EVFS_PASSPHRASE", cfg.passphrase.clone()),
+        ("EVFS_KMS_KEY_ID", cfg.kms_key_id.clone()),
+        ("EVFS_KMS_ENDPOINT", cfg.kms_endpoint.clone()),
+    ];
+    let given = vars[..3].iter().filter(|(_, v)| v.is_some()).count();
+    if given > 1 {
+        return Err("--keyfile, --passphrase and --kms-key-id are mutually exclusive".into());
+    }
+    if given == 0 {
+        let from_env = [
+            "EVFS_KEYFILE",
+            "EVFS_PASSPHRASE",
+            "EVFS_KEK",
+            "EVFS_KMS_KEY_ID",
+        ]
+        .iter()
+        .any(|v| env::var_os(v).is_some());
+        if !from_env {
+            return Err("no key source: pass --keyfile, --passphrase or --kms-key-id".into());
+        }
+        return Ok(());
+    }
+
+    for (name, value) in vars {
+        match value {
+            // SAFETY: single-threaded; no other thread reads the environment yet.
+            Some(value) => unsafe { env::set_var(name, value) },
+            None => unsafe { env::remove_var(name) },
+        }
+    }
+    unsafe { env::remove_var("EVFS_KEK") };
+    Ok(())
+}
+
+fn load_sqlevfs_on_conn(conn: &Connection, evfs_lib: &PathBuf) -> AppResult<()> {
+    unsafe {
+        conn.load_extension_enable()?;
+        conn.load_extension(evfs_lib, None::<&str>)?;
+        conn.load_extension_disable()?;
+    }
+    Ok(())
+}
+
+/// Run every statement in `sql`, printing result rows `|`-separated.
+fn execute_sql(conn: &Connection, sql: &str) -> AppResult<()> {
+    let mut batch = Batch::new(conn, sql);
+    while let Some(mut stmt) = batch.next()? {
+        let columns = stmt.column_count();
+        let mut rows = stmt.raw_query();
+        while let Some(row) = rows.next()? {
+            let fields = (0..columns)
+                .map(|i| row.get_ref(i).map(format_value))
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            println!("{}", fields.join("|"));
+        }
+    }
+    Ok(())
+}
+
+fn format_value(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned(),
+        ValueRef::Blob(b) => {
+            let hex: String = b.iter().map(|byte| format!("{byte:02X}")).collect();
+            format!("X'{hex}'")
+        }
+    }
+}
+
+fn parse_args(args: Vec<String>) -> AppResult<Config> {
+    let mut cfg = Config::default();
+    let mut i = 0usize;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--help" | "-h" => {
+                print_help();
+                std::process::exit(0);
+            }
+            "--keyfile" => {
+                i += 1;
+                cfg.keyfile = Some(args.get(i).ok_or("missing value for --keyfile")?.into());
+            }
+            "--passphrase" => {
+                i += 1;
+                cfg.passphrase = Some(args.get(i).ok_or("missing value for --passphrase")?.clone());
+            }
+            "--kms-key-id" => {
+                i += 1;
+                cfg.kms_key_id = Some(args.get(i).ok_or("missing value for --kms-key-id")?.clone());
+            }
+            "--kms-endpoint" => {
+                i += 1;
+                cfg.kms_endpoint = Some(
+                    args.get(i)
+                        .ok_or("missing value for --kms-endpoint")?
+                        .clone(),
+                );
+            }
+            "--evfs-lib" => {
+                i += 1;
+                cfg.evfs_lib = Some(args.get(i).ok_or("missing value for --evfs-lib")?.into());
+            }
+            "--sql-file" => {
+                i += 1;
+                cfg.sql_file = Some(args.get(i).ok_or("missing value for --sql-file")?.into());
+            }
+            "--verbose" | "-v" => cfg.verbose = true,
+            other if other.starts_with('-') => {
+                return Err(format!("unknown option '{other}'").into());
+            }
+            path => {
+                if cfg.db_path.replace(path.into()).is_some() {
+                    return Err("only one database path may be given".into());
+                }
+            }
+        }
+        i += 1;
+    }
+    Ok(cfg)
+}
+
+fn print_help() {
+    println!("Usage: palisade [options] <database>");
+    println!("  --keyfile PATH       KEK from a local keyfile");
+    println!("  --passphrase TEXT    KEK derived from a passphrase");
+    println!("  --kms-key-id ID      KEK from a cloud KMS key");
+    println!("  --kms-endpoint URL   KMS region / endpoint override");
+    println!("  --evfs-lib PATH      sqlevfs extension (default $PALISADE_EVFS_LIB or libsqlevfs)");
+    println!("  --sql-file PATH      SQL to execute (default: stdin)");
+    println!("  --verbose            print the storage policy report to stderr");
+}
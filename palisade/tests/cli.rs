@@ -0,0 +1,99 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+fn evfs_lib() -> PathBuf {
+    let lib = Path::new(env!("CARGO_MANIFEST_DIR")).join("../sqlevfs/target/debug/libsqlevfs.so");
+    assert!(
+        lib.exists(),
+        "{} is missing: run `cargo build` in ../sqlevfs first",
+        lib.display()
+    );
+    lib
+}
+
+fn palisade(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_palisade"))
+        .args(args)
+        .env_remove("EVFS_KEYFILE")
+        .env_remove("EVFS_PASSPHRASE")
+        .env_remove("EVFS_KEK")
+        .env_remove("EVFS_KMS_KEY_ID")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("spawn palisade");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().expect("wait for palisade")
+}
+
+#[test]
+fn creates_a_table_and_reads_it_back() {
+    let lib = evfs_lib();
+    let lib = lib.to_str().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let keyfile = dir.path().join("master.key");
+    std::fs::write(&keyfile, [0x42u8; 32]).unwrap();
+    let keyfile = keyfile.to_str().unwrap();
+    let db = dir.path().join("app.db");
+    let db = db.to_str().unwrap();
+
+    let sql_file = dir.path().join("setup.sql");
+    std::fs::write(
+        &sql_file,
+        "CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT);
+         INSERT INTO notes (body) VALUES ('hello'), ('world');
+         SELECT id, body FROM notes ORDER BY id;",
+    )
+    .unwrap();
+    let out = palisade(
+        &[
+            "--keyfile",
+            keyfile,
+            "--evfs-lib",
+            lib,
+            "--sql-file",
+            sql_file.to_str().unwrap(),
+            "--verbose",
+            db,
+        ],
+        "",
+    );
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "1|hello\n2|world\n");
+    assert!(String::from_utf8_lossy(&out.stderr).contains("journal_mode:"));
+
+    // A second run reads the encrypted file back from stdin-provided SQL.
+    let out = palisade(
+        &["--keyfile", keyfile, "--evfs-lib", lib, db],
+        "SELECT body FROM notes WHERE id = 2;",
+    );
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "world\n");
+
+    let raw = std::fs::read(db).unwrap();
+    assert!(!raw.windows(5).any(|w| w == b"hello"));
+}
+
+#[test]
+fn requires_a_key_source() {
+    let out = palisade(&["app.db"], "SELECT 1;");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("no key source"));
+}
@@ -0,0 +1,98 @@
+//! Page encryption throughput, in pages/sec, for each supported cipher.
+//! Run with `cargo bench --bench page_crypto`, and again with
+//! `--features parallel` to compare the rayon batch path.
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use sqlevfs::crypto::{
+    keys::Dek,
+    page::{MIN_RESERVE, decrypt_page, decrypt_pages, encrypt_page, encrypt_pages},
+};
+
+const PAGE_SIZES: [u32; 3] = [512, 4096, 65536];
+
+/// Pages per batch; above `PARALLEL_THRESHOLD` so `parallel` kicks in.
+const BATCH: usize = 256;
+
+fn plaintext(page_size: u32, pages: usize) -> Vec<u8> {
+    (0..page_size as usize * pages).map(|i| i as u8).collect()
+}
+
+fn single_page(c: &mut Criterion) {
+    let dek = Dek::generate();
+    let mut group = c.benchmark_group("aes-gcm/page");
+    group.throughput(Throughput::Elements(1));
+    for page_size in PAGE_SIZES {
+        let plain = plaintext(page_size, 1);
+        group.bench_with_input(
+            BenchmarkId::new("encrypt", page_size),
+            &plain,
+            |b, plain| {
+                b.iter_batched_ref(
+                    || plain.clone(),
+                    |page| encrypt_page(page, 2, &dek, MIN_RESERVE).unwrap(),
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+
+        let mut sealed = plain;
+        encrypt_page(&mut sealed, 2, &dek, MIN_RESERVE).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("decrypt", page_size),
+            &sealed,
+            |b, sealed| {
+                b.iter_batched_ref(
+                    || sealed.clone(),
+                    |page| decrypt_page(page, 2, &dek, MIN_RESERVE).unwrap(),
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+/// `encrypt_pages` / `decrypt_pages` over a multi-page buffer. These take
+/// the rayon path when built with `parallel` and run serially otherwise.
+fn batch(c: &mut Criterion) {
+    let dek = Dek::generate();
+    let path = if cfg!(feature = "parallel") {
+        "parallel"
+    } else {
+        "serial"
+    };
+    let mut group = c.benchmark_group(format!("aes-gcm/batch-{path}"));
+    group.throughput(Throughput::Elements(BATCH as u64));
+    for page_size in PAGE_SIZES {
+        let plain = plaintext(page_size, BATCH);
+        group.bench_with_input(
+            BenchmarkId::new("encrypt", page_size),
+            &plain,
+            |b, plain| {
+                b.iter_batched_ref(
+                    || plain.clone(),
+                    |buf| encrypt_pages(buf, page_size, 2, &dek, MIN_RESERVE).unwrap(),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+
+        let mut sealed = plain;
+        encrypt_pages(&mut sealed, page_size, 2, &dek, MIN_RESERVE).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("decrypt", page_size),
+            &sealed,
+            |b, sealed| {
+                b.iter_batched_ref(
+                    || sealed.clone(),
+                    |buf| decrypt_pages(buf, page_size, 2, &dek, MIN_RESERVE).unwrap(),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, single_page, batch);
+criterion_main!(benches);
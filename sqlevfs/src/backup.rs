@@ -14,11 +14,12 @@ use bincode::config;
 use crate::{
     crypto::{
         envelope,
-        keys::{Dek, WrappedDek},
+        header,
+        keys::{Dek, KeyScope, WrappedDek},
         page as page_crypto,
     },
     debug,
-    keyring::Keyring,
+    keyring::{self, EMBEDDED_KEYRING_SIZE, Keyring},
     kms::KmsProvider,
 };
 
@@ -89,6 +90,11 @@ pub fn create_backup(
             let src_dek = source_keyring.dek_for(&crate::crypto::keys::KeyScope::Database)?;
             page_crypto::decrypt_page(&mut page_buf, page_no, &src_dek, reserve)?;
         }
+        // A sealed header is opened so the backup restores under any keyring.
+        if page_no == 1 && header::is_sealed_header(&page_buf, reserve) {
+            let header_dek = source_keyring.dek_for(&KeyScope::Header)?;
+            header::open_header(&mut page_buf, &header_dek, reserve)?;
+        }
 
         // Re-encrypt under backup DEK.
         page_crypto::encrypt_page(&mut page_buf, page_no, &backup_dek, reserve)?;
@@ -235,6 +241,79 @@ impl VerifyResult {
     }
 }
 
+/// Check every page of an encrypted database without opening it in
+/// SQLite.
+///
+/// Page size and reserve are taken from page 1, which EVFS keeps in
+/// plaintext. Every other page must carry the EVFS marker and pass its
+/// AES-GCM tag under the keyring's database DEK.
+pub fn verify_integrity(db_path: &Path, keyring: &Keyring) -> anyhow::Result<VerifyReport> {
+    let raw = std::fs::read(db_path)?;
+    let data = if keyring::is_embedded_block(&raw) {
+        raw.get(EMBEDDED_KEYRING_SIZE..).unwrap_or_default()
+    } else {
+        &raw[..]
+    };
+    anyhow::ensure!(data.len() >= 100, "database too short for a header");
+
+    let page_size = match u16::from_be_bytes([data[16], data[17]]) {
+        1 => 65536,
+        n => n as usize,
+    };
+    anyhow::ensure!(
+        (512..=65536).contains(&page_size) && page_size.is_power_of_two(),
+        "cannot determine page size from page 1 (got {page_size})"
+    );
+    let reserve = data[20] as usize;
+
+    let header_intact = is_plaintext_header(data)
+        && reserve >= page_crypto::MIN_RESERVE
+        && data.len() % page_size == 0;
+
+    let dek = keyring.dek_for(&KeyScope::Database)?;
+    let page_count = data.len().div_ceil(page_size);
+    let mut failed_pages = vec![];
+
+    for (i, chunk) in data.chunks(page_size).enumerate().skip(1) {
+        let page_no = i as u32 + 1;
+        let mut page_buf = chunk.to_vec();
+        let result =
+            if page_buf.len() != page_size || !page_crypto::is_encrypted_page(&page_buf, reserve) {
+                Err(page_crypto::PageError::MissingMarker.into())
+            } else {
+                page_crypto::decrypt_page(&mut page_buf, page_no, &dek, reserve)
+            };
+        if let Err(e) = result {
+            if debug() {
+                eprintln!("sqlevfs: verify_integrity: page {page_no} failed: {e}");
+            }
+            failed_pages.push(page_no);
+        }
+    }
+
+    Ok(VerifyReport {
+        page_count: page_count as u32,
+        header_intact,
+        failed_pages,
+    })
+}
+
+#[derive(Debug)]
+pub struct VerifyReport {
+    pub page_count: u32,
+    /// Page 1 has the SQLite magic, room for the EVFS reserve, and the
+    /// file is a whole number of pages.
+    pub header_intact: bool,
+    /// Pages that lack the EVFS marker or fail authentication.
+    pub failed_pages: Vec<u32>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.header_intact && self.failed_pages.is_empty()
+    }
+}
+
 /// Rotate backup encryption: re-wrap the backup DEK under a new KEK
 /// without re-encrypting every page.
 ///
@@ -279,6 +358,105 @@ pub fn rotate_backup_kek(
     Ok(())
 }
 
+/// Encrypt an existing plaintext SQLite database into a new evfs one.
+///
+/// The source is opened with the default VFS and copied with
+/// `VACUUM INTO`, asking SQLite for `reserve` spare bytes per page so
+/// every page has room for the EVFS trailer. Page 1 then stays
+/// plaintext, as in a freshly created evfs database, and every other
+/// page is encrypted under the keyring's database DEK. The keyring is
+/// bound to `dst_evfs`, so its sidecar is written next to it and the
+/// result opens under the evfs VFS with the same `page_size` and
+/// `reserve`.
+#[cfg(feature = "rusqlite")]
+pub fn encrypt_plaintext_db(
+    src_plaintext: &Path,
+    dst_evfs: &Path,
+    keyring: &Keyring,
+    page_size: u32,
+    reserve: usize,
+) -> anyhow::Result<()> {
+    use rusqlite::{Connection, OpenFlags};
+
+    page_crypto::validate_page_layout(page_size, reserve)?;
+    anyhow::ensure!(
+        !dst_evfs.exists(),
+        "{} already exists; refusing to overwrite it",
+        dst_evfs.display()
+    );
+    let dst = dst_evfs
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("destination path is not valid UTF-8"))?;
+
+    let src = Connection::open_with_flags(src_plaintext, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    src.execute_batch(&format!("PRAGMA page_size = {page_size};"))?;
+    let mut reserve_bytes = reserve as libc::c_int;
+    let rc = unsafe {
+        libsqlite3_sys::sqlite3_file_control(
+            src.handle(),
+            c"main".as_ptr(),
+            libsqlite3_sys::SQLITE_FCNTL_RESERVE_BYTES,
+            &mut reserve_bytes as *mut libc::c_int as *mut libc::c_void,
+        )
+    };
+    anyhow::ensure!(
+        rc == libsqlite3_sys::SQLITE_OK,
+        "SQLITE_FCNTL_RESERVE_BYTES failed: {rc}"
+    );
+    src.execute("VACUUM INTO ?1", [dst])?;
+    drop(src);
+
+    let result = encrypt_vacuumed_copy(dst_evfs, keyring, page_size, reserve);
+    if result.is_err() {
+        let _ = std::fs::remove_file(dst_evfs);
+    }
+    result
+}
+
+/// Encrypt pages 2.. of the plaintext `VACUUM INTO` output in place.
+#[cfg(feature = "rusqlite")]
+fn encrypt_vacuumed_copy(
+    path: &Path,
+    keyring: &Keyring,
+    page_size: u32,
+    reserve: usize,
+) -> anyhow::Result<()> {
+    let mut raw = std::fs::read(path)?;
+    anyhow::ensure!(
+        raw.len() >= 100 && is_plaintext_header(&raw),
+        "VACUUM INTO produced no SQLite header"
+    );
+    let copied_page_size = match u16::from_be_bytes([raw[16], raw[17]]) {
+        1 => 65536,
+        n => n as u32,
+    };
+    anyhow::ensure!(
+        copied_page_size == page_size && raw[20] as usize == reserve,
+        "copy has page_size {copied_page_size} and reserve {}, wanted {page_size} and {reserve}",
+        raw[20]
+    );
+    anyhow::ensure!(
+        raw.len() % page_size as usize == 0,
+        "copy size {} is not a multiple of page_size {page_size}",
+        raw.len()
+    );
+
+    keyring.set_sidecar_path(path);
+    let dek = keyring.dek_for(&KeyScope::Database)?;
+    let page_count = raw.len() / page_size as usize;
+    // Page 1 keeps its plaintext SQLite header.
+    page_crypto::encrypt_pages(&mut raw[page_size as usize..], page_size, 2, &dek, reserve)?;
+
+    std::fs::write(path, &raw)?;
+    if debug() {
+        eprintln!(
+            "sqlevfs: encrypted plaintext database: {page_count} pages -> {}",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
 fn is_plaintext_header(page: &[u8]) -> bool {
     page.len() >= 16 && &page[0..16] == b"SQLite format 3\0"
 }
@@ -392,6 +570,53 @@ mod tests {
         let _ = std::fs::remove_dir_all(&dir);
     }
 
+    #[test]
+    fn verify_integrity_reports_corrupted_page() {
+        let page_size: usize = 4096;
+        let reserve: usize = crate::crypto::page::MIN_RESERVE;
+        let page_count = 4;
+
+        let provider = test_provider([0x5A; 32]);
+        let keyring = Keyring::new(provider);
+        let dek = keyring.dek_for(&KeyScope::Database).unwrap();
+
+        let mut db_bytes = vec![0x11u8; page_count * page_size];
+        db_bytes[..16].copy_from_slice(b"SQLite format 3\0");
+        db_bytes[16..18].copy_from_slice(&(page_size as u16).to_be_bytes());
+        db_bytes[20] = reserve as u8;
+        for (i, page) in db_bytes.chunks_mut(page_size).enumerate().skip(1) {
+            crate::crypto::page::encrypt_page(page, i as u32 + 1, &dek, reserve).unwrap();
+        }
+
+        let dir = std::env::temp_dir().join("evfs-verify-integrity-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("test.db");
+        std::fs::write(&db_path, &db_bytes).unwrap();
+
+        let report = verify_integrity(&db_path, &keyring).unwrap();
+        assert!(report.is_ok(), "{report:?}");
+        assert_eq!(report.page_count, page_count as u32);
+
+        // Flip one ciphertext byte on page 3.
+        db_bytes[2 * page_size + 100] ^= 0x01;
+        std::fs::write(&db_path, &db_bytes).unwrap();
+
+        let report = verify_integrity(&db_path, &keyring).unwrap();
+        assert!(report.header_intact);
+        assert_eq!(report.failed_pages, vec![3]);
+
+        // A damaged header is reported even when the pages are fine.
+        db_bytes[2 * page_size + 100] ^= 0x01;
+        db_bytes[0] = b'X';
+        std::fs::write(&db_path, &db_bytes).unwrap();
+
+        let report = verify_integrity(&db_path, &keyring).unwrap();
+        assert!(!report.header_intact);
+        assert!(report.failed_pages.is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn kek_rotation_preserves_data() {
         let page_size: u32 = 4096;
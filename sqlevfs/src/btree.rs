@@ -0,0 +1,264 @@
+//! Just enough of the SQLite b-tree page format to find every page that
+//! belongs to one b-tree: interior pages, leaves and overflow chains.
+//! See <https://www.sqlite.org/fileformat2.html#b_tree_pages>.
+
+use std::collections::HashSet;
+
+const INDEX_INTERIOR: u8 = 0x02;
+const TABLE_INTERIOR: u8 = 0x05;
+const INDEX_LEAF: u8 = 0x0a;
+const TABLE_LEAF: u8 = 0x0d;
+
+/// Every page of the b-tree rooted at `root`, root first: interior and
+/// leaf pages plus the overflow pages of large cells. `read_page`
+/// returns a page's plaintext; `usable_size` is the page size minus the
+/// reserve. Fails on a malformed page or a page reached twice.
+pub fn btree_pages(
+    root: u32,
+    usable_size: usize,
+    mut read_page: impl FnMut(u32) -> anyhow::Result<Vec<u8>>,
+) -> anyhow::Result<Vec<u32>> {
+    let mut pages = Vec::new();
+    let mut seen = HashSet::new();
+    let mut visit = |page_no: u32, pages: &mut Vec<u32>| -> anyhow::Result<()> {
+        anyhow::ensure!(page_no != 0, "b-tree points at page 0");
+        anyhow::ensure!(seen.insert(page_no), "b-tree reaches page {page_no} twice");
+        pages.push(page_no);
+        Ok(())
+    };
+
+    let mut pending = vec![root];
+    while let Some(page_no) = pending.pop() {
+        visit(page_no, &mut pages)?;
+        let page = read_page(page_no)?;
+        let cells = parse_btree_page(&page, page_no, usable_size)?;
+        pending.extend(cells.children.iter().rev());
+        for mut next in cells.overflow {
+            while next != 0 {
+                visit(next, &mut pages)?;
+                let overflow = read_page(next)?;
+                anyhow::ensure!(overflow.len() >= 4, "overflow page {next} is truncated");
+                next = u32::from_be_bytes(overflow[..4].try_into().unwrap());
+            }
+        }
+    }
+    Ok(pages)
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct BtreeCells {
+    /// Child pages of an interior page, left to right.
+    children: Vec<u32>,
+    /// First overflow page of each cell that spills.
+    overflow: Vec<u32>,
+}
+
+fn parse_btree_page(page: &[u8], page_no: u32, usable_size: usize) -> anyhow::Result<BtreeCells> {
+    // Page 1 starts with the 100-byte database header.
+    let hdr = if page_no == 1 { 100 } else { 0 };
+    let byte = |at: usize| {
+        page.get(at)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("page {page_no} is truncated"))
+    };
+    let u16_at = |at: usize| -> anyhow::Result<usize> {
+        Ok(u16::from_be_bytes([byte(at)?, byte(at + 1)?]) as usize)
+    };
+    let u32_at = |at: usize| -> anyhow::Result<u32> {
+        Ok(u32::from_be_bytes([
+            byte(at)?,
+            byte(at + 1)?,
+            byte(at + 2)?,
+            byte(at + 3)?,
+        ]))
+    };
+
+    let kind = byte(hdr)?;
+    let interior = match kind {
+        INDEX_INTERIOR | TABLE_INTERIOR => true,
+        INDEX_LEAF | TABLE_LEAF => false,
+        _ => anyhow::bail!("page {page_no} is not a b-tree page (type {kind:#04x})"),
+    };
+    let n_cells = u16_at(hdr + 3)?;
+    let cell_ptrs = hdr + if interior { 12 } else { 8 };
+
+    let mut cells = BtreeCells::default();
+    for i in 0..n_cells {
+        let mut at = u16_at(cell_ptrs + 2 * i)?;
+        if interior {
+            cells.children.push(u32_at(at)?);
+            at += 4;
+        }
+        if kind == TABLE_INTERIOR {
+            continue;
+        }
+        let (payload, len) = read_varint(page.get(at..).unwrap_or_default());
+        at += len;
+        if kind == TABLE_LEAF {
+            at += read_varint(page.get(at..).unwrap_or_default()).1;
+        }
+        let local = local_payload(kind, payload, usable_size);
+        if local < payload as usize {
+            cells.overflow.push(u32_at(at + local)?);
+        }
+    }
+    if interior {
+        cells.children.push(u32_at(hdr + 8)?);
+    }
+    Ok(cells)
+}
+
+/// Bytes of a `payload`-byte cell stored on the b-tree page itself; the
+/// rest spills to overflow pages.
+fn local_payload(kind: u8, payload: u64, usable_size: usize) -> usize {
+    let u = usable_size as u64;
+    let max_local = if kind == TABLE_LEAF {
+        u - 35
+    } else {
+        (u - 12) * 64 / 255 - 23
+    };
+    if payload <= max_local {
+        return payload as usize;
+    }
+    let min_local = (u - 12) * 32 / 255 - 23;
+    let k = min_local + (payload - min_local) % (u - 4);
+    (if k <= max_local { k } else { min_local }) as usize
+}
+
+/// SQLite's big-endian varint: up to 8 bytes of 7 bits, then a full
+/// ninth byte. Returns the value and its length.
+fn read_varint(bytes: &[u8]) -> (u64, usize) {
+    let mut value = 0u64;
+    for (i, &b) in bytes.iter().take(9).enumerate() {
+        if i == 8 {
+            return ((value << 8) | b as u64, 9);
+        }
+        value = (value << 7) | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            return (value, i + 1);
+        }
+    }
+    (value, bytes.len().min(9))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    const PAGE: usize = 1024;
+
+    fn varint(mut v: u64) -> Vec<u8> {
+        assert!(v < 1 << 56);
+        let mut out = vec![(v & 0x7f) as u8];
+        v >>= 7;
+        while v > 0 {
+            out.insert(0, (v & 0x7f) as u8 | 0x80);
+            v >>= 7;
+        }
+        out
+    }
+
+    /// A b-tree page of `kind` holding `cells`, packed from the end.
+    fn btree_page(kind: u8, cells: &[Vec<u8>], right_child: Option<u32>) -> Vec<u8> {
+        let mut page = vec![0u8; PAGE];
+        page[0] = kind;
+        page[3..5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+        let ptrs = if let Some(right) = right_child {
+            page[8..12].copy_from_slice(&right.to_be_bytes());
+            12
+        } else {
+            8
+        };
+        let mut end = PAGE;
+        for (i, cell) in cells.iter().enumerate() {
+            end -= cell.len();
+            page[end..end + cell.len()].copy_from_slice(cell);
+            page[ptrs + 2 * i..ptrs + 2 * i + 2].copy_from_slice(&(end as u16).to_be_bytes());
+        }
+        page
+    }
+
+    fn overflow_page(next: u32) -> Vec<u8> {
+        let mut page = vec![0u8; PAGE];
+        page[..4].copy_from_slice(&next.to_be_bytes());
+        page
+    }
+
+    /// A table leaf cell for a `payload`-byte record spilling to `first`.
+    fn spilled_leaf_cell(rowid: u64, payload: u64, first: u32) -> Vec<u8> {
+        let mut cell = varint(payload);
+        cell.extend(varint(rowid));
+        cell.extend(vec![0xab; local_payload(TABLE_LEAF, payload, PAGE)]);
+        cell.extend(first.to_be_bytes());
+        cell
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for v in [0, 1, 127, 128, 16_383, 16_384, 1 << 40] {
+            assert_eq!(read_varint(&varint(v)), (v, varint(v).len()));
+        }
+        assert_eq!(read_varint(&[0xff; 9]), (u64::MAX, 9));
+    }
+
+    #[test]
+    fn local_payload_matches_the_file_format() {
+        // usable 1024: table leaves keep up to 989 bytes, else spill to
+        // M = 103 or K when that fits.
+        assert_eq!(local_payload(TABLE_LEAF, 989, PAGE), 989);
+        assert_eq!(local_payload(TABLE_LEAF, 990, PAGE), 103);
+        assert_eq!(
+            local_payload(TABLE_LEAF, 5000, PAGE),
+            103 + (5000 - 103) % 1020
+        );
+        assert_eq!(local_payload(INDEX_LEAF, 230, PAGE), 230);
+        assert_eq!(local_payload(INDEX_LEAF, 231, PAGE), 103);
+    }
+
+    #[test]
+    fn walks_interior_leaves_and_overflow_chains() -> anyhow::Result<()> {
+        let payload = 3000;
+        let mut db: HashMap<u32, Vec<u8>> = HashMap::new();
+        // Root 2 → leaves 3 and 4; leaf 4's row spills to 5 → 6 → 7.
+        let mut left = 3u32.to_be_bytes().to_vec();
+        left.extend(varint(1));
+        db.insert(2, btree_page(TABLE_INTERIOR, &[left], Some(4)));
+        let mut small = varint(1);
+        small.extend(varint(1));
+        small.push(0);
+        db.insert(3, btree_page(TABLE_LEAF, &[small], None));
+        db.insert(
+            4,
+            btree_page(TABLE_LEAF, &[spilled_leaf_cell(2, payload, 5)], None),
+        );
+        db.insert(5, overflow_page(6));
+        db.insert(6, overflow_page(7));
+        db.insert(7, overflow_page(0));
+
+        let read = |n: u32| {
+            db.get(&n)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("no page {n}"))
+        };
+        assert_eq!(btree_pages(2, PAGE, read)?, vec![2, 3, 4, 5, 6, 7]);
+        Ok(())
+    }
+
+    #[test]
+    fn cycles_and_foreign_pages_are_rejected() {
+        let looped = |n: u32| {
+            Ok(if n == 2 {
+                btree_page(TABLE_LEAF, &[spilled_leaf_cell(1, 3000, 3)], None)
+            } else {
+                overflow_page(3)
+            })
+        };
+        let err = btree_pages(2, PAGE, looped).unwrap_err();
+        assert!(err.to_string().contains("twice"), "{err}");
+
+        let err = btree_pages(2, PAGE, |_| Ok(overflow_page(0))).unwrap_err();
+        assert!(err.to_string().contains("not a b-tree page"), "{err}");
+    }
+}
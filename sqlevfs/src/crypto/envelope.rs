@@ -1,11 +1,32 @@
 use aes_gcm::{Aes256Gcm, KeyInit, Nonce, aead::Aead};
+use hkdf::Hkdf;
+use sha2::Sha256;
 
 use super::keys::{Dek, WrappedDek};
-use crate::kms::KmsProvider;
+use crate::{
+    keyring::KeyringError,
+    kms::{KmsMetrics, KmsProvider, is_transient},
+};
 
 /// Wrap a DEK under the current KEK from the provider.
 pub fn wrap_dek(dek: &Dek, provider: &dyn KmsProvider) -> anyhow::Result<WrappedDek> {
-    let (kek_id, kek_bytes) = provider.get_kek()?;
+    wrap_dek_with_metrics(dek, provider, None)
+}
+
+/// [`wrap_dek`], reporting the operation to `metrics` if given.
+pub fn wrap_dek_with_metrics(
+    dek: &Dek,
+    provider: &dyn KmsProvider,
+    metrics: Option<&dyn KmsMetrics>,
+) -> anyhow::Result<WrappedDek> {
+    trace_span!("wrap_dek", cipher = "aes-256-gcm");
+    if let Some(m) = metrics {
+        m.on_wrap();
+    }
+    let (kek_id, kek_bytes) = {
+        trace_span!("kms.get_kek", provider = ?provider.blob_tag());
+        provider.get_kek()?
+    };
     anyhow::ensure!(kek_bytes.len() == 32, "KEK must be 32 bytes");
 
     let cipher = Aes256Gcm::new_from_slice(&kek_bytes)?;
@@ -22,16 +43,67 @@ pub fn wrap_dek(dek: &Dek, provider: &dyn KmsProvider) -> anyhow::Result<Wrapped
     })
 }
 
-/// Unwrap a DEK using the provider to resolve the KEK.
-pub fn unwrap_dek(wrapped: &WrappedDek, provider: &dyn KmsProvider) -> anyhow::Result<Dek> {
-    let kek_bytes = provider.get_kek_by_id(&wrapped.kek_id)?;
+/// Salt for [`derive_dek`]; changing it changes every derived DEK.
+const DERIVED_DEK_SALT: &[u8] = b"sqlevfs-derived-dek-v1";
+
+/// Derive the DEK for `scope` from the provider's current KEK with
+/// HKDF-SHA256, so the same KEK always yields the same DEK and nothing
+/// needs persisting.
+pub fn derive_dek(scope: &str, provider: &dyn KmsProvider) -> anyhow::Result<Dek> {
+    trace_span!("derive_dek", scope);
+    let (_, kek_bytes) = {
+        trace_span!("kms.get_kek", provider = ?provider.blob_tag());
+        provider.get_kek()?
+    };
     anyhow::ensure!(kek_bytes.len() == 32, "KEK must be 32 bytes");
 
-    let cipher = Aes256Gcm::new_from_slice(&kek_bytes)?;
-    let nonce = Nonce::from_slice(&wrapped.nonce);
-    let plaintext = cipher
-        .decrypt(nonce, wrapped.ciphertext.as_ref())
-        .map_err(|e| anyhow::anyhow!("unwrap decrypt failed: {e}"))?;
+    let mut bytes = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(DERIVED_DEK_SALT), &kek_bytes)
+        .expand(scope.as_bytes(), &mut bytes)
+        .map_err(|e| anyhow::anyhow!("hkdf failed: {e}"))?;
+    Ok(Dek::from_bytes(bytes))
+}
+
+/// Unwrap a DEK using the provider to resolve the KEK. A provider that
+/// cannot serve the KEK for a [transient](crate::kms::KmsTransientError)
+/// reason fails with [`KeyringError::KmsUnavailable`].
+pub fn unwrap_dek(wrapped: &WrappedDek, provider: &dyn KmsProvider) -> anyhow::Result<Dek> {
+    unwrap_dek_with_metrics(wrapped, provider, None)
+}
+
+/// [`unwrap_dek`], reporting the operation to `metrics` if given.
+pub fn unwrap_dek_with_metrics(
+    wrapped: &WrappedDek,
+    provider: &dyn KmsProvider,
+    metrics: Option<&dyn KmsMetrics>,
+) -> anyhow::Result<Dek> {
+    trace_span!("unwrap_dek", kek_id = %wrapped.kek_id.0, cipher = "aes-256-gcm");
+    if let Some(m) = metrics {
+        m.on_unwrap();
+    }
+    let kek_bytes = {
+        trace_span!("kms.get_kek_by_id", provider = ?provider.blob_tag());
+        provider.get_kek_by_id(&wrapped.kek_id)
+    }
+    .map_err(|source| {
+        if is_transient(&source) {
+            KeyringError::KmsUnavailable {
+                attempts: 1,
+                source,
+            }
+            .into()
+        } else {
+            source
+        }
+    })?;
+    let plaintext = match decrypt_with_kek(wrapped, &kek_bytes) {
+        Ok(plaintext) => plaintext,
+        Err(e) => provider
+            .retired_keks(&wrapped.kek_id)
+            .iter()
+            .find_map(|kek| decrypt_with_kek(wrapped, kek).ok())
+            .ok_or(e)?,
+    };
 
     anyhow::ensure!(plaintext.len() == 32, "DEK plaintext must be 32 bytes");
     let mut buf = [0u8; 32];
@@ -40,6 +112,15 @@ pub fn unwrap_dek(wrapped: &WrappedDek, provider: &dyn KmsProvider) -> anyhow::R
     Ok(Dek::from_bytes(buf))
 }
 
+fn decrypt_with_kek(wrapped: &WrappedDek, kek_bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    anyhow::ensure!(kek_bytes.len() == 32, "KEK must be 32 bytes");
+    let cipher = Aes256Gcm::new_from_slice(kek_bytes)?;
+    let nonce = Nonce::from_slice(&wrapped.nonce);
+    cipher
+        .decrypt(nonce, wrapped.ciphertext.as_ref())
+        .map_err(|e| anyhow::anyhow!("unwrap decrypt failed: {e}"))
+}
+
 fn rand_nonce() -> [u8; 12] {
     let mut n = [0u8; 12];
     getrandom::fill(&mut n).expect("getrandom failed");
@@ -242,7 +323,7 @@ mod tests {
         // Create a wrapped DEK with wrong plaintext length
         let short_plaintext = vec![0xAAu8; 16]; // Should be 32
         let nonce = rand_nonce();
-        let cipher = Aes256Gcm::new_from_slice(&vec![0xBBu8; 32]).unwrap();
+        let cipher = Aes256Gcm::new_from_slice(&[0xBBu8; 32]).unwrap();
         let nonce_ref = Nonce::from_slice(&nonce);
         let ciphertext = cipher.encrypt(nonce_ref, short_plaintext.as_ref()).unwrap();
 
@@ -0,0 +1,173 @@
+//! Optional encryption of the 100-byte database header on page 1.
+//!
+//! Page 1 is otherwise plaintext, so its header gives away the schema
+//! cookie, change counter, page count, text encoding and so on. When
+//! sealed, every header byte is encrypted except those evfs must read
+//! before any key is available:
+//!
+//! | Offset | Field            | Why it stays readable                  |
+//! |--------|------------------|----------------------------------------|
+//! | 0..16  | magic            | recognise the file as SQLite / evfs    |
+//! | 16..18 | page size        | locate page boundaries and page 2      |
+//! | 20     | reserved bytes   | locate each page's tag, marker, nonce  |
+//!
+//! The tag, marker and nonce go in page 1's reserve region, at the same
+//! offsets an encrypted page uses, with a marker of its own so page 1 is
+//! never mistaken for an encrypted page.
+
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce, aead::AeadInPlace};
+
+use super::{
+    keys::Dek,
+    page::{MARKER_LEN, MIN_RESERVE, NONCE_LEN, PageError, TAG_LEN, ct_bytes_eq},
+};
+
+/// Length of the SQLite database header.
+pub const HEADER_LEN: usize = 100;
+
+/// Marks a page 1 whose header is sealed.
+pub const HEADER_MARKER: &[u8; MARKER_LEN] = b"EVFSh1";
+
+/// Header bytes that stay plaintext; see the module docs.
+const PLAINTEXT: [std::ops::Range<usize>; 2] = [0..18, 20..21];
+
+fn sealed_offsets() -> impl Iterator<Item = usize> {
+    (0..HEADER_LEN).filter(|i| !PLAINTEXT.iter().any(|r| r.contains(i)))
+}
+
+fn plaintext_fields(page: &[u8]) -> Vec<u8> {
+    PLAINTEXT
+        .iter()
+        .flat_map(|r| page[r.clone()].iter().copied())
+        .collect()
+}
+
+fn trailer(page_len: usize, reserve: usize) -> Option<usize> {
+    (reserve >= MIN_RESERVE && page_len >= HEADER_LEN + reserve).then(|| page_len - reserve)
+}
+
+/// Whether page 1's header is sealed.
+pub fn is_sealed_header(page: &[u8], reserve: usize) -> bool {
+    trailer(page.len(), reserve).is_some_and(|payload_len| {
+        let marker = payload_len + TAG_LEN..payload_len + TAG_LEN + MARKER_LEN;
+        ct_bytes_eq(&page[marker], HEADER_MARKER)
+    })
+}
+
+/// Encrypt page 1's header fields in place. The readable fields are
+/// authenticated, so tampering with them fails [`open_header`].
+pub fn seal_header(page: &mut [u8], dek: &Dek, reserve: usize) -> anyhow::Result<()> {
+    let Some(payload_len) = trailer(page.len(), reserve) else {
+        return Err(PageError::ReserveTooSmall { reserve }.into());
+    };
+
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    getrandom::fill(&mut nonce_bytes).expect("getrandom failed");
+    let mut fields: Vec<u8> = sealed_offsets().map(|i| page[i]).collect();
+    let tag = Aes256Gcm::new_from_slice(dek.as_bytes())?
+        .encrypt_in_place_detached(
+            Nonce::from_slice(&nonce_bytes),
+            &plaintext_fields(page),
+            &mut fields,
+        )
+        .map_err(|e| anyhow::anyhow!("header encrypt failed: {e}"))?;
+
+    for (i, b) in sealed_offsets().zip(fields) {
+        page[i] = b;
+    }
+    let mut at = payload_len;
+    for part in [&tag[..], HEADER_MARKER, &nonce_bytes] {
+        page[at..at + part.len()].copy_from_slice(part);
+        at += part.len();
+    }
+    Ok(())
+}
+
+/// Decrypt a sealed header in place and clear its trailer. Returns
+/// `Ok(false)`, leaving the page alone, when the header is not sealed.
+pub fn open_header(page: &mut [u8], dek: &Dek, reserve: usize) -> anyhow::Result<bool> {
+    if !is_sealed_header(page, reserve) {
+        return Ok(false);
+    }
+    let payload_len = page.len() - reserve;
+    let tag_end = payload_len + TAG_LEN;
+    let nonce_start = tag_end + MARKER_LEN;
+
+    let mut fields: Vec<u8> = sealed_offsets().map(|i| page[i]).collect();
+    Aes256Gcm::new_from_slice(dek.as_bytes())?
+        .decrypt_in_place_detached(
+            Nonce::from_slice(&page[nonce_start..nonce_start + NONCE_LEN]),
+            &plaintext_fields(page),
+            &mut fields,
+            page[payload_len..tag_end].into(),
+        )
+        .map_err(|_| PageError::AuthFailed)?;
+
+    for (i, b) in sealed_offsets().zip(fields) {
+        page[i] = b;
+    }
+    page[payload_len..nonce_start + NONCE_LEN].fill(0);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::page::{default_reserve, is_encrypted_page};
+
+    const RESERVE: usize = 48;
+
+    fn page1() -> Vec<u8> {
+        let mut page = vec![0u8; 4096];
+        page[0..16].copy_from_slice(b"SQLite format 3\0");
+        page[16..18].copy_from_slice(&4096u16.to_be_bytes());
+        page[18] = 1;
+        page[19] = 1;
+        page[20] = RESERVE as u8;
+        // Schema cookie.
+        page[40..44].copy_from_slice(&0xC0DE_CAFEu32.to_be_bytes());
+        page[100..105].copy_from_slice(b"btree");
+        page
+    }
+
+    #[test]
+    fn seal_round_trips_and_keeps_readable_fields() {
+        let dek = Dek::generate();
+        let original = page1();
+        let mut page = original.clone();
+
+        seal_header(&mut page, &dek, RESERVE).unwrap();
+        assert!(is_sealed_header(&page, RESERVE));
+        assert!(!is_encrypted_page(&page, RESERVE));
+        assert_eq!(page[..18], original[..18]);
+        assert_eq!(page[20], original[20]);
+        assert_eq!(page[100..105], original[100..105]);
+        assert_ne!(page[40..44], original[40..44], "schema cookie is sealed");
+
+        assert!(open_header(&mut page, &dek, RESERVE).unwrap());
+        assert_eq!(page, original);
+    }
+
+    #[test]
+    fn open_leaves_a_plain_header_alone() {
+        let mut page = page1();
+        assert!(!open_header(&mut page, &Dek::generate(), RESERVE).unwrap());
+        assert_eq!(page, page1());
+    }
+
+    #[test]
+    fn tampering_with_a_readable_field_fails_to_open() {
+        let dek = Dek::generate();
+        let mut page = page1();
+        seal_header(&mut page, &dek, RESERVE).unwrap();
+        page[20] ^= 1;
+        assert!(open_header(&mut page, &dek, RESERVE).is_err());
+    }
+
+    #[test]
+    fn sealing_needs_the_evfs_trailer() {
+        let mut page = page1();
+        assert!(seal_header(&mut page, &Dek::generate(), 16).is_err());
+        assert!(seal_header(&mut page, &Dek::generate(), default_reserve(4096)).is_ok());
+    }
+}
@@ -1,6 +1,6 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
 
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 /// A 256-bit data encryption key. Zeroized on drop.
 #[derive(Clone, Zeroize, ZeroizeOnDrop, PartialEq, Eq)]
@@ -30,6 +30,15 @@ pub enum KeyScope {
     Table(String),
     /// Single column.
     Column { table: String, column: String },
+    /// Page images in the database's rollback journal.
+    Journal,
+    /// Pages owned by one tenant (by tenant id), so that destroying its
+    /// DEK crypto-shreds that tenant alone.
+    Tenant(String),
+    /// The sealed fields of the database header on page 1.
+    Header,
+    /// Page images in the database's write-ahead log.
+    Wal,
 }
 
 impl Dek {
@@ -43,9 +52,43 @@ impl Dek {
         Self { bytes }
     }
 
+    /// Import a known DEK, e.g. a test vector or one quoted in a bug
+    /// report, from 64 hex characters.
+    pub fn from_hex(hex: &str) -> anyhow::Result<Self> {
+        let bytes = Zeroizing::new(decode_hex(hex.trim())?);
+        let bytes = <[u8; 32]>::try_from(bytes.as_slice()).map_err(|_| {
+            anyhow::anyhow!("DEK must decode to exactly 32 bytes, got {}", bytes.len())
+        })?;
+        Ok(Self { bytes })
+    }
+
     pub fn as_bytes(&self) -> &[u8; 32] {
         &self.bytes
     }
+
+    /// The key as hex, for reproducing decryption issues with
+    /// [`from_hex`](Self::from_hex). Only built with the `dangerous-debug`
+    /// feature, and warns on stderr every time it is called.
+    #[cfg(feature = "dangerous-debug")]
+    pub fn to_hex(&self) -> Zeroizing<String> {
+        eprintln!("sqlevfs: WARNING: exporting a DEK in plaintext (dangerous-debug)");
+        Zeroizing::new(self.bytes.iter().map(|b| format!("{b:02x}")).collect())
+    }
+}
+
+/// Decode an even-length string of hex digits.
+pub(crate) fn decode_hex(value: &str) -> anyhow::Result<Vec<u8>> {
+    anyhow::ensure!(
+        value.len().is_multiple_of(2) && value.bytes().all(|b| b.is_ascii_hexdigit()),
+        "invalid hex digit"
+    );
+    (0..value.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&value[i..i + 2], 16)
+                .map_err(|_| anyhow::anyhow!("invalid hex digit"))
+        })
+        .collect()
 }
 
 impl fmt::Debug for Dek {
@@ -62,6 +105,100 @@ impl fmt::Display for KeyScope {
             KeyScope::Column { table, column } => {
                 write!(f, "column:{table}.{column}")
             }
+            KeyScope::Journal => write!(f, "journal"),
+            KeyScope::Tenant(t) => write!(f, "tenant:{t}"),
+            KeyScope::Header => write!(f, "header"),
+            KeyScope::Wal => write!(f, "wal"),
+        }
+    }
+}
+
+/// Inverse of the `Display` form used as the persisted keyring key. A
+/// column scope splits at the last '.', so table names may contain dots
+/// (`main.users`); column names may not.
+impl FromStr for KeyScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "database" => return Ok(KeyScope::Database),
+            "journal" => return Ok(KeyScope::Journal),
+            "header" => return Ok(KeyScope::Header),
+            "wal" => return Ok(KeyScope::Wal),
+            _ => {}
+        }
+        if let Some(table) = s.strip_prefix("table:") {
+            return Ok(KeyScope::Table(table.to_string()));
+        }
+        if let Some(tenant) = s.strip_prefix("tenant:") {
+            return Ok(KeyScope::Tenant(tenant.to_string()));
         }
+        if let Some((table, column)) = s
+            .strip_prefix("column:")
+            .and_then(|rest| rest.rsplit_once('.'))
+        {
+            return Ok(KeyScope::Column {
+                table: table.to_string(),
+                column: column.to_string(),
+            });
+        }
+        anyhow::bail!("unknown key scope '{s}'")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_strings_round_trip() {
+        for scope in [
+            KeyScope::Database,
+            KeyScope::Journal,
+            KeyScope::Header,
+            KeyScope::Wal,
+            KeyScope::Table("users".into()),
+            KeyScope::Column {
+                table: "users".into(),
+                column: "ssn".into(),
+            },
+            KeyScope::Tenant("acme".into()),
+        ] {
+            assert_eq!(scope.to_string().parse::<KeyScope>().unwrap(), scope);
+        }
+        assert!("column:users".parse::<KeyScope>().is_err());
+
+        let dotted = KeyScope::Column {
+            table: "main.users".into(),
+            column: "ssn".into(),
+        };
+        assert_eq!(dotted.to_string().parse::<KeyScope>().unwrap(), dotted);
+    }
+
+    #[test]
+    fn dek_from_hex() {
+        let hex = "000102030405060708090a0b0c0d0e0f101112131415161718191A1B1C1D1E1F";
+        let expected: [u8; 32] = std::array::from_fn(|i| i as u8);
+        assert_eq!(Dek::from_hex(hex).unwrap().as_bytes(), &expected);
+        assert_eq!(
+            Dek::from_hex(&format!(" {hex}\n")).unwrap().as_bytes(),
+            &expected
+        );
+
+        let err = Dek::from_hex(&hex[..62]).unwrap_err();
+        assert!(err.to_string().contains("exactly 32 bytes, got 31"));
+        assert!(Dek::from_hex(&format!("{hex}00")).is_err());
+        assert!(Dek::from_hex(&hex[..63]).is_err());
+        assert!(Dek::from_hex(&hex.replace('0', "g")).is_err());
+        assert!(Dek::from_hex(&hex.replacen("00", "é", 1)).is_err());
+    }
+
+    #[cfg(feature = "dangerous-debug")]
+    #[test]
+    fn dek_hex_round_trip() {
+        let dek = Dek::generate();
+        let hex = dek.to_hex();
+        assert_eq!(hex.len(), 64);
+        assert_eq!(Dek::from_hex(&hex).unwrap(), dek);
     }
 }
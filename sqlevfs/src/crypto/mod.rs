@@ -1,3 +1,4 @@
 pub mod envelope;
+pub mod header;
 pub mod keys;
 pub mod page;
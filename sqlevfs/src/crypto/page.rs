@@ -1,4 +1,5 @@
 use aes_gcm::{Aes256Gcm, KeyInit, Nonce, aead::Aead};
+use subtle::ConstantTimeEq;
 
 use super::keys::Dek;
 
@@ -6,22 +7,133 @@ pub const TAG_LEN: usize = 16;
 pub const MARKER: &[u8; 6] = b"EVFSv1";
 pub const MARKER_LEN: usize = 6;
 pub const NONCE_LEN: usize = 12;
+pub const VERSION_LEN: usize = 1;
+/// The smallest usable reserve: tag, marker and nonce. A reserve this
+/// small has no room for the version byte and holds format v0 pages.
 pub const MIN_RESERVE: usize = TAG_LEN + MARKER_LEN + NONCE_LEN;
+/// The smallest reserve that carries the format version byte.
+pub const VERSIONED_RESERVE: usize = MIN_RESERVE + VERSION_LEN;
+/// Room left for future reserve-layout fields on pages large enough that
+/// it costs well under 1%.
+pub const SPARE_RESERVE: usize = 13;
+
+/// Reserve every database was created with before it depended on the
+/// page size. Existing files whose header records no usable reserve are
+/// opened with it.
+pub const LEGACY_RESERVE: usize = 48;
+
+/// Reserve used when the builder is not given one: the trailer alone on
+/// pages under 4096 bytes, the trailer plus [`SPARE_RESERVE`] otherwise.
+pub fn default_reserve(page_size: u32) -> usize {
+    if page_size >= 4096 {
+        VERSIONED_RESERVE + SPARE_RESERVE
+    } else {
+        VERSIONED_RESERVE
+    }
+}
+
+/// Reserve-region layout written by this build. Pages written before
+/// the version byte existed carry 0 there and are read as version 1;
+/// a [`MIN_RESERVE`] reserve has no version byte and is version 0.
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Bytes of the reserve the trailer occupies: the tag, marker and nonce,
+/// plus the version byte when the reserve has room for it.
+pub fn trailer_len(reserve: usize) -> usize {
+    reserve.min(VERSIONED_RESERVE)
+}
+
+/// Why a page could not be encrypted or decrypted. Returned inside the
+/// `anyhow::Error`, so callers can `downcast_ref::<PageError>()` to tell
+/// a page that was never encrypted from a wrong key or tampering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageError {
+    /// The page has no `EVFSv1` marker, i.e. it was never encrypted.
+    MissingMarker,
+    /// AES-GCM rejected the tag: wrong DEK or modified ciphertext.
+    AuthFailed,
+    /// The reserve cannot hold the tag, marker and nonce.
+    ReserveTooSmall { reserve: usize },
+    /// The page was written by a newer evfs with a reserve layout this
+    /// build does not understand.
+    UnsupportedVersion { version: u8 },
+}
+
+impl std::fmt::Display for PageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PageError::MissingMarker => f.write_str("missing EVFS marker"),
+            PageError::AuthFailed => f.write_str("page decrypt failed: aead::Error"),
+            PageError::ReserveTooSmall { reserve } => write!(
+                f,
+                "reserve ({reserve}) must be >= {MIN_RESERVE} (tag+marker+nonce)"
+            ),
+            PageError::UnsupportedVersion { version } => write!(
+                f,
+                "database uses evfs format v{version}, this build supports v{FORMAT_VERSION}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PageError {}
 
 fn ensure_reserve(reserve: usize) -> anyhow::Result<()> {
+    if reserve < MIN_RESERVE {
+        return Err(PageError::ReserveTooSmall { reserve }.into());
+    }
+    Ok(())
+}
+
+fn ensure_payload(page_len: usize, reserve: usize) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        reserve < page_len,
+        "reserve ({reserve}) must be smaller than the page ({page_len} bytes) to leave room for a payload",
+    );
+    Ok(())
+}
+
+/// Check up front that `page_size` and `reserve` form a layout SQLite
+/// will accept and that leaves room for the EVFS trailer.
+pub fn validate_page_layout(page_size: u32, reserve: usize) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        (512..=65536).contains(&page_size) && page_size.is_power_of_two(),
+        "page_size ({page_size}) must be a power of two between 512 and 65536",
+    );
+    ensure_reserve(reserve)?;
     anyhow::ensure!(
-        reserve >= MIN_RESERVE,
-        "reserve ({reserve}) must be >= {MIN_RESERVE} (tag+marker+nonce)",
+        reserve <= u8::MAX as usize,
+        "reserve ({reserve}) must be <= 255, the most SQLite can reserve per page",
+    );
+    anyhow::ensure!(
+        page_size as usize - reserve >= 480,
+        "page_size ({page_size}) leaves only {} usable bytes after reserve ({reserve}); SQLite needs 480",
+        page_size as usize - reserve,
     );
     Ok(())
 }
 
+/// Whether `page` carries the `EVFSv1` marker. The format version is
+/// not considered here: a page from a newer evfs is still ciphertext
+/// and must not be passed through as plaintext; [`decrypt_page`] and
+/// [`check_format_version`] reject it instead.
 pub fn is_encrypted_page(page: &[u8], reserve: usize) -> bool {
     if reserve < MIN_RESERVE || page.len() < reserve {
         return false;
     }
-    let payload_len = page.len() - reserve;
-    page.get(marker_range(payload_len)) == Some(MARKER.as_slice())
+    has_marker(page, page.len() - reserve)
+}
+
+/// Compare reserve-region bytes in constant time. None of today's fields
+/// are secret, but later MAC-like fields must not leak how close a
+/// forged page came to matching.
+pub fn ct_bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+fn has_marker(page: &[u8], payload_len: usize) -> bool {
+    page.get(marker_range(payload_len))
+        .is_some_and(|marker| ct_bytes_eq(marker, MARKER))
 }
 
 fn marker_range(payload_len: usize) -> std::ops::Range<usize> {
@@ -32,6 +144,25 @@ fn nonce_range(payload_len: usize) -> std::ops::Range<usize> {
     (payload_len + TAG_LEN + MARKER_LEN)..(payload_len + TAG_LEN + MARKER_LEN + NONCE_LEN)
 }
 
+fn version_offset(payload_len: usize) -> usize {
+    payload_len + TAG_LEN + MARKER_LEN + NONCE_LEN
+}
+
+/// Reject an encrypted page whose reserve layout is newer than
+/// [`FORMAT_VERSION`]. Pages without the marker, or without room for
+/// the version byte (format v0), are not checked.
+pub fn check_format_version(page: &[u8], reserve: usize) -> Result<(), PageError> {
+    if !is_encrypted_page(page, reserve) || reserve < VERSIONED_RESERVE {
+        return Ok(());
+    }
+    let version = page[version_offset(page.len() - reserve)];
+    if ct_bytes_eq(&[version], &[0]) || ct_bytes_eq(&[version], &[FORMAT_VERSION]) {
+        Ok(())
+    } else {
+        Err(PageError::UnsupportedVersion { version })
+    }
+}
+
 /// Encrypt a database page in place.
 pub fn encrypt_page(
     page: &mut [u8],
@@ -39,8 +170,10 @@ pub fn encrypt_page(
     dek: &Dek,
     reserve: usize,
 ) -> anyhow::Result<()> {
+    trace_span!("encrypt_page", page_no = _page_no, cipher = "aes-256-gcm");
     ensure_reserve(reserve)?;
     let page_len = page.len();
+    ensure_payload(page_len, reserve)?;
     let payload_len = page_len - reserve;
 
     let nonce_bytes = rand_nonce();
@@ -63,6 +196,9 @@ pub fn encrypt_page(
     page[marker_range(payload_len)].copy_from_slice(MARKER);
     // Store nonce after marker so decrypt can recover the per-write nonce.
     page[nonce_range(payload_len)].copy_from_slice(&nonce_bytes);
+    if reserve >= VERSIONED_RESERVE {
+        page[version_offset(payload_len)] = FORMAT_VERSION;
+    }
 
     Ok(())
 }
@@ -74,16 +210,17 @@ pub fn decrypt_page(
     dek: &Dek,
     reserve: usize,
 ) -> anyhow::Result<()> {
+    trace_span!("decrypt_page", page_no = _page_no, cipher = "aes-256-gcm");
     ensure_reserve(reserve)?;
     let page_len = page.len();
+    ensure_payload(page_len, reserve)?;
     let payload_len = page_len - reserve;
 
     // Verify marker before attempting AEAD decrypt.
-    let mr = marker_range(payload_len);
-    anyhow::ensure!(
-        page.get(mr.clone()) == Some(MARKER.as_slice()),
-        "missing EVFS marker"
-    );
+    if !has_marker(page, payload_len) {
+        return Err(PageError::MissingMarker.into());
+    }
+    check_format_version(page, reserve)?;
 
     let mut nonce_bytes = [0u8; NONCE_LEN];
     nonce_bytes.copy_from_slice(&page[nonce_range(payload_len)]);
@@ -97,7 +234,7 @@ pub fn decrypt_page(
 
     let plaintext = cipher
         .decrypt(nonce, buf.as_ref())
-        .map_err(|e| anyhow::anyhow!("page decrypt failed: {e}"))?;
+        .map_err(|_| PageError::AuthFailed)?;
 
     page[..plaintext.len()].copy_from_slice(&plaintext);
     // Zero out the tag area in the reserved region.
@@ -107,6 +244,90 @@ pub fn decrypt_page(
     Ok(())
 }
 
+/// With the `parallel` feature, batches of at least this many pages are
+/// spread across the rayon thread pool; smaller ones are not worth it.
+#[cfg(feature = "parallel")]
+pub const PARALLEL_THRESHOLD: usize = 64;
+
+/// Encrypt each whole `page_size` chunk of `buf` in place, numbering
+/// pages from `start_page_no`. A trailing partial chunk is left as is.
+/// Returns the number of pages encrypted.
+pub fn encrypt_pages(
+    buf: &mut [u8],
+    page_size: u32,
+    start_page_no: u32,
+    dek: &Dek,
+    reserve: usize,
+) -> anyhow::Result<usize> {
+    for_each_page(buf, page_size, start_page_no, |page, page_no| {
+        encrypt_page(page, page_no, dek, reserve)
+    })
+}
+
+/// Decrypt counterpart of [`encrypt_pages`].
+pub fn decrypt_pages(
+    buf: &mut [u8],
+    page_size: u32,
+    start_page_no: u32,
+    dek: &Dek,
+    reserve: usize,
+) -> anyhow::Result<usize> {
+    for_each_page(buf, page_size, start_page_no, |page, page_no| {
+        decrypt_page(page, page_no, dek, reserve)
+    })
+}
+
+fn for_each_page(
+    buf: &mut [u8],
+    page_size: u32,
+    start_page_no: u32,
+    f: impl Fn(&mut [u8], u32) -> anyhow::Result<()> + Sync,
+) -> anyhow::Result<usize> {
+    anyhow::ensure!(page_size > 0, "page_size must be non-zero");
+
+    #[cfg(feature = "parallel")]
+    if buf.len() / page_size as usize >= PARALLEL_THRESHOLD {
+        return par_for_each_page(buf, page_size, start_page_no, f);
+    }
+
+    serial_for_each_page(buf, page_size, start_page_no, f)
+}
+
+fn serial_for_each_page(
+    buf: &mut [u8],
+    page_size: u32,
+    start_page_no: u32,
+    f: impl Fn(&mut [u8], u32) -> anyhow::Result<()>,
+) -> anyhow::Result<usize> {
+    let mut count = 0;
+    for (page, page_no) in buf
+        .chunks_exact_mut(page_size as usize)
+        .zip(start_page_no..)
+    {
+        f(page, page_no)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Each page has its own nonce, so pages encrypt independently.
+#[cfg(feature = "parallel")]
+fn par_for_each_page(
+    buf: &mut [u8],
+    page_size: u32,
+    start_page_no: u32,
+    f: impl Fn(&mut [u8], u32) -> anyhow::Result<()> + Sync,
+) -> anyhow::Result<usize> {
+    use rayon::prelude::*;
+
+    let chunks = buf.par_chunks_exact_mut(page_size as usize);
+    let count = chunks.len();
+    chunks
+        .enumerate()
+        .try_for_each(|(i, page)| f(page, start_page_no + i as u32))?;
+    Ok(count)
+}
+
 fn rand_nonce() -> [u8; NONCE_LEN] {
     let mut n = [0u8; NONCE_LEN];
     getrandom::fill(&mut n).expect("getrandom failed");
@@ -118,6 +339,31 @@ mod tests {
     use super::*;
     use crate::crypto::keys::Dek;
 
+    #[test]
+    fn validate_page_layout_limits() {
+        assert!(validate_page_layout(4096, 48).is_ok());
+        assert!(validate_page_layout(1024, MIN_RESERVE).is_ok());
+        assert!(validate_page_layout(4096, 10).is_err());
+        assert!(validate_page_layout(4096, 256).is_err());
+        assert!(validate_page_layout(3000, 48).is_err());
+        // 512 - 34 leaves less than SQLite's 480-byte minimum.
+        assert!(validate_page_layout(512, MIN_RESERVE).is_err());
+    }
+
+    #[test]
+    fn reserve_filling_the_page_is_an_error() {
+        let dek = Dek::generate();
+
+        let mut page = vec![0u8; 512];
+        let err = encrypt_page(&mut page, 2, &dek, 512).unwrap_err();
+        assert!(err.to_string().contains("smaller than the page"), "{err}");
+        assert!(decrypt_page(&mut page, 2, &dek, 512).is_err());
+
+        let mut page = vec![0u8; MIN_RESERVE];
+        assert!(encrypt_page(&mut page, 2, &dek, MIN_RESERVE + 1).is_err());
+        assert!(decrypt_page(&mut page, 2, &dek, MIN_RESERVE + 1).is_err());
+    }
+
     #[test]
     fn round_trip() {
         let dek = Dek::generate();
@@ -225,7 +471,8 @@ mod tests {
         let mut page = vec![0xCDu8; 4096];
 
         encrypt_page(&mut page, 1, &dek1, reserve).unwrap();
-        assert!(decrypt_page(&mut page, 1, &dek2, reserve).is_err());
+        let err = decrypt_page(&mut page, 1, &dek2, reserve).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&PageError::AuthFailed));
     }
 
     #[test]
@@ -252,7 +499,8 @@ mod tests {
         // Tamper with the ciphertext
         page[100] ^= 0xFF;
 
-        assert!(decrypt_page(&mut page, 1, &dek, reserve).is_err());
+        let err = decrypt_page(&mut page, 1, &dek, reserve).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&PageError::AuthFailed));
     }
 
     #[test]
@@ -268,7 +516,8 @@ mod tests {
         // Tamper with the tag
         page[payload_len] ^= 0xFF;
 
-        assert!(decrypt_page(&mut page, 1, &dek, reserve).is_err());
+        let err = decrypt_page(&mut page, 1, &dek, reserve).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&PageError::AuthFailed));
     }
 
     #[test]
@@ -310,8 +559,16 @@ mod tests {
         let reserve = MIN_RESERVE - 1;
         let mut page = vec![0x11u8; 4096];
 
-        let result = encrypt_page(&mut page, 1, &dek, reserve);
-        assert!(result.is_err());
+        let err = encrypt_page(&mut page, 1, &dek, reserve).unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&PageError::ReserveTooSmall { reserve })
+        );
+        let err = decrypt_page(&mut page, 1, &dek, reserve).unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&PageError::ReserveTooSmall { reserve })
+        );
     }
 
     #[test]
@@ -381,6 +638,174 @@ mod tests {
         let dek = Dek::generate();
         let reserve = 48;
         let mut page = vec![0u8; 4096]; // plaintext / no marker
-        assert!(decrypt_page(&mut page, 2, &dek, reserve).is_err());
+        let err = decrypt_page(&mut page, 2, &dek, reserve).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&PageError::MissingMarker));
+        assert_eq!(err.to_string(), "missing EVFS marker");
+    }
+
+    #[test]
+    fn version_byte_written_after_nonce() {
+        let dek = Dek::generate();
+        let reserve = 48;
+        let mut page = vec![0x11u8; 4096];
+
+        encrypt_page(&mut page, 2, &dek, reserve).unwrap();
+        assert_eq!(page[version_offset(4096 - reserve)], FORMAT_VERSION);
+        assert_eq!(check_format_version(&page, reserve), Ok(()));
+    }
+
+    #[test]
+    fn legacy_zero_version_byte_still_decrypts() {
+        let dek = Dek::generate();
+        let reserve = 48;
+        let mut page = vec![0x11u8; 4096];
+
+        encrypt_page(&mut page, 2, &dek, reserve).unwrap();
+        // The version byte is outside the AEAD payload.
+        page[version_offset(4096 - reserve)] = 0;
+        decrypt_page(&mut page, 2, &dek, reserve).unwrap();
+        assert!(page[..4096 - reserve].iter().all(|b| *b == 0x11));
+    }
+
+    #[test]
+    fn unversioned_reserve_round_trips_as_version_0() {
+        let dek = Dek::generate();
+        let reserve = MIN_RESERVE;
+        let mut page = vec![0x11u8; 1024];
+
+        encrypt_page(&mut page, 2, &dek, reserve).unwrap();
+        assert_eq!(check_format_version(&page, reserve), Ok(()));
+        decrypt_page(&mut page, 2, &dek, reserve).unwrap();
+        assert!(page[..1024 - reserve].iter().all(|b| *b == 0x11));
+        assert_eq!(trailer_len(reserve), MIN_RESERVE);
+        assert_eq!(trailer_len(48), VERSIONED_RESERVE);
+    }
+
+    #[test]
+    fn newer_format_version_is_rejected() {
+        let dek = Dek::generate();
+        let reserve = 48;
+        let mut page = vec![0x11u8; 4096];
+
+        encrypt_page(&mut page, 2, &dek, reserve).unwrap();
+        page[version_offset(4096 - reserve)] = FORMAT_VERSION + 1;
+
+        let newer = PageError::UnsupportedVersion {
+            version: FORMAT_VERSION + 1,
+        };
+        assert_eq!(check_format_version(&page, reserve), Err(newer));
+        // Still ciphertext, so never passed through as plaintext.
+        assert!(is_encrypted_page(&page, reserve));
+        let err = decrypt_page(&mut page, 2, &dek, reserve).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&newer));
+        assert_eq!(
+            err.to_string(),
+            "database uses evfs format v2, this build supports v1"
+        );
+    }
+
+    #[test]
+    fn ct_bytes_eq_matches_only_identical_bytes() {
+        assert!(ct_bytes_eq(MARKER, b"EVFSv1"));
+        assert!(!ct_bytes_eq(MARKER, b"EVFSv2"));
+        assert!(!ct_bytes_eq(MARKER, b"FVFSv1"));
+        assert!(!ct_bytes_eq(MARKER, b"EVFSv"));
+        assert!(ct_bytes_eq(&[FORMAT_VERSION], &[FORMAT_VERSION]));
+        assert!(!ct_bytes_eq(&[FORMAT_VERSION], &[FORMAT_VERSION + 1]));
+    }
+
+    #[test]
+    fn batch_round_trip_exact_multiple() {
+        let dek = Dek::generate();
+        let reserve = 48;
+        let mut buf: Vec<u8> = (0..4 * 1024).map(|i| (i / 1024) as u8 + 1).collect();
+        let original = buf.clone();
+
+        assert_eq!(encrypt_pages(&mut buf, 1024, 2, &dek, reserve).unwrap(), 4);
+        assert!(
+            buf.chunks(1024)
+                .all(|page| is_encrypted_page(page, reserve))
+        );
+
+        assert_eq!(decrypt_pages(&mut buf, 1024, 2, &dek, reserve).unwrap(), 4);
+        for (page, orig) in buf.chunks(1024).zip(original.chunks(1024)) {
+            assert_eq!(&page[..1024 - reserve], &orig[..1024 - reserve]);
+        }
+    }
+
+    #[test]
+    fn batch_leaves_trailing_partial_chunk() {
+        let dek = Dek::generate();
+        let reserve = 48;
+        let mut buf = vec![0x22u8; 2 * 1024 + 100];
+
+        assert_eq!(encrypt_pages(&mut buf, 1024, 1, &dek, reserve).unwrap(), 2);
+        assert!(buf[2048..].iter().all(|b| *b == 0x22));
+
+        assert_eq!(decrypt_pages(&mut buf, 1024, 1, &dek, reserve).unwrap(), 2);
+        assert!(buf[..1024 - reserve].iter().all(|b| *b == 0x22));
+        assert!(buf[2048..].iter().all(|b| *b == 0x22));
+
+        let mut short = vec![0u8; 100];
+        assert_eq!(
+            encrypt_pages(&mut short, 1024, 1, &dek, reserve).unwrap(),
+            0
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_and_serial_batches_agree() {
+        let dek = Dek::generate();
+        let reserve = 48;
+        let page_size = 4096;
+        let pages = 1024; // 4 MiB, well over PARALLEL_THRESHOLD
+        let original: Vec<u8> = (0..pages * page_size).map(|i| (i % 251) as u8).collect();
+        let payload_matches = |buf: &[u8]| {
+            buf.chunks(page_size)
+                .zip(original.chunks(page_size))
+                .all(|(a, b)| a[..page_size - reserve] == b[..page_size - reserve])
+        };
+        let serial = |buf: &mut [u8], decrypt: bool| {
+            serial_for_each_page(buf, page_size as u32, 1, |page, page_no| {
+                if decrypt {
+                    decrypt_page(page, page_no, &dek, reserve)
+                } else {
+                    encrypt_page(page, page_no, &dek, reserve)
+                }
+            })
+            .unwrap()
+        };
+
+        // Parallel encrypt, serial decrypt.
+        let mut buf = original.clone();
+        assert_eq!(
+            encrypt_pages(&mut buf, page_size as u32, 1, &dek, reserve).unwrap(),
+            pages
+        );
+        assert!(buf.chunks(page_size).all(|p| is_encrypted_page(p, reserve)));
+        assert_eq!(serial(&mut buf, true), pages);
+        assert!(payload_matches(&buf));
+
+        // Serial encrypt, parallel decrypt.
+        let mut buf = original.clone();
+        assert_eq!(serial(&mut buf, false), pages);
+        assert_eq!(
+            decrypt_pages(&mut buf, page_size as u32, 1, &dek, reserve).unwrap(),
+            pages
+        );
+        assert!(payload_matches(&buf));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_batch_reports_page_errors() {
+        let dek = Dek::generate();
+        let mut buf = vec![0x33u8; PARALLEL_THRESHOLD * 1024];
+        encrypt_pages(&mut buf, 1024, 1, &dek, 48).unwrap();
+        buf[10 * 1024] ^= 1;
+
+        let err = decrypt_pages(&mut buf, 1024, 1, &dek, 48).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&PageError::AuthFailed));
     }
 }
@@ -3,36 +3,171 @@
 use std::{collections::HashMap, sync::Arc};
 
 use crate::{
+    btree::btree_pages,
     crypto::{
-        keys::KeyScope,
-        page::{decrypt_page, encrypt_page},
+        header::is_sealed_header,
+        keys::{Dek, KeyScope},
+        page::{decrypt_page, encrypt_page, is_encrypted_page},
     },
-    keyring::Keyring,
+    keyring::{Keyring, is_embedded_block},
 };
 
+const SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// Page 1 of a database has neither the plaintext SQLite header evfs
+/// keeps nor the `EVFSv1` marker - most likely SQLCipher, which also
+/// encrypts the header, or some other foreign encryption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForeignDatabaseError;
+
+impl std::fmt::Display for ForeignDatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            "this looks like a SQLCipher/foreign-encrypted database, not an evfs database \
+             (no SQLite header and no EVFSv1 marker on page 1)",
+        )
+    }
+}
+
+impl std::error::Error for ForeignDatabaseError {}
+
+/// Check the first page read from a main database file. An all-zero
+/// (or empty) page is a database that has not been written yet.
+pub fn check_first_page(page: &[u8], reserve_size: usize) -> Result<(), ForeignDatabaseError> {
+    if page.iter().all(|b| *b == 0)
+        || page.starts_with(SQLITE_MAGIC)
+        || is_encrypted_page(page, reserve_size)
+    {
+        return Ok(());
+    }
+    Err(ForeignDatabaseError)
+}
+
+/// Page 1 records a different reserve size than the VFS is configured
+/// with; every page's tag/marker/nonce offsets would be wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReserveMismatchError {
+    pub file: usize,
+    pub configured: usize,
+}
+
+impl std::fmt::Display for ReserveMismatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "database was created with reserve {}, builder configured {}",
+            self.file, self.configured
+        )
+    }
+}
+
+impl std::error::Error for ReserveMismatchError {}
+
+/// Compare the reserve byte (offset 20) of an existing page 1 with the
+/// configured reserve size. Pages without a SQLite header, and headers
+/// with no reserve at all (plain SQLite files), are not checked.
+pub fn check_reserve_size(page: &[u8], configured: usize) -> Result<(), ReserveMismatchError> {
+    if page.len() < 21 || !page.starts_with(SQLITE_MAGIC) {
+        return Ok(());
+    }
+    match page[20] as usize {
+        0 => Ok(()),
+        file if file == configured => Ok(()),
+        file => Err(ReserveMismatchError { file, configured }),
+    }
+}
+
+/// Encryption was bypassed (`?evfs_encrypt=off`) for a database that
+/// evfs has already encrypted; plaintext writes would land on top of
+/// ciphertext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncryptedDatabaseError;
+
+impl std::fmt::Display for EncryptedDatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("database has evfs-encrypted pages; refusing to open it with evfs_encrypt=off")
+    }
+}
+
+impl std::error::Error for EncryptedDatabaseError {}
+
+/// Check that a database may be opened with encryption bypassed.
+/// `file_start` is the beginning of the file, at least its first two
+/// pages when it has them: page 1 keeps a readable header, so page 2, a
+/// sealed header or an embedded keyring block is what gives an evfs
+/// database away.
+pub fn check_encryption_bypass(file_start: &[u8]) -> Result<(), EncryptedDatabaseError> {
+    if is_embedded_block(file_start) {
+        return Err(EncryptedDatabaseError);
+    }
+    if file_start.len() < 100 || !file_start.starts_with(SQLITE_MAGIC) {
+        return Ok(());
+    }
+    let page_size = match u16::from_be_bytes([file_start[16], file_start[17]]) {
+        1 => 65536,
+        n => n as usize,
+    };
+    let reserve = file_start[20] as usize;
+    if file_start
+        .get(..page_size)
+        .is_some_and(|page1| is_sealed_header(page1, reserve))
+    {
+        return Err(EncryptedDatabaseError);
+    }
+    match file_start.get(page_size..2 * page_size) {
+        Some(page2) if is_encrypted_page(page2, reserve) => Err(EncryptedDatabaseError),
+        _ => Ok(()),
+    }
+}
+
 /// Shared context carried by every open file handle.
 pub struct FileContext {
     pub keyring: Arc<Keyring>,
     pub page_size: u32,
     pub reserve_size: usize,
+    /// `false` makes [`encrypt_page`](Self::encrypt_page) and
+    /// [`decrypt_page`](Self::decrypt_page) no-ops.
     pub encrypt_enabled: bool,
-    /// Lazily-built map from btree root page → KeyScope.
+    /// Rollback journal handle: every page image uses the
+    /// `KeyScope::Journal` DEK instead of the database's.
+    pub is_journal: bool,
+    /// Lazily-built map from btree page → KeyScope: roots only, or every
+    /// page of each btree when built with
+    /// [`build_full_page_scope_map`](Self::build_full_page_scope_map).
     /// `None` means "use Database scope for everything".
     pub page_scope_map: Option<HashMap<u32, KeyScope>>,
 }
 
 impl FileContext {
+    fn dek_for_page(&self, page_no: u32) -> anyhow::Result<Dek> {
+        if self.is_journal {
+            return self.keyring.dek_for(&KeyScope::Journal);
+        }
+        self.keyring
+            .dek_for_page(page_no, self.page_scope_map.as_ref())
+    }
+
+    /// See [`check_first_page`]; fails with [`ForeignDatabaseError`].
+    pub fn check_first_page(&self, page: &[u8]) -> anyhow::Result<()> {
+        Ok(check_first_page(page, self.reserve_size)?)
+    }
+
     pub fn encrypt_page(&self, page: &mut [u8], page_no: u32) -> anyhow::Result<()> {
-        let dek = self
-            .keyring
-            .dek_for_page(page_no, self.page_scope_map.as_ref())?;
+        if !self.encrypt_enabled {
+            return Ok(());
+        }
+        let dek = self.dek_for_page(page_no)?;
         encrypt_page(page, page_no, &dek, self.reserve_size)
     }
 
+    /// A page that has never been written reads back as all zeroes
+    /// (e.g. while `VACUUM` grows the file) and is returned untouched.
+    /// Any other page without the `EVFSv1` marker is an error.
     pub fn decrypt_page(&self, page: &mut [u8], page_no: u32) -> anyhow::Result<()> {
-        let dek = self
-            .keyring
-            .dek_for_page(page_no, self.page_scope_map.as_ref())?;
+        if !self.encrypt_enabled || page.iter().all(|b| *b == 0) {
+            return Ok(());
+        }
+        let dek = self.dek_for_page(page_no)?;
         decrypt_page(page, page_no, &dek, self.reserve_size)
     }
 
@@ -48,13 +183,53 @@ impl FileContext {
         }
         self.page_scope_map = Some(map);
     }
+
+    /// Like [`build_page_scope_map`](Self::build_page_scope_map), but
+    /// cover every page of each table's b-tree (interior pages, leaves
+    /// and overflow chains), not only its root, so shredding a table's
+    /// DEK orphans all of its rows. Pass index roots under their table's
+    /// name to cover them too. `read_page` returns a page's plaintext.
+    ///
+    /// The map is a snapshot of the file: pages SQLite later frees, reuses
+    /// or moves (e.g. on `VACUUM`) keep the scope they had until it is
+    /// rebuilt.
+    pub fn build_full_page_scope_map(
+        &mut self,
+        root_pages: &[(String, u32)],
+        mut read_page: impl FnMut(u32) -> anyhow::Result<Vec<u8>>,
+    ) -> anyhow::Result<()> {
+        let usable_size = self.page_size as usize - self.reserve_size;
+        let mut map = HashMap::new();
+        for (table_name, root_page) in root_pages {
+            for page_no in btree_pages(*root_page, usable_size, &mut read_page)? {
+                map.insert(page_no, KeyScope::Table(table_name.clone()));
+            }
+        }
+        self.page_scope_map = Some(map);
+        Ok(())
+    }
+
+    /// Assign pages to tenants, e.g. the btrees the tenancy layer keeps
+    /// per tenant, so each is encrypted under that tenant's
+    /// `KeyScope::Tenant` DEK. Tenant assignments take precedence over
+    /// table scopes from [`build_page_scope_map`](Self::build_page_scope_map).
+    pub fn assign_tenant_pages(&mut self, tenant_pages: &[(String, u32)]) {
+        let map = self.page_scope_map.get_or_insert_with(HashMap::new);
+        for (tenant, page_no) in tenant_pages {
+            map.insert(*page_no, KeyScope::Tenant(tenant.clone()));
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        crypto::{keys::KeyScope, page::MIN_RESERVE},
+        crypto::{
+            keys::KeyScope,
+            page::{MIN_RESERVE, PageError},
+        },
+        policy::Enforce,
         tests::MockKmsProvider,
     };
 
@@ -68,6 +243,7 @@ mod tests {
             page_size: 4096,
             reserve_size: MIN_RESERVE,
             encrypt_enabled: true,
+            is_journal: false,
             page_scope_map: None,
         };
 
@@ -261,4 +437,187 @@ mod tests {
         assert!(map2.contains_key(&20));
         assert!(map2.contains_key(&30));
     }
+
+    #[test]
+    fn test_journal_pages_use_their_own_dek() -> Result<(), anyhow::Error> {
+        let db = create_test_context(false);
+        let journal = FileContext {
+            keyring: db.keyring.clone(),
+            page_size: db.page_size,
+            reserve_size: db.reserve_size,
+            encrypt_enabled: true,
+            is_journal: true,
+            page_scope_map: None,
+        };
+
+        let mut page = vec![0u8; 4096];
+        page[..5].copy_from_slice(b"hello");
+        let original = page.clone();
+        journal.encrypt_page(&mut page, 2)?;
+
+        // The database DEK cannot read a journal copy of the same page.
+        assert!(db.decrypt_page(&mut page.clone(), 2).is_err());
+        journal.decrypt_page(&mut page, 2)?;
+        assert_eq!(&page[..5], &original[..5]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tenant_pages_use_distinct_deks() -> Result<(), anyhow::Error> {
+        let mut ctx = create_test_context(true);
+        ctx.assign_tenant_pages(&[("acme".to_string(), 40), ("globex".to_string(), 50)]);
+
+        let map = ctx.page_scope_map.as_ref().unwrap();
+        assert_eq!(map.get(&40), Some(&KeyScope::Tenant("acme".to_string())));
+        assert_eq!(map.get(&10), Some(&KeyScope::Table("users".to_string())));
+
+        let acme = ctx.dek_for_page(40)?;
+        let globex = ctx.dek_for_page(50)?;
+        assert_ne!(acme, globex);
+        assert_ne!(acme, ctx.keyring.dek_for(&KeyScope::Database)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shredding_tenant_scope_makes_only_its_pages_unreadable() -> Result<(), anyhow::Error> {
+        let mut ctx = create_test_context(false);
+        ctx.assign_tenant_pages(&[("acme".to_string(), 40), ("globex".to_string(), 50)]);
+
+        let mut pages = [40u32, 50, 1].map(|page_no| {
+            let mut page = vec![0u8; 4096];
+            page[..4].copy_from_slice(&page_no.to_be_bytes());
+            (page_no, page)
+        });
+        for (page_no, page) in &mut pages {
+            ctx.encrypt_page(page, *page_no)?;
+        }
+
+        let acme = KeyScope::Tenant("acme".to_string());
+        assert!(
+            ctx.keyring
+                .shred_scope(&acme, ctx.page_scope_map.as_ref(), Enforce::Error)
+                .is_err()
+        );
+        ctx.keyring
+            .shred_scope(&acme, ctx.page_scope_map.as_ref(), Enforce::Warn)?;
+
+        let [(_, acme), (_, globex), (_, db)] = &mut pages;
+        assert!(ctx.decrypt_page(acme, 40).is_err());
+        ctx.decrypt_page(globex, 50)?;
+        assert_eq!(&globex[..4], &50u32.to_be_bytes());
+        ctx.decrypt_page(db, 1)?;
+        assert_eq!(&db[..4], &1u32.to_be_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_foreign_first_page_is_rejected() {
+        let ctx = create_test_context(false);
+
+        let mut foreign = vec![0u8; 4096];
+        getrandom::fill(&mut foreign).unwrap();
+        let err = ctx.check_first_page(&foreign).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ForeignDatabaseError>(),
+            Some(&ForeignDatabaseError)
+        );
+        assert!(err.to_string().contains("SQLCipher"));
+
+        let mut sqlite = vec![0u8; 4096];
+        sqlite[..16].copy_from_slice(SQLITE_MAGIC);
+        assert!(ctx.check_first_page(&sqlite).is_ok());
+        assert!(ctx.check_first_page(&[0u8; 4096]).is_ok());
+        assert!(ctx.check_first_page(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_reserve_mismatch_is_reported() {
+        let mut page1 = vec![0u8; 4096];
+        page1[..16].copy_from_slice(SQLITE_MAGIC);
+        page1[20] = 48;
+
+        assert!(check_reserve_size(&page1, 48).is_ok());
+        let err = check_reserve_size(&page1, 32).unwrap_err();
+        assert_eq!(
+            err,
+            ReserveMismatchError {
+                file: 48,
+                configured: 32
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "database was created with reserve 48, builder configured 32"
+        );
+
+        page1[20] = 0;
+        assert!(check_reserve_size(&page1, 32).is_ok());
+        assert!(check_reserve_size(&[0u8; 4096], 32).is_ok());
+    }
+
+    #[test]
+    fn test_disabled_context_passes_pages_through() {
+        let mut ctx = create_test_context(false);
+        ctx.encrypt_enabled = false;
+
+        let mut page = vec![0x5au8; 4096];
+        ctx.encrypt_page(&mut page, 2).unwrap();
+        assert!(page.iter().all(|b| *b == 0x5a));
+        ctx.decrypt_page(&mut page, 2).unwrap();
+        assert!(page.iter().all(|b| *b == 0x5a));
+    }
+
+    #[test]
+    fn test_encryption_bypass_allowed_for_plaintext_db() {
+        let mut db = vec![0x11u8; 2 * 4096];
+        db[..16].copy_from_slice(SQLITE_MAGIC);
+        db[16..18].copy_from_slice(&4096u16.to_be_bytes());
+        db[20] = 0;
+        assert!(check_encryption_bypass(&db).is_ok());
+
+        // New or truncated files have nothing to protect.
+        assert!(check_encryption_bypass(&[]).is_ok());
+        assert!(check_encryption_bypass(&db[..4096]).is_ok());
+    }
+
+    #[test]
+    fn test_encryption_bypass_refused_for_encrypted_db() {
+        let ctx = create_test_context(false);
+        let mut db = vec![0x11u8; 2 * 4096];
+        db[..16].copy_from_slice(SQLITE_MAGIC);
+        db[16..18].copy_from_slice(&4096u16.to_be_bytes());
+        db[20] = MIN_RESERVE as u8;
+        ctx.encrypt_page(&mut db[4096..], 2).unwrap();
+
+        assert_eq!(check_encryption_bypass(&db), Err(EncryptedDatabaseError));
+
+        let mut sealed = db[..4096].to_vec();
+        crate::crypto::header::seal_header(&mut sealed, &Dek::generate(), MIN_RESERVE).unwrap();
+        assert_eq!(
+            check_encryption_bypass(&sealed),
+            Err(EncryptedDatabaseError)
+        );
+
+        let mut embedded = crate::keyring::encode_embedded(&Default::default()).unwrap();
+        embedded.extend_from_slice(&db);
+        assert_eq!(
+            check_encryption_bypass(&embedded),
+            Err(EncryptedDatabaseError)
+        );
+    }
+
+    #[test]
+    fn test_never_written_page_reads_as_zeroes() {
+        let ctx = create_test_context(false);
+
+        let mut blank = vec![0u8; 4096];
+        ctx.decrypt_page(&mut blank, 3).unwrap();
+        assert!(blank.iter().all(|b| *b == 0));
+
+        // Zeroed reserve but a non-zero payload is not a fresh page.
+        let mut markerless = vec![0u8; 4096];
+        markerless[0] = 1;
+        let err = ctx.decrypt_page(&mut markerless, 3).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&PageError::MissingMarker));
+    }
 }
@@ -1,79 +1,578 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::Duration,
 };
 
+use anyhow::Context;
 use bincode::config;
 use parking_lot::RwLock;
 
 use crate::{
     crypto::{
         envelope,
-        keys::{Dek, KeyScope, WrappedDek},
+        keys::{Dek, KekId, KeyScope, WrappedDek},
+        page,
     },
-    kms::KmsProvider,
+    kms::{KmsMetrics, KmsProvider},
+    policy::Enforce,
 };
 
+/// Current [`PersistedKeyring`] encoding. Bump when fields change.
+pub const KEYRING_FORMAT_VERSION: u16 = 2;
+
+/// A keyring blob was written by a newer build using a format this
+/// build cannot read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyringVersionError {
+    pub version: u16,
+}
+
+impl std::fmt::Display for KeyringVersionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "keyring uses format v{}, this build supports v{KEYRING_FORMAT_VERSION}",
+            self.version
+        )
+    }
+}
+
+impl std::error::Error for KeyringVersionError {}
+
+/// Failures obtaining a DEK that callers may want to handle apart from
+/// a wrong key or a corrupt keyring.
+#[derive(Debug)]
+pub enum KeyringError {
+    /// The KMS could not serve the KEK needed to unwrap a persisted DEK,
+    /// even after `attempts` tries. The wrapped DEK itself may be fine;
+    /// the operation can be retried once the KMS is reachable.
+    KmsUnavailable {
+        attempts: u32,
+        source: anyhow::Error,
+    },
+}
+
+impl std::fmt::Display for KeyringError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyringError::KmsUnavailable { attempts, source } => write!(
+                f,
+                "KMS unavailable after {attempts} attempt(s), retry later: {source}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KeyringError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KeyringError::KmsUnavailable { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
+
+/// Bounded retry of KEK lookups that fail with
+/// [`KeyringError::KmsUnavailable`].
+#[derive(Clone, Copy, Debug)]
+struct KmsRetry {
+    attempts: u32,
+    backoff: Duration,
+}
+
 /// On-disk format: only wrapped DEKs, never plaintext.
-#[derive(Clone, Default, bincode::Encode, bincode::Decode)]
+#[derive(Clone, bincode::Encode, bincode::Decode)]
 pub struct PersistedKeyring {
+    /// Always [`KEYRING_FORMAT_VERSION`] when written by this build.
+    pub version: u16,
     pub keys: HashMap<String, WrappedDek>,
+    /// Advanced by every creation and removal; orders `created` against
+    /// `removed` across the processes sharing a sidecar.
+    pub revision: u64,
+    /// Revision each entry of `keys` was created at; 0 when unknown.
+    pub created: HashMap<String, u64>,
+    /// Tombstones: scopes shredded or collected, and the revision they
+    /// were removed at. A copy of the scope's DEK created before then,
+    /// still held by another process, is dropped on merge rather than
+    /// written back.
+    pub removed: HashMap<String, u64>,
+}
+
+impl Default for PersistedKeyring {
+    fn default() -> Self {
+        Self {
+            version: KEYRING_FORMAT_VERSION,
+            keys: HashMap::new(),
+            revision: 0,
+            created: HashMap::new(),
+            removed: HashMap::new(),
+        }
+    }
+}
+
+/// Format v1: no revisions or tombstones.
+#[derive(bincode::Decode)]
+struct KeyringV1 {
+    version: u16,
+    keys: HashMap<String, WrappedDek>,
+}
+
+/// Keyrings written before the version field existed.
+#[derive(bincode::Decode)]
+struct LegacyKeyring {
+    keys: HashMap<String, WrappedDek>,
+}
+
+impl PersistedKeyring {
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::encode_to_vec(self, config::standard())
+            .expect("PersistedKeyring encoding cannot fail")
+    }
+
+    fn created_at(&self, key: &str) -> u64 {
+        self.created.get(key).copied().unwrap_or(0)
+    }
+
+    /// Add `key` as created at a new revision.
+    fn insert(&mut self, key: &str, wrapped: WrappedDek) {
+        self.revision += 1;
+        self.keys.insert(key.to_owned(), wrapped);
+        self.created.insert(key.to_owned(), self.revision);
+    }
+
+    /// Remove `key`, leaving a tombstone at a new revision.
+    fn remove(&mut self, key: &str) {
+        self.revision += 1;
+        self.keys.remove(key);
+        self.created.remove(key);
+        self.removed.insert(key.to_owned(), self.revision);
+    }
+
+    /// Merge in `other`, e.g. the sidecar as another process last wrote
+    /// it. Tombstones win over entries created before them; otherwise
+    /// an entry held here wins unless `other`'s was created later.
+    /// Returns the keys whose entry here was dropped or replaced.
+    fn merge(&mut self, other: Self) -> Vec<String> {
+        self.revision = self.revision.max(other.revision);
+        for (key, at) in other.removed {
+            let removed = self.removed.entry(key).or_insert(at);
+            *removed = (*removed).max(at);
+        }
+
+        let mut stale = Vec::new();
+        for (key, wrapped) in other.keys {
+            let at = other.created.get(&key).copied().unwrap_or(0);
+            if self.keys.contains_key(&key) {
+                if self.created_at(&key) >= at {
+                    continue;
+                }
+                stale.push(key.clone());
+            }
+            self.keys.insert(key.clone(), wrapped);
+            self.created.insert(key, at);
+        }
+
+        let removed: Vec<String> = self
+            .keys
+            .keys()
+            .filter(|key| {
+                self.removed
+                    .get(*key)
+                    .is_some_and(|at| self.created_at(key) < *at)
+            })
+            .cloned()
+            .collect();
+        for key in removed {
+            self.keys.remove(&key);
+            self.created.remove(&key);
+            if !stale.contains(&key) {
+                stale.push(key);
+            }
+        }
+        stale
+    }
+
+    /// Decode a keyring blob. Unversioned blobs from older builds are
+    /// accepted; a newer format fails with [`KeyringVersionError`]
+    /// rather than a bincode parse error.
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        let current = bincode::decode_from_slice::<Self, _>(bytes, config::standard());
+        if let Ok((keyring, len)) = &current
+            && keyring.version == KEYRING_FORMAT_VERSION
+            && *len == bytes.len()
+        {
+            return Ok(keyring.clone());
+        }
+
+        if let Ok((v1, len)) = bincode::decode_from_slice::<KeyringV1, _>(bytes, config::standard())
+            && v1.version == 1
+            && len == bytes.len()
+        {
+            return Ok(Self {
+                keys: v1.keys,
+                ..Self::default()
+            });
+        }
+
+        if let Ok((legacy, len)) =
+            bincode::decode_from_slice::<LegacyKeyring, _>(bytes, config::standard())
+            && len == bytes.len()
+        {
+            return Ok(Self {
+                keys: legacy.keys,
+                ..Self::default()
+            });
+        }
+
+        // Newer formats may append fields, so only the version is read.
+        if let Ok((version, _)) = bincode::decode_from_slice::<u16, _>(bytes, config::standard())
+            && version > KEYRING_FORMAT_VERSION
+        {
+            return Err(KeyringVersionError { version }.into());
+        }
+
+        let (keyring, _) = current?;
+        anyhow::bail!("unrecognised keyring format v{}", keyring.version)
+    }
+}
+
+/// Where wrapped DEKs are persisted for a database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyringStorage {
+    /// `<db>.evfs-keyring` file next to the database.
+    #[default]
+    Sidecar,
+    /// Fixed-size block at the start of the database file, ahead of
+    /// page 1, so the encrypted file is fully self-describing.
+    Embedded,
+    /// Nothing persisted: each DEK is derived from the KEK and its scope
+    /// with HKDF. Suits single-user device encryption, at a cost: scopes
+    /// are not independent of the KEK, so a scope cannot be
+    /// crypto-shredded and the KEK cannot be rotated without re-encrypting
+    /// the database: rotating the keyfile rotates every DEK with it.
+    /// Needs a provider whose KEK is the same in every process; see
+    /// [`KmsProvider::kek_is_deterministic`].
+    Derived,
+}
+
+/// Size of the embedded keyring block. Database page 1 starts at this
+/// offset, so pages stay aligned to their size in the file for page sizes
+/// up to 16 KiB. 32 and 64 KiB pages still work, but are not aligned.
+pub const EMBEDDED_KEYRING_SIZE: usize = 16384;
+
+const EMBEDDED_MAGIC: &[u8; 8] = b"EVFSKRNG";
+const EMBEDDED_HEADER_SIZE: usize = EMBEDDED_MAGIC.len() + 4;
+
+/// Writes an encoded embedded keyring block to offset 0 of the
+/// database file. Implemented by the VFS over its open file handle.
+pub trait EmbeddedKeyringWriter: Send + Sync {
+    fn write_block(&self, block: &[u8]) -> anyhow::Result<()>;
+}
+
+enum Binding {
+    Sidecar {
+        db_path: PathBuf,
+        sidecar: PathBuf,
+    },
+    Embedded {
+        db_path: PathBuf,
+        /// Open handles able to write the block; the newest is used.
+        writers: Vec<Arc<dyn EmbeddedKeyringWriter>>,
+    },
+    Derived {
+        db_path: PathBuf,
+    },
+}
+
+impl Binding {
+    fn db_path(&self) -> &Path {
+        match self {
+            Binding::Sidecar { db_path, .. }
+            | Binding::Embedded { db_path, .. }
+            | Binding::Derived { db_path } => db_path,
+        }
+    }
+}
+
+/// A cached plaintext DEK and the tick it was last handed out at.
+struct CachedDek {
+    dek: Dek,
+    last_used: AtomicU64,
 }
 
 /// Runtime keyring - holds unwrapped DEKs in memory.
 pub struct Keyring {
     provider: Arc<dyn KmsProvider>,
+    /// Notified of every wrap/unwrap against `provider`.
+    metrics: Option<Arc<dyn KmsMetrics>>,
     /// scope-string → plaintext DEK (zeroized on drop).
-    cache: RwLock<HashMap<String, Dek>>,
+    cache: RwLock<HashMap<String, CachedDek>>,
+    /// Maximum cached DEKs; least-recently-used ones beyond it are evicted.
+    cache_capacity: Option<usize>,
+    /// Retry policy for unwraps while the KMS is unreachable.
+    kms_retry: Option<KmsRetry>,
+    /// Recency clock for `CachedDek::last_used`.
+    clock: AtomicU64,
+    /// Bumped when the keyring is rebound to another database, so a DEK
+    /// unwrapped for the old one is never cached for the new one.
+    epoch: AtomicU64,
+    /// The provider's [`kek_generation`](KmsProvider::kek_generation) the
+    /// persisted keyring was last wrapped under.
+    kek_generation: AtomicU64,
     /// On-disk representation (wrapped DEKs).
     persisted: RwLock<PersistedKeyring>,
-    /// Optional path to persist the keyring sidecar.
-    sidecar_path: RwLock<Option<PathBuf>>,
+    /// Where the persisted keyring is written, if bound to a database.
+    binding: RwLock<Option<Binding>>,
+    /// Embedded keyring changed while no writer was available.
+    dirty: AtomicBool,
 }
 
 impl Keyring {
     pub fn new(provider: Arc<dyn KmsProvider>) -> Self {
+        let kek_generation = provider.kek_generation();
         Self {
             provider,
+            metrics: None,
             cache: RwLock::new(HashMap::new()),
+            cache_capacity: None,
+            kms_retry: None,
+            clock: AtomicU64::new(0),
+            epoch: AtomicU64::new(0),
+            kek_generation: AtomicU64::new(kek_generation),
             persisted: RwLock::new(PersistedKeyring::default()),
-            sidecar_path: RwLock::new(None),
+            binding: RwLock::new(None),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// A keyring with the same provider and settings but no database
+    /// bound and nothing cached, to serve another database alongside this
+    /// one.
+    pub(crate) fn sibling(&self) -> Self {
+        let mut keyring = Self::new(self.provider.clone());
+        keyring.metrics = self.metrics.clone();
+        keyring.cache_capacity = self.cache_capacity;
+        keyring.kms_retry = self.kms_retry;
+        keyring
+    }
+
+    /// Report each KMS wrap/unwrap made by this keyring to `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<dyn KmsMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Keep at most `capacity` plaintext DEKs in memory. Evicted DEKs are
+    /// zeroized and re-unwrapped from the persisted keyring on next use.
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache_capacity = Some(capacity);
+        self
+    }
+
+    /// Try an unwrap up to `attempts` times in total while the KMS is
+    /// unavailable, sleeping `backoff` after the first failure and
+    /// doubling it after each one. Other unwrap failures are not retried.
+    pub fn with_kms_retry(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.kms_retry = Some(KmsRetry {
+            attempts: attempts.max(1),
+            backoff,
+        });
+        self
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Evict least-recently-used DEKs beyond the cache capacity. Only
+    /// entries that can be re-unwrapped from `persisted` are eligible.
+    fn evict_over_capacity(&self, cache: &mut HashMap<String, CachedDek>) {
+        let Some(capacity) = self.cache_capacity else {
+            return;
+        };
+        let persisted = self.persisted.read();
+        while cache.len() > capacity {
+            let victim = cache
+                .iter()
+                .filter(|(key, _)| persisted.keys.contains_key(*key))
+                .min_by_key(|(_, entry)| entry.last_used.load(Ordering::Relaxed))
+                .map(|(key, _)| key.clone());
+            match victim {
+                Some(key) => cache.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    fn wrap(&self, dek: &Dek) -> anyhow::Result<WrappedDek> {
+        envelope::wrap_dek_with_metrics(dek, self.provider.as_ref(), self.metrics.as_deref())
+    }
+
+    /// Unwrap through the KMS, retrying per [`with_kms_retry`](Self::with_kms_retry).
+    /// A KMS that stays unreachable fails with
+    /// [`KeyringError::KmsUnavailable`] carrying the number of attempts.
+    fn unwrap(&self, wrapped: &WrappedDek) -> anyhow::Result<Dek> {
+        let retry = self.kms_retry.unwrap_or(KmsRetry {
+            attempts: 1,
+            backoff: Duration::ZERO,
+        });
+        let mut backoff = retry.backoff;
+        let mut attempt = 1;
+        loop {
+            let err = match envelope::unwrap_dek_with_metrics(
+                wrapped,
+                self.provider.as_ref(),
+                self.metrics.as_deref(),
+            ) {
+                Ok(dek) => return Ok(dek),
+                Err(err) => err,
+            };
+            let source = match err.downcast::<KeyringError>() {
+                Ok(KeyringError::KmsUnavailable { source, .. }) => source,
+                Err(err) => return Err(err),
+            };
+            if attempt >= retry.attempts {
+                return Err(KeyringError::KmsUnavailable {
+                    attempts: attempt,
+                    source,
+                }
+                .into());
+            }
+            std::thread::sleep(backoff);
+            backoff = backoff.saturating_mul(2);
+            attempt += 1;
+        }
+    }
+
+    /// Switching databases must not leak DEKs/state from previous ones.
+    fn reset_if_switching(&self, binding: &Option<Binding>, db_path: &Path) {
+        if binding.as_ref().map(Binding::db_path) != Some(db_path) {
+            let mut cache = self.cache.write();
+            cache.clear();
+            self.epoch.fetch_add(1, Ordering::AcqRel);
+            drop(cache);
+            *self.persisted.write() = PersistedKeyring::default();
+            self.dirty.store(false, Ordering::Release);
         }
     }
 
     /// Bind this keyring to a sidecar file next to the database.
     /// Called when the VFS opens a database file.
     pub fn set_sidecar_path(&self, db_path: &Path) {
-        let mut guard = self.sidecar_path.write();
-        let sidecar = db_path.with_extension("evfs-keyring");
-        let changed = guard.as_ref() != Some(&sidecar);
+        self.set_sidecar_path_explicit(db_path, &db_path.with_extension("evfs-keyring"));
+    }
 
-        // Switching databases must not leak DEKs/state from previous sidecars.
-        if changed {
-            self.cache.write().clear();
-            *self.persisted.write() = PersistedKeyring::default();
-        }
+    /// Like [`set_sidecar_path`](Self::set_sidecar_path), but keep the
+    /// sidecar at `sidecar` rather than next to the database, e.g. in a
+    /// separate secure directory when the database is on read-only media.
+    pub fn set_sidecar_path_explicit(&self, db_path: &Path, sidecar: &Path) {
+        let mut guard = self.binding.write();
+        let sidecar = sidecar.to_path_buf();
+        self.reset_if_switching(&guard, db_path);
 
         if sidecar.exists()
             && let Ok(data) = std::fs::read(&sidecar)
-            && let Ok(kr) = bincode::decode_from_slice(&data, config::standard()).map(|r| r.0)
+            && let Ok(kr) = PersistedKeyring::decode(&data)
         {
             *self.persisted.write() = kr;
         }
 
-        *guard = Some(sidecar);
+        *guard = Some(Binding::Sidecar {
+            db_path: db_path.to_path_buf(),
+            sidecar,
+        });
     }
 
-    /// Flush wrapped DEKs to the sidecar file.
-    fn flush(&self) {
-        let guard = self.sidecar_path.read();
-        if let Some(ref path) = *guard {
-            let persisted = self.persisted.read();
-            if let Ok(data) = bincode::encode_to_vec(&*persisted, config::standard()) {
-                let _ = std::fs::write(path, data);
+    /// Bind this keyring to a database that stores its wrapped DEKs in
+    /// an embedded block. `block` is the first
+    /// [`EMBEDDED_KEYRING_SIZE`] bytes of the file (all zeroes for a new
+    /// database). `writer`, if given, is used to persist new DEKs.
+    pub fn load_embedded(
+        &self,
+        db_path: &Path,
+        block: &[u8],
+        writer: Option<Arc<dyn EmbeddedKeyringWriter>>,
+    ) -> anyhow::Result<()> {
+        let persisted = decode_embedded(block)?;
+
+        let mut guard = self.binding.write();
+        self.reset_if_switching(&guard, db_path);
+        if let Some(kr) = persisted {
+            *self.persisted.write() = kr;
+        }
+
+        let mut writers = match guard.take() {
+            Some(Binding::Embedded {
+                db_path: p,
+                writers,
+            }) if p == db_path => writers,
+            _ => Vec::new(),
+        };
+        writers.extend(writer);
+        *guard = Some(Binding::Embedded {
+            db_path: db_path.to_path_buf(),
+            writers,
+        });
+        drop(guard);
+
+        if self.dirty.load(Ordering::Acquire) {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Bind this keyring to a database whose DEKs are derived from the
+    /// KEK rather than stored; see [`KeyringStorage::Derived`].
+    pub fn set_derived_path(&self, db_path: &Path) {
+        let mut guard = self.binding.write();
+        self.reset_if_switching(&guard, db_path);
+        *guard = Some(Binding::Derived {
+            db_path: db_path.to_path_buf(),
+        });
+    }
+
+    fn is_derived(&self) -> bool {
+        matches!(&*self.binding.read(), Some(Binding::Derived { .. }))
+    }
+
+    /// Forget a writer registered with [`load_embedded`](Self::load_embedded),
+    /// e.g. because its file handle is closing.
+    pub fn release_embedded_writer(&self, writer: &Arc<dyn EmbeddedKeyringWriter>) {
+        if let Some(Binding::Embedded { writers, .. }) = self.binding.write().as_mut() {
+            writers.retain(|w| !Arc::ptr_eq(w, writer));
+        }
+    }
+
+    /// Flush wrapped DEKs to the sidecar file or embedded block. Sidecar
+    /// writes merge in entries added by other processes first.
+    fn flush(&self) -> anyhow::Result<()> {
+        let guard = self.binding.read();
+        match &*guard {
+            Some(Binding::Sidecar { .. }) => {
+                drop(guard);
+                if let Some(mut file) = self.lock_sidecar()? {
+                    self.merge_from_sidecar(&mut file)?;
+                    self.write_sidecar(&mut file)?;
+                }
             }
+            Some(Binding::Embedded { writers, .. }) => {
+                let block = encode_embedded(&self.persisted.read())?;
+                let Some(writer) = writers.last() else {
+                    self.dirty.store(true, Ordering::Release);
+                    anyhow::bail!("embedded keyring has no writable database handle");
+                };
+                writer.write_block(&block)?;
+                self.dirty.store(false, Ordering::Release);
+            }
+            Some(Binding::Derived { .. }) | None => {}
         }
+        Ok(())
     }
 
     /// Get or create the DEK for a given scope.
@@ -83,34 +582,302 @@ impl Keyring {
         // Fast path.
         {
             let cache = self.cache.read();
-            if let Some(dek) = cache.get(&key) {
-                return Ok(dek.clone());
+            if let Some(entry) = cache.get(&key) {
+                entry.last_used.store(self.tick(), Ordering::Relaxed);
+                return Ok(entry.dek.clone());
             }
         }
 
-        // Slow path - acquire write lock.
+        let dek = self.load_dek(scope)?;
+        self.rewrap_if_rotated();
+        Ok(dek)
+    }
+
+    /// Slow path of [`dek_for`](Self::dek_for): unwrap, derive or create
+    /// the DEK for `scope` and cache it.
+    fn load_dek(&self, scope: &KeyScope) -> anyhow::Result<Dek> {
+        let key = scope.to_string();
+        trace_span!("dek_for", scope = %key);
+        let epoch = self.epoch.load(Ordering::Acquire);
+        // Checked before taking the cache lock, which binding changes
+        // take after the binding lock.
+        let derived = self.is_derived();
+
+        // Unwrapping, and wrapping a new DEK, may wait on the KMS through
+        // every retry, so both run without the cache lock: other scopes'
+        // pages keep being served.
+        let existing = self.persisted.read().keys.get(&key).cloned();
+        let (dek, existing) = match existing {
+            _ if derived => (envelope::derive_dek(&key, self.provider.as_ref())?, None),
+            Some(wrapped) => (self.unwrap(&wrapped)?, Some(wrapped)),
+            None => match self.create_dek(&key)? {
+                Some((dek, wrapped)) => (dek, Some(wrapped)),
+                None => return self.load_dek(scope),
+            },
+        };
+
         let mut cache = self.cache.write();
-        // Double-check.
-        if let Some(dek) = cache.get(&key) {
-            return Ok(dek.clone());
-        }
-
-        let dek = {
-            let persisted = self.persisted.read();
-            if let Some(wrapped) = persisted.keys.get(&key) {
-                envelope::unwrap_dek(wrapped, self.provider.as_ref())?
-            } else {
-                drop(persisted);
-                let dek = Dek::generate();
-                let wrapped = envelope::wrap_dek(&dek, self.provider.as_ref())?;
-                self.persisted.write().keys.insert(key.clone(), wrapped);
-                self.flush();
-                dek
+        // Rebound, or the scope shredded or persisted elsewhere, while
+        // unlocked: start over.
+        if !cache.contains_key(&key)
+            && (self.epoch.load(Ordering::Acquire) != epoch
+                || (!derived && self.persisted.read().keys.get(&key) != existing.as_ref()))
+        {
+            drop(cache);
+            return self.load_dek(scope);
+        }
+
+        let entry = cache.entry(key).or_insert_with(|| CachedDek {
+            dek,
+            last_used: AtomicU64::new(0),
+        });
+        entry.last_used.store(self.tick(), Ordering::Relaxed);
+        let dek = entry.dek.clone();
+        self.evict_over_capacity(&mut cache);
+        Ok(dek)
+    }
+
+    /// Re-wrap the persisted keyring once the provider has replaced its
+    /// KEK in process (e.g. a watched keyfile reloaded), so DEKs wrapped
+    /// under the old bytes outlive the process that still holds them.
+    /// Failures are retried on the next slow-path lookup.
+    fn rewrap_if_rotated(&self) {
+        let current = self.provider.kek_generation();
+        let seen = self.kek_generation.swap(current, Ordering::AcqRel);
+        if seen == current || self.is_derived() {
+            return;
+        }
+        if let Err(e) = self.rewrap_all() {
+            self.kek_generation.store(seen, Ordering::Release);
+            if crate::debug() {
+                eprintln!("sqlevfs: re-wrapping keyring after a KEK reload failed: {e:#}");
+            }
+        }
+    }
+
+    /// Generate and persist a DEK for `key`, returning it with its
+    /// wrapped form. `None` if another thread, or another process sharing
+    /// the sidecar, already has; its entry is in `persisted` for the
+    /// caller to unwrap. The sidecar stays locked from re-reading it
+    /// until the new entry is written back.
+    fn create_dek(&self, key: &str) -> anyhow::Result<Option<(Dek, WrappedDek)>> {
+        let mut lock = self.lock_sidecar()?;
+        if let Some(file) = lock.as_mut() {
+            self.merge_from_sidecar(file)?;
+        }
+
+        if self.persisted.read().keys.contains_key(key) {
+            return Ok(None);
+        }
+
+        let dek = Dek::generate();
+        let wrapped = self.wrap(&dek)?;
+        {
+            // Without the sidecar lock, another thread may have won while
+            // this one was wrapping.
+            let mut persisted = self.persisted.write();
+            if persisted.keys.contains_key(key) {
+                return Ok(None);
+            }
+            persisted.insert(key, wrapped.clone());
+        }
+        let flushed = match lock.as_mut() {
+            Some(file) => self.write_sidecar(file),
+            None => self.flush(),
+        };
+        if let Err(e) = flushed {
+            let mut persisted = self.persisted.write();
+            persisted.keys.remove(key);
+            persisted.created.remove(key);
+            return Err(e);
+        }
+        Ok(Some((dek, wrapped)))
+    }
+
+    /// Open the bound sidecar and take an exclusive advisory lock on it.
+    /// `None` when there is no sidecar binding. The lock is released on
+    /// drop.
+    fn lock_sidecar(&self) -> anyhow::Result<Option<File>> {
+        let guard = self.binding.read();
+        let Some(Binding::Sidecar { sidecar, .. }) = &*guard else {
+            return Ok(None);
+        };
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(sidecar)
+            .with_context(|| format!("opening keyring sidecar {}", sidecar.display()))?;
+        file.lock()
+            .with_context(|| format!("locking keyring sidecar {}", sidecar.display()))?;
+        Ok(Some(file))
+    }
+
+    /// Adopt entries and tombstones written to the locked sidecar by
+    /// other processes, per [`PersistedKeyring::merge`]. DEKs shredded or
+    /// replaced elsewhere leave the cache too. A sidecar that does not
+    /// decode is left to [`write_sidecar`](Self::write_sidecar).
+    fn merge_from_sidecar(&self, file: &mut File) -> anyhow::Result<()> {
+        let mut data = Vec::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut data)?;
+        if let Ok(disk) = PersistedKeyring::decode(&data) {
+            let stale = self.persisted.write().merge(disk);
+            if !stale.is_empty() {
+                let mut cache = self.cache.write();
+                for key in &stale {
+                    cache.remove(key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the locked sidecar's contents and sync them. A sidecar in
+    /// a newer format is never overwritten: its DEKs could not be merged
+    /// in first.
+    fn write_sidecar(&self, file: &mut File) -> anyhow::Result<()> {
+        let mut existing = Vec::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut existing)?;
+        if let Err(e) = PersistedKeyring::decode(&existing)
+            && e.is::<KeyringVersionError>()
+        {
+            return Err(e);
+        }
+
+        let data = self.persisted.read().encode();
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&data)?;
+        file.sync_data().context("syncing keyring sidecar")?;
+        Ok(())
+    }
+
+    /// Crypto-shred `scope`: drop its DEK from the cache (zeroizing it)
+    /// and from the persisted keyring, then flush. Pages encrypted under
+    /// it can never be decrypted again; a later [`dek_for`](Self::dek_for)
+    /// creates an unrelated DEK. The flush leaves a tombstone, so other
+    /// processes sharing the sidecar drop their copy on their next flush
+    /// instead of writing it back.
+    ///
+    /// Scopes still in use are refused under [`Enforce::Error`] and only
+    /// logged (with `SQLEVFS_DEBUG`) under [`Enforce::Warn`]: the ones the
+    /// VFS keys database, journal, WAL and header pages under, and any
+    /// `page_scope_map` still assigns pages to.
+    pub fn shred_scope(
+        &self,
+        scope: &KeyScope,
+        page_scope_map: Option<&HashMap<u32, KeyScope>>,
+        enforce: Enforce,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !self.is_derived(),
+            "cannot shred {scope}: its DEK is derived from the KEK"
+        );
+        let referencing = page_scope_map
+            .map(|m| m.values().filter(|s| *s == scope).count())
+            .unwrap_or(0);
+        let in_use = match scope {
+            KeyScope::Database | KeyScope::Journal | KeyScope::Wal | KeyScope::Header => {
+                Some(format!("the VFS encrypts pages under {scope}"))
             }
+            _ if referencing > 0 => Some(format!(
+                "{referencing} page(s) are still encrypted under {scope}"
+            )),
+            _ => None,
         };
+        if let Some(msg) = in_use {
+            match enforce {
+                Enforce::Warn => {
+                    if crate::debug() {
+                        eprintln!("sqlevfs: shredding {scope}: {msg}");
+                    }
+                }
+                Enforce::Error => anyhow::bail!("refusing to shred {scope}: {msg}"),
+            }
+        }
 
-        cache.insert(key, dek.clone());
-        Ok(dek)
+        let key = scope.to_string();
+        let mut lock = self.lock_sidecar()?;
+        if let Some(file) = lock.as_mut() {
+            self.merge_from_sidecar(file)?;
+        }
+        self.cache.write().remove(&key);
+        self.persisted.write().remove(&key);
+        match lock.as_mut() {
+            Some(file) => self.write_sidecar(file),
+            None => self.flush(),
+        }
+    }
+
+    /// Drop the DEKs of tables and columns not in `live_scopes`, e.g. ones
+    /// left behind by `DROP TABLE`, from the cache (zeroizing them) and
+    /// from the persisted keyring. Returns how many were reclaimed. As with
+    /// [`shred_scope`](Self::shred_scope), tombstones keep other processes
+    /// sharing the sidecar from writing them back.
+    ///
+    /// Only [`KeyScope::Table`] and [`KeyScope::Column`] DEKs are
+    /// collected: the database, journal, WAL and header DEKs are always
+    /// live, and a tenant's DEK is only destroyed through
+    /// [`shred_scope`](Self::shred_scope).
+    pub fn gc(&self, live_scopes: &[KeyScope]) -> anyhow::Result<usize> {
+        let orphaned = |key: &String| match key.parse::<KeyScope>() {
+            Ok(scope @ (KeyScope::Table(_) | KeyScope::Column { .. })) => {
+                !live_scopes.contains(&scope)
+            }
+            _ => false,
+        };
+
+        let mut lock = self.lock_sidecar()?;
+        if let Some(file) = lock.as_mut() {
+            self.merge_from_sidecar(file)?;
+        }
+        let mut cache = self.cache.write();
+        let mut persisted = self.persisted.write();
+        let reclaimed: HashSet<String> = cache
+            .keys()
+            .chain(persisted.keys.keys())
+            .filter(|key| orphaned(key))
+            .cloned()
+            .collect();
+        for key in &reclaimed {
+            cache.remove(key);
+            persisted.remove(key);
+        }
+        drop((cache, persisted));
+        if reclaimed.is_empty() {
+            return Ok(0);
+        }
+        match lock.as_mut() {
+            Some(file) => self.write_sidecar(file)?,
+            None => self.flush()?,
+        }
+        Ok(reclaimed.len())
+    }
+
+    /// [`gc`](Self::gc) against the schema of `conn`'s main database:
+    /// every table it still has, and each of their columns, is live.
+    #[cfg(feature = "rusqlite")]
+    pub fn gc_schema(&self, conn: &rusqlite::Connection) -> anyhow::Result<usize> {
+        let mut stmt = conn.prepare(
+            "SELECT m.name, c.name FROM main.sqlite_master AS m
+             LEFT JOIN pragma_table_info(m.name, 'main') AS c
+             WHERE m.type = 'table'",
+        )?;
+        let mut live = HashSet::new();
+        for row in stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get(1)?)))? {
+            let (table, column): (String, Option<String>) = row?;
+            if let Some(column) = column {
+                live.insert(KeyScope::Column {
+                    table: table.clone(),
+                    column,
+                });
+            }
+            live.insert(KeyScope::Table(table));
+        }
+        self.gc(&live.into_iter().collect::<Vec<_>>())
     }
 
     /// Resolve which DEK to use for a given page number.
@@ -134,19 +901,205 @@ impl Keyring {
     pub fn rewrap_all(&self) -> anyhow::Result<()> {
         let cache = self.cache.read();
         let mut persisted = self.persisted.write();
-        for (scope_key, dek) in cache.iter() {
-            let wrapped = envelope::wrap_dek(dek, self.provider.as_ref())?;
+        for (scope_key, entry) in cache.iter() {
+            let wrapped = self.wrap(&entry.dek)?;
             persisted.keys.insert(scope_key.clone(), wrapped);
         }
+        // DEKs not in the cache (evicted, or never used) are held only
+        // wrapped; unwrap them under their old KEK to re-wrap.
+        let uncached: Vec<String> = persisted
+            .keys
+            .keys()
+            .filter(|key| !cache.contains_key(*key))
+            .cloned()
+            .collect();
+        for scope_key in uncached {
+            let dek = self.unwrap(&persisted.keys[&scope_key])?;
+            let wrapped = self.wrap(&dek)?;
+            persisted.keys.insert(scope_key, wrapped);
+        }
         drop(persisted);
         drop(cache);
-        self.flush();
+        self.flush()
+    }
+
+    /// Serialize the wrapped DEKs (never plaintext) for escrow, e.g. in
+    /// a secrets vault. Restore with [`import_wrapped`](Self::import_wrapped).
+    pub fn export_wrapped(&self) -> Vec<u8> {
+        self.persisted.read().encode()
+    }
+
+    /// Merge escrowed wrapped DEKs from [`export_wrapped`](Self::export_wrapped)
+    /// and flush. Scopes missing locally are added, unless shredded or
+    /// collected since the export. An existing scope is replaced only by
+    /// a newer entry: one wrapped under the provider's current KEK when
+    /// the local entry is not.
+    pub fn import_wrapped(&self, bytes: &[u8]) -> anyhow::Result<()> {
+        let incoming = PersistedKeyring::decode(bytes)?;
+        let (current, _) = self.provider.get_kek()?;
+
+        let mut cache = self.cache.write();
+        let mut persisted = self.persisted.write();
+        for (scope_key, wrapped) in incoming.keys {
+            let at = incoming
+                .created
+                .get(&scope_key)
+                .copied()
+                .unwrap_or(0)
+                .max(persisted.created_at(&scope_key));
+            if persisted
+                .removed
+                .get(&scope_key)
+                .is_some_and(|removed| at < *removed)
+            {
+                continue;
+            }
+            match persisted.keys.get(&scope_key) {
+                None => {}
+                Some(existing) if wrapped.kek_id == current && existing.kek_id != current => {
+                    cache.remove(&scope_key);
+                }
+                Some(_) => continue,
+            }
+            persisted.created.insert(scope_key.clone(), at);
+            persisted.keys.insert(scope_key, wrapped);
+        }
+        drop(persisted);
+        drop(cache);
+        self.flush()
+    }
+
+    /// Unwrap and cache the DEKs of `scopes` ahead of use, so later
+    /// reads of them never wait on the KMS. Pass [`scopes`](Self::scopes)
+    /// to warm everything persisted. Scopes without a persisted DEK are
+    /// skipped rather than created, and already-cached ones cost nothing,
+    /// so this is idempotent and safe to call from several threads. With
+    /// a cache capacity, only the last `capacity` scopes stay warm.
+    pub fn warm(&self, scopes: &[KeyScope]) -> anyhow::Result<()> {
+        for scope in scopes {
+            if self.persisted.read().keys.contains_key(&scope.to_string()) {
+                self.dek_for(scope)?;
+            }
+        }
         Ok(())
     }
 
+    /// Scopes that have a persisted DEK, e.g. to list the columns a
+    /// column-encryption layer has keyed.
+    pub fn scopes(&self) -> Vec<KeyScope> {
+        self.persisted
+            .read()
+            .keys
+            .keys()
+            .filter_map(|key| key.parse().ok())
+            .collect()
+    }
+
+    /// Distinct KEKs the persisted DEKs are wrapped under, sorted by id.
+    /// More than one, or one other than the provider's current KEK, means
+    /// a [`rewrap_all`](Self::rewrap_all) is still due.
+    pub fn current_kek_ids(&self) -> Vec<KekId> {
+        let mut ids: Vec<KekId> = self
+            .persisted
+            .read()
+            .keys
+            .values()
+            .map(|wrapped| wrapped.kek_id.clone())
+            .collect();
+        ids.sort_by(|a, b| a.0.cmp(&b.0));
+        ids.dedup();
+        ids
+    }
+
+    /// The [`current_kek_ids`](Self::current_kek_ids) other than the
+    /// provider's current KEK, i.e. retired KEKs this database still needs.
+    pub fn retired_kek_ids(&self) -> anyhow::Result<Vec<KekId>> {
+        let (current, _) = self.provider.get_kek()?;
+        Ok(self
+            .current_kek_ids()
+            .into_iter()
+            .filter(|id| *id != current)
+            .collect())
+    }
+
     pub fn provider(&self) -> &dyn KmsProvider {
         self.provider.as_ref()
     }
+
+    /// Readiness probe: wrap a throwaway DEK through the provider, unwrap
+    /// it again (with the usual retries) and round-trip a scratch page
+    /// under it. Neither the cache nor any database is touched, so it is
+    /// safe to call on a keyring in use.
+    pub fn self_test(&self) -> anyhow::Result<()> {
+        let dek = Dek::generate();
+        let wrapped = self
+            .wrap(&dek)
+            .map_err(|e| anyhow::anyhow!("self-test: wrap failed: {e}"))?;
+        let unwrapped = self
+            .unwrap(&wrapped)
+            .map_err(|e| anyhow::anyhow!("self-test: unwrap failed: {e}"))?;
+        anyhow::ensure!(unwrapped == dek, "self-test: KMS returned a different DEK");
+
+        let (page_size, reserve) = (4096, page::default_reserve(4096));
+        let mut scratch = vec![0u8; page_size];
+        getrandom::fill(&mut scratch[..page_size - reserve]).expect("getrandom failed");
+        let original = scratch.clone();
+        page::encrypt_page(&mut scratch, 2, &unwrapped, reserve)?;
+        anyhow::ensure!(
+            scratch[..page_size - reserve] != original[..page_size - reserve],
+            "self-test: page was not encrypted"
+        );
+        page::decrypt_page(&mut scratch, 2, &unwrapped, reserve)?;
+        anyhow::ensure!(
+            scratch[..page_size - reserve] == original[..page_size - reserve],
+            "self-test: page did not round-trip"
+        );
+        Ok(())
+    }
+}
+
+/// Encode `keyring` as an [`EMBEDDED_KEYRING_SIZE`]-byte block:
+/// magic, little-endian payload length, bincode payload, zero padding.
+pub fn encode_embedded(keyring: &PersistedKeyring) -> anyhow::Result<Vec<u8>> {
+    let payload = keyring.encode();
+    anyhow::ensure!(
+        EMBEDDED_HEADER_SIZE + payload.len() <= EMBEDDED_KEYRING_SIZE,
+        "embedded keyring is full ({} bytes, limit {})",
+        payload.len(),
+        EMBEDDED_KEYRING_SIZE - EMBEDDED_HEADER_SIZE
+    );
+    let mut block = vec![0u8; EMBEDDED_KEYRING_SIZE];
+    block[..EMBEDDED_MAGIC.len()].copy_from_slice(EMBEDDED_MAGIC);
+    block[EMBEDDED_MAGIC.len()..EMBEDDED_HEADER_SIZE]
+        .copy_from_slice(&(payload.len() as u32).to_le_bytes());
+    block[EMBEDDED_HEADER_SIZE..EMBEDDED_HEADER_SIZE + payload.len()].copy_from_slice(&payload);
+    Ok(block)
+}
+
+/// Whether `bytes` starts with an embedded keyring block header.
+pub fn is_embedded_block(bytes: &[u8]) -> bool {
+    bytes.starts_with(EMBEDDED_MAGIC)
+}
+
+/// Decode an embedded keyring block. Returns `Ok(None)` for an
+/// all-zero block (a database that has not generated any DEK yet).
+pub fn decode_embedded(block: &[u8]) -> anyhow::Result<Option<PersistedKeyring>> {
+    if block.iter().all(|b| *b == 0) {
+        return Ok(None);
+    }
+    anyhow::ensure!(
+        block.len() >= EMBEDDED_HEADER_SIZE && block.starts_with(EMBEDDED_MAGIC),
+        "missing embedded keyring header"
+    );
+    let len = u32::from_le_bytes(
+        block[EMBEDDED_MAGIC.len()..EMBEDDED_HEADER_SIZE]
+            .try_into()
+            .unwrap(),
+    ) as usize;
+    let payload = block
+        .get(EMBEDDED_HEADER_SIZE..EMBEDDED_HEADER_SIZE + len)
+        .ok_or_else(|| anyhow::anyhow!("embedded keyring length {len} exceeds block"))?;
+    Ok(Some(PersistedKeyring::decode(payload)?))
 }
 
 #[cfg(test)]
@@ -236,6 +1189,165 @@ mod tests {
         assert_eq!(dek_unmapped, dek_db);
     }
 
+    #[test]
+    fn test_shred_scope_removes_persisted_entry() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = dir.path().join("app.db");
+        let keyring = Keyring::new(MockKmsProvider::new());
+        keyring.set_sidecar_path(&db);
+
+        let acme = KeyScope::Tenant("acme".to_string());
+        let globex = KeyScope::Tenant("globex".to_string());
+        let old = keyring.dek_for(&acme).unwrap();
+        keyring.dek_for(&globex).unwrap();
+        keyring.shred_scope(&acme, None, Enforce::Error).unwrap();
+        assert_eq!(keyring.scopes(), vec![globex.clone()]);
+
+        // The sidecar no longer holds it either.
+        let reopened = Keyring::new(keyring.provider.clone());
+        reopened.set_sidecar_path(&db);
+        assert_eq!(reopened.scopes(), vec![globex.clone()]);
+        assert_ne!(reopened.dek_for(&acme).unwrap(), old);
+    }
+
+    #[test]
+    fn test_shred_scope_sticks_across_processes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = dir.path().join("app.db");
+        let provider = Arc::new(RotatingKms(parking_lot::Mutex::new(1)));
+        let shredder = Keyring::new(provider.clone());
+        shredder.set_sidecar_path(&db);
+        let acme = KeyScope::Tenant("acme".to_string());
+        let old = shredder.dek_for(&acme).unwrap();
+
+        // Another process holding acme's DEK flushes after the shred.
+        let other = Keyring::new(provider.clone());
+        other.set_sidecar_path(&db);
+        assert_eq!(other.dek_for(&acme).unwrap(), old);
+        shredder.shred_scope(&acme, None, Enforce::Error).unwrap();
+        other.dek_for(&KeyScope::Table("t1".into())).unwrap();
+
+        let reopened = Keyring::new(provider.clone());
+        reopened.set_sidecar_path(&db);
+        assert_eq!(reopened.scopes(), vec![KeyScope::Table("t1".into())]);
+        // The other process dropped its cached copy too.
+        let fresh = other.dek_for(&acme).unwrap();
+        assert_ne!(fresh, old);
+
+        // A DEK created after the shred is not caught by its tombstone.
+        shredder.dek_for(&KeyScope::Table("t2".into())).unwrap();
+        let reopened = Keyring::new(provider);
+        reopened.set_sidecar_path(&db);
+        assert_eq!(reopened.dek_for(&acme).unwrap(), fresh);
+    }
+
+    #[test]
+    fn test_shred_scope_in_use() {
+        let keyring = Keyring::new(MockKmsProvider::new());
+        let acme = KeyScope::Tenant("acme".to_string());
+        let dek = keyring.dek_for(&acme).unwrap();
+        let page_map = HashMap::from([(40, acme.clone())]);
+
+        let err = keyring
+            .shred_scope(&acme, Some(&page_map), Enforce::Error)
+            .unwrap_err();
+        assert!(err.to_string().contains("1 page(s)"));
+        assert_eq!(keyring.dek_for(&acme).unwrap(), dek);
+
+        keyring
+            .shred_scope(&acme, Some(&page_map), Enforce::Warn)
+            .unwrap();
+        assert!(keyring.scopes().is_empty());
+    }
+
+    #[test]
+    fn test_shred_scope_refuses_vfs_scopes() {
+        let keyring = Keyring::new(MockKmsProvider::new());
+        let dek = keyring.dek_for(&KeyScope::Database).unwrap();
+
+        for scope in [
+            KeyScope::Database,
+            KeyScope::Journal,
+            KeyScope::Wal,
+            KeyScope::Header,
+        ] {
+            let err = keyring
+                .shred_scope(&scope, None, Enforce::Error)
+                .unwrap_err();
+            assert!(err.to_string().contains("the VFS encrypts"), "{err}");
+        }
+        assert_eq!(keyring.dek_for(&KeyScope::Database).unwrap(), dek);
+
+        keyring
+            .shred_scope(&KeyScope::Database, None, Enforce::Warn)
+            .unwrap();
+        assert_ne!(keyring.dek_for(&KeyScope::Database).unwrap(), dek);
+    }
+
+    #[test]
+    fn test_gc_sticks_across_processes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = dir.path().join("app.db");
+        let provider = Arc::new(RotatingKms(parking_lot::Mutex::new(1)));
+        let users = KeyScope::Table("users".to_string());
+        let orders = KeyScope::Table("orders".to_string());
+        let collector = Keyring::new(provider.clone());
+        collector.set_sidecar_path(&db);
+        collector.dek_for(&users).unwrap();
+        let old = collector.dek_for(&orders).unwrap();
+
+        // Another process still holding orders' DEK flushes after the gc.
+        let other = Keyring::new(provider.clone());
+        other.set_sidecar_path(&db);
+        assert_eq!(other.dek_for(&orders).unwrap(), old);
+        assert_eq!(collector.gc(std::slice::from_ref(&users)).unwrap(), 1);
+        other.dek_for(&KeyScope::Database).unwrap();
+
+        let reopened = Keyring::new(provider);
+        reopened.set_sidecar_path(&db);
+        let mut scopes = reopened.scopes();
+        scopes.sort_by_key(|s| s.to_string());
+        assert_eq!(scopes, vec![KeyScope::Database, users]);
+        assert_ne!(other.dek_for(&orders).unwrap(), old);
+    }
+
+    #[test]
+    fn test_gc_drops_orphaned_scopes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = dir.path().join("app.db");
+        let keyring = Keyring::new(MockKmsProvider::new());
+        keyring.set_sidecar_path(&db);
+
+        let users = KeyScope::Table("users".to_string());
+        let orders = KeyScope::Table("orders".to_string());
+        let email = KeyScope::Column {
+            table: "users".to_string(),
+            column: "email".to_string(),
+        };
+        keyring.dek_for(&KeyScope::Database).unwrap();
+        let live = keyring.dek_for(&users).unwrap();
+        keyring.dek_for(&orders).unwrap();
+        keyring.dek_for(&email).unwrap();
+
+        assert_eq!(keyring.gc(std::slice::from_ref(&users)).unwrap(), 2);
+        let cached: Vec<String> = keyring.cache.read().keys().cloned().collect();
+        assert!(!cached.contains(&orders.to_string()));
+        assert!(!cached.contains(&email.to_string()));
+        let mut scopes = keyring.scopes();
+        scopes.sort_by_key(|s| s.to_string());
+        assert_eq!(scopes, vec![KeyScope::Database, users.clone()]);
+
+        // The sidecar no longer holds them either, and a second pass has
+        // nothing left to reclaim.
+        let reopened = Keyring::new(keyring.provider.clone());
+        reopened.set_sidecar_path(&db);
+        let mut reopened_scopes = reopened.scopes();
+        reopened_scopes.sort_by_key(|s| s.to_string());
+        assert_eq!(reopened_scopes, scopes);
+        assert_eq!(keyring.dek_for(&users).unwrap(), live);
+        assert_eq!(keyring.gc(std::slice::from_ref(&users)).unwrap(), 0);
+    }
+
     #[test]
     fn test_rewrap_all() {
         let provider = MockKmsProvider::new();
@@ -296,4 +1408,724 @@ mod tests {
         let _ = std::fs::remove_file(db2.with_extension("evfs-keyring"));
         let _ = std::fs::remove_dir_all(&root);
     }
+
+    struct RecordingWriter(parking_lot::Mutex<Vec<Vec<u8>>>);
+
+    impl EmbeddedKeyringWriter for RecordingWriter {
+        fn write_block(&self, block: &[u8]) -> anyhow::Result<()> {
+            self.0.lock().push(block.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_embedded_block_round_trip() {
+        assert!(
+            decode_embedded(&[0u8; EMBEDDED_KEYRING_SIZE])
+                .unwrap()
+                .is_none()
+        );
+
+        let provider = MockKmsProvider::new();
+        let mut kr = PersistedKeyring::default();
+        let wrapped = envelope::wrap_dek(&Dek::generate(), provider.as_ref()).unwrap();
+        kr.keys.insert("database".into(), wrapped.clone());
+
+        let block = encode_embedded(&kr).unwrap();
+        assert_eq!(block.len(), EMBEDDED_KEYRING_SIZE);
+        let decoded = decode_embedded(&block).unwrap().unwrap();
+        assert_eq!(decoded.keys.get("database"), Some(&wrapped));
+
+        assert!(decode_embedded(b"SQLite format 3\0").is_err());
+    }
+
+    #[test]
+    fn test_embedded_keyring_persists_through_writer() {
+        struct FixedKms;
+        impl KmsProvider for FixedKms {
+            fn get_kek(&self) -> anyhow::Result<(crate::crypto::keys::KekId, Vec<u8>)> {
+                Ok((crate::crypto::keys::KekId("fixed".into()), vec![0x11; 32]))
+            }
+
+            fn get_kek_by_id(&self, _: &crate::crypto::keys::KekId) -> anyhow::Result<Vec<u8>> {
+                Ok(vec![0x11; 32])
+            }
+        }
+
+        let provider: Arc<dyn KmsProvider> = Arc::new(FixedKms);
+        let db = std::path::Path::new("/nonexistent/embedded.db");
+        let writer = Arc::new(RecordingWriter(Default::default()));
+
+        let keyring = Keyring::new(provider.clone());
+        keyring
+            .load_embedded(db, &[0u8; EMBEDDED_KEYRING_SIZE], Some(writer.clone()))
+            .unwrap();
+        let dek = keyring.dek_for(&KeyScope::Database).unwrap();
+
+        let block = writer.0.lock().last().cloned().expect("block written");
+
+        // A fresh keyring reading only the block recovers the same DEK.
+        let reopened = Keyring::new(provider);
+        reopened.load_embedded(db, &block, None).unwrap();
+        assert_eq!(
+            reopened.dek_for(&KeyScope::Database).unwrap().as_bytes(),
+            dek.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_embedded_keyring_without_writer_rejects_new_deks() {
+        let provider = MockKmsProvider::new();
+        let db = std::path::Path::new("/nonexistent/readonly.db");
+        let writer: Arc<dyn EmbeddedKeyringWriter> = Arc::new(RecordingWriter(Default::default()));
+
+        let keyring = Keyring::new(provider);
+        keyring
+            .load_embedded(db, &[0u8; EMBEDDED_KEYRING_SIZE], Some(writer.clone()))
+            .unwrap();
+        keyring.release_embedded_writer(&writer);
+
+        let err = keyring.dek_for(&KeyScope::Database).unwrap_err();
+        assert!(err.to_string().contains("no writable database handle"));
+        assert!(keyring.persisted.read().keys.is_empty());
+    }
+
+    /// Provider whose current KEK can be rotated; generation `n` is
+    /// `KekId("kek-n")` with key bytes `[n; 32]`.
+    struct RotatingKms(parking_lot::Mutex<u8>);
+
+    impl KmsProvider for RotatingKms {
+        fn get_kek(&self) -> anyhow::Result<(crate::crypto::keys::KekId, Vec<u8>)> {
+            let n = *self.0.lock();
+            Ok((crate::crypto::keys::KekId(format!("kek-{n}")), vec![n; 32]))
+        }
+
+        fn get_kek_by_id(&self, id: &crate::crypto::keys::KekId) -> anyhow::Result<Vec<u8>> {
+            let n: u8 = id.0.trim_start_matches("kek-").parse()?;
+            Ok(vec![n; 32])
+        }
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let provider = Arc::new(RotatingKms(parking_lot::Mutex::new(1)));
+        let source = Keyring::new(provider.clone());
+        let db_dek = source.dek_for(&KeyScope::Database).unwrap();
+        let table_dek = source.dek_for(&KeyScope::Table("users".into())).unwrap();
+
+        let escrow = source.export_wrapped();
+
+        let restored = Keyring::new(provider);
+        restored.import_wrapped(&escrow).unwrap();
+        assert_eq!(
+            restored.dek_for(&KeyScope::Database).unwrap().as_bytes(),
+            db_dek.as_bytes()
+        );
+        assert_eq!(
+            restored
+                .dek_for(&KeyScope::Table("users".into()))
+                .unwrap()
+                .as_bytes(),
+            table_dek.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_import_keeps_newer_entry_on_conflict() {
+        let provider = Arc::new(RotatingKms(parking_lot::Mutex::new(1)));
+        let source = Keyring::new(provider.clone());
+        source.dek_for(&KeyScope::Database).unwrap();
+        let old_escrow = source.export_wrapped();
+
+        *provider.0.lock() = 2;
+        source.rewrap_all().unwrap();
+        let new_escrow = source.export_wrapped();
+
+        let kek_of = |kr: &Keyring| kr.persisted.read().keys["database"].kek_id.0.clone();
+
+        let target = Keyring::new(provider.clone());
+        target.import_wrapped(&old_escrow).unwrap();
+        assert_eq!(kek_of(&target), "kek-1");
+
+        // Newer (current-KEK) entry replaces the stale one...
+        target.import_wrapped(&new_escrow).unwrap();
+        assert_eq!(kek_of(&target), "kek-2");
+
+        // ...and an older one never overwrites it.
+        target.import_wrapped(&old_escrow).unwrap();
+        assert_eq!(kek_of(&target), "kek-2");
+    }
+
+    #[test]
+    fn test_import_rejects_garbage() {
+        let keyring = Keyring::new(MockKmsProvider::new());
+        assert!(keyring.import_wrapped(b"not a keyring").is_err());
+    }
+
+    #[derive(Default)]
+    struct CountingMetrics {
+        wraps: std::sync::atomic::AtomicUsize,
+        unwraps: std::sync::atomic::AtomicUsize,
+    }
+
+    impl KmsMetrics for CountingMetrics {
+        fn on_wrap(&self) {
+            self.wraps.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_unwrap(&self) {
+            self.unwraps.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_metrics_count_each_wrap_and_unwrap() {
+        let metrics = Arc::new(CountingMetrics::default());
+        let provider = Arc::new(RotatingKms(parking_lot::Mutex::new(1)));
+        let keyring = Keyring::new(provider).with_metrics(metrics.clone());
+
+        for i in 0..5 {
+            keyring.dek_for(&KeyScope::Table(format!("t{i}"))).unwrap();
+            // Cache hits reach neither the KMS nor the metrics.
+            keyring.dek_for(&KeyScope::Table(format!("t{i}"))).unwrap();
+        }
+        assert_eq!(metrics.wraps.load(Ordering::Relaxed), 5);
+        assert_eq!(metrics.unwraps.load(Ordering::Relaxed), 0);
+
+        keyring.cache.write().clear();
+        keyring.dek_for(&KeyScope::Table("t0".into())).unwrap();
+        assert_eq!(metrics.unwraps.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_cache_capacity_evicts_least_recently_used() {
+        let metrics = Arc::new(CountingMetrics::default());
+        let provider = Arc::new(RotatingKms(parking_lot::Mutex::new(1)));
+        let keyring = Keyring::new(provider)
+            .with_metrics(metrics.clone())
+            .with_cache_capacity(2);
+        let table = |name: &str| KeyScope::Table(name.into());
+
+        let a = keyring.dek_for(&table("a")).unwrap();
+        keyring.dek_for(&table("b")).unwrap();
+        // Touch `a` so `b` is the least recently used.
+        keyring.dek_for(&table("a")).unwrap();
+        keyring.dek_for(&table("c")).unwrap();
+
+        let cached =
+            |k: &Keyring, name: &str| k.cache.read().contains_key(&format!("table:{name}"));
+        assert_eq!(keyring.cache.read().len(), 2);
+        assert!(cached(&keyring, "a"));
+        assert!(!cached(&keyring, "b"));
+        assert!(cached(&keyring, "c"));
+        assert_eq!(keyring.persisted.read().keys.len(), 3);
+
+        // `b` comes back from the persisted keyring, evicting `a`.
+        keyring.dek_for(&table("b")).unwrap();
+        assert_eq!(metrics.unwraps.load(Ordering::Relaxed), 1);
+        assert!(!cached(&keyring, "a"));
+        assert_eq!(keyring.dek_for(&table("a")).unwrap(), a);
+        assert_eq!(metrics.unwraps.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_rewrap_all_covers_evicted_deks() {
+        let provider = Arc::new(RotatingKms(parking_lot::Mutex::new(1)));
+        let keyring = Keyring::new(provider.clone()).with_cache_capacity(1);
+        let first = keyring.dek_for(&KeyScope::Table("a".into())).unwrap();
+        keyring.dek_for(&KeyScope::Table("b".into())).unwrap();
+
+        *provider.0.lock() = 2;
+        keyring.rewrap_all().unwrap();
+        assert!(
+            keyring
+                .persisted
+                .read()
+                .keys
+                .values()
+                .all(|w| w.kek_id.0 == "kek-2")
+        );
+        assert_eq!(
+            keyring.dek_for(&KeyScope::Table("a".into())).unwrap(),
+            first
+        );
+    }
+
+    #[test]
+    fn test_current_kek_ids_follow_rewrap() {
+        let provider = Arc::new(RotatingKms(parking_lot::Mutex::new(1)));
+        let keyring = Keyring::new(provider.clone());
+        keyring.dek_for(&KeyScope::Table("a".into())).unwrap();
+        keyring.dek_for(&KeyScope::Table("b".into())).unwrap();
+        assert_eq!(keyring.current_kek_ids(), vec![KekId("kek-1".into())]);
+
+        *provider.0.lock() = 2;
+        keyring.dek_for(&KeyScope::Table("c".into())).unwrap();
+        assert_eq!(
+            keyring.current_kek_ids(),
+            vec![KekId("kek-1".into()), KekId("kek-2".into())]
+        );
+        assert_eq!(
+            keyring.retired_kek_ids().unwrap(),
+            vec![KekId("kek-1".into())]
+        );
+
+        keyring.rewrap_all().unwrap();
+        assert_eq!(keyring.current_kek_ids(), vec![KekId("kek-2".into())]);
+        assert!(keyring.retired_kek_ids().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_explicit_sidecar_path_round_trips() {
+        let provider = Arc::new(RotatingKms(parking_lot::Mutex::new(1)));
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let key_dir = tempfile::TempDir::new().unwrap();
+        let db = db_dir.path().join("app.db");
+        let sidecar = key_dir.path().join("app.keys");
+
+        let keyring = Keyring::new(provider.clone());
+        keyring.set_sidecar_path_explicit(&db, &sidecar);
+        let dek = keyring.dek_for(&KeyScope::Database).unwrap();
+
+        assert!(sidecar.exists());
+        assert!(!db.with_extension("evfs-keyring").exists());
+
+        let reopened = Keyring::new(provider);
+        reopened.set_sidecar_path_explicit(&db, &sidecar);
+        let again = reopened.dek_for(&KeyScope::Database).unwrap();
+        assert_eq!(again.as_bytes(), dek.as_bytes());
+    }
+
+    #[test]
+    fn test_keyfile_reload_rewraps_the_persisted_keyring() {
+        use crate::kms::local::DeviceKeyProvider;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let keyfile = dir.path().join("kek");
+        let db = dir.path().join("app.db");
+        let sidecar = dir.path().join("app.keys");
+        std::fs::write(&keyfile, [0x01u8; 32]).unwrap();
+
+        let provider = DeviceKeyProvider::from_keyfile(keyfile.clone()).watch_keyfile(true);
+        let keyring = Keyring::new(Arc::new(provider));
+        keyring.set_sidecar_path_explicit(&db, &sidecar);
+        let dek = keyring.dek_for(&KeyScope::Database).unwrap();
+
+        // Rotate the keyfile in place; the next lookup reloads the KEK.
+        std::fs::write(&keyfile, [0x02u8; 32]).unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(&keyfile)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        keyring.dek_for(&KeyScope::Table("t1".into())).unwrap();
+
+        // A new process knows only the new keyfile.
+        let restarted = Keyring::new(Arc::new(DeviceKeyProvider::from_keyfile(keyfile)));
+        restarted.set_sidecar_path_explicit(&db, &sidecar);
+        let again = restarted.dek_for(&KeyScope::Database).unwrap();
+        assert_eq!(again.as_bytes(), dek.as_bytes());
+    }
+
+    /// A layout as a future build might write it: extra fields after the
+    /// ones this build knows about.
+    #[derive(bincode::Encode)]
+    struct FutureKeyring {
+        version: u16,
+        keys: HashMap<String, WrappedDek>,
+        salt: Vec<u8>,
+    }
+
+    fn sample_keys() -> HashMap<String, WrappedDek> {
+        let provider = MockKmsProvider::new();
+        let wrapped = envelope::wrap_dek(&Dek::generate(), provider.as_ref()).unwrap();
+        HashMap::from([("database".to_string(), wrapped)])
+    }
+
+    #[test]
+    fn test_persisted_keyring_version_gate() {
+        let keys = sample_keys();
+
+        let current = PersistedKeyring {
+            keys: keys.clone(),
+            ..Default::default()
+        };
+        let decoded = PersistedKeyring::decode(&current.encode()).unwrap();
+        assert_eq!(decoded.version, KEYRING_FORMAT_VERSION);
+        assert_eq!(decoded.keys, keys);
+
+        let future = FutureKeyring {
+            version: KEYRING_FORMAT_VERSION + 1,
+            keys,
+            salt: vec![7; 16],
+        };
+        let bytes = bincode::encode_to_vec(&future, config::standard()).unwrap();
+        let Err(err) = PersistedKeyring::decode(&bytes) else {
+            panic!("future keyring decoded");
+        };
+        let newer = KEYRING_FORMAT_VERSION + 1;
+        assert_eq!(
+            err.downcast_ref::<KeyringVersionError>(),
+            Some(&KeyringVersionError { version: newer })
+        );
+        assert!(err.to_string().contains(&format!("format v{newer}")));
+    }
+
+    #[test]
+    fn test_v1_keyring_still_decodes() {
+        let keys = sample_keys();
+        let v1 = bincode::encode_to_vec((1u16, &keys), config::standard()).unwrap();
+        let decoded = PersistedKeyring::decode(&v1).unwrap();
+        assert_eq!(decoded.version, KEYRING_FORMAT_VERSION);
+        assert_eq!(decoded.keys, keys);
+        assert!(decoded.removed.is_empty());
+    }
+
+    #[test]
+    fn test_unversioned_keyring_still_decodes() {
+        for keys in [HashMap::new(), sample_keys()] {
+            // Pre-versioning sidecars encoded the key map alone.
+            let legacy = bincode::encode_to_vec(&keys, config::standard()).unwrap();
+            let decoded = PersistedKeyring::decode(&legacy).unwrap();
+            assert_eq!(decoded.version, KEYRING_FORMAT_VERSION);
+            assert_eq!(decoded.keys, keys);
+        }
+    }
+
+    #[test]
+    fn test_newer_sidecar_is_not_overwritten() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = dir.path().join("app.db");
+        let sidecar = db.with_extension("evfs-keyring");
+        let future = FutureKeyring {
+            version: KEYRING_FORMAT_VERSION + 1,
+            keys: HashMap::new(),
+            salt: vec![],
+        };
+        let bytes = bincode::encode_to_vec(&future, config::standard()).unwrap();
+        std::fs::write(&sidecar, &bytes).unwrap();
+
+        let keyring = Keyring::new(Arc::new(RotatingKms(parking_lot::Mutex::new(1))));
+        keyring.set_sidecar_path(&db);
+        let err = keyring.dek_for(&KeyScope::Database).unwrap_err();
+        assert!(err.is::<KeyringVersionError>());
+        assert_eq!(std::fs::read(&sidecar).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_unwritable_sidecar_fails_the_new_dek() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = dir.path().join("app.db");
+        // A directory where the sidecar should be: opening it fails.
+        let sidecar = dir.path().join("app.keys");
+        std::fs::create_dir(&sidecar).unwrap();
+
+        let keyring = Keyring::new(Arc::new(RotatingKms(parking_lot::Mutex::new(1))));
+        keyring.set_sidecar_path_explicit(&db, &sidecar);
+        let err = keyring.dek_for(&KeyScope::Database).unwrap_err();
+        assert!(format!("{err:#}").contains("keyring sidecar"), "{err:#}");
+        assert!(keyring.persisted.read().keys.is_empty());
+    }
+
+    /// Fails the first `failures` KEK lookups as an outage (or, with
+    /// `permanent`, as an unknown KEK), then serves like [`RotatingKms`];
+    /// `wrong_kek` serves bytes that cannot unwrap.
+    struct FlakyKms {
+        failures: parking_lot::Mutex<u32>,
+        lookups: std::sync::atomic::AtomicU32,
+        wrong_kek: bool,
+        permanent: bool,
+    }
+
+    impl FlakyKms {
+        fn new(failures: u32) -> Arc<Self> {
+            Arc::new(Self {
+                failures: parking_lot::Mutex::new(failures),
+                lookups: Default::default(),
+                wrong_kek: false,
+                permanent: false,
+            })
+        }
+    }
+
+    impl KmsProvider for FlakyKms {
+        fn get_kek(&self) -> anyhow::Result<(KekId, Vec<u8>)> {
+            RotatingKms(parking_lot::Mutex::new(1)).get_kek()
+        }
+
+        fn get_kek_by_id(&self, id: &KekId) -> anyhow::Result<Vec<u8>> {
+            self.lookups.fetch_add(1, Ordering::Relaxed);
+            let mut failures = self.failures.lock();
+            if *failures > 0 {
+                *failures -= 1;
+                if self.permanent {
+                    anyhow::bail!("unknown KEK id: {id:?}");
+                }
+                return Err(
+                    crate::kms::KmsTransientError(anyhow::anyhow!("connection refused")).into(),
+                );
+            }
+            if self.wrong_kek {
+                return Ok(vec![0xEE; 32]);
+            }
+            RotatingKms(parking_lot::Mutex::new(1)).get_kek_by_id(id)
+        }
+    }
+
+    /// A keyring over `provider` holding the wrapped database DEK from
+    /// another keyring, so the next `dek_for` must unwrap through the KMS.
+    fn keyring_needing_unwrap(provider: Arc<FlakyKms>) -> (Keyring, Dek) {
+        let source = Keyring::new(Arc::new(RotatingKms(parking_lot::Mutex::new(1))));
+        let dek = source.dek_for(&KeyScope::Database).unwrap();
+        let keyring = Keyring::new(provider);
+        keyring.import_wrapped(&source.export_wrapped()).unwrap();
+        (keyring, dek)
+    }
+
+    #[test]
+    fn test_kms_retry_recovers_from_transient_failures() {
+        let provider = FlakyKms::new(2);
+        let (keyring, dek) = keyring_needing_unwrap(provider.clone());
+        let keyring = keyring.with_kms_retry(3, Duration::from_millis(1));
+
+        assert_eq!(keyring.dek_for(&KeyScope::Database).unwrap(), dek);
+        assert_eq!(provider.lookups.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_kms_unavailable_after_retries_are_exhausted() {
+        let provider = FlakyKms::new(5);
+        let (keyring, dek) = keyring_needing_unwrap(provider.clone());
+
+        // Without a retry policy the first failure is reported.
+        let err = keyring.dek_for(&KeyScope::Database).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<KeyringError>(),
+            Some(KeyringError::KmsUnavailable { attempts: 1, .. })
+        ));
+
+        let keyring = keyring.with_kms_retry(3, Duration::from_millis(1));
+        let err = keyring.dek_for(&KeyScope::Database).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<KeyringError>(),
+            Some(KeyringError::KmsUnavailable { attempts: 3, .. })
+        ));
+        assert!(err.to_string().contains("connection refused"), "{err}");
+        assert_eq!(provider.lookups.load(Ordering::Relaxed), 4);
+
+        // The KMS is back: the DEK was never lost.
+        assert_eq!(keyring.dek_for(&KeyScope::Database).unwrap(), dek);
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn test_dek_for_emits_an_unwrap_span() {
+        use tracing::{Event, Metadata, Subscriber, span};
+
+        /// Records the name of every span created.
+        struct SpanNames(Arc<parking_lot::Mutex<Vec<&'static str>>>);
+
+        impl Subscriber for SpanNames {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+                let mut names = self.0.lock();
+                names.push(attrs.metadata().name());
+                span::Id::from_u64(names.len() as u64)
+            }
+
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, _: &Event<'_>) {}
+
+            fn enter(&self, _: &span::Id) {}
+
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let (keyring, dek) = keyring_needing_unwrap(FlakyKms::new(0));
+        let names = Arc::new(parking_lot::Mutex::new(vec![]));
+        tracing::subscriber::with_default(SpanNames(names.clone()), || {
+            assert_eq!(keyring.dek_for(&KeyScope::Database).unwrap(), dek);
+        });
+        let names = names.lock();
+        for expected in ["dek_for", "unwrap_dek", "kms.get_kek_by_id"] {
+            assert!(names.contains(&expected), "no {expected} span in {names:?}");
+        }
+    }
+
+    #[test]
+    fn test_self_test_passes_with_a_working_kms() {
+        let keyring = Keyring::new(Arc::new(RotatingKms(parking_lot::Mutex::new(1))));
+        keyring.self_test().unwrap();
+        assert!(
+            keyring.scopes().is_empty(),
+            "self-test must not persist DEKs"
+        );
+    }
+
+    #[test]
+    fn test_self_test_fails_when_the_kms_cannot_unwrap() {
+        let err = Keyring::new(FlakyKms::new(1)).self_test().unwrap_err();
+        assert!(err.to_string().contains("unwrap failed"), "{err}");
+        assert!(err.to_string().contains("connection refused"), "{err}");
+
+        let wrong = Arc::new(FlakyKms {
+            failures: parking_lot::Mutex::new(0),
+            lookups: Default::default(),
+            wrong_kek: true,
+            permanent: false,
+        });
+        assert!(Keyring::new(wrong).self_test().is_err());
+    }
+
+    #[test]
+    fn test_wrong_kek_is_not_retried() {
+        let provider = Arc::new(FlakyKms {
+            failures: parking_lot::Mutex::new(0),
+            lookups: Default::default(),
+            wrong_kek: true,
+            permanent: false,
+        });
+        let (keyring, _) = keyring_needing_unwrap(provider.clone());
+        let keyring = keyring.with_kms_retry(3, Duration::from_millis(1));
+
+        let err = keyring.dek_for(&KeyScope::Database).unwrap_err();
+        assert!(err.downcast_ref::<KeyringError>().is_none(), "{err}");
+        assert_eq!(provider.lookups.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_permanent_kms_errors_are_not_retried() {
+        let provider = Arc::new(FlakyKms {
+            failures: parking_lot::Mutex::new(5),
+            lookups: Default::default(),
+            wrong_kek: false,
+            permanent: true,
+        });
+        let (keyring, _) = keyring_needing_unwrap(provider.clone());
+        let keyring = keyring.with_kms_retry(3, Duration::from_millis(1));
+
+        let err = keyring.dek_for(&KeyScope::Database).unwrap_err();
+        assert!(err.downcast_ref::<KeyringError>().is_none(), "{err}");
+        assert!(err.to_string().contains("unknown KEK id"), "{err}");
+        assert_eq!(provider.lookups.load(Ordering::Relaxed), 1);
+    }
+
+    /// Holds every KEK lookup for an unwrap (or, with `wraps`, a wrap)
+    /// until released, to look at the keyring while it is in flight.
+    struct GatedKms {
+        entered: std::sync::mpsc::SyncSender<()>,
+        release: parking_lot::Mutex<std::sync::mpsc::Receiver<()>>,
+        wraps: bool,
+    }
+
+    impl GatedKms {
+        fn wait(&self) {
+            self.entered.send(()).unwrap();
+            self.release.lock().recv().unwrap();
+        }
+    }
+
+    impl KmsProvider for GatedKms {
+        fn get_kek(&self) -> anyhow::Result<(KekId, Vec<u8>)> {
+            if self.wraps {
+                self.wait();
+            }
+            RotatingKms(parking_lot::Mutex::new(1)).get_kek()
+        }
+
+        fn get_kek_by_id(&self, id: &KekId) -> anyhow::Result<Vec<u8>> {
+            if !self.wraps {
+                self.wait();
+            }
+            RotatingKms(parking_lot::Mutex::new(1)).get_kek_by_id(id)
+        }
+    }
+
+    #[test]
+    fn test_unwrap_does_not_hold_the_cache_lock() {
+        let (entered_tx, entered) = std::sync::mpsc::sync_channel(0);
+        let (release, release_rx) = std::sync::mpsc::sync_channel(0);
+        let source = Keyring::new(Arc::new(RotatingKms(parking_lot::Mutex::new(1))));
+        let dek = source.dek_for(&KeyScope::Database).unwrap();
+        let keyring = Arc::new(Keyring::new(Arc::new(GatedKms {
+            entered: entered_tx,
+            release: parking_lot::Mutex::new(release_rx),
+            wraps: false,
+        })));
+        keyring.import_wrapped(&source.export_wrapped()).unwrap();
+
+        let worker = {
+            let keyring = keyring.clone();
+            std::thread::spawn(move || keyring.dek_for(&KeyScope::Database))
+        };
+        entered.recv().unwrap();
+        // Other scopes' pages can still reach the cache mid-unwrap.
+        assert!(keyring.cache.try_write().is_some());
+        release.send(()).unwrap();
+        assert_eq!(worker.join().unwrap().unwrap(), dek);
+    }
+
+    #[test]
+    fn test_new_dek_is_wrapped_without_the_cache_lock() {
+        let (entered_tx, entered) = std::sync::mpsc::sync_channel(0);
+        let (release, release_rx) = std::sync::mpsc::sync_channel(0);
+        let keyring = Arc::new(Keyring::new(Arc::new(GatedKms {
+            entered: entered_tx,
+            release: parking_lot::Mutex::new(release_rx),
+            wraps: true,
+        })));
+
+        let worker = {
+            let keyring = keyring.clone();
+            std::thread::spawn(move || keyring.dek_for(&KeyScope::Database))
+        };
+        entered.recv().unwrap();
+        // Other scopes' pages can still reach the cache mid-wrap.
+        assert!(keyring.cache.try_write().is_some());
+        release.send(()).unwrap();
+        let dek = worker.join().unwrap().unwrap();
+        assert_eq!(keyring.dek_for(&KeyScope::Database).unwrap(), dek);
+    }
+
+    #[test]
+    fn test_warm_caches_persisted_deks() {
+        let provider = Arc::new(RotatingKms(parking_lot::Mutex::new(1)));
+        let source = Keyring::new(provider.clone());
+        let table = |name: &str| KeyScope::Table(name.into());
+        for name in ["a", "b", "c"] {
+            source.dek_for(&table(name)).unwrap();
+        }
+
+        let metrics = Arc::new(CountingMetrics::default());
+        let keyring = Keyring::new(provider).with_metrics(metrics.clone());
+        keyring.import_wrapped(&source.export_wrapped()).unwrap();
+
+        let scopes = keyring.scopes();
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| keyring.warm(&scopes).unwrap());
+            }
+        });
+        // Unknown scopes are not created by warming.
+        keyring.warm(&[table("missing")]).unwrap();
+        assert_eq!(metrics.unwraps.load(Ordering::Relaxed), 3);
+        assert_eq!(metrics.wraps.load(Ordering::Relaxed), 0);
+        assert_eq!(keyring.cache.read().len(), 3);
+
+        for name in ["a", "b", "c"] {
+            assert_eq!(
+                keyring.dek_for(&table(name)).unwrap(),
+                source.dek_for(&table(name)).unwrap()
+            );
+        }
+        assert_eq!(metrics.unwraps.load(Ordering::Relaxed), 3);
+    }
 }
@@ -0,0 +1,154 @@
+use std::future::Future;
+
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use super::{AsyncKmsProvider, BlobTag, KmsProvider};
+use crate::crypto::keys::KekId;
+
+/// Sync [`KmsProvider`] over an [`AsyncKmsProvider`], driving each call
+/// to completion on a dedicated single-threaded runtime.
+///
+/// Calls made from inside another tokio runtime are moved to a scoped
+/// thread first, since a runtime cannot block on a future from one of
+/// its own worker threads.
+pub struct BlockingKms<P> {
+    inner: P,
+    /// Only `None` while dropping.
+    rt: Option<Runtime>,
+}
+
+impl<P: AsyncKmsProvider> BlockingKms<P> {
+    pub fn new(inner: P) -> anyhow::Result<Self> {
+        let rt = Builder::new_current_thread().enable_all().build()?;
+        Ok(Self {
+            inner,
+            rt: Some(rt),
+        })
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    fn block_on<T: Send>(&self, fut: impl Future<Output = T> + Send) -> T {
+        let rt = self.rt.as_ref().expect("runtime lives until drop");
+        if Handle::try_current().is_err() {
+            return rt.block_on(fut);
+        }
+        std::thread::scope(|s| {
+            s.spawn(|| rt.block_on(fut))
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+}
+
+impl<P> Drop for BlockingKms<P> {
+    fn drop(&mut self) {
+        // A plain drop blocks, which panics inside an async context.
+        if let Some(rt) = self.rt.take() {
+            rt.shutdown_background();
+        }
+    }
+}
+
+impl<P: AsyncKmsProvider> KmsProvider for BlockingKms<P> {
+    fn get_kek(&self) -> anyhow::Result<(KekId, Vec<u8>)> {
+        self.block_on(self.inner.get_kek())
+    }
+
+    fn get_kek_by_id(&self, id: &KekId) -> anyhow::Result<Vec<u8>> {
+        self.block_on(self.inner.get_kek_by_id(id))
+    }
+
+    fn blob_tag(&self) -> BlobTag {
+        self.inner.blob_tag()
+    }
+
+    fn wrap_blob_raw(&self, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.block_on(self.inner.wrap_blob_raw(plaintext))
+    }
+
+    fn unwrap_blob_raw(&self, ciphertext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.block_on(self.inner.unwrap_blob_raw(ciphertext))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use futures::future::BoxFuture;
+
+    use super::*;
+    use crate::{crypto::keys::KeyScope, keyring::Keyring};
+
+    /// Async provider that yields to the runtime before answering, like a
+    /// network-backed KMS would.
+    struct SlowKms {
+        calls: AtomicUsize,
+    }
+
+    impl AsyncKmsProvider for SlowKms {
+        fn get_kek(&self) -> BoxFuture<'_, anyhow::Result<(KekId, Vec<u8>)>> {
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                self.calls.fetch_add(1, Ordering::Relaxed);
+                Ok((KekId("async-kek".into()), vec![0x42; 32]))
+            })
+        }
+
+        fn get_kek_by_id<'a>(&'a self, id: &'a KekId) -> BoxFuture<'a, anyhow::Result<Vec<u8>>> {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                self.calls.fetch_add(1, Ordering::Relaxed);
+                anyhow::ensure!(id.0 == "async-kek", "unknown KEK {}", id.0);
+                Ok(vec![0x42; 32])
+            })
+        }
+    }
+
+    fn blocking_provider() -> Arc<BlockingKms<SlowKms>> {
+        Arc::new(
+            BlockingKms::new(SlowKms {
+                calls: AtomicUsize::new(0),
+            })
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_keyring_works_through_blocking_adapter() {
+        let provider = blocking_provider();
+        let keyring = Keyring::new(provider.clone());
+        let dek = keyring.dek_for(&KeyScope::Database).unwrap();
+
+        // Unwrapping goes back through the async provider.
+        let restored = Keyring::new(provider.clone());
+        restored.import_wrapped(&keyring.export_wrapped()).unwrap();
+        let again = restored.dek_for(&KeyScope::Database).unwrap();
+
+        assert_eq!(again.as_bytes(), dek.as_bytes());
+        assert!(provider.inner().calls.load(Ordering::Relaxed) >= 2);
+    }
+
+    #[test]
+    fn test_direct_wrap_defaults_to_unsupported() {
+        let provider = blocking_provider();
+        assert!(provider.wrap_blob(b"dek").is_err());
+        assert!(provider.unwrap_blob(b"dek").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocking_adapter_inside_a_runtime() {
+        let provider = blocking_provider();
+        let keyring = Keyring::new(provider);
+        assert!(keyring.dek_for(&KeyScope::Database).is_ok());
+    }
+}
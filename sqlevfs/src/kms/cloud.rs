@@ -2,7 +2,7 @@ use base64::{Engine, engine::general_purpose::STANDARD as BASE64_STANDARD};
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
-use super::KmsProvider;
+use super::{BlobTag, KmsProvider, request_error};
 use crate::crypto::keys::KekId;
 
 /// Cloud KMS provider that talks to an HTTP endpoint.
@@ -31,8 +31,6 @@ struct GenerateDataKeyRequest<'a> {
 
 #[derive(Deserialize)]
 struct GenerateDataKeyResponse {
-    #[serde(rename = "KeyId")]
-    key_id: String,
     #[serde(rename = "Plaintext")]
     plaintext: String, // base64
     #[serde(rename = "CiphertextBlob")]
@@ -76,7 +74,8 @@ impl CloudKmsProvider {
         let resp: GenerateDataKeyResponse = ureq::post(url)
             .set("X-Amz-Target", "TrentService.GenerateDataKey")
             .set("Content-Type", "application/x-amz-json-1.1")
-            .send_json(serde_json::to_value(&body)?)?
+            .send_json(serde_json::to_value(&body)?)
+            .map_err(request_error)?
             .into_json()?;
 
         let plaintext = base64_decode(&resp.plaintext)?;
@@ -101,7 +100,8 @@ impl CloudKmsProvider {
         let resp: DecryptResponse = ureq::post(url)
             .set("X-Amz-Target", "TrentService.Decrypt")
             .set("Content-Type", "application/x-amz-json-1.1")
-            .send_json(serde_json::to_value(&body)?)?
+            .send_json(serde_json::to_value(&body)?)
+            .map_err(request_error)?
             .into_json()?;
 
         base64_decode(&resp.plaintext)
@@ -133,7 +133,11 @@ impl KmsProvider for CloudKmsProvider {
         self.decrypt_data_key(&id.0)
     }
 
-    fn wrap_blob(&self, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    fn blob_tag(&self) -> BlobTag {
+        BlobTag::Aws
+    }
+
+    fn wrap_blob_raw(&self, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
         let url = self.base_url();
 
         #[derive(Serialize)]
@@ -158,13 +162,14 @@ impl KmsProvider for CloudKmsProvider {
         let resp: EncryptResponse = ureq::post(url)
             .set("X-Amz-Target", "TrentService.Encrypt")
             .set("Content-Type", "application/x-amz-json-1.1")
-            .send_json(serde_json::to_value(&body)?)?
+            .send_json(serde_json::to_value(&body)?)
+            .map_err(request_error)?
             .into_json()?;
 
         base64_decode(&resp.ciphertext_blob)
     }
 
-    fn unwrap_blob(&self, ciphertext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    fn unwrap_blob_raw(&self, ciphertext: &[u8]) -> anyhow::Result<Vec<u8>> {
         let b64 = base64_encode(ciphertext);
         self.decrypt_data_key(&b64)
     }
@@ -220,12 +225,6 @@ mod tests {
             gdk_plaintext_len: Arc<StdMutex<Option<usize>>>,
         }
 
-        impl OutState {
-            fn set_generate_plaintext_len(&self, len: Option<usize>) {
-                *self.gdk_plaintext_len.lock().unwrap() = len;
-            }
-        }
-
         fn write_json_ok(mut stream: TcpStream, body: serde_json::Value) {
             let body = body.to_string();
             let resp = format!(
@@ -519,6 +518,7 @@ mod tests {
 
         let pt = b"top secret bytes".to_vec();
         let ct = p.wrap_blob(&pt).unwrap();
+        assert_eq!(ct[0], BlobTag::Aws as u8);
         let got = p.unwrap_blob(&ct).unwrap();
 
         assert_eq!(got, pt);
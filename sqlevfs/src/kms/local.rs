@@ -1,48 +1,216 @@
-use std::path::PathBuf;
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
 
 use argon2::Argon2;
+use hkdf::Hkdf;
 use parking_lot::Mutex;
+use sha2::Sha256;
+use zeroize::Zeroizing;
 
 use super::KmsProvider;
-use crate::crypto::keys::KekId;
+use crate::crypto::keys::{KekId, decode_hex};
 
-/// Device-local KEK provider. Reads a 32-byte key from a file, or
-/// derives one from a passphrase via Argon2id.
+/// Device-local KEK provider. Reads a 32-byte key from a file, derives
+/// one from an arbitrary-length keyfile via a [`KdfKind`], or derives one
+/// from a passphrase via Argon2id.
 pub struct DeviceKeyProvider {
     id: KekId,
-    /// Cached KEK bytes - computed once, then reused.
+    /// Cached KEK bytes - computed once, then reused until [`reload`].
+    ///
+    /// [`reload`]: Self::reload
     cached: Mutex<Option<Vec<u8>>>,
+    /// KEKs replaced by a reload, still accepted for unwrapping.
+    retired: Mutex<Vec<Vec<u8>>>,
+    /// Number of reloads that replaced the KEK.
+    generation: AtomicU64,
+    /// Reload when the keyfile's mtime differs from the one recorded.
+    watch: bool,
+    loaded_mtime: Mutex<Option<SystemTime>>,
     source: KeySource,
+    /// Older KEK versions, consulted by id when unwrapping only.
+    previous: Vec<DeviceKeyProvider>,
 }
 
 enum KeySource {
     File(PathBuf),
+    FileKdf(PathBuf, KdfKind),
     Passphrase(String),
 }
 
+/// KDF used to turn arbitrary-length keyfile contents into a 32-byte KEK.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfKind {
+    /// HKDF-SHA256; fast, suitable for high-entropy inputs.
+    HkdfSha256,
+    /// Argon2id; slow, suitable for low-entropy secrets.
+    Argon2id,
+}
+
+impl KdfKind {
+    fn label(self) -> &'static str {
+        match self {
+            KdfKind::HkdfSha256 => "hkdf-sha256",
+            KdfKind::Argon2id => "argon2id",
+        }
+    }
+
+    fn derive(self, input: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut kek = [0u8; 32];
+        match self {
+            KdfKind::HkdfSha256 => {
+                Hkdf::<Sha256>::new(Some(DEFAULT_SALT), input)
+                    .expand(HKDF_INFO, &mut kek)
+                    .map_err(|e| anyhow::anyhow!("hkdf failed: {e}"))?;
+            }
+            KdfKind::Argon2id => {
+                Argon2::default()
+                    .hash_password_into(input, DEFAULT_SALT, &mut kek)
+                    .map_err(|e| anyhow::anyhow!("argon2 failed: {e}"))?;
+            }
+        }
+        Ok(kek.to_vec())
+    }
+}
+
 /// Fixed salt for passphrase derivation. In production, store a
 /// random salt alongside the database and pass it in.
 const DEFAULT_SALT: &[u8; 16] = b"evfs-default-slt";
 
+/// HKDF context string binding derived keys to their use as a KEK.
+const HKDF_INFO: &[u8] = b"evfs keyfile kek v1";
+
 impl DeviceKeyProvider {
     pub fn from_keyfile(path: PathBuf) -> Self {
         let id = KekId(format!("device:file:{}", path.display()));
         Self {
             id,
             cached: Mutex::new(None),
+            retired: Mutex::new(Vec::new()),
+            generation: AtomicU64::new(0),
+            watch: false,
+            loaded_mtime: Mutex::new(None),
+            previous: Vec::new(),
             source: KeySource::File(path),
         }
     }
 
+    /// Like [`from_keyfile`](Self::from_keyfile), but accepts a keyfile of
+    /// any non-zero length and derives the 32-byte KEK from it with `kdf`.
+    pub fn from_keyfile_kdf(path: PathBuf, kdf: KdfKind) -> Self {
+        let id = KekId(format!("device:file+{}:{}", kdf.label(), path.display()));
+        Self {
+            id,
+            cached: Mutex::new(None),
+            retired: Mutex::new(Vec::new()),
+            generation: AtomicU64::new(0),
+            watch: false,
+            loaded_mtime: Mutex::new(None),
+            previous: Vec::new(),
+            source: KeySource::FileKdf(path, kdf),
+        }
+    }
+
     pub fn from_passphrase(passphrase: &str) -> Self {
         let id = KekId("device:passphrase".into());
         Self {
             id,
             cached: Mutex::new(None),
+            retired: Mutex::new(Vec::new()),
+            generation: AtomicU64::new(0),
+            watch: false,
+            loaded_mtime: Mutex::new(None),
+            previous: Vec::new(),
             source: KeySource::Passphrase(passphrase.to_owned()),
         }
     }
 
+    /// Tag this KEK with a version, appended to its id as `#v<version>`, so
+    /// that several keyfiles or passphrases can be told apart; see
+    /// [`with_previous`](Self::with_previous). DEKs wrapped before the
+    /// version was added carry the bare id and still unwrap with it.
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.id = KekId(format!("{}#v{version}", self.id.0));
+        self
+    }
+
+    /// Keep an older KEK version around for unwrapping, e.g. the keyfile
+    /// a rotation is moving away from. DEKs still wrapped under its id
+    /// unwrap with it while new ones are wrapped under this KEK, so a
+    /// database whose rotation was interrupted still opens. Versions must
+    /// have distinct ids; use [`with_version`](Self::with_version).
+    pub fn with_previous(mut self, previous: DeviceKeyProvider) -> Self {
+        self.previous.push(previous);
+        self
+    }
+
+    /// The provider serving KEK `id`: this one or an older version. An id
+    /// from before [`with_version`](Self::with_version) was used goes to
+    /// the version with the same source.
+    fn version(&self, id: &KekId) -> Option<&DeviceKeyProvider> {
+        self.exact_version(id)
+            .or_else(|| self.versions().find(|v| v.unversioned_id() == id.0))
+    }
+
+    fn exact_version(&self, id: &KekId) -> Option<&DeviceKeyProvider> {
+        if id == &self.id {
+            return Some(self);
+        }
+        self.previous.iter().find_map(|p| p.exact_version(id))
+    }
+
+    /// This provider and every older version.
+    fn versions(&self) -> Box<dyn Iterator<Item = &DeviceKeyProvider> + '_> {
+        Box::new(std::iter::once(self).chain(self.previous.iter().flat_map(|p| p.versions())))
+    }
+
+    /// The id without the `#v<version>` suffix.
+    fn unversioned_id(&self) -> &str {
+        self.id
+            .0
+            .rsplit_once("#v")
+            .map_or(&self.id.0, |(base, _)| base)
+    }
+
+    /// Re-read the keyfile on the next KEK lookup whenever its mtime
+    /// changes, instead of only on [`reload`](Self::reload).
+    pub fn watch_keyfile(mut self, watch: bool) -> Self {
+        self.watch = watch;
+        self
+    }
+
+    /// Drop the cached KEK and re-read it from the keyfile, e.g. after
+    /// the file was rotated. DEKs already unwrapped stay valid, and the
+    /// previous KEK is kept in memory so DEKs wrapped under it can still
+    /// be unwrapped. A [`Keyring`] over this provider notices the new KEK
+    /// on its next key lookup and re-wraps the persisted keyring under it.
+    ///
+    /// [`Keyring`]: crate::keyring::Keyring
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let mtime = self.keyfile_mtime();
+        let kek = self.load_kek()?;
+        let mut guard = self.cached.lock();
+        if let Some(old) = guard.replace(kek.clone())
+            && old != kek
+        {
+            self.retired.lock().push(old);
+            self.generation.fetch_add(1, Ordering::AcqRel);
+        }
+        *self.loaded_mtime.lock() = mtime;
+        Ok(())
+    }
+
+    fn keyfile_mtime(&self) -> Option<SystemTime> {
+        match &self.source {
+            KeySource::File(path) | KeySource::FileKdf(path, _) => {
+                std::fs::metadata(path).and_then(|m| m.modified()).ok()
+            }
+            KeySource::Passphrase(_) => None,
+        }
+    }
+
     fn load_kek(&self) -> anyhow::Result<Vec<u8>> {
         match &self.source {
             KeySource::File(path) => {
@@ -54,23 +222,32 @@ impl DeviceKeyProvider {
                 );
                 Ok(bytes)
             }
-            KeySource::Passphrase(pw) => {
-                let mut kek = [0u8; 32];
-                Argon2::default()
-                    .hash_password_into(pw.as_bytes(), DEFAULT_SALT, &mut kek)
-                    .map_err(|e| anyhow::anyhow!("argon2 failed: {e}"))?;
-                Ok(kek.to_vec())
+            KeySource::FileKdf(path, kdf) => {
+                let bytes = std::fs::read(path)?;
+                anyhow::ensure!(!bytes.is_empty(), "keyfile is empty");
+                kdf.derive(&bytes)
             }
+            KeySource::Passphrase(pw) => KdfKind::Argon2id.derive(pw.as_bytes()),
         }
     }
 
     fn get_cached_or_load(&self) -> anyhow::Result<Vec<u8>> {
+        let stale = self.watch
+            && self.cached.lock().is_some()
+            && self
+                .keyfile_mtime()
+                .is_some_and(|mtime| *self.loaded_mtime.lock() != Some(mtime));
+        if stale {
+            self.reload()?;
+        }
         let mut guard = self.cached.lock();
         if let Some(ref cached) = *guard {
             return Ok(cached.clone());
         }
+        let mtime = self.keyfile_mtime();
         let kek = self.load_kek()?;
         *guard = Some(kek.clone());
+        *self.loaded_mtime.lock() = mtime;
         Ok(kek)
     }
 }
@@ -81,6 +258,83 @@ impl KmsProvider for DeviceKeyProvider {
         Ok((self.id.clone(), bytes))
     }
 
+    fn kek_is_deterministic(&self) -> bool {
+        true
+    }
+
+    fn kek_generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    fn get_kek_by_id(&self, id: &KekId) -> anyhow::Result<Vec<u8>> {
+        match self.version(id) {
+            Some(version) => version.get_cached_or_load(),
+            None => anyhow::bail!("unknown KEK id: {id:?} (expected {:?})", self.id),
+        }
+    }
+
+    fn retired_keks(&self, id: &KekId) -> Vec<Vec<u8>> {
+        self.version(id)
+            .map(|version| version.retired.lock().clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Default environment variable read by [`EnvKeyProvider::from_default_env`].
+pub const DEFAULT_KEK_ENV: &str = "EVFS_KEK";
+
+/// KEK provider for container deployments that inject secrets as
+/// environment variables. The variable must hold a 32-byte key encoded
+/// as hex (64 characters) or standard base64.
+pub struct EnvKeyProvider {
+    id: KekId,
+    var: String,
+    /// Cached KEK bytes - decoded once, then reused.
+    cached: Mutex<Option<Vec<u8>>>,
+}
+
+impl EnvKeyProvider {
+    pub fn from_env(var: impl Into<String>) -> Self {
+        let var = var.into();
+        let id = KekId(format!("device:env:{var}"));
+        Self {
+            id,
+            var,
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub fn from_default_env() -> Self {
+        Self::from_env(DEFAULT_KEK_ENV)
+    }
+
+    fn load_kek(&self) -> anyhow::Result<Vec<u8>> {
+        let value = std::env::var(&self.var)
+            .map_err(|e| anyhow::anyhow!("cannot read {}: {e}", self.var))?;
+        decode_kek(value.trim()).map_err(|e| anyhow::anyhow!("invalid KEK in {}: {e}", self.var))
+    }
+
+    fn get_cached_or_load(&self) -> anyhow::Result<Vec<u8>> {
+        let mut guard = self.cached.lock();
+        if let Some(ref cached) = *guard {
+            return Ok(cached.clone());
+        }
+        let kek = self.load_kek()?;
+        *guard = Some(kek.clone());
+        Ok(kek)
+    }
+}
+
+impl KmsProvider for EnvKeyProvider {
+    fn get_kek(&self) -> anyhow::Result<(KekId, Vec<u8>)> {
+        let bytes = self.get_cached_or_load()?;
+        Ok((self.id.clone(), bytes))
+    }
+
+    fn kek_is_deterministic(&self) -> bool {
+        true
+    }
+
     fn get_kek_by_id(&self, id: &KekId) -> anyhow::Result<Vec<u8>> {
         anyhow::ensure!(
             id == &self.id,
@@ -91,13 +345,77 @@ impl KmsProvider for DeviceKeyProvider {
     }
 }
 
+/// KEK provider for scratch databases: a random 32-byte KEK generated at
+/// construction and held only in memory. Once the provider is dropped,
+/// nothing it wrapped can be unwrapped again.
+pub struct EphemeralKeyProvider {
+    id: KekId,
+    kek: Zeroizing<Vec<u8>>,
+}
+
+impl EphemeralKeyProvider {
+    pub fn new() -> Self {
+        let mut kek = Zeroizing::new(vec![0u8; 32]);
+        getrandom::fill(&mut kek).expect("getrandom failed");
+        let mut tag = [0u8; 8];
+        getrandom::fill(&mut tag).expect("getrandom failed");
+        let tag: String = tag.iter().map(|b| format!("{b:02x}")).collect();
+        Self {
+            id: KekId(format!("ephemeral:{tag}")),
+            kek,
+        }
+    }
+}
+
+impl Default for EphemeralKeyProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KmsProvider for EphemeralKeyProvider {
+    fn get_kek(&self) -> anyhow::Result<(KekId, Vec<u8>)> {
+        Ok((self.id.clone(), self.kek.to_vec()))
+    }
+
+    fn get_kek_by_id(&self, id: &KekId) -> anyhow::Result<Vec<u8>> {
+        anyhow::ensure!(
+            id == &self.id,
+            "unknown KEK id: {id:?} (expected {:?})",
+            self.id
+        );
+        Ok(self.kek.to_vec())
+    }
+}
+
+/// Decode a 32-byte KEK from hex or base64. Hex is tried first since a
+/// 64-character hex string is never valid 32-byte base64.
+fn decode_kek(value: &str) -> anyhow::Result<Vec<u8>> {
+    use base64::Engine as _;
+
+    let bytes = if value.len() == 64 && value.bytes().all(|b| b.is_ascii_hexdigit()) {
+        decode_hex(value)?
+    } else {
+        base64::engine::general_purpose::STANDARD
+            .decode(value)
+            .map_err(|_| anyhow::anyhow!("expected hex or base64"))?
+    };
+    anyhow::ensure!(
+        bytes.len() == 32,
+        "KEK must decode to exactly 32 bytes, got {}",
+        bytes.len()
+    );
+    Ok(bytes)
+}
+
 #[cfg(test)]
 mod tests {
-    use std::io::Write;
+    use std::{io::Write, sync::Arc};
 
     use tempfile::NamedTempFile;
 
     use super::*;
+    use crate::{crypto::keys::KeyScope, keyring::Keyring};
 
     #[test]
     fn test_from_keyfile_id() {
@@ -156,6 +474,68 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_keyfile_kdf_id() {
+        let path = PathBuf::from("/test/key.bin");
+        let provider = DeviceKeyProvider::from_keyfile_kdf(path.clone(), KdfKind::HkdfSha256);
+        assert_eq!(
+            provider.id,
+            KekId(format!("device:file+hkdf-sha256:{}", path.display()))
+        );
+    }
+
+    #[test]
+    fn test_keyfile_kdf_short_and_long_inputs() -> anyhow::Result<()> {
+        for kdf in [KdfKind::HkdfSha256, KdfKind::Argon2id] {
+            for len in [16usize, 4096] {
+                let mut file = NamedTempFile::new()?;
+                file.write_all(&vec![0x5Au8; len])?;
+                file.flush()?;
+
+                let kek1 = DeviceKeyProvider::from_keyfile_kdf(file.path().to_path_buf(), kdf)
+                    .load_kek()?;
+                let kek2 = DeviceKeyProvider::from_keyfile_kdf(file.path().to_path_buf(), kdf)
+                    .load_kek()?;
+
+                assert_eq!(kek1.len(), 32);
+                assert_eq!(kek1, kek2, "{kdf:?} must be stable for {len}-byte input");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_keyfile_kdf_kinds_differ() -> anyhow::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        file.write_all(&[0x42u8; 32])?;
+        file.flush()?;
+
+        let raw = DeviceKeyProvider::from_keyfile(file.path().to_path_buf()).load_kek()?;
+        let hkdf =
+            DeviceKeyProvider::from_keyfile_kdf(file.path().to_path_buf(), KdfKind::HkdfSha256)
+                .load_kek()?;
+        let argon =
+            DeviceKeyProvider::from_keyfile_kdf(file.path().to_path_buf(), KdfKind::Argon2id)
+                .load_kek()?;
+
+        assert_ne!(raw, hkdf);
+        assert_ne!(raw, argon);
+        assert_ne!(hkdf, argon);
+        Ok(())
+    }
+
+    #[test]
+    fn test_keyfile_kdf_empty_file() -> anyhow::Result<()> {
+        let file = NamedTempFile::new()?;
+
+        let provider =
+            DeviceKeyProvider::from_keyfile_kdf(file.path().to_path_buf(), KdfKind::HkdfSha256);
+        let result = provider.load_kek();
+
+        assert!(result.unwrap_err().to_string().contains("empty"));
+        Ok(())
+    }
+
     #[test]
     fn test_keyfile_not_found() {
         let provider = DeviceKeyProvider::from_keyfile(PathBuf::from("/nonexistent/path/key.bin"));
@@ -337,4 +717,182 @@ mod tests {
         assert_eq!(kek.len(), 32);
         Ok(())
     }
+
+    #[test]
+    fn test_env_kek_hex() -> anyhow::Result<()> {
+        let var = "EVFS_TEST_KEK_HEX";
+        unsafe { std::env::set_var(var, "ab".repeat(32)) };
+
+        let provider = EnvKeyProvider::from_env(var);
+        let (id, kek) = provider.get_kek()?;
+
+        assert_eq!(id, KekId(format!("device:env:{var}")));
+        assert_eq!(kek, vec![0xABu8; 32]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_kek_base64() -> anyhow::Result<()> {
+        use base64::Engine as _;
+
+        let var = "EVFS_TEST_KEK_B64";
+        let encoded = base64::engine::general_purpose::STANDARD.encode([0x11u8; 32]);
+        unsafe { std::env::set_var(var, format!("{encoded}\n")) };
+
+        let provider = EnvKeyProvider::from_env(var);
+        let kek = provider.get_kek_by_id(&KekId(format!("device:env:{var}")))?;
+
+        assert_eq!(kek, vec![0x11u8; 32]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_kek_malformed() {
+        let var = "EVFS_TEST_KEK_BAD";
+        unsafe { std::env::set_var(var, "not a key!") };
+        let err = EnvKeyProvider::from_env(var).get_kek().unwrap_err();
+        assert!(err.to_string().contains("expected hex or base64"));
+
+        // Well-formed base64, wrong length.
+        unsafe { std::env::set_var(var, "AAAA") };
+        let err = EnvKeyProvider::from_env(var).get_kek().unwrap_err();
+        assert!(err.to_string().contains("exactly 32 bytes"));
+    }
+
+    #[test]
+    fn test_env_kek_unset() {
+        let provider = EnvKeyProvider::from_env("EVFS_TEST_KEK_UNSET");
+        assert!(provider.get_kek().is_err());
+    }
+
+    #[test]
+    fn test_ephemeral_providers_cannot_read_each_other() -> anyhow::Result<()> {
+        let first = Arc::new(EphemeralKeyProvider::new());
+        let second = Arc::new(EphemeralKeyProvider::new());
+        assert_ne!(first.get_kek()?, second.get_kek()?);
+
+        let keyring = Keyring::new(first.clone());
+        let dek = keyring.dek_for(&KeyScope::Database)?;
+
+        // The same wrapped DEKs under the other provider stay sealed.
+        let other = Keyring::new(second);
+        other.import_wrapped(&keyring.export_wrapped())?;
+        assert!(other.dek_for(&KeyScope::Database).is_err());
+
+        let again = Keyring::new(first);
+        again.import_wrapped(&keyring.export_wrapped())?;
+        assert_eq!(again.dek_for(&KeyScope::Database)?, dek);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reload_picks_up_rotated_keyfile() -> anyhow::Result<()> {
+        let file = NamedTempFile::new()?;
+        std::fs::write(file.path(), [0x01u8; 32])?;
+        let provider = Arc::new(DeviceKeyProvider::from_keyfile(file.path().to_path_buf()));
+        let keyring = Keyring::new(provider.clone());
+        let dek = keyring.dek_for(&KeyScope::Database)?;
+
+        std::fs::write(file.path(), [0x02u8; 32])?;
+        // Without a reload the cached KEK is still served.
+        assert_eq!(provider.get_kek()?.1, vec![0x01; 32]);
+
+        provider.reload()?;
+        assert_eq!(provider.get_kek()?.1, vec![0x02; 32]);
+        keyring.rewrap_all()?;
+
+        // The persisted DEK now unwraps under the new KEK alone.
+        let fresh = DeviceKeyProvider::from_keyfile(file.path().to_path_buf());
+        let reopened = Keyring::new(Arc::new(fresh));
+        reopened.import_wrapped(&keyring.export_wrapped())?;
+        assert_eq!(reopened.dek_for(&KeyScope::Database)?, dek);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deks_wrapped_under_two_kek_versions_both_unwrap() -> anyhow::Result<()> {
+        use crate::crypto::{envelope, keys::Dek};
+
+        let old_file = NamedTempFile::new()?;
+        std::fs::write(old_file.path(), [0x01u8; 32])?;
+        let new_file = NamedTempFile::new()?;
+        std::fs::write(new_file.path(), [0x02u8; 32])?;
+        let old = || DeviceKeyProvider::from_keyfile(old_file.path().to_path_buf()).with_version(1);
+
+        // Rotation interrupted: one DEK still under v1, one already under v2.
+        let old_dek = Dek::generate();
+        let old_wrapped = envelope::wrap_dek(&old_dek, &old())?;
+        let current = DeviceKeyProvider::from_keyfile(new_file.path().to_path_buf())
+            .with_version(2)
+            .with_previous(old());
+        let new_dek = Dek::generate();
+        let new_wrapped = envelope::wrap_dek(&new_dek, &current)?;
+        assert_ne!(old_wrapped.kek_id, new_wrapped.kek_id);
+        assert!(new_wrapped.kek_id.0.ends_with("#v2"));
+
+        assert_eq!(envelope::unwrap_dek(&old_wrapped, &current)?, old_dek);
+        assert_eq!(envelope::unwrap_dek(&new_wrapped, &current)?, new_dek);
+        assert_eq!(current.get_kek()?.1, vec![0x02; 32]);
+
+        let unknown = KekId("device:passphrase#v3".into());
+        assert!(current.get_kek_by_id(&unknown).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deks_wrapped_before_versioning_still_unwrap() -> anyhow::Result<()> {
+        use crate::crypto::{envelope, keys::Dek};
+
+        let old_file = NamedTempFile::new()?;
+        std::fs::write(old_file.path(), [0x01u8; 32])?;
+        let new_file = NamedTempFile::new()?;
+        std::fs::write(new_file.path(), [0x02u8; 32])?;
+
+        let dek = Dek::generate();
+        let wrapped = envelope::wrap_dek(
+            &dek,
+            &DeviceKeyProvider::from_keyfile(old_file.path().to_path_buf()),
+        )?;
+        assert!(!wrapped.kek_id.0.contains("#v"));
+
+        let current = DeviceKeyProvider::from_keyfile(new_file.path().to_path_buf())
+            .with_version(2)
+            .with_previous(
+                DeviceKeyProvider::from_keyfile(old_file.path().to_path_buf()).with_version(1),
+            );
+        assert_eq!(envelope::unwrap_dek(&wrapped, &current)?, dek);
+        Ok(())
+    }
+
+    #[test]
+    fn test_passphrase_versions_are_told_apart_by_id() -> anyhow::Result<()> {
+        let provider = DeviceKeyProvider::from_passphrase("new")
+            .with_version(2)
+            .with_previous(DeviceKeyProvider::from_passphrase("old").with_version(1));
+        let v1 = KekId("device:passphrase#v1".into());
+        assert_eq!(
+            provider.get_kek_by_id(&v1)?,
+            DeviceKeyProvider::from_passphrase("old").load_kek()?
+        );
+        assert_ne!(provider.get_kek_by_id(&v1)?, provider.get_kek()?.1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_keyfile_reloads_on_mtime_change() -> anyhow::Result<()> {
+        let file = NamedTempFile::new()?;
+        std::fs::write(file.path(), [0x01u8; 32])?;
+        let provider =
+            DeviceKeyProvider::from_keyfile(file.path().to_path_buf()).watch_keyfile(true);
+        assert_eq!(provider.get_kek()?.1, vec![0x01; 32]);
+
+        std::fs::write(file.path(), [0x02u8; 32])?;
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(file.path())?
+            .set_modified(later)?;
+        assert_eq!(provider.get_kek()?.1, vec![0x02; 32]);
+        Ok(())
+    }
 }
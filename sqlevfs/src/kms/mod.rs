@@ -1,6 +1,11 @@
+pub mod blocking;
 pub mod cloud;
+#[cfg(feature = "gcp-kms")]
+pub mod gcp;
 pub mod local;
 
+use futures::future::BoxFuture;
+
 use crate::crypto::keys::KekId;
 
 /// Synchronous interface for obtaining key-encryption keys.
@@ -12,15 +17,240 @@ pub trait KmsProvider: Send + Sync + 'static {
     /// unwrap DEKs wrapped under older KEKs).
     fn get_kek_by_id(&self, id: &KekId) -> anyhow::Result<Vec<u8>>;
 
+    /// KEK bytes previously served under `id` and since replaced in
+    /// process (e.g. by a keyfile reload). Unwrapping falls back to these
+    /// so DEKs wrapped before the change stay readable until re-wrapped.
+    fn retired_keks(&self, _id: &KekId) -> Vec<Vec<u8>> {
+        Vec::new()
+    }
+
+    /// Whether [`get_kek`](Self::get_kek) returns the same KEK in every
+    /// process configured alike, e.g. from a keyfile or passphrase rather
+    /// than generated per process. [`KeyringStorage::Derived`](crate::keyring::KeyringStorage::Derived)
+    /// keeps nothing but the KEK, so it requires this.
+    fn kek_is_deterministic(&self) -> bool {
+        false
+    }
+
+    /// Count of in-process KEK replacements under an unchanged id, e.g.
+    /// keyfile reloads. A [`Keyring`](crate::keyring::Keyring) re-wraps its
+    /// persisted DEKs when it sees this change, before the retired KEK is
+    /// lost with the process.
+    fn kek_generation(&self) -> u64 {
+        0
+    }
+
+    /// Which kind of provider this is, recorded in every blob frame.
+    fn blob_tag(&self) -> BlobTag {
+        BlobTag::Local
+    }
+
     /// Optional: ask the KMS to wrap a blob directly (for providers
-    /// where the KEK never leaves the HSM). Default falls back to
-    /// local envelope encryption.
-    fn wrap_blob(&self, _plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    /// where the KEK never leaves the HSM), in its own wire format.
+    /// Default falls back to local envelope encryption.
+    fn wrap_blob_raw(&self, _plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
         anyhow::bail!("direct wrap not supported; use local envelope")
     }
 
-    /// Optional: ask the KMS to unwrap a blob directly.
-    fn unwrap_blob(&self, _ciphertext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    /// Optional: ask the KMS to unwrap a blob from
+    /// [`wrap_blob_raw`](Self::wrap_blob_raw) directly.
+    fn unwrap_blob_raw(&self, _ciphertext: &[u8]) -> anyhow::Result<Vec<u8>> {
         anyhow::bail!("direct unwrap not supported; use local envelope")
     }
+
+    /// [`wrap_blob_raw`](Self::wrap_blob_raw), framed with this provider's
+    /// [`BlobTag`] and [`BLOB_FRAME_VERSION`]; see [`frame_blob`].
+    fn wrap_blob(&self, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        Ok(frame_blob(self.blob_tag(), &self.wrap_blob_raw(plaintext)?))
+    }
+
+    /// Unwrap a blob from [`wrap_blob`](Self::wrap_blob). A blob framed by
+    /// another kind of provider fails with "provider mismatch" before the
+    /// KMS is asked to decrypt it.
+    fn unwrap_blob(&self, framed: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.unwrap_blob_raw(unframe_blob(self.blob_tag(), framed)?)
+    }
+}
+
+/// Current layout of the frame written by [`frame_blob`].
+pub const BLOB_FRAME_VERSION: u16 = 1;
+
+const BLOB_HEADER_LEN: usize = 3;
+
+/// Provider type that wrapped a blob: the first byte of its frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum BlobTag {
+    /// Local envelope providers (keyfile, passphrase, env, ephemeral).
+    Local = 1,
+    /// AWS KMS, or a KMS with the same JSON API.
+    Aws = 2,
+    /// Google Cloud KMS.
+    Gcp = 3,
+}
+
+impl BlobTag {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(BlobTag::Local),
+            2 => Some(BlobTag::Aws),
+            3 => Some(BlobTag::Gcp),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for BlobTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BlobTag::Local => "local",
+            BlobTag::Aws => "aws",
+            BlobTag::Gcp => "gcp",
+        })
+    }
+}
+
+/// Frame provider ciphertext as `tag (1 byte) | version (u16 BE) |
+/// ciphertext`, so a blob says which kind of provider can unwrap it.
+pub fn frame_blob(tag: BlobTag, ciphertext: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(BLOB_HEADER_LEN + ciphertext.len());
+    framed.push(tag as u8);
+    framed.extend_from_slice(&BLOB_FRAME_VERSION.to_be_bytes());
+    framed.extend_from_slice(ciphertext);
+    framed
+}
+
+/// Check a frame from [`frame_blob`] against the `expected` provider tag
+/// and return the provider ciphertext inside it.
+pub fn unframe_blob(expected: BlobTag, framed: &[u8]) -> anyhow::Result<&[u8]> {
+    anyhow::ensure!(
+        framed.len() >= BLOB_HEADER_LEN,
+        "wrapped blob is too short to carry a provider frame"
+    );
+    match BlobTag::from_byte(framed[0]) {
+        Some(tag) if tag == expected => {}
+        Some(tag) => anyhow::bail!(
+            "provider mismatch: blob was wrapped by a {tag} provider, this is a {expected} provider"
+        ),
+        None => anyhow::bail!(
+            "provider mismatch: blob has unknown provider tag {:#04x}",
+            framed[0]
+        ),
+    }
+    let version = u16::from_be_bytes([framed[1], framed[2]]);
+    anyhow::ensure!(
+        version <= BLOB_FRAME_VERSION,
+        "blob frame version {version} is newer than supported ({BLOB_FRAME_VERSION})"
+    );
+    Ok(&framed[BLOB_HEADER_LEN..])
+}
+
+/// Async counterpart of [`KmsProvider`] for providers built on async
+/// clients (cloud SDKs, HTTP). Methods return boxed futures so the trait
+/// stays object-safe. The VFS is synchronous; wrap an implementation in
+/// [`blocking::BlockingKms`] to use it as a [`KmsProvider`].
+pub trait AsyncKmsProvider: Send + Sync + 'static {
+    /// See [`KmsProvider::get_kek`].
+    fn get_kek(&self) -> BoxFuture<'_, anyhow::Result<(KekId, Vec<u8>)>>;
+
+    /// See [`KmsProvider::get_kek_by_id`].
+    fn get_kek_by_id<'a>(&'a self, id: &'a KekId) -> BoxFuture<'a, anyhow::Result<Vec<u8>>>;
+
+    /// See [`KmsProvider::blob_tag`].
+    fn blob_tag(&self) -> BlobTag {
+        BlobTag::Local
+    }
+
+    /// See [`KmsProvider::wrap_blob_raw`].
+    fn wrap_blob_raw<'a>(&'a self, _plaintext: &'a [u8]) -> BoxFuture<'a, anyhow::Result<Vec<u8>>> {
+        Box::pin(async { anyhow::bail!("direct wrap not supported; use local envelope") })
+    }
+
+    /// See [`KmsProvider::unwrap_blob_raw`].
+    fn unwrap_blob_raw<'a>(
+        &'a self,
+        _ciphertext: &'a [u8],
+    ) -> BoxFuture<'a, anyhow::Result<Vec<u8>>> {
+        Box::pin(async { anyhow::bail!("direct unwrap not supported; use local envelope") })
+    }
+}
+
+/// A KMS request that failed in a way a retry may fix: the service could
+/// not be reached, or answered 429 or 5xx. Providers return it inside
+/// their `anyhow::Error`; only these failures are reported (and retried)
+/// as [`KeyringError::KmsUnavailable`](crate::keyring::KeyringError::KmsUnavailable).
+/// An unknown KEK id or a missing keyfile fails at once.
+#[derive(Debug)]
+pub struct KmsTransientError(pub anyhow::Error);
+
+impl std::fmt::Display for KmsTransientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for KmsTransientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Whether `err` carries a [`KmsTransientError`] anywhere in its chain.
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<KmsTransientError>())
+}
+
+/// Mark a failed HTTP request to a KMS as transient when retrying it may
+/// succeed; see [`KmsTransientError`].
+pub(crate) fn request_error(e: ureq::Error) -> anyhow::Error {
+    match &e {
+        ureq::Error::Transport(_) => KmsTransientError(e.into()).into(),
+        ureq::Error::Status(code, _) if *code == 429 || *code >= 500 => {
+            KmsTransientError(e.into()).into()
+        }
+        ureq::Error::Status(..) => e.into(),
+    }
+}
+
+/// Observer for KMS traffic, e.g. to feed Prometheus counters. Each
+/// callback fires once per DEK wrap/unwrap, whether or not it succeeds.
+pub trait KmsMetrics: Send + Sync + 'static {
+    fn on_wrap(&self) {}
+    fn on_unwrap(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{local::EphemeralKeyProvider, *};
+
+    #[test]
+    fn test_frame_round_trip() -> anyhow::Result<()> {
+        let framed = frame_blob(BlobTag::Gcp, b"ciphertext");
+        assert_eq!(framed[0], BlobTag::Gcp as u8);
+        assert_eq!(&framed[1..3], &BLOB_FRAME_VERSION.to_be_bytes());
+        assert_eq!(unframe_blob(BlobTag::Gcp, &framed)?, b"ciphertext");
+        Ok(())
+    }
+
+    #[test]
+    fn test_aws_blob_fed_to_local_provider_is_a_mismatch() {
+        let aws_blob = frame_blob(BlobTag::Aws, b"aws ciphertext");
+        let err = EphemeralKeyProvider::new()
+            .unwrap_blob(&aws_blob)
+            .unwrap_err();
+        assert!(err.to_string().contains("provider mismatch"), "{err}");
+        assert!(err.to_string().contains("aws"), "{err}");
+    }
+
+    #[test]
+    fn test_malformed_frames_are_rejected() {
+        assert!(unframe_blob(BlobTag::Local, &[1, 0]).is_err());
+        let err = unframe_blob(BlobTag::Local, &[0x7f, 0, 1]).unwrap_err();
+        assert!(err.to_string().contains("provider mismatch"));
+
+        let mut newer = frame_blob(BlobTag::Local, b"x");
+        newer[1..3].copy_from_slice(&(BLOB_FRAME_VERSION + 1).to_be_bytes());
+        let err = unframe_blob(BlobTag::Local, &newer).unwrap_err();
+        assert!(err.to_string().contains("newer than supported"));
+    }
 }
@@ -1,4 +1,15 @@
+/// Enter a `tracing` span, at debug level, until the end of the enclosing
+/// block. Expands to nothing without the `trace` feature, so the span's
+/// fields are not even evaluated.
+macro_rules! trace_span {
+    ($($args:tt)*) => {
+        #[cfg(feature = "trace")]
+        let _trace_span = tracing::debug_span!($($args)*).entered();
+    };
+}
+
 pub mod backup;
+pub mod btree;
 pub mod crypto;
 pub mod io;
 pub mod keyring;
@@ -11,10 +22,11 @@ use std::{
     ffi::c_void,
     path::PathBuf,
     sync::{Arc, atomic::AtomicPtr},
+    time::Duration,
 };
 
-use keyring::Keyring;
-use kms::KmsProvider;
+use keyring::{Keyring, KeyringStorage};
+use kms::{KmsMetrics, KmsProvider};
 use libsqlite3_sys::SQLITE_ERROR;
 
 static EXT_HANDLE: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
@@ -62,6 +74,12 @@ pub enum Mode {
         keyfile: Option<PathBuf>,
         passphrase: Option<String>,
     },
+    /// Single device - KEK injected through an environment variable
+    /// (hex or base64), e.g. from a Kubernetes secret.
+    EnvKey {
+        /// Variable name; defaults to `EVFS_KEK` when built from the env.
+        var: String,
+    },
     /// Multi-tenant SaaS - each tenant has a cloud KMS key.
     TenantKey {
         /// Cloud KMS key identifier (ARN, resource name, key URI, …).
@@ -69,13 +87,39 @@ pub enum Mode {
         /// Region / endpoint override.
         endpoint: Option<String>,
     },
+    /// Scratch data - a random KEK that lives only as long as the
+    /// process; databases written under it are unreadable afterwards.
+    Ephemeral,
 }
 
+/// Page size of new databases when the builder sets none.
+pub const DEFAULT_PAGE_SIZE: u32 = 4096;
+
+/// Largest page number the VFS reads or writes when the builder sets no
+/// limit: 4 TiB of 4 KiB pages.
+pub const DEFAULT_MAX_PAGES: u32 = 1 << 30;
+
 pub struct EvfsBuilder {
     pub name: String,
-    pub page_size: u32,
-    pub reserve_size: usize,
+    /// Explicit page size; `None` reads it from an existing database's
+    /// header on open and uses [`DEFAULT_PAGE_SIZE`] for new ones.
+    pub page_size: Option<u32>,
+    /// Explicit reserve, from [`crypto::page::MIN_RESERVE`] to 255 bytes,
+    /// leaving at least 480 usable per page; `None` means
+    /// [`crypto::page::default_reserve`]`(page_size)`.
+    pub reserve_size: Option<usize>,
     pub provider: Arc<dyn KmsProvider>,
+    pub read_only: bool,
+    pub keyring_storage: KeyringStorage,
+    pub keyring_path: Option<PathBuf>,
+    pub metrics: Option<Arc<dyn KmsMetrics>>,
+    pub dek_cache_capacity: Option<usize>,
+    /// `(attempts, initial backoff)` for unwraps while the KMS is down.
+    pub kms_retry: Option<(u32, Duration)>,
+    pub base_vfs: Option<String>,
+    pub allow_replace: bool,
+    pub encrypt_header: bool,
+    pub max_pages: u32,
 }
 
 impl EvfsBuilder {
@@ -93,44 +137,215 @@ impl EvfsBuilder {
                     panic!("DeviceKey mode requires keyfile or passphrase");
                 }
             }
+            Mode::EnvKey { var } => Arc::new(kms::local::EnvKeyProvider::from_env(var)),
             Mode::TenantKey { key_id, endpoint } => {
                 Arc::new(kms::cloud::CloudKmsProvider::new(key_id, endpoint))
             }
+            Mode::Ephemeral => Arc::new(kms::local::EphemeralKeyProvider::new()),
         };
         Self {
             name: "evfs".into(),
-            page_size: 4096,
-            reserve_size: 48, // 16 tag + 6 marker + 26 spare
+            page_size: None,
+            reserve_size: None,
             provider,
+            read_only: false,
+            keyring_storage: KeyringStorage::Sidecar,
+            keyring_path: None,
+            metrics: None,
+            dek_cache_capacity: None,
+            kms_retry: None,
+            base_vfs: None,
+            allow_replace: false,
+            encrypt_header: false,
+            max_pages: DEFAULT_MAX_PAGES,
         }
     }
 
+    /// Fix the page size, for new databases and existing ones alike.
+    /// Without it an existing database is opened with the page size its
+    /// header records.
     pub fn page_size(mut self, size: u32) -> Self {
-        self.page_size = size;
+        self.page_size = Some(size);
         self
     }
 
+    /// The page size new databases get: the explicit one if set, else
+    /// [`DEFAULT_PAGE_SIZE`].
+    pub fn effective_page_size(&self) -> u32 {
+        self.page_size.unwrap_or(DEFAULT_PAGE_SIZE)
+    }
+
+    /// Override the computed reserve, for new databases and existing ones
+    /// alike. Without it an existing database is opened with the reserve
+    /// its header records.
     pub fn reserve_size(mut self, size: usize) -> Self {
-        self.reserve_size = size;
+        self.reserve_size = Some(size);
         self
     }
 
+    /// The reserve `register()` will use for new databases: the explicit
+    /// one if set, else the default for the page size.
+    pub fn effective_reserve_size(&self) -> usize {
+        self.reserve_size
+            .unwrap_or_else(|| crypto::page::default_reserve(self.effective_page_size()))
+    }
+
     pub fn vfs_name(mut self, name: impl Into<String>) -> Self {
         self.name = name.into();
         self
     }
 
+    /// Refuse all writes below SQLite: `xWrite`/`xTruncate` on the
+    /// database, its journal and WAL return `SQLITE_READONLY` even if the
+    /// connection was opened read-write. Temp files are still writable.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Keep wrapped DEKs in a `.evfs-keyring` sidecar (the default) or
+    /// in a block embedded at the start of the database file, or derive
+    /// them from the KEK and keep nothing.
+    pub fn keyring_storage(mut self, storage: KeyringStorage) -> Self {
+        self.keyring_storage = storage;
+        self
+    }
+
+    /// Keep the sidecar keyring at `path` instead of `<db>.evfs-keyring`,
+    /// e.g. in a separate secure directory or when the database is on
+    /// read-only media. Every database opened through this VFS shares
+    /// it, so use one VFS per database.
+    pub fn keyring_path(mut self, path: PathBuf) -> Self {
+        self.keyring_path = Some(path);
+        self
+    }
+
+    /// Report every KMS wrap/unwrap made by the VFS's keyring, e.g. to
+    /// count calls against a billed cloud KMS.
+    pub fn with_metrics(mut self, metrics: Arc<dyn KmsMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Bound the number of plaintext DEKs held in memory (default:
+    /// unbounded). Least-recently-used DEKs are evicted and zeroized, and
+    /// re-unwrapped through the KMS when next needed.
+    pub fn dek_cache_capacity(mut self, capacity: usize) -> Self {
+        self.dek_cache_capacity = Some(capacity);
+        self
+    }
+
+    /// Retry a DEK unwrap up to `attempts` times, with exponential
+    /// backoff from `backoff`, while the KMS is unreachable (default: one
+    /// attempt). Reads that still fail return `SQLITE_AUTH` instead of an
+    /// I/O error.
+    pub fn kms_retry(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.kms_retry = Some((attempts, backoff));
+        self
+    }
+
+    /// Stack encryption on the already-registered VFS `name` (e.g. a
+    /// network or object-store VFS) instead of the default one. Only page
+    /// I/O goes through it: a sidecar keyring is still a local file, so
+    /// pair this with [`KeyringStorage::Embedded`] when there is no local
+    /// filesystem.
+    pub fn base_vfs(mut self, name: &str) -> Self {
+        self.base_vfs = Some(name.to_string());
+        self
+    }
+
+    /// Let `register()` replace a VFS already registered under this name
+    /// (default: it fails with "VFS '<name>' already registered").
+    /// Connections already open on the old VFS keep using it.
+    pub fn allow_replace(mut self, allow: bool) -> Self {
+        self.allow_replace = allow;
+        self
+    }
+
+    /// Also encrypt the database header on page 1 (default: plaintext),
+    /// leaving only the magic, page size and reserve byte readable. The
+    /// rest of the header, including the schema cookie and page count,
+    /// is sealed under its own DEK from the first write of page 1.
+    pub fn encrypt_header(mut self, encrypt: bool) -> Self {
+        self.encrypt_header = encrypt;
+        self
+    }
+
+    /// Refuse database reads and writes past page `pages` (default:
+    /// [`DEFAULT_MAX_PAGES`]), so a corrupt or hostile header cannot send
+    /// the VFS to absurd offsets. Writes beyond it fail with `SQLITE_FULL`,
+    /// reads with `SQLITE_IOERR_READ`.
+    pub fn max_pages(mut self, pages: u32) -> Self {
+        self.max_pages = pages;
+        self
+    }
+
+    /// Run [`Keyring::self_test`] against this builder's provider, with its
+    /// metrics and KMS retries, before (or without) registering: e.g. from
+    /// a readiness probe.
+    pub fn self_test(&self) -> anyhow::Result<()> {
+        self.keyring().self_test()
+    }
+
+    fn keyring(&self) -> Keyring {
+        let mut keyring = Keyring::new(self.provider.clone());
+        if let Some(metrics) = &self.metrics {
+            keyring = keyring.with_metrics(metrics.clone());
+        }
+        if let Some(capacity) = self.dek_cache_capacity {
+            keyring = keyring.with_cache_capacity(capacity);
+        }
+        if let Some((attempts, backoff)) = self.kms_retry {
+            keyring = keyring.with_kms_retry(attempts, backoff);
+        }
+        keyring
+    }
+
     /// Register the VFS with SQLite. Returns the keyring for use with
-    /// the backup API.
+    /// the backup API. It serves one database at a time; databases opened
+    /// alongside it (e.g. by `ATTACH`) get keyrings of their own.
+    ///
+    /// Fails before touching SQLite if the page size and reserve cannot
+    /// hold an encrypted page, or if the name is taken and
+    /// [`allow_replace`](Self::allow_replace) was not set.
     pub fn register(self) -> anyhow::Result<Arc<Keyring>> {
-        let keyring = Arc::new(Keyring::new(self.provider));
+        anyhow::ensure!(
+            self.keyring_storage != KeyringStorage::Derived || self.provider.kek_is_deterministic(),
+            "KeyringStorage::Derived needs a KEK that is the same in every process (keyfile, \
+             passphrase or environment key); this provider's is not, so every DEK would change \
+             on restart"
+        );
+        let page_size = self.effective_page_size();
+        let reserve_size = self.effective_reserve_size();
+        crypto::page::validate_page_layout(page_size, reserve_size)?;
+        let needed = crypto::page::default_reserve(page_size);
+        if self.reserve_size.is_some() && reserve_size > needed && debug() {
+            eprintln!(
+                "sqlevfs: reserve_size {reserve_size} wastes {} bytes per page; page_size {page_size} needs {needed}",
+                reserve_size - needed,
+            );
+        } else if self.reserve_size.is_none() && debug() {
+            eprintln!(
+                "sqlevfs: reserve_size defaulted to {reserve_size} for page_size {page_size}"
+            );
+        }
+        let keyring = Arc::new(self.keyring());
         vfs::register_evfs(
             &self.name,
             vfs::EvfsConfig {
                 keyring: keyring.clone(),
-                page_size: self.page_size,
-                reserve_size: self.reserve_size,
+                page_size,
+                reserve_size,
+                detect_page_size: self.page_size.is_none(),
+                detect_reserve_size: self.reserve_size.is_none(),
                 raft: None,
+                read_only: self.read_only,
+                keyring_storage: self.keyring_storage,
+                keyring_path: self.keyring_path,
+                base_vfs: self.base_vfs,
+                allow_replace: self.allow_replace,
+                encrypt_header: self.encrypt_header,
+                max_pages: self.max_pages,
             },
         )?;
         Ok(keyring)
@@ -138,7 +353,8 @@ impl EvfsBuilder {
 }
 
 /// Auto-register a default device-key VFS when loaded via LD_PRELOAD.
-/// Set `EVFS_KEYFILE` or `EVFS_PASSPHRASE` to activate.
+/// Set `EVFS_KEYFILE`, `EVFS_PASSPHRASE`, or `EVFS_KEK` to activate, and
+/// `EVFS_ENCRYPT_HEADER` to seal the database header.
 #[unsafe(no_mangle)]
 pub extern "C" fn sqlite3_sqlevfs_init(
     _db: *mut std::ffi::c_void,
@@ -172,6 +388,10 @@ pub extern "C" fn sqlite3_sqlevfs_init(
             keyfile: None,
             passphrase: Some(pw),
         }
+    } else if std::env::var_os(kms::local::DEFAULT_KEK_ENV).is_some() {
+        Mode::EnvKey {
+            var: kms::local::DEFAULT_KEK_ENV.into(),
+        }
     } else if let Ok(key_id) = std::env::var("EVFS_KMS_KEY_ID") {
         Mode::TenantKey {
             key_id,
@@ -182,20 +402,28 @@ pub extern "C" fn sqlite3_sqlevfs_init(
         return SQLITE_ERROR;
     };
 
-    match EvfsBuilder::new(mode).register() {
-        Ok(_) => {
-            if !_db.is_null() {
-                let db = _db.cast::<libsqlite3_sys::sqlite3>();
-                raft_hooks::register_raft_sql_functions(db);
-                raft_hooks::try_autostart(db);
-            }
-            libsqlite3_sys::SQLITE_OK_LOAD_PERMANENTLY
-        }
-        Err(e) => {
-            eprintln!("sqlevfs: registration failed: {e}");
-            SQLITE_ERROR
-        }
+    // Loading the extension again (e.g. into a second connection to pick up
+    // the SQL functions) must not build a second VFS over the first.
+    let registered = unsafe { !libsqlite3_sys::sqlite3_vfs_find(c"evfs".as_ptr()).is_null() };
+    let encrypt_header =
+        std::env::var("EVFS_ENCRYPT_HEADER").is_ok_and(|v| !v.is_empty() && v != "0");
+    if !registered
+        && let Err(e) = EvfsBuilder::new(mode)
+            .encrypt_header(encrypt_header)
+            .register()
+    {
+        eprintln!("sqlevfs: registration failed: {e}");
+        return SQLITE_ERROR;
+    }
+
+    if !_db.is_null() {
+        let db = _db.cast::<libsqlite3_sys::sqlite3>();
+        raft_hooks::register_raft_sql_functions(db);
+        #[cfg(feature = "rusqlite")]
+        policy::register_policy_sql_functions(db);
+        raft_hooks::try_autostart(db);
     }
+    libsqlite3_sys::SQLITE_OK_LOAD_PERMANENTLY
 }
 
 #[cfg(test)]
@@ -229,7 +457,7 @@ pub(crate) mod tests {
             Ok(vec![0xBB; 32]) // Dummy KEK
         }
 
-        fn wrap_blob(&self, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        fn wrap_blob_raw(&self, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
             *self.wrap_count.lock().unwrap() += 1;
             // Simple mock: prepend marker byte
             let mut result = vec![0xFF];
@@ -237,7 +465,7 @@ pub(crate) mod tests {
             Ok(result)
         }
 
-        fn unwrap_blob(&self, ciphertext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        fn unwrap_blob_raw(&self, ciphertext: &[u8]) -> anyhow::Result<Vec<u8>> {
             *self.unwrap_count.lock().unwrap() += 1;
             // Simple mock: strip marker byte
             if ciphertext.is_empty() || ciphertext[0] != 0xFF {
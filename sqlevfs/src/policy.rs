@@ -1,5 +1,7 @@
 use std::path::{Path, PathBuf};
 
+use serde::Serialize;
+
 #[derive(Debug, Clone, Copy)]
 pub enum Enforce {
     Warn,
@@ -11,6 +13,15 @@ pub struct StoragePolicy {
     pub journal_mode: JournalModePolicy,
     pub temp_store: TempStorePolicy,
     pub enforce: Enforce,
+    /// Turn on `PRAGMA secure_delete`. Under evfs this also overwrites
+    /// truncated tails, deleted journals/WAL files, and the keyring
+    /// sidecar once the database is deleted (best-effort on SSDs).
+    pub secure_delete: bool,
+    /// Directory SQLite puts temp files in. When set, the `temp_store`
+    /// ramdisk check runs against it and an allowed `temp_store=FILE`
+    /// also sets `PRAGMA temp_store_directory` to it. Otherwise the
+    /// check uses `SQLITE_TMPDIR`, falling back to the system temp dir.
+    pub temp_dir_override: Option<PathBuf>,
 }
 
 impl Default for StoragePolicy {
@@ -19,6 +30,8 @@ impl Default for StoragePolicy {
             journal_mode: JournalModePolicy::Memory,
             temp_store: TempStorePolicy::Memory,
             enforce: Enforce::Warn,
+            secure_delete: false,
+            temp_dir_override: None,
         }
     }
 }
@@ -60,10 +73,14 @@ pub enum TempStoreFallback {
     None,
 }
 
-#[derive(Debug, Clone)]
+/// What [`apply_storage_policy`] found and did. `Display` is for humans;
+/// [`to_json`](Self::to_json) is for log pipelines.
+#[derive(Debug, Clone, Serialize)]
 pub struct PolicyReport {
+    #[serde(serialize_with = "serialize_path_lossy")]
     pub db_dir: PathBuf,
     pub db_dir_fstype: Option<String>,
+    #[serde(serialize_with = "serialize_path_lossy")]
     pub temp_dir: PathBuf,
     pub temp_dir_fstype: Option<String>,
     pub applied_journal_mode: Option<String>,
@@ -75,6 +92,46 @@ impl PolicyReport {
     fn note(&mut self, s: impl Into<String>) {
         self.notes.push(s.into());
     }
+
+    /// The report as a JSON object with the same fields as the struct.
+    /// Fallbacks show up in `notes` (e.g. `"temp_store=FILE denied; fell
+    /// back to MEMORY"`).
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("PolicyReport serialization cannot fail")
+    }
+}
+
+/// Paths need not be UTF-8; don't let one fail the whole report.
+fn serialize_path_lossy<S: serde::Serializer>(path: &Path, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&path.to_string_lossy())
+}
+
+impl std::fmt::Display for PolicyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let or_unknown = |v: &Option<String>| v.clone().unwrap_or_else(|| "unknown".into());
+        writeln!(
+            f,
+            "db_dir: {} ({})",
+            self.db_dir.display(),
+            or_unknown(&self.db_dir_fstype)
+        )?;
+        writeln!(
+            f,
+            "temp_dir: {} ({})",
+            self.temp_dir.display(),
+            or_unknown(&self.temp_dir_fstype)
+        )?;
+        writeln!(
+            f,
+            "journal_mode: {}",
+            or_unknown(&self.applied_journal_mode)
+        )?;
+        write!(f, "temp_store: {}", or_unknown(&self.applied_temp_store))?;
+        for note in &self.notes {
+            write!(f, "\nnote: {note}")?;
+        }
+        Ok(())
+    }
 }
 
 fn is_ramdisk_fstype(fstype: &str) -> bool {
@@ -87,20 +144,28 @@ fn canonical_or_original(p: &Path) -> PathBuf {
 
 #[cfg(target_os = "linux")]
 mod linux_mounts {
+    use std::os::unix::fs::MetadataExt;
+
     use anyhow::Context;
 
     use super::*;
 
     #[derive(Debug, Clone)]
-    struct MountInfo {
+    pub(super) struct MountInfo {
         mount_point: PathBuf,
+        /// `major:minor` from field 3; shared by every bind mount of a
+        /// filesystem.
+        dev: Option<(u32, u32)>,
         fstype: String,
     }
 
     fn parse_mountinfo() -> anyhow::Result<Vec<MountInfo>> {
         let s =
             std::fs::read_to_string("/proc/self/mountinfo").context("read /proc/self/mountinfo")?;
+        Ok(parse_mountinfo_str(&s))
+    }
 
+    pub(super) fn parse_mountinfo_str(s: &str) -> Vec<MountInfo> {
         let mut out = Vec::new();
         for line in s.lines() {
             let Some((pre, post)) = line.split_once(" - ") else {
@@ -112,6 +177,9 @@ mod linux_mounts {
                 continue;
             }
             let mount_point = PathBuf::from(pre_fields[4]);
+            let dev = pre_fields[2]
+                .split_once(':')
+                .and_then(|(maj, min)| Some((maj.parse().ok()?, min.parse().ok()?)));
 
             let post_fields: Vec<&str> = post.split_whitespace().collect();
             if post_fields.is_empty() {
@@ -121,30 +189,49 @@ mod linux_mounts {
 
             out.push(MountInfo {
                 mount_point,
+                dev,
                 fstype,
             });
         }
 
-        Ok(out)
+        out
     }
 
-    pub(super) fn fstype_for_path(path: &Path) -> anyhow::Result<Option<String>> {
-        let path = canonical_or_original(path);
-        let mounts = parse_mountinfo()?;
-
-        let mut best: Option<(usize, String)> = None;
+    /// Pick the mount holding `path`. Mounts whose device matches `dev`
+    /// (the path's `st_dev`) win over prefix-only matches, so bind
+    /// mounts and paths reached through symlinks resolve to the right
+    /// filesystem; the longest matching prefix breaks ties.
+    pub(super) fn select_fstype(
+        mounts: &[MountInfo],
+        path: &Path,
+        dev: Option<(u32, u32)>,
+    ) -> Option<String> {
+        let mut best: Option<((bool, bool, usize), &str)> = None;
 
         for m in mounts {
-            if path.starts_with(&m.mount_point) {
-                let score = m.mount_point.as_os_str().len();
-                let better = best.as_ref().map(|(s, _)| score > *s).unwrap_or(true);
-                if better {
-                    best = Some((score, m.fstype));
-                }
+            let dev_match = dev.is_some() && m.dev == dev;
+            let prefix_match = path.starts_with(&m.mount_point);
+            if !dev_match && !prefix_match {
+                continue;
+            }
+            let score = (dev_match, prefix_match, m.mount_point.as_os_str().len());
+            if best.as_ref().is_none_or(|(s, _)| score > *s) {
+                best = Some((score, &m.fstype));
             }
         }
 
-        Ok(best.map(|(_, f)| f))
+        best.map(|(_, f)| f.to_string())
+    }
+
+    fn dev_for_path(path: &Path) -> Option<(u32, u32)> {
+        let dev = std::fs::metadata(path).ok()?.dev();
+        Some((libc::major(dev), libc::minor(dev)))
+    }
+
+    pub(super) fn fstype_for_path(path: &Path) -> anyhow::Result<Option<String>> {
+        let path = canonical_or_original(path);
+        let mounts = parse_mountinfo()?;
+        Ok(select_fstype(&mounts, &path, dev_for_path(&path)))
     }
 }
 
@@ -161,6 +248,26 @@ fn fstype_for_path_best_effort(path: &Path) -> anyhow::Result<Option<String>> {
     }
 }
 
+/// The directory SQLite will create temp files in, as far as the policy
+/// can tell without a connection.
+fn sqlite_temp_dir(policy: &StoragePolicy) -> PathBuf {
+    if let Some(dir) = &policy.temp_dir_override {
+        return dir.clone();
+    }
+    match std::env::var_os("SQLITE_TMPDIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => std::env::temp_dir(),
+    }
+}
+
+/// `PRAGMA temp_store_directory` for `dir`, quoted as an SQL string.
+fn temp_store_directory_sql(dir: &Path) -> String {
+    format!(
+        "PRAGMA temp_store_directory='{}';",
+        dir.to_string_lossy().replace('\'', "''")
+    )
+}
+
 fn enforce_or_fallback(enforce: Enforce, msg: &str) -> anyhow::Result<()> {
     match enforce {
         Enforce::Warn => {
@@ -171,12 +278,28 @@ fn enforce_or_fallback(enforce: Enforce, msg: &str) -> anyhow::Result<()> {
     }
 }
 
-#[cfg(feature = "rusqlite")]
-pub fn apply_storage_policy(
-    conn: &rusqlite::Connection,
+/// One step of a decided policy, in the order `apply_storage_policy`
+/// performs them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    /// Run a PRAGMA; `context` labels a failure.
+    Pragma {
+        sql: &'static str,
+        context: &'static str,
+    },
+    /// Point SQLite's temp files at `temp_dir_override`.
+    TempStoreDirectory(PathBuf),
+    /// A requested mode was refused; subject to [`Enforce`].
+    Refuse(String),
+    Note(&'static str),
+}
+
+/// Detect filesystem types and decide every PRAGMA without touching a
+/// connection. `applied_*` in the report hold the decided values.
+fn decide_storage_policy(
     db_path: &Path,
     policy: &StoragePolicy,
-) -> anyhow::Result<PolicyReport> {
+) -> anyhow::Result<(PolicyReport, Vec<Step>)> {
     use anyhow::Context;
 
     let db_dir = db_path
@@ -185,7 +308,7 @@ pub fn apply_storage_policy(
         .unwrap_or_else(|| PathBuf::from("."));
     let db_dir = canonical_or_original(&db_dir);
 
-    let temp_dir = canonical_or_original(&std::env::temp_dir());
+    let temp_dir = canonical_or_original(&sqlite_temp_dir(policy));
 
     let db_dir_fstype = fstype_for_path_best_effort(&db_dir)
         .context("determine filesystem type for db directory")?;
@@ -201,17 +324,22 @@ pub fn apply_storage_policy(
         applied_temp_store: None,
         notes: vec![],
     };
+    let mut steps = Vec::new();
 
     // --- Journal mode ---
     match policy.journal_mode {
         JournalModePolicy::Memory => {
-            conn.execute_batch("PRAGMA journal_mode=MEMORY;")
-                .context("set PRAGMA journal_mode=MEMORY")?;
+            steps.push(Step::Pragma {
+                sql: "PRAGMA journal_mode=MEMORY;",
+                context: "set PRAGMA journal_mode=MEMORY",
+            });
             report.applied_journal_mode = Some("MEMORY".into());
         }
         JournalModePolicy::Off => {
-            conn.execute_batch("PRAGMA journal_mode=OFF;")
-                .context("set PRAGMA journal_mode=OFF")?;
+            steps.push(Step::Pragma {
+                sql: "PRAGMA journal_mode=OFF;",
+                context: "set PRAGMA journal_mode=OFF",
+            });
             report.applied_journal_mode = Some("OFF".into());
         }
         JournalModePolicy::DeleteOnlyIfRamdisk { fallback } => {
@@ -221,32 +349,41 @@ pub fn apply_storage_policy(
                 .unwrap_or(false);
 
             if ok {
-                conn.execute_batch("PRAGMA journal_mode=DELETE;")
-                    .context("set PRAGMA journal_mode=DELETE")?;
+                steps.push(Step::Pragma {
+                    sql: "PRAGMA journal_mode=DELETE;",
+                    context: "set PRAGMA journal_mode=DELETE",
+                });
                 report.applied_journal_mode = Some("DELETE".into());
             } else {
                 let fstype = db_dir_fstype.clone().unwrap_or_else(|| "unknown".into());
-                let msg = format!(
+                steps.push(Step::Refuse(format!(
                     "storage policy: refusing journal_mode=DELETE because db dir is not on ramdisk (db_dir={}, fstype={fstype}); risk of plaintext journal on disk",
                     report.db_dir.display(),
-                );
-                enforce_or_fallback(policy.enforce, &msg)?;
+                )));
 
                 match fallback {
                     JournalModeFallback::Memory => {
-                        conn.execute_batch("PRAGMA journal_mode=MEMORY;")
-                            .context("fallback PRAGMA journal_mode=MEMORY")?;
+                        steps.push(Step::Pragma {
+                            sql: "PRAGMA journal_mode=MEMORY;",
+                            context: "fallback PRAGMA journal_mode=MEMORY",
+                        });
                         report.applied_journal_mode = Some("MEMORY".into());
-                        report.note("journal_mode=DELETE denied; fell back to MEMORY");
+                        steps.push(Step::Note(
+                            "journal_mode=DELETE denied; fell back to MEMORY",
+                        ));
                     }
                     JournalModeFallback::Off => {
-                        conn.execute_batch("PRAGMA journal_mode=OFF;")
-                            .context("fallback PRAGMA journal_mode=OFF")?;
+                        steps.push(Step::Pragma {
+                            sql: "PRAGMA journal_mode=OFF;",
+                            context: "fallback PRAGMA journal_mode=OFF",
+                        });
                         report.applied_journal_mode = Some("OFF".into());
-                        report.note("journal_mode=DELETE denied; fell back to OFF");
+                        steps.push(Step::Note("journal_mode=DELETE denied; fell back to OFF"));
                     }
                     JournalModeFallback::None => {
-                        report.note("journal_mode=DELETE denied; no fallback applied");
+                        steps.push(Step::Note(
+                            "journal_mode=DELETE denied; no fallback applied",
+                        ));
                     }
                 }
             }
@@ -256,8 +393,10 @@ pub fn apply_storage_policy(
     // --- Temp store ---
     match policy.temp_store {
         TempStorePolicy::Memory => {
-            conn.execute_batch("PRAGMA temp_store=MEMORY;")
-                .context("set PRAGMA temp_store=MEMORY")?;
+            steps.push(Step::Pragma {
+                sql: "PRAGMA temp_store=MEMORY;",
+                context: "set PRAGMA temp_store=MEMORY",
+            });
             report.applied_temp_store = Some("MEMORY".into());
         }
         TempStorePolicy::FileOnlyIfRamdisk { fallback } => {
@@ -267,32 +406,68 @@ pub fn apply_storage_policy(
                 .unwrap_or(false);
 
             if ok {
-                conn.execute_batch("PRAGMA temp_store=FILE;")
-                    .context("set PRAGMA temp_store=FILE")?;
+                if policy.temp_dir_override.is_some() {
+                    steps.push(Step::TempStoreDirectory(report.temp_dir.clone()));
+                }
+                steps.push(Step::Pragma {
+                    sql: "PRAGMA temp_store=FILE;",
+                    context: "set PRAGMA temp_store=FILE",
+                });
                 report.applied_temp_store = Some("FILE".into());
             } else {
                 let fstype = temp_dir_fstype.clone().unwrap_or_else(|| "unknown".into());
-                let msg = format!(
+                steps.push(Step::Refuse(format!(
                     "storage policy: refusing temp_store=FILE because temp dir is not on ramdisk (temp_dir={}, fstype={fstype}); risk of plaintext temp files on disk",
                     report.temp_dir.display(),
-                );
-                enforce_or_fallback(policy.enforce, &msg)?;
+                )));
 
                 match fallback {
                     TempStoreFallback::Memory => {
-                        conn.execute_batch("PRAGMA temp_store=MEMORY;")
-                            .context("fallback PRAGMA temp_store=MEMORY")?;
+                        steps.push(Step::Pragma {
+                            sql: "PRAGMA temp_store=MEMORY;",
+                            context: "fallback PRAGMA temp_store=MEMORY",
+                        });
                         report.applied_temp_store = Some("MEMORY".into());
-                        report.note("temp_store=FILE denied; fell back to MEMORY");
+                        steps.push(Step::Note("temp_store=FILE denied; fell back to MEMORY"));
                     }
                     TempStoreFallback::None => {
-                        report.note("temp_store=FILE denied; no fallback applied");
+                        steps.push(Step::Note("temp_store=FILE denied; no fallback applied"));
                     }
                 }
             }
         }
     }
 
+    if policy.secure_delete {
+        steps.push(Step::Pragma {
+            sql: "PRAGMA secure_delete=ON;",
+            context: "set PRAGMA secure_delete=ON",
+        });
+    }
+
+    Ok((report, steps))
+}
+
+#[cfg(feature = "rusqlite")]
+pub fn apply_storage_policy(
+    conn: &rusqlite::Connection,
+    db_path: &Path,
+    policy: &StoragePolicy,
+) -> anyhow::Result<PolicyReport> {
+    use anyhow::Context;
+
+    let (mut report, steps) = decide_storage_policy(db_path, policy)?;
+    for step in steps {
+        match step {
+            Step::Pragma { sql, context } => conn.execute_batch(sql).context(context)?,
+            Step::TempStoreDirectory(dir) => conn
+                .execute_batch(&temp_store_directory_sql(&dir))
+                .context("set PRAGMA temp_store_directory")?,
+            Step::Refuse(msg) => enforce_or_fallback(policy.enforce, &msg)?,
+            Step::Note(note) => report.note(note),
+        }
+    }
+
     // Optional: Verify what SQLite reports back (best-effort).
     // journal_mode returns a string; temp_store returns an integer.
     if let Ok(jm) = conn.query_row("PRAGMA journal_mode;", [], |r| r.get::<_, String>(0)) {
@@ -307,6 +482,86 @@ pub fn apply_storage_policy(
     Ok(report)
 }
 
+/// Dry run of [`apply_storage_policy`]: makes the same decisions but
+/// runs no PRAGMA. `applied_*` hold what would be applied and `notes`
+/// list each "would apply" step. Fails exactly where the applying call
+/// would under [`Enforce::Error`], before anything has been changed.
+#[cfg(feature = "rusqlite")]
+pub fn apply_storage_policy_plan(
+    conn: &rusqlite::Connection,
+    db_path: &Path,
+    policy: &StoragePolicy,
+) -> anyhow::Result<PolicyReport> {
+    let (mut report, steps) = decide_storage_policy(db_path, policy)?;
+    for step in steps {
+        match step {
+            Step::Pragma { sql, .. } => report.note(format!("would apply {sql}")),
+            Step::TempStoreDirectory(dir) => {
+                report.note(format!("would apply {}", temp_store_directory_sql(&dir)))
+            }
+            Step::Refuse(msg) => match policy.enforce {
+                Enforce::Warn => report.note(format!("would warn: {msg}")),
+                Enforce::Error => anyhow::bail!("{msg}"),
+            },
+            Step::Note(note) => report.note(format!("would note: {note}")),
+        }
+    }
+
+    if let Ok(jm) = conn.query_row("PRAGMA journal_mode;", [], |r| r.get::<_, String>(0)) {
+        report.note(format!("sqlite currently reports journal_mode={jm}"));
+    }
+    if let Ok(ts) = conn.query_row("PRAGMA temp_store;", [], |r| r.get::<_, i64>(0)) {
+        report.note(format!(
+            "sqlite currently reports temp_store={ts} (0 default,1 file,2 memory)"
+        ));
+    }
+
+    Ok(report)
+}
+
+/// Register `evfs_storage_policy()`, which applies the default
+/// [`StoragePolicy`] to the calling connection's main database and returns
+/// the [`PolicyReport`] as text.
+#[cfg(feature = "rusqlite")]
+pub(crate) fn register_policy_sql_functions(db: *mut libsqlite3_sys::sqlite3) {
+    unsafe {
+        libsqlite3_sys::sqlite3_create_function_v2(
+            db,
+            c"evfs_storage_policy".as_ptr(),
+            0,
+            libsqlite3_sys::SQLITE_UTF8,
+            std::ptr::null_mut(),
+            Some(ffi_evfs_storage_policy),
+            None,
+            None,
+            None,
+        );
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+extern "C" fn ffi_evfs_storage_policy(
+    ctx: *mut libsqlite3_sys::sqlite3_context,
+    _argc: std::ffi::c_int,
+    _argv: *mut *mut libsqlite3_sys::sqlite3_value,
+) {
+    use crate::raft_hooks::{main_db_path, sqlite_error, sqlite_text};
+
+    let res = (|| -> anyhow::Result<String> {
+        let db = unsafe { libsqlite3_sys::sqlite3_context_db_handle(ctx) };
+        let path =
+            main_db_path(db).ok_or_else(|| anyhow::anyhow!("main database has no file path"))?;
+        // Borrowed handle: dropping `conn` does not close the caller's db.
+        let conn = unsafe { rusqlite::Connection::from_handle(db) }?;
+        let report = apply_storage_policy(&conn, &path, &StoragePolicy::default())?;
+        Ok(report.to_string())
+    })();
+    match res {
+        Ok(text) => sqlite_text(ctx, &text),
+        Err(e) => sqlite_error(ctx, "evfs_storage_policy", e),
+    }
+}
+
 #[cfg(not(feature = "rusqlite"))]
 pub fn apply_storage_policy(
     _conn: &(),
@@ -316,6 +571,15 @@ pub fn apply_storage_policy(
     anyhow::bail!("apply_storage_policy requires crate feature `rusqlite`")
 }
 
+#[cfg(not(feature = "rusqlite"))]
+pub fn apply_storage_policy_plan(
+    _conn: &(),
+    _db_path: &Path,
+    _policy: &StoragePolicy,
+) -> anyhow::Result<PolicyReport> {
+    anyhow::bail!("apply_storage_policy_plan requires crate feature `rusqlite`")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -349,4 +613,226 @@ mod tests {
         let res = enforce_or_fallback(Enforce::Error, "boom");
         assert!(res.is_err());
     }
+
+    fn ramdisk_policy() -> StoragePolicy {
+        StoragePolicy {
+            journal_mode: JournalModePolicy::DeleteOnlyIfRamdisk {
+                fallback: JournalModeFallback::Memory,
+            },
+            temp_store: TempStorePolicy::Memory,
+            enforce: Enforce::Warn,
+            secure_delete: false,
+            temp_dir_override: None,
+        }
+    }
+
+    #[test]
+    fn decide_secure_delete_adds_pragma() {
+        let policy = StoragePolicy {
+            secure_delete: true,
+            ..StoragePolicy::default()
+        };
+        let (_, steps) = decide_storage_policy(Path::new("x.db"), &policy).unwrap();
+        assert_eq!(
+            steps.last(),
+            Some(&Step::Pragma {
+                sql: "PRAGMA secure_delete=ON;",
+                context: "set PRAGMA secure_delete=ON",
+            })
+        );
+
+        let (_, steps) =
+            decide_storage_policy(Path::new("x.db"), &StoragePolicy::default()).unwrap();
+        assert!(
+            !steps
+                .iter()
+                .any(|s| matches!(s, Step::Pragma { sql, .. } if sql.contains("secure_delete")))
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn decide_allows_delete_journal_only_on_ramdisk() {
+        let shm = Path::new("/dev/shm/sqlevfs-policy.db");
+        if fstype_for_path_best_effort(Path::new("/dev/shm"))
+            .ok()
+            .flatten()
+            .as_deref()
+            .is_none_or(|f| !is_ramdisk_fstype(f))
+        {
+            eprintln!("skipping: /dev/shm is not a ramdisk here");
+            return;
+        }
+
+        let (report, steps) = decide_storage_policy(shm, &ramdisk_policy()).unwrap();
+        assert_eq!(report.applied_journal_mode.as_deref(), Some("DELETE"));
+        assert!(!steps.iter().any(|s| matches!(s, Step::Refuse(_))));
+
+        let disk = Path::new(env!("CARGO_MANIFEST_DIR")).join("policy.db");
+        let (report, steps) = decide_storage_policy(&disk, &ramdisk_policy()).unwrap();
+        if report
+            .db_dir_fstype
+            .as_deref()
+            .is_some_and(is_ramdisk_fstype)
+        {
+            return;
+        }
+        assert_eq!(report.applied_journal_mode.as_deref(), Some("MEMORY"));
+        assert!(matches!(steps[0], Step::Refuse(_)));
+        assert_eq!(
+            steps[1],
+            Step::Pragma {
+                sql: "PRAGMA journal_mode=MEMORY;",
+                context: "fallback PRAGMA journal_mode=MEMORY",
+            }
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn select_fstype_prefers_device_match_for_bind_mounts() {
+        use linux_mounts::{parse_mountinfo_str, select_fstype};
+
+        let mounts = parse_mountinfo_str(
+            "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+30 22 0:40 / /var/lib/docker/overlay2/abc/merged rw - overlay overlay rw
+31 22 0:25 / /dev/shm rw,nosuid shared:2 - tmpfs tmpfs rw
+45 22 0:25 /app-data /srv/data rw,relatime shared:2 - tmpfs tmpfs rw
+garbage line without separator
+",
+        );
+        assert_eq!(mounts.len(), 4);
+
+        // Plain prefix match.
+        assert_eq!(
+            select_fstype(&mounts, Path::new("/dev/shm/x.db"), Some((0, 25))),
+            Some("tmpfs".into())
+        );
+        // Bind mount of the tmpfs: path prefix and device both match.
+        assert_eq!(
+            select_fstype(&mounts, Path::new("/srv/data/x.db"), Some((0, 25))),
+            Some("tmpfs".into())
+        );
+        // Reached through a path outside any tmpfs mount point (e.g. a
+        // symlink); the device still identifies the tmpfs.
+        assert_eq!(
+            select_fstype(&mounts, Path::new("/home/user/link/x.db"), Some((0, 25))),
+            Some("tmpfs".into())
+        );
+        // Overlay: device of the merged dir wins over the root prefix.
+        assert_eq!(
+            select_fstype(
+                &mounts,
+                Path::new("/var/lib/docker/overlay2/abc/merged/db"),
+                Some((0, 40))
+            ),
+            Some("overlay".into())
+        );
+        // Unknown device falls back to the longest prefix.
+        assert_eq!(
+            select_fstype(&mounts, Path::new("/srv/data/x.db"), None),
+            Some("tmpfs".into())
+        );
+        assert_eq!(
+            select_fstype(&mounts, Path::new("/var2/x.db"), Some((9, 9))),
+            Some("ext4".into())
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn decide_temp_store_checks_the_override_dir() {
+        let shm = PathBuf::from("/dev/shm");
+        if fstype_for_path_best_effort(&shm)
+            .ok()
+            .flatten()
+            .as_deref()
+            .is_none_or(|f| !is_ramdisk_fstype(f))
+        {
+            eprintln!("skipping: /dev/shm is not a ramdisk here");
+            return;
+        }
+
+        let policy = StoragePolicy {
+            temp_store: TempStorePolicy::FileOnlyIfRamdisk {
+                fallback: TempStoreFallback::Memory,
+            },
+            enforce: Enforce::Error,
+            temp_dir_override: Some(shm.clone()),
+            ..StoragePolicy::default()
+        };
+        let disk = Path::new(env!("CARGO_MANIFEST_DIR")).join("policy.db");
+        let (report, steps) = decide_storage_policy(&disk, &policy).unwrap();
+        assert_eq!(report.temp_dir, canonical_or_original(&shm));
+        assert_eq!(report.applied_temp_store.as_deref(), Some("FILE"));
+        assert!(!steps.iter().any(|s| matches!(s, Step::Refuse(_))));
+        let at = steps
+            .iter()
+            .position(|s| matches!(s, Step::TempStoreDirectory(dir) if *dir == report.temp_dir))
+            .expect("temp_store_directory step");
+        assert_eq!(
+            steps[at + 1],
+            Step::Pragma {
+                sql: "PRAGMA temp_store=FILE;",
+                context: "set PRAGMA temp_store=FILE",
+            }
+        );
+    }
+
+    #[test]
+    fn temp_store_directory_sql_quotes_the_path() {
+        assert_eq!(
+            temp_store_directory_sql(Path::new("/mnt/it's")),
+            "PRAGMA temp_store_directory='/mnt/it''s';"
+        );
+    }
+
+    #[test]
+    fn report_json_lists_fallback() {
+        let disk = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let policy = StoragePolicy {
+            temp_store: TempStorePolicy::FileOnlyIfRamdisk {
+                fallback: TempStoreFallback::Memory,
+            },
+            temp_dir_override: Some(disk.clone()),
+            ..StoragePolicy::default()
+        };
+        let (mut report, steps) = decide_storage_policy(&disk.join("x.db"), &policy).unwrap();
+        if report
+            .temp_dir_fstype
+            .as_deref()
+            .is_some_and(is_ramdisk_fstype)
+        {
+            return;
+        }
+        for step in steps {
+            if let Step::Note(note) = step {
+                report.note(note);
+            }
+        }
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        for key in [
+            "db_dir",
+            "db_dir_fstype",
+            "temp_dir",
+            "temp_dir_fstype",
+            "applied_journal_mode",
+            "applied_temp_store",
+            "notes",
+        ] {
+            assert!(json.get(key).is_some(), "missing {key} in {json}");
+        }
+        assert_eq!(json["applied_journal_mode"], "MEMORY");
+        assert_eq!(json["applied_temp_store"], "MEMORY");
+        assert_eq!(
+            json["temp_dir"],
+            canonical_or_original(&disk).to_str().unwrap()
+        );
+        assert_eq!(
+            json["notes"],
+            serde_json::json!(["temp_store=FILE denied; fell back to MEMORY"])
+        );
+    }
 }
@@ -42,7 +42,7 @@ use tonic::transport::Endpoint;
 use crate::{
     EvfsBuilder,
     Mode,
-    keyring::Keyring,
+    keyring::{Keyring, KeyringStorage},
     vfs::{
         EvfsConfig,
         consensus::{
@@ -159,6 +159,15 @@ impl RaftManager {
                     page_size: cfg.page_size,
                     reserve_size: cfg.reserve_size,
                     raft: Some(raft.clone()),
+                    read_only: false,
+                    keyring_storage: KeyringStorage::Sidecar,
+                    keyring_path: None,
+                    base_vfs: None,
+                    allow_replace: false,
+                    encrypt_header: false,
+                    detect_page_size: false,
+                    detect_reserve_size: false,
+                    max_pages: crate::DEFAULT_MAX_PAGES,
                 },
             )
         {
@@ -294,6 +303,10 @@ fn mode_from_env() -> Result<Mode> {
             keyfile: None,
             passphrase: Some(pw),
         })
+    } else if std::env::var_os(crate::kms::local::DEFAULT_KEK_ENV).is_some() {
+        Ok(Mode::EnvKey {
+            var: crate::kms::local::DEFAULT_KEK_ENV.into(),
+        })
     } else if let Ok(key_id) = std::env::var("EVFS_KMS_KEY_ID") {
         Ok(Mode::TenantKey {
             key_id,
@@ -301,7 +314,7 @@ fn mode_from_env() -> Result<Mode> {
         })
     } else {
         anyhow::bail!(
-            "no key source configured (set EVFS_KEYFILE, EVFS_PASSPHRASE, or EVFS_KMS_KEY_ID)"
+            "no key source configured (set EVFS_KEYFILE, EVFS_PASSPHRASE, EVFS_KEK, or EVFS_KMS_KEY_ID)"
         );
     }
 }
@@ -361,7 +374,7 @@ fn derive_replay_target(
     })
 }
 
-fn main_db_path(db: *mut sqlite3) -> Option<PathBuf> {
+pub(crate) fn main_db_path(db: *mut sqlite3) -> Option<PathBuf> {
     unsafe {
         let p = sqlite3_db_filename(db, c"main".as_ptr());
         if p.is_null() {
@@ -479,7 +492,7 @@ fn start_with_config(cfg: RaftSqlConfig) -> Result<()> {
     guard.start_node(cfg)
 }
 
-fn sqlite_error(ctx: *mut sqlite3_context, prefix: &str, e: impl std::fmt::Display) {
+pub(crate) fn sqlite_error(ctx: *mut sqlite3_context, prefix: &str, e: impl std::fmt::Display) {
     let msg =
         CString::new(format!("{prefix}: {e}")).unwrap_or_else(|_| CString::new(prefix).unwrap());
     unsafe {
@@ -487,7 +500,7 @@ fn sqlite_error(ctx: *mut sqlite3_context, prefix: &str, e: impl std::fmt::Displ
     }
 }
 
-fn sqlite_text(ctx: *mut sqlite3_context, value: &str) {
+pub(crate) fn sqlite_text(ctx: *mut sqlite3_context, value: &str) {
     let msg = CString::new(value).unwrap_or_else(|_| CString::new("").unwrap());
     unsafe {
         sqlite3_result_text(ctx, msg.as_ptr(), -1, SQLITE_TRANSIENT());
@@ -4,27 +4,30 @@
 //! `PageCryptor` handle so that `vfs.rs` has no direct knowledge of
 //! cipher details.
 
-use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
-};
+use std::sync::Arc;
 
 use crate::{
     crypto::{
+        header::{HEADER_LEN, is_sealed_header, open_header, seal_header},
         keys::KeyScope,
-        page::{decrypt_page, encrypt_page, is_encrypted_page},
+        page::{PageError, decrypt_page, encrypt_page, is_encrypted_page, trailer_len},
     },
-    keyring::Keyring,
+    keyring::{EmbeddedKeyringWriter, Keyring},
 };
 
 /// Thin handle over a [`Keyring`] that provides page-level encrypt /
 /// decrypt in the form the VFS layer expects.
+///
+/// Every database page is keyed under [`KeyScope::Database`]: which
+/// b-tree owns a page changes as SQLite reuses and moves pages, and a
+/// tenant table keeps every tenant's rows in the same b-tree.
 #[derive(Clone)]
 pub struct PageCryptor {
     keyring: Arc<Keyring>,
     pub page_size: u32,
     pub reserve_size: usize,
-    page_scope_map: Arc<RwLock<HashMap<u32, KeyScope>>>,
+    /// Seal the database header on page 1 under [`KeyScope::Header`].
+    pub encrypt_header: bool,
 }
 
 impl PageCryptor {
@@ -33,10 +36,36 @@ impl PageCryptor {
             keyring,
             page_size,
             reserve_size,
-            page_scope_map: Arc::new(RwLock::new(HashMap::new())),
+            encrypt_header: false,
+        }
+    }
+
+    pub fn with_encrypted_header(mut self, encrypt_header: bool) -> Self {
+        self.encrypt_header = encrypt_header;
+        self
+    }
+
+    /// The same page layout over another keyring, e.g. one serving a
+    /// different database.
+    pub fn with_keyring(&self, keyring: Arc<Keyring>) -> Self {
+        Self::new(keyring, self.page_size, self.reserve_size)
+            .with_encrypted_header(self.encrypt_header)
+    }
+
+    /// The same keyring and options over another page layout, e.g. the
+    /// one an existing database's header records.
+    pub fn with_layout(&self, page_size: u32, reserve_size: usize) -> Self {
+        Self {
+            page_size,
+            reserve_size,
+            ..self.clone()
         }
     }
 
+    pub fn keyring(&self) -> &Arc<Keyring> {
+        &self.keyring
+    }
+
     /// Encrypt `buf` in-place for the given 1-based `page_no`.
     ///
     /// Page 1 is intentionally never encrypted because SQLite reads its
@@ -44,28 +73,130 @@ impl PageCryptor {
     pub fn encrypt(&self, buf: &mut [u8], page_no: u32) -> anyhow::Result<()> {
         debug_assert_ne!(page_no, 0, "page numbers are 1-based");
         debug_assert_ne!(page_no, 1, "caller must guard against encrypting page 1");
-        let dek = self
-            .keyring
-            .dek_for_page(page_no, self.page_scope_map.read().ok().as_deref())?;
+        let dek = self.keyring.dek_for(&KeyScope::Database)?;
         encrypt_page(buf, page_no, &dek, self.reserve_size)
     }
 
     /// Decrypt `buf` in-place for the given 1-based `page_no`.
     ///
-    /// Returns `Ok(false)` when the page is not encrypted (e.g. freshly
-    /// initialised file), `Ok(true)` on success.
+    /// Returns `Ok(false)` for a page that has never been written, which
+    /// reads back as all zeroes (e.g. while `VACUUM` grows the file), and
+    /// `Ok(true)` on success. Any other page without the `EVFSv1` marker
+    /// is an error rather than plaintext to pass through.
     pub fn decrypt(&self, buf: &mut [u8], page_no: u32) -> anyhow::Result<bool> {
         debug_assert_ne!(page_no, 0, "page numbers are 1-based");
         if !is_encrypted_page(buf, self.reserve_size) {
+            if buf.iter().any(|b| *b != 0) {
+                return Err(PageError::MissingMarker.into());
+            }
             return Ok(false);
         }
-        let dek = self
-            .keyring
-            .dek_for_page(page_no, self.page_scope_map.read().ok().as_deref())?;
+        let dek = self.keyring.dek_for(&KeyScope::Database)?;
         decrypt_page(buf, page_no, &dek, self.reserve_size)?;
+        self.clear_trailer(buf);
+        Ok(true)
+    }
+
+    /// Zero the tag, marker, nonce and version left in a decrypted page's
+    /// reserve. SQLite checksums journal and WAL pages as it sees them,
+    /// and a later decrypt only reproduces them if the trailer it sees
+    /// never depends on the nonce.
+    fn clear_trailer(&self, buf: &mut [u8]) {
+        let payload_len = buf.len() - self.reserve_size;
+        buf[payload_len..payload_len + trailer_len(self.reserve_size)].fill(0);
+    }
+
+    /// Prepare page 1 for disk: record the reserve in its header and, with
+    /// [`encrypt_header`](Self::encrypt_header), seal the header fields.
+    /// `buf` must start at offset 0 of the page.
+    pub fn seal_page1(&self, buf: &mut [u8]) -> anyhow::Result<()> {
+        if buf.len() >= HEADER_LEN {
+            buf[20] = self.reserve_size as u8;
+        }
+        if self.encrypt_header && buf.len() == self.page_size as usize {
+            let dek = self.keyring.dek_for(&KeyScope::Header)?;
+            seal_header(buf, &dek, self.reserve_size)?;
+        }
+        Ok(())
+    }
+
+    /// Undo [`seal_page1`](Self::seal_page1). A plaintext header, e.g. one
+    /// written before header encryption was turned on, is left as is.
+    pub fn open_page1(&self, buf: &mut [u8]) -> anyhow::Result<bool> {
+        if buf.len() != self.page_size as usize || !is_sealed_header(buf, self.reserve_size) {
+            return Ok(false);
+        }
+        let dek = self.keyring.dek_for(&KeyScope::Header)?;
+        open_header(buf, &dek, self.reserve_size)
+    }
+
+    /// [`encrypt`](Self::encrypt), or [`seal_page1`](Self::seal_page1)
+    /// for page 1.
+    pub fn encrypt_any(&self, buf: &mut [u8], page_no: u32) -> anyhow::Result<()> {
+        match page_no {
+            1 => self.seal_page1(buf),
+            _ => self.encrypt(buf, page_no),
+        }
+    }
+
+    /// [`decrypt`](Self::decrypt), or [`open_page1`](Self::open_page1)
+    /// for page 1.
+    pub fn decrypt_any(&self, buf: &mut [u8], page_no: u32) -> anyhow::Result<bool> {
+        match page_no {
+            1 => self.open_page1(buf),
+            _ => self.decrypt(buf, page_no),
+        }
+    }
+
+    /// Encrypt a page image held in the rollback journal. Journal copies
+    /// use their own [`KeyScope::Journal`] DEK, so they never share a key
+    /// with the pages of the main database.
+    pub fn encrypt_journal(&self, buf: &mut [u8]) -> anyhow::Result<()> {
+        let dek = self.keyring.dek_for(&KeyScope::Journal)?;
+        // Nonces are random per write; the page number is not bound.
+        encrypt_page(buf, 0, &dek, self.reserve_size)
+    }
+
+    /// Decrypt a journal page image in place. Returns `Ok(false)` for an
+    /// image written before journal encryption, which is left as is.
+    pub fn decrypt_journal(&self, buf: &mut [u8]) -> anyhow::Result<bool> {
+        if !is_encrypted_page(buf, self.reserve_size) {
+            return Ok(false);
+        }
+        let dek = self.keyring.dek_for(&KeyScope::Journal)?;
+        decrypt_page(buf, 0, &dek, self.reserve_size)?;
+        self.clear_trailer(buf);
         Ok(true)
     }
 
+    /// Encrypt a page image held in a WAL frame under its own
+    /// [`KeyScope::Wal`] DEK. Page 1 is encrypted whole, header included:
+    /// SQLite never reads the WAL except through the VFS.
+    pub fn encrypt_wal(&self, buf: &mut [u8]) -> anyhow::Result<()> {
+        let dek = self.keyring.dek_for(&KeyScope::Wal)?;
+        encrypt_page(buf, 0, &dek, self.reserve_size)
+    }
+
+    /// Decrypt a WAL page image for `page_no` in place. Frames written
+    /// before the WAL had its own scope, under the database's DEKs or
+    /// with a sealed header on page 1, are still read.
+    pub fn decrypt_wal(&self, buf: &mut [u8], page_no: u32) -> anyhow::Result<bool> {
+        if !is_encrypted_page(buf, self.reserve_size) {
+            return self.decrypt_any(buf, page_no);
+        }
+        let dek = self.keyring.dek_for(&KeyScope::Wal)?;
+        match decrypt_page(buf, 0, &dek, self.reserve_size) {
+            Ok(()) => {
+                self.clear_trailer(buf);
+                Ok(true)
+            }
+            Err(e) if page_no != 1 && e.downcast_ref() == Some(&PageError::AuthFailed) => {
+                self.decrypt(buf, page_no)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// Returns `true` when `buf` looks like an encrypted page.
     #[inline]
     pub fn is_encrypted(&self, buf: &[u8]) -> bool {
@@ -77,32 +208,104 @@ impl PageCryptor {
     pub fn set_db_path(&self, path: &std::path::Path) {
         self.keyring.set_sidecar_path(path);
     }
+
+    /// Like [`set_db_path`](Self::set_db_path), with the sidecar at an
+    /// explicit path instead of next to the database.
+    pub fn set_db_path_with_sidecar(&self, path: &std::path::Path, sidecar: &std::path::Path) {
+        self.keyring.set_sidecar_path_explicit(path, sidecar);
+    }
+
+    /// Like [`set_db_path`](Self::set_db_path), for databases whose DEKs
+    /// are derived from the KEK instead of stored.
+    pub fn set_db_path_derived(&self, path: &std::path::Path) {
+        self.keyring.set_derived_path(path);
+    }
+
+    /// Like [`set_db_path`](Self::set_db_path), for databases that keep
+    /// the keyring in an embedded block ahead of page 1.
+    pub fn set_db_path_embedded(
+        &self,
+        path: &std::path::Path,
+        block: &[u8],
+        writer: Option<Arc<dyn EmbeddedKeyringWriter>>,
+    ) -> anyhow::Result<()> {
+        self.keyring.load_embedded(path, block, writer)
+    }
+
+    pub fn release_embedded_writer(&self, writer: &Arc<dyn EmbeddedKeyringWriter>) {
+        self.keyring.release_embedded_writer(writer);
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use super::*;
+    use crate::{crypto::keys::KekId, kms::KmsProvider};
+
+    struct FakeKms;
+    impl KmsProvider for FakeKms {
+        fn get_kek(&self) -> anyhow::Result<(KekId, Vec<u8>)> {
+            Ok((KekId("k".into()), vec![0u8; 32]))
+        }
+
+        fn get_kek_by_id(&self, _: &KekId) -> anyhow::Result<Vec<u8>> {
+            Ok(vec![0u8; 32])
+        }
+    }
+
+    fn cryptor(reserve: usize) -> PageCryptor {
+        let keyring = Arc::new(Keyring::new(Arc::new(FakeKms)));
+        PageCryptor::new(keyring, 4096, reserve)
+    }
 
     #[test]
     fn is_not_encrypted_for_zeroed_page() {
-        use std::sync::Arc;
+        let buf = vec![0u8; 4096];
+        assert!(!cryptor(32).is_encrypted(&buf));
+    }
 
-        use crate::{crypto::keys::KekId, kms::KmsProvider};
+    #[test]
+    fn only_never_written_pages_decrypt_without_a_marker() {
+        let cryptor = cryptor(48);
+        let mut blank = vec![0u8; 4096];
+        assert!(!cryptor.decrypt(&mut blank, 3).unwrap());
+        assert!(blank.iter().all(|b| *b == 0));
 
-        struct FakeKms;
-        impl KmsProvider for FakeKms {
-            fn get_kek(&self) -> anyhow::Result<(KekId, Vec<u8>)> {
-                Ok((KekId("k".into()), vec![0u8; 32]))
-            }
+        let mut markerless = vec![0u8; 4096];
+        markerless[0] = 1;
+        let err = cryptor.decrypt(&mut markerless, 3).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&PageError::MissingMarker));
+    }
 
-            fn get_kek_by_id(&self, _: &KekId) -> anyhow::Result<Vec<u8>> {
-                Ok(vec![0u8; 32])
-            }
-        }
+    #[test]
+    fn wal_pages_decrypt_to_what_sqlite_wrote() {
+        let cryptor = cryptor(48);
+        let mut original = vec![0x5Au8; 4096];
+        original[4096 - 48..].fill(0);
 
-        let keyring = Arc::new(Keyring::new(Arc::new(FakeKms)));
-        let cryptor = PageCryptor::new(keyring, 4096, 32);
-        let buf = vec![0u8; 4096];
-        assert!(!cryptor.is_encrypted(&buf));
+        let mut page = original.clone();
+        cryptor.encrypt_wal(&mut page).unwrap();
+        assert!(cryptor.is_encrypted(&page));
+        // The database's DEK does not open a WAL image.
+        assert!(cryptor.decrypt(&mut page.clone(), 3).is_err());
+
+        assert!(cryptor.decrypt_wal(&mut page, 3).unwrap());
+        assert_eq!(page, original, "trailer must not leak into the checksum");
+    }
+
+    #[test]
+    fn wal_reads_frames_written_under_the_database_dek() {
+        let cryptor = cryptor(48);
+        let original = vec![0x33u8; 4096 - 48]
+            .into_iter()
+            .chain([0u8; 48])
+            .collect::<Vec<_>>();
+
+        let mut page = original.clone();
+        cryptor.encrypt(&mut page, 7).unwrap();
+        assert!(cryptor.decrypt_wal(&mut page, 7).unwrap());
+        assert_eq!(page, original);
     }
 }
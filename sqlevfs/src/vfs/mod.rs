@@ -12,22 +12,36 @@
 
 pub mod consensus;
 pub mod crypt;
+mod secure_delete;
 
 use std::{
+    collections::HashMap,
     ffi::{CStr, CString, c_char, c_int, c_void},
+    path::{Path, PathBuf},
     ptr,
-    sync::Arc,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
 };
 
 use libsqlite3_sys::*;
+use parking_lot::Mutex;
 
 use crate::{
-    crypto::page::MIN_RESERVE,
+    crypto::page::{LEGACY_RESERVE, MIN_RESERVE, check_format_version},
     debug,
-    keyring::Keyring,
+    keyring::{
+        EMBEDDED_KEYRING_SIZE,
+        EmbeddedKeyringWriter,
+        Keyring,
+        KeyringError,
+        KeyringStorage,
+    },
     vfs::{
         consensus::{handle::RaftHandle, wal::WalFileState},
         crypt::PageCryptor,
+        secure_delete::{SecureDeleteSet, overwrite_file, pragma_enables, shred_file, zero_range},
     },
 };
 
@@ -48,25 +62,135 @@ struct EvfsFile {
     encrypt_enabled: bool,
     /// Whether WAL-frame encryption is active for this fd.
     wal_encrypt_enabled: bool,
+    /// Whether this fd is a rollback journal whose page images are
+    /// encrypted under the journal DEK.
+    journal_encrypt_enabled: bool,
     /// Optional Raft handle; populated in `evfs_open` when replication
     /// is enabled.  Stored here so `xSync` and `xLock` can reach it
     /// without an extra indirection through the VFS struct.
     raft_handle: *mut Option<Arc<RaftHandle>>,
+    /// Reject every write and truncate on this fd with `SQLITE_READONLY`:
+    /// set for the database, its journal and WAL under a read-only VFS,
+    /// never for temp files and statement journals.
+    read_only: bool,
+    /// Physical offset of page 1; non-zero when an embedded keyring
+    /// block precedes the database.
+    data_offset: i64,
+    /// Writer registered with the keyring for the embedded block;
+    /// released on close.
+    keyring_writer: *mut Option<Arc<dyn EmbeddedKeyringWriter>>,
+    /// The VFS this file was opened through; leaked at registration.
+    global: *const EvfsGlobal,
+    /// Name passed to `xOpen`; SQLite keeps it valid until `xClose`.
+    name: *const c_char,
+    /// Whether this is a main database, whose `PRAGMA secure_delete` is
+    /// tracked in [`EvfsGlobal::secure_delete`].
+    is_main_db: bool,
 }
 
 // -- Global VFS context -----------------------------------------------
 
 struct EvfsGlobal {
+    /// Keyring and layout for new databases.
     cryptor: PageCryptor,
+    /// Open existing databases with the page size their header records.
+    detect_page_size: bool,
+    /// Open existing databases with the reserve their header records.
+    detect_reserve_size: bool,
     inner_vfs: *mut sqlite3_vfs,
     /// Optional Raft handle; `None` = standalone (encrypt-only) mode.
     raft: Option<Arc<RaftHandle>>,
+    /// Refuse all mutation at the file layer, regardless of open flags.
+    read_only: bool,
+    /// Highest database page number reads and writes may reach.
+    max_pages: u32,
+    /// Where main databases keep their wrapped DEKs.
+    keyring_storage: KeyringStorage,
+    /// Sidecar path overriding `<db>.evfs-keyring`.
+    keyring_path: Option<PathBuf>,
     /// Our io_methods table (static lifetime after registration).
     io_methods: sqlite3_io_methods,
+    /// Main-database handles and whether each overwrites released bytes.
+    secure_delete: SecureDeleteSet,
+    /// Files opened through this VFS and not yet closed; it cannot be
+    /// freed by [`unregister_evfs`] while any remain.
+    open_files: AtomicUsize,
+    /// Open main databases, by path, and the keyring serving each.
+    databases: Mutex<HashMap<PathBuf, OpenDatabase>>,
+}
+
+struct OpenDatabase {
+    keyring: Arc<Keyring>,
+    /// The database's page layout, for its journal and WAL files.
+    page_size: u32,
+    reserve_size: usize,
+    /// Main-database handles open on the path.
+    handles: usize,
+}
+
+impl EvfsGlobal {
+    /// Take a handle on the keyring for the main database at `path`. The
+    /// VFS's own keyring serves one database at a time, as it always has;
+    /// a database opened while it is busy with another (e.g. by `ATTACH`)
+    /// gets a keyring of its own, bound to its own sidecar or block.
+    fn acquire_db_keyring(&self, path: &Path, layout: &PageCryptor) -> Arc<Keyring> {
+        let mut databases = self.databases.lock();
+        if let Some(db) = databases.get_mut(path) {
+            db.handles += 1;
+            return db.keyring.clone();
+        }
+        let shared = self.cryptor.keyring();
+        let keyring = if databases
+            .values()
+            .any(|db| Arc::ptr_eq(&db.keyring, shared))
+        {
+            Arc::new(shared.sibling())
+        } else {
+            shared.clone()
+        };
+        databases.insert(
+            path.to_path_buf(),
+            OpenDatabase {
+                keyring: keyring.clone(),
+                page_size: layout.page_size,
+                reserve_size: layout.reserve_size,
+                handles: 1,
+            },
+        );
+        keyring
+    }
+
+    fn release_db_keyring(&self, path: &Path) {
+        let mut databases = self.databases.lock();
+        if let Some(db) = databases.get_mut(path) {
+            db.handles -= 1;
+            if db.handles == 0 {
+                databases.remove(path);
+            }
+        }
+    }
+
+    /// A cryptor with the keyring and page layout of the open database a
+    /// journal or WAL file belongs to, falling back to the VFS's own.
+    unsafe fn cryptor_for_sidecar_file(&self, z_name: *const c_char) -> PageCryptor {
+        let db_name = unsafe { sqlite3_filename_database(z_name) };
+        let db_path = (!db_name.is_null())
+            .then(|| unsafe { CStr::from_ptr(db_name) }.to_str().ok())
+            .flatten()
+            .map(Path::new);
+        let databases = self.databases.lock();
+        match db_path.and_then(|p| databases.get(p)) {
+            Some(db) => self
+                .cryptor
+                .with_layout(db.page_size, db.reserve_size)
+                .with_keyring(db.keyring.clone()),
+            None => self.cryptor.with_keyring(self.cryptor.keyring().clone()),
+        }
+    }
 }
 
 // Safety: inner_vfs comes from SQLite and is valid for the process
-// lifetime. EvfsGlobal is leaked and never mutated after registration.
+// lifetime. EvfsGlobal is leaked and only mutated behind its locks.
 unsafe impl Send for EvfsGlobal {}
 unsafe impl Sync for EvfsGlobal {}
 
@@ -88,6 +212,50 @@ fn page_start_offset(page_no: u32, page_size: i64) -> i64 {
     (page_no as i64 - 1) * page_size
 }
 
+/// Whether an xRead/xWrite of `amt` bytes at `offset` reaches past page
+/// `max_pages`. Checked before any page arithmetic, which narrows page
+/// numbers to `u32`.
+fn beyond_max_pages(offset: i64, amt: c_int, page_size: i64, max_pages: u32) -> bool {
+    let last = offset.saturating_add(amt.max(1) as i64 - 1);
+    last / page_size >= max_pages as i64
+}
+
+/// The part of an xRead/xWrite range that falls within one page.
+#[derive(Debug, PartialEq, Eq)]
+struct PageSegment {
+    page_no: u32,
+    /// Offset of the segment within its page.
+    in_page: usize,
+    len: usize,
+}
+
+impl PageSegment {
+    fn is_whole_page(&self, page_size: i64) -> bool {
+        self.in_page == 0 && self.len as i64 == page_size
+    }
+}
+
+/// Split `amt` bytes at `offset` into per-page segments, so sub-page and
+/// unaligned I/O (the 100-byte header read, say) can be served by
+/// decrypting or re-encrypting whole pages. Empty when `amt` is zero.
+fn page_segments(offset: i64, amt: usize, page_size: i64) -> Vec<PageSegment> {
+    let end = offset.saturating_add(amt as i64);
+    let mut segments = Vec::new();
+    let mut pos = offset;
+    while pos < end {
+        let page_no = page_no_for_offset(pos, page_size);
+        let page_end = page_start_offset(page_no, page_size) + page_size;
+        let seg_end = end.min(page_end);
+        segments.push(PageSegment {
+            page_no,
+            in_page: (pos - page_start_offset(page_no, page_size)) as usize,
+            len: (seg_end - pos) as usize,
+        });
+        pos = seg_end;
+    }
+    segments
+}
+
 #[inline]
 fn follower_may_write(raft: Option<&Arc<RaftHandle>>) -> bool {
     raft.is_none_or(|r| r.is_leader())
@@ -100,6 +268,200 @@ fn is_writer_shm_lock(offset: c_int, flags: c_int) -> bool {
     is_lock && is_exclusive && offset < FOLLOWER_WRITER_LOCK_MAX_OFFSET
 }
 
+/// Rollback journal records are `pgno (4) | page image | checksum (4)`
+/// after a sector-aligned header, so page images are exactly the
+/// page-sized I/Os at offsets 4 mod 8. Headers, page numbers, checksums
+/// and the super-journal name stay plaintext.
+#[inline]
+fn is_journal_page_io(page_size: u32, i_amt: c_int, i_ofst: i64) -> bool {
+    i_amt as u32 == page_size && i_ofst % 8 == 4
+}
+
+// -- Embedded keyring writer ----------------------------------------
+
+/// Suffix of the journal an embedded keyring block is written through,
+/// next to its database.
+const KEYRING_JOURNAL_SUFFIX: &str = "-evfskr";
+
+/// Follows the block in a keyring journal once the block is synced. A
+/// journal without it was torn before the database was touched.
+const KEYRING_JOURNAL_COMMIT: &[u8; 8] = b"EVFSKRJC";
+
+/// Keyring journal name for the database at `db_path`, terminated by
+/// two NULs like the names SQLite passes to xOpen.
+fn keyring_journal_name(db_path: &Path) -> Vec<u8> {
+    let mut name = db_path.as_os_str().as_encoded_bytes().to_vec();
+    name.extend_from_slice(KEYRING_JOURNAL_SUFFIX.as_bytes());
+    name.extend_from_slice(&[0, 0]);
+    name
+}
+
+/// A file opened directly on the inner VFS, closed on drop.
+struct InnerFile(*mut sqlite3_file);
+
+impl InnerFile {
+    unsafe fn open(vfs: *mut sqlite3_vfs, name: &[u8], flags: c_int) -> Result<Self, c_int> {
+        unsafe {
+            let sz = (*vfs).szOsFile as usize;
+            let buf = libc::malloc(sz) as *mut sqlite3_file;
+            if buf.is_null() {
+                return Err(SQLITE_NOMEM);
+            }
+            ptr::write_bytes(buf as *mut u8, 0, sz);
+            let file = Self(buf);
+            let rc = ((*vfs).xOpen.unwrap())(
+                vfs,
+                name.as_ptr() as *const c_char,
+                buf,
+                flags,
+                ptr::null_mut(),
+            );
+            if rc != SQLITE_OK {
+                return Err(rc);
+            }
+            Ok(file)
+        }
+    }
+}
+
+impl Drop for InnerFile {
+    fn drop(&mut self) {
+        unsafe {
+            if !(*self.0).pMethods.is_null() {
+                let _ = ((*(*self.0).pMethods).xClose.unwrap())(self.0);
+            }
+            libc::free(self.0 as *mut c_void);
+        }
+    }
+}
+
+/// Persists the embedded keyring block through a main DB's inner file.
+struct InnerKeyringWriter {
+    inner: *mut sqlite3_file,
+    inner_vfs: *mut sqlite3_vfs,
+    /// From [`keyring_journal_name`].
+    journal: Vec<u8>,
+}
+
+// Safety: the inner file outlives the writer; `evfs_close` releases the
+// writer from the keyring before closing the file.
+unsafe impl Send for InnerKeyringWriter {}
+unsafe impl Sync for InnerKeyringWriter {}
+
+impl EmbeddedKeyringWriter for InnerKeyringWriter {
+    fn write_block(&self, block: &[u8]) -> anyhow::Result<()> {
+        let check = |rc: c_int, what: &str| -> anyhow::Result<()> {
+            anyhow::ensure!(rc == SQLITE_OK, "embedded keyring {what} failed: {rc}");
+            Ok(())
+        };
+        unsafe {
+            // Journal the block before overwriting it in place: a crash
+            // mid-write leaves a committed journal that the next open
+            // rolls forward, never a torn keyring.
+            let flags = SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE | SQLITE_OPEN_MAIN_JOURNAL;
+            let journal = InnerFile::open(self.inner_vfs, &self.journal, flags)
+                .map_err(|rc| anyhow::anyhow!("embedded keyring journal open failed: {rc}"))?;
+            check(inner_truncate(journal.0, 0), "journal truncate")?;
+            check(inner_sync(journal.0), "journal sync")?;
+            check(inner_write(journal.0, block, 0), "journal write")?;
+            check(inner_sync(journal.0), "journal sync")?;
+            let commit_at = block.len() as i64;
+            check(
+                inner_write(journal.0, KEYRING_JOURNAL_COMMIT, commit_at),
+                "journal commit",
+            )?;
+            check(inner_sync(journal.0), "journal sync")?;
+
+            // New DEKs may protect WAL frames synced before the main DB,
+            // so the block must be durable on its own.
+            check(inner_write(self.inner, block, 0), "write")?;
+            check(inner_sync(self.inner), "sync")?;
+
+            drop(journal);
+            let rc = ((*self.inner_vfs).xDelete.unwrap())(
+                self.inner_vfs,
+                self.journal.as_ptr() as *const c_char,
+                0,
+            );
+            if rc != SQLITE_IOERR_DELETE_NOENT {
+                check(rc, "journal delete")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Roll a committed keyring journal left behind by a crash forward into
+/// the block at the start of `inner`, and drop a torn one. Returns the
+/// journaled block, which read-only handles use instead of the stale
+/// one in place. A journal another connection is still writing, under
+/// its RESERVED lock, is left alone.
+unsafe fn recover_keyring_journal(
+    inner_vfs: *mut sqlite3_vfs,
+    inner: *mut sqlite3_file,
+    journal: &[u8],
+    read_only: bool,
+) -> Result<Option<Vec<u8>>, c_int> {
+    unsafe {
+        let name = journal.as_ptr() as *const c_char;
+        let mut exists = 0;
+        let rc =
+            ((*inner_vfs).xAccess.unwrap())(inner_vfs, name, SQLITE_ACCESS_EXISTS, &mut exists);
+        if rc != SQLITE_OK {
+            return Err(rc);
+        }
+        if exists == 0 {
+            return Ok(None);
+        }
+        let mut reserved = 0;
+        let rc = ((*(*inner).pMethods).xCheckReservedLock.unwrap())(inner, &mut reserved);
+        if rc != SQLITE_OK {
+            return Err(rc);
+        }
+        if reserved != 0 {
+            return Ok(None);
+        }
+
+        let flags = if read_only {
+            SQLITE_OPEN_READONLY
+        } else {
+            SQLITE_OPEN_READWRITE
+        } | SQLITE_OPEN_MAIN_JOURNAL;
+        let file = InnerFile::open(inner_vfs, journal, flags)?;
+        let mut buf = vec![0u8; EMBEDDED_KEYRING_SIZE + KEYRING_JOURNAL_COMMIT.len()];
+        let rc = inner_read(file.0, &mut buf, 0);
+        if rc != SQLITE_OK && rc != SQLITE_IOERR_SHORT_READ {
+            return Err(rc);
+        }
+        drop(file);
+        let committed = rc == SQLITE_OK && buf.ends_with(KEYRING_JOURNAL_COMMIT);
+        buf.truncate(EMBEDDED_KEYRING_SIZE);
+        let recovered = committed.then_some(buf);
+        if read_only {
+            return Ok(recovered);
+        }
+
+        if let Some(block) = &recovered {
+            if debug() {
+                eprintln!("sqlevfs: rolling the embedded keyring journal forward");
+            }
+            let rc = inner_write(inner, block, 0);
+            if rc != SQLITE_OK {
+                return Err(rc);
+            }
+            let rc = inner_sync(inner);
+            if rc != SQLITE_OK {
+                return Err(rc);
+            }
+        }
+        let rc = ((*inner_vfs).xDelete.unwrap())(inner_vfs, name, 0);
+        if rc != SQLITE_OK && rc != SQLITE_IOERR_DELETE_NOENT {
+            return Err(rc);
+        }
+        Ok(recovered)
+    }
+}
+
 // -- Inner file helpers ----------------------------------------------
 
 unsafe fn inner_filesize(inner: *mut sqlite3_file) -> Option<i64> {
@@ -110,6 +472,36 @@ unsafe fn inner_filesize(inner: *mut sqlite3_file) -> Option<i64> {
     }
 }
 
+unsafe fn inner_read(inner: *mut sqlite3_file, buf: &mut [u8], offset: i64) -> c_int {
+    unsafe {
+        ((*(*inner).pMethods).xRead.unwrap())(
+            inner,
+            buf.as_mut_ptr() as *mut c_void,
+            buf.len() as c_int,
+            offset,
+        )
+    }
+}
+
+unsafe fn inner_write(inner: *mut sqlite3_file, data: &[u8], offset: i64) -> c_int {
+    unsafe {
+        ((*(*inner).pMethods).xWrite.unwrap())(
+            inner,
+            data.as_ptr() as *const c_void,
+            data.len() as c_int,
+            offset,
+        )
+    }
+}
+
+unsafe fn inner_truncate(inner: *mut sqlite3_file, size: i64) -> c_int {
+    unsafe { ((*(*inner).pMethods).xTruncate.unwrap())(inner, size) }
+}
+
+unsafe fn inner_sync(inner: *mut sqlite3_file) -> c_int {
+    unsafe { ((*(*inner).pMethods).xSync.unwrap())(inner, SQLITE_SYNC_NORMAL) }
+}
+
 fn wal_encrypt_frame_in_place(cryptor: &PageCryptor, frame: &mut [u8]) -> anyhow::Result<()> {
     let page_size = cryptor.page_size as usize;
     if frame.len() != WAL_FRAME_HEADER_SIZE + page_size {
@@ -117,14 +509,11 @@ fn wal_encrypt_frame_in_place(cryptor: &PageCryptor, frame: &mut [u8]) -> anyhow
     }
 
     let page_no = u32::from_be_bytes(frame[0..4].try_into().unwrap_or([0; 4]));
-    if page_no <= 1 {
+    let page = &mut frame[WAL_FRAME_HEADER_SIZE..WAL_FRAME_HEADER_SIZE + page_size];
+    if page_no == 0 {
         return Ok(());
     }
-
-    cryptor.encrypt(
-        &mut frame[WAL_FRAME_HEADER_SIZE..WAL_FRAME_HEADER_SIZE + page_size],
-        page_no,
-    )
+    cryptor.encrypt_wal(page)
 }
 
 fn wal_decrypt_frame_in_place(cryptor: &PageCryptor, frame: &mut [u8]) -> anyhow::Result<()> {
@@ -134,17 +523,28 @@ fn wal_decrypt_frame_in_place(cryptor: &PageCryptor, frame: &mut [u8]) -> anyhow
     }
 
     let page_no = u32::from_be_bytes(frame[0..4].try_into().unwrap_or([0; 4]));
-    if page_no <= 1 {
-        return Ok(());
+    let page = &mut frame[WAL_FRAME_HEADER_SIZE..WAL_FRAME_HEADER_SIZE + page_size];
+    if page_no != 0 {
+        let _decrypted = cryptor.decrypt_wal(page, page_no)?;
     }
-
-    let _decrypted = cryptor.decrypt(
-        &mut frame[WAL_FRAME_HEADER_SIZE..WAL_FRAME_HEADER_SIZE + page_size],
-        page_no,
-    )?;
     Ok(())
 }
 
+/// SQLite result for a page that could not be decrypted on read. An
+/// unreachable KMS is `SQLITE_AUTH` rather than an I/O error, so callers
+/// can tell it apart from a wrong key or corruption and retry later.
+fn decrypt_error_rc(e: &anyhow::Error) -> c_int {
+    match e.downcast_ref::<KeyringError>() {
+        Some(KeyringError::KmsUnavailable { .. }) => {
+            if debug() {
+                eprintln!("sqlevfs: {e}");
+            }
+            SQLITE_AUTH
+        }
+        None => SQLITE_IOERR_READ,
+    }
+}
+
 unsafe fn wal_read_encrypted(
     inner: *mut sqlite3_file,
     cryptor: &PageCryptor,
@@ -210,7 +610,7 @@ unsafe fn wal_read_encrypted(
                 if debug() {
                     eprintln!("sqlevfs: xRead WAL decrypt frame at {frame_off}: {e}");
                 }
-                return SQLITE_IOERR_READ;
+                return decrypt_error_rc(&e);
             }
 
             let seg_start = data_start.max(frame_off);
@@ -346,7 +746,154 @@ unsafe fn wal_write_encrypted(
 
 // -- Page-1 initialisation -------------------------------------------
 
-fn try_reserve_page1(cryptor: &PageCryptor, inner: *mut sqlite3_file) -> c_int {
+/// Whether the database URI carries `evfs_encrypt=off`. Only main-db,
+/// journal and WAL names can be queried for URI parameters.
+unsafe fn encryption_bypassed(z_name: *const c_char, flags: c_int) -> bool {
+    let named = SQLITE_OPEN_MAIN_DB | SQLITE_OPEN_MAIN_JOURNAL | SQLITE_OPEN_WAL;
+    !z_name.is_null()
+        && (flags & named) != 0
+        && unsafe { sqlite3_uri_boolean(z_name, c"evfs_encrypt".as_ptr(), 1) } == 0
+}
+
+/// Refuse `evfs_encrypt=off` for a database evfs has already
+/// encrypted; see [`crate::io::check_encryption_bypass`].
+fn check_bypass_allowed(inner: *mut sqlite3_file) -> c_int {
+    unsafe {
+        let Some(sz) = inner_filesize(inner) else {
+            return SQLITE_IOERR;
+        };
+        // Enough for the keyring block or two pages of the largest size.
+        let len = sz.clamp(0, (EMBEDDED_KEYRING_SIZE as i64).max(2 * 65536));
+        let mut head = vec![0u8; len as usize];
+        if len > 0 {
+            let rc = ((*(*inner).pMethods).xRead.unwrap())(
+                inner,
+                head.as_mut_ptr() as *mut c_void,
+                len as c_int,
+                0,
+            );
+            if rc != SQLITE_OK {
+                return rc;
+            }
+        }
+        match crate::io::check_encryption_bypass(&head) {
+            Ok(()) => SQLITE_OK,
+            Err(e) => {
+                if debug() {
+                    eprintln!("sqlevfs: {e}");
+                }
+                SQLITE_CANTOPEN
+            }
+        }
+    }
+}
+
+/// Read page 1 of an existing main database and reject it if it is
+/// foreign-encrypted; see [`crate::io::check_first_page`], or was created
+/// with a different reserve size ([`crate::io::check_reserve_size`]).
+/// Page 2, the first encrypted page, must not use a newer reserve format
+/// version.
+fn check_existing_page1(
+    cryptor: &PageCryptor,
+    inner: *mut sqlite3_file,
+    data_offset: i64,
+) -> c_int {
+    unsafe {
+        let Some(sz) = inner_filesize(inner) else {
+            return SQLITE_IOERR;
+        };
+        if sz <= data_offset {
+            return SQLITE_OK;
+        }
+        let mut page1 = vec![0u8; cryptor.page_size as usize];
+        let rc = ((*(*inner).pMethods).xRead.unwrap())(
+            inner,
+            page1.as_mut_ptr() as *mut c_void,
+            page1.len() as c_int,
+            data_offset,
+        );
+        if rc != SQLITE_OK && rc != SQLITE_IOERR_SHORT_READ {
+            return rc;
+        }
+        if let Err(e) = crate::io::check_first_page(&page1, cryptor.reserve_size) {
+            if debug() {
+                eprintln!("sqlevfs: {e}");
+            }
+            return SQLITE_NOTADB;
+        }
+        if let Err(e) = crate::io::check_reserve_size(&page1, cryptor.reserve_size) {
+            if debug() {
+                eprintln!("sqlevfs: {e}");
+            }
+            return SQLITE_CANTOPEN;
+        }
+
+        let page2_offset = data_offset + cryptor.page_size as i64;
+        if sz < page2_offset + cryptor.page_size as i64 {
+            return SQLITE_OK;
+        }
+        let mut page2 = page1;
+        let rc = ((*(*inner).pMethods).xRead.unwrap())(
+            inner,
+            page2.as_mut_ptr() as *mut c_void,
+            page2.len() as c_int,
+            page2_offset,
+        );
+        if rc != SQLITE_OK {
+            return rc;
+        }
+        match check_format_version(&page2, cryptor.reserve_size) {
+            Ok(()) => SQLITE_OK,
+            Err(e) => {
+                if debug() {
+                    eprintln!("sqlevfs: {e}");
+                }
+                SQLITE_CANTOPEN
+            }
+        }
+    }
+}
+
+/// The page layout of the database being opened: the page size and the
+/// reserve from an existing file's header, for whichever of the two was
+/// not configured. A header without a usable reserve gets
+/// [`LEGACY_RESERVE`], the reserve existing databases were created with.
+/// New, empty or unrecognised files get the configured layout, which
+/// [`check_existing_page1`] then checks as usual.
+fn detect_layout(global: &EvfsGlobal, inner: *mut sqlite3_file, data_offset: i64) -> PageCryptor {
+    let configured = &global.cryptor;
+    let mut header = [0u8; 100];
+    let rc = unsafe {
+        ((*(*inner).pMethods).xRead.unwrap())(
+            inner,
+            header.as_mut_ptr() as *mut c_void,
+            header.len() as c_int,
+            data_offset,
+        )
+    };
+    if rc != SQLITE_OK || !header.starts_with(b"SQLite format 3\0") {
+        return configured.clone();
+    }
+    let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+        _ if !global.detect_page_size => configured.page_size,
+        1 => 65536,
+        n => n as u32,
+    };
+    if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+        return configured.clone();
+    }
+    let reserve_size = match header[20] as usize {
+        file if global.detect_reserve_size && file >= MIN_RESERVE => file,
+        _ if global.detect_reserve_size => LEGACY_RESERVE,
+        _ => configured.reserve_size,
+    };
+    if debug() && (page_size, reserve_size) != (configured.page_size, configured.reserve_size) {
+        eprintln!("sqlevfs: xOpen: detected page_size {page_size}, reserve {reserve_size}");
+    }
+    configured.with_layout(page_size, reserve_size)
+}
+
+fn try_reserve_page1(cryptor: &PageCryptor, inner: *mut sqlite3_file, data_offset: i64) -> c_int {
     unsafe {
         let Some(sz) = inner_filesize(inner) else {
             return SQLITE_IOERR;
@@ -402,7 +949,7 @@ fn try_reserve_page1(cryptor: &PageCryptor, inner: *mut sqlite3_file) -> c_int {
             inner,
             page1.as_ptr() as *const c_void,
             page_size as c_int,
-            0,
+            data_offset,
         );
         if rcw != SQLITE_OK {
             return rcw;
@@ -434,8 +981,16 @@ unsafe extern "C" fn evfs_open(
         let inner_vfs = global.inner_vfs;
         let efile = file as *mut EvfsFile;
 
-        let encrypt_enabled = (flags & SQLITE_OPEN_MAIN_DB) != 0;
+        let bypass = encryption_bypassed(z_name, flags);
+        let is_main_db = (flags & SQLITE_OPEN_MAIN_DB) != 0;
+        let encrypt_enabled = is_main_db && !bypass;
         let is_wal = (flags & SQLITE_OPEN_WAL) != 0;
+        let is_journal = (flags & SQLITE_OPEN_MAIN_JOURNAL) != 0 && !bypass;
+        let data_offset = if encrypt_enabled && global.keyring_storage == KeyringStorage::Embedded {
+            EMBEDDED_KEYRING_SIZE as i64
+        } else {
+            0
+        };
 
         // Allocate inner file buffer.
         let inner_sz = (*inner_vfs).szOsFile as usize;
@@ -451,9 +1006,21 @@ unsafe extern "C" fn evfs_open(
             return rc;
         }
 
-        // Pre-create page 1 for brand-new MAIN database files only.
-        if encrypt_enabled && (flags & SQLITE_OPEN_CREATE) != 0 {
-            let rc = try_reserve_page1(&global.cryptor, inner_buf);
+        // An existing database keeps the layout it was created with.
+        let layout = if encrypt_enabled && (global.detect_page_size || global.detect_reserve_size) {
+            detect_layout(global, inner_buf, data_offset)
+        } else {
+            global.cryptor.clone()
+        };
+
+        // Refuse files evfs cannot have written, e.g. SQLCipher databases,
+        // and bypassing encryption for files it has encrypted.
+        if encrypt_enabled || (is_main_db && bypass) {
+            let rc = if bypass {
+                check_bypass_allowed(inner_buf)
+            } else {
+                check_existing_page1(&layout, inner_buf, data_offset)
+            };
             if rc != SQLITE_OK {
                 let _ = ((*(*inner_buf).pMethods).xClose.unwrap())(inner_buf);
                 libc::free(inner_buf as *mut c_void);
@@ -461,14 +1028,83 @@ unsafe extern "C" fn evfs_open(
             }
         }
 
-        // Per-file cryptor (clone is cheap: Arc inside).
-        let cryptor = Box::into_raw(Box::new(global.cryptor.clone()));
+        // Pre-create page 1 for brand-new MAIN database files only.
+        if encrypt_enabled && !global.read_only && (flags & SQLITE_OPEN_CREATE) != 0 {
+            let rc = try_reserve_page1(&layout, inner_buf, data_offset);
+            if rc != SQLITE_OK {
+                let _ = ((*(*inner_buf).pMethods).xClose.unwrap())(inner_buf);
+                libc::free(inner_buf as *mut c_void);
+                return rc;
+            }
+        }
 
-        // Bind sidecar path on the MAIN DB file only.
-        if encrypt_enabled && !z_name.is_null() {
-            let name = CStr::from_ptr(z_name);
-            if let Ok(s) = name.to_str() {
-                (*cryptor).set_db_path(std::path::Path::new(s));
+        // Per-file cryptor over the keyring of the database it belongs to.
+        let db_path = if encrypt_enabled && !z_name.is_null() {
+            CStr::from_ptr(z_name).to_str().ok().map(Path::new)
+        } else {
+            None
+        };
+        let cryptor = match db_path {
+            Some(path) => layout.with_keyring(global.acquire_db_keyring(path, &layout)),
+            None if (is_wal || is_journal) && !bypass && !z_name.is_null() => {
+                global.cryptor_for_sidecar_file(z_name)
+            }
+            None => layout.with_keyring(global.cryptor.keyring().clone()),
+        };
+        let cryptor = Box::into_raw(Box::new(cryptor));
+
+        // Bind the keyring to the MAIN DB file only.
+        let mut keyring_writer = None;
+        if let Some(path) = db_path {
+            if global.keyring_storage == KeyringStorage::Derived {
+                (*cryptor).set_db_path_derived(path);
+            } else if data_offset == 0 {
+                match &global.keyring_path {
+                    Some(sidecar) => (*cryptor).set_db_path_with_sidecar(path, sidecar),
+                    None => (*cryptor).set_db_path(path),
+                }
+            } else {
+                let journal = keyring_journal_name(path);
+                let mut block = vec![0u8; EMBEDDED_KEYRING_SIZE];
+                let rc =
+                    match recover_keyring_journal(inner_vfs, inner_buf, &journal, global.read_only)
+                    {
+                        Ok(Some(recovered)) => {
+                            block = recovered;
+                            SQLITE_OK
+                        }
+                        Ok(None) => inner_read(inner_buf, &mut block, 0),
+                        Err(rc) => rc,
+                    };
+                let writer: Option<Arc<dyn EmbeddedKeyringWriter>> = if global.read_only {
+                    None
+                } else {
+                    Some(Arc::new(InnerKeyringWriter {
+                        inner: inner_buf,
+                        inner_vfs,
+                        journal,
+                    }))
+                };
+                let bound = if rc == SQLITE_OK || rc == SQLITE_IOERR_SHORT_READ {
+                    (*cryptor)
+                        .set_db_path_embedded(path, &block, writer.clone())
+                        .map_err(|e| {
+                            if debug() {
+                                eprintln!("sqlevfs: xOpen embedded keyring: {e}");
+                            }
+                            SQLITE_NOTADB
+                        })
+                } else {
+                    Err(rc)
+                };
+                if let Err(rc) = bound {
+                    drop(Box::from_raw(cryptor));
+                    global.release_db_keyring(path);
+                    let _ = ((*(*inner_buf).pMethods).xClose.unwrap())(inner_buf);
+                    libc::free(inner_buf as *mut c_void);
+                    return rc;
+                }
+                keyring_writer = writer;
             }
         }
 
@@ -480,7 +1116,7 @@ unsafe extern "C" fn evfs_open(
                 } else {
                     CStr::from_ptr(z_name).to_string_lossy().into_owned()
                 };
-                Some(WalFileState::new(db_name, global.cryptor.page_size))
+                Some(WalFileState::new(db_name, (*cryptor).page_size))
             } else {
                 None
             }));
@@ -494,13 +1130,50 @@ unsafe extern "C" fn evfs_open(
         (*efile).cryptor = cryptor;
         (*efile).wal_state = wal_state;
         (*efile).encrypt_enabled = encrypt_enabled;
-        (*efile).wal_encrypt_enabled = is_wal;
+        (*efile).wal_encrypt_enabled = is_wal && !bypass;
+        (*efile).journal_encrypt_enabled = is_journal;
         (*efile).raft_handle = raft_handle;
+        // Queries on a read-only database still spill sorts and temp
+        // tables to files of their own.
+        let persistent = SQLITE_OPEN_MAIN_DB | SQLITE_OPEN_MAIN_JOURNAL | SQLITE_OPEN_WAL;
+        (*efile).read_only = global.read_only && (flags & persistent) != 0;
+        (*efile).data_offset = data_offset;
+        (*efile).keyring_writer = Box::into_raw(Box::new(keyring_writer));
+        (*efile).global = global;
+        (*efile).name = z_name;
+        (*efile).is_main_db = is_main_db;
+        if is_main_db && let Some(path) = file_path(efile) {
+            global.secure_delete.open(efile as usize, path);
+        }
+        global.open_files.fetch_add(1, Ordering::AcqRel);
 
         SQLITE_OK
     }
 }
 
+/// The path this file was opened under, if it has one.
+unsafe fn file_path<'a>(efile: *const EvfsFile) -> Option<&'a Path> {
+    let name = unsafe { (*efile).name };
+    if name.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(name) }.to_str().ok().map(Path::new)
+}
+
+/// Whether bytes this file releases are overwritten: a main database
+/// follows its own connection's `PRAGMA secure_delete`, a journal or WAL
+/// the connection writing through it.
+unsafe fn secure_delete(efile: *const EvfsFile) -> bool {
+    unsafe {
+        let global = &*(*efile).global;
+        if (*efile).is_main_db {
+            global.secure_delete.is_on(efile as usize)
+        } else {
+            file_path(efile).is_some_and(|p| global.secure_delete.covers(p))
+        }
+    }
+}
+
 // -- xClose ----------------------------------------------------------
 
 unsafe extern "C" fn evfs_close(file: *mut sqlite3_file) -> c_int {
@@ -511,6 +1184,47 @@ unsafe extern "C" fn evfs_close(file: *mut sqlite3_file) -> c_int {
         let efile = file as *mut EvfsFile;
         let inner = (*efile).inner_file;
 
+        // The keyring must stop writing through `inner` before it closes.
+        if !(*efile).keyring_writer.is_null() {
+            let writer = Box::from_raw((*efile).keyring_writer);
+            if let Some(ref w) = *writer
+                && !(*efile).cryptor.is_null()
+            {
+                (*(*efile).cryptor).release_embedded_writer(w);
+            }
+            (*efile).keyring_writer = ptr::null_mut();
+        }
+
+        // The database is gone: its wrapped DEKs have nothing left to
+        // protect, so scrub the sidecar too.
+        let global = &*(*efile).global;
+        if secure_delete(efile)
+            && (*efile).encrypt_enabled
+            && (*efile).data_offset == 0
+            && let Some(path) = file_path(efile)
+            && !path.exists()
+        {
+            let sidecar = global
+                .keyring_path
+                .clone()
+                .unwrap_or_else(|| path.with_extension("evfs-keyring"));
+            if let Err(e) = shred_file(&sidecar)
+                && debug()
+            {
+                eprintln!("sqlevfs: xClose: shred {}: {e}", sidecar.display());
+            }
+        }
+
+        if (*efile).is_main_db {
+            global.secure_delete.close(efile as usize);
+        }
+
+        if (*efile).encrypt_enabled
+            && let Some(path) = file_path(efile)
+        {
+            global.release_db_keyring(path);
+        }
+
         let rc = if !inner.is_null() && !(*inner).pMethods.is_null() {
             ((*(*inner).pMethods).xClose.unwrap())(inner)
         } else {
@@ -532,6 +1246,7 @@ unsafe extern "C" fn evfs_close(file: *mut sqlite3_file) -> c_int {
             drop(Box::from_raw((*efile).raft_handle));
             (*efile).raft_handle = ptr::null_mut();
         }
+        (*(*efile).global).open_files.fetch_sub(1, Ordering::AcqRel);
 
         rc
     }
@@ -553,6 +1268,20 @@ unsafe extern "C" fn evfs_read(
         let inner = (*efile).inner_file;
         let cryptor = &*(*efile).cryptor;
 
+        if (*efile).journal_encrypt_enabled {
+            let rc = ((*(*inner).pMethods).xRead.unwrap())(inner, buf, i_amt, i_ofst);
+            if rc == SQLITE_OK && is_journal_page_io(cryptor.page_size, i_amt, i_ofst) {
+                let slice = std::slice::from_raw_parts_mut(buf as *mut u8, i_amt as usize);
+                if let Err(e) = cryptor.decrypt_journal(slice) {
+                    if debug() {
+                        eprintln!("sqlevfs: xRead journal decrypt at {i_ofst}: {e}");
+                    }
+                    return decrypt_error_rc(&e);
+                }
+            }
+            return rc;
+        }
+
         if !(*efile).encrypt_enabled && !(*efile).wal_encrypt_enabled {
             return ((*(*inner).pMethods).xRead.unwrap())(inner, buf, i_amt, i_ofst);
         }
@@ -563,70 +1292,75 @@ unsafe extern "C" fn evfs_read(
 
         let page_size = cryptor.page_size as i64;
         let amt = i_amt as usize;
+        let base = (*efile).data_offset;
+
+        if beyond_max_pages(i_ofst, i_amt, page_size, (*(*efile).global).max_pages) {
+            if debug() {
+                eprintln!("sqlevfs: xRead at {i_ofst} is past max_pages");
+            }
+            return SQLITE_IOERR_READ;
+        }
 
         // Fast path: full aligned page read.
         if i_amt as u32 == cryptor.page_size && i_ofst % page_size == 0 {
-            let rc = ((*(*inner).pMethods).xRead.unwrap())(inner, buf, i_amt, i_ofst);
+            let rc = ((*(*inner).pMethods).xRead.unwrap())(inner, buf, i_amt, i_ofst + base);
             if rc != SQLITE_OK {
                 return rc;
             }
             let page_no = page_no_for_offset(i_ofst, page_size);
-            if page_no != 1 {
-                let slice = std::slice::from_raw_parts_mut(buf as *mut u8, amt);
-                if let Err(e) = cryptor.decrypt(slice, page_no) {
-                    if debug() {
-                        eprintln!("sqlevfs: xRead decrypt page {page_no}: {e}");
-                    }
-                    return SQLITE_IOERR_READ;
+            let slice = std::slice::from_raw_parts_mut(buf as *mut u8, amt);
+            if let Err(e) = cryptor.decrypt_any(slice, page_no) {
+                if debug() {
+                    eprintln!("sqlevfs: xRead decrypt page {page_no}: {e}");
                 }
+                return decrypt_error_rc(&e);
             }
             return SQLITE_OK;
         }
 
         // Slow path: sub-page or cross-page range read.
         let out = std::slice::from_raw_parts_mut(buf as *mut u8, amt);
-        let start = i_ofst;
-        let end = i_ofst.checked_add(i_amt as i64).unwrap_or(i64::MAX);
-        let first_page = page_no_for_offset(start, page_size);
-        let last_page = page_no_for_offset(end - 1, page_size);
-
         let mut out_cursor = 0usize;
-        for page_no in first_page..=last_page {
-            let p_start = page_start_offset(page_no, page_size);
-            let p_end = p_start + page_size;
-            let seg_start = start.max(p_start);
-            let seg_end = end.min(p_end);
-            let seg_len = (seg_end - seg_start) as usize;
+        let mut any_short = false;
+        for seg in page_segments(i_ofst, amt, page_size) {
+            let p_start = page_start_offset(seg.page_no, page_size);
 
             let mut page_buf = vec![0u8; cryptor.page_size as usize];
             let rc = ((*(*inner).pMethods).xRead.unwrap())(
                 inner,
                 page_buf.as_mut_ptr() as *mut c_void,
                 cryptor.page_size as c_int,
-                p_start,
+                p_start + base,
             );
             let short_read = rc == SQLITE_IOERR_SHORT_READ;
             if rc != SQLITE_OK && !short_read {
                 return rc;
             }
+            any_short |= short_read;
 
-            if page_no != 1
-                && !short_read
-                && let Err(e) = cryptor.decrypt(&mut page_buf, page_no)
-            {
+            if short_read {
+                // A torn encrypted page cannot be decrypted; never hand
+                // back its ciphertext. Page 1's header is readable as is.
+                if seg.page_no != 1 {
+                    page_buf.fill(0);
+                }
+            } else if let Err(e) = cryptor.decrypt_any(&mut page_buf, seg.page_no) {
                 if debug() {
-                    eprintln!("sqlevfs: xRead slow-path decrypt page {page_no}: {e}");
+                    eprintln!("sqlevfs: xRead slow-path decrypt page {}: {e}", seg.page_no);
                 }
-                return SQLITE_IOERR_READ;
+                return decrypt_error_rc(&e);
             }
 
-            let in_page_off = (seg_start - p_start) as usize;
-            out[out_cursor..out_cursor + seg_len]
-                .copy_from_slice(&page_buf[in_page_off..in_page_off + seg_len]);
-            out_cursor += seg_len;
+            out[out_cursor..out_cursor + seg.len]
+                .copy_from_slice(&page_buf[seg.in_page..seg.in_page + seg.len]);
+            out_cursor += seg.len;
         }
         debug_assert_eq!(out_cursor, out.len());
-        SQLITE_OK
+        if any_short {
+            SQLITE_IOERR_SHORT_READ
+        } else {
+            SQLITE_OK
+        }
     }
 }
 
@@ -646,6 +1380,28 @@ unsafe extern "C" fn evfs_write(
         let inner = (*efile).inner_file;
         let cryptor = &*(*efile).cryptor;
 
+        if (*efile).read_only {
+            return SQLITE_READONLY;
+        }
+
+        if (*efile).journal_encrypt_enabled && is_journal_page_io(cryptor.page_size, i_amt, i_ofst)
+        {
+            let mut page_buf =
+                std::slice::from_raw_parts(buf as *const u8, i_amt as usize).to_vec();
+            if let Err(e) = cryptor.encrypt_journal(&mut page_buf) {
+                if debug() {
+                    eprintln!("sqlevfs: xWrite journal encrypt at {i_ofst}: {e}");
+                }
+                return SQLITE_IOERR_WRITE;
+            }
+            return ((*(*inner).pMethods).xWrite.unwrap())(
+                inner,
+                page_buf.as_ptr() as *const c_void,
+                i_amt,
+                i_ofst,
+            );
+        }
+
         if !(*efile).encrypt_enabled && !(*efile).wal_encrypt_enabled {
             return ((*(*inner).pMethods).xWrite.unwrap())(inner, buf, i_amt, i_ofst);
         }
@@ -660,18 +1416,23 @@ unsafe extern "C" fn evfs_write(
 
         let page_size = cryptor.page_size as i64;
         let amt = i_amt as usize;
+        let base = (*efile).data_offset;
+
+        if beyond_max_pages(i_ofst, i_amt, page_size, (*(*efile).global).max_pages) {
+            if debug() {
+                eprintln!("sqlevfs: xWrite at {i_ofst} is past max_pages");
+            }
+            return SQLITE_FULL;
+        }
 
         // Fast path: full aligned page write.
         if i_amt as u32 == cryptor.page_size && i_ofst % page_size == 0 {
             let page_no = page_no_for_offset(i_ofst, page_size);
             let mut page_buf = std::slice::from_raw_parts(buf as *const u8, amt).to_vec();
 
-            if page_no == 1 {
-                // Page 1 stays plaintext; just keep the reserve field correct.
-                if cryptor.reserve_size <= u8::MAX as usize && page_buf.len() >= 21 {
-                    page_buf[20] = cryptor.reserve_size as u8;
-                }
-            } else if let Err(e) = cryptor.encrypt(&mut page_buf, page_no) {
+            // Page 1 keeps a readable header; encrypt_any only fixes its
+            // reserve field, and seals the rest if header encryption is on.
+            if let Err(e) = cryptor.encrypt_any(&mut page_buf, page_no) {
                 if debug() {
                     eprintln!("sqlevfs: xWrite encrypt page {page_no}: {e}");
                 }
@@ -687,63 +1448,49 @@ unsafe extern "C" fn evfs_write(
                 inner,
                 page_buf.as_ptr() as *const c_void,
                 i_amt,
-                i_ofst,
+                i_ofst + base,
             );
         }
 
         // Slow path: sub-page or cross-page range write.
         let inp = std::slice::from_raw_parts(buf as *const u8, amt);
-        let start = i_ofst;
-        let end = i_ofst.checked_add(i_amt as i64).unwrap_or(i64::MAX);
-        let first_page = page_no_for_offset(start, page_size);
-        let last_page = page_no_for_offset(end - 1, page_size);
-
         let mut in_cursor = 0usize;
-        for page_no in first_page..=last_page {
+        for seg in page_segments(i_ofst, amt, page_size) {
+            let page_no = seg.page_no;
             let p_start = page_start_offset(page_no, page_size);
-            let p_end = p_start + page_size;
-            let seg_start = start.max(p_start);
-            let seg_end = end.min(p_end);
-            let seg_len = (seg_end - seg_start) as usize;
-            let in_page_off = (seg_start - p_start) as usize;
-
             let mut page_buf = vec![0u8; cryptor.page_size as usize];
-            let covers_whole_page = seg_len == cryptor.page_size as usize && in_page_off == 0;
 
-            if !covers_whole_page {
+            // Read-modify-write: decrypt the rest of the page so only the
+            // new bytes change.
+            if !seg.is_whole_page(page_size) {
                 let rc = ((*(*inner).pMethods).xRead.unwrap())(
                     inner,
                     page_buf.as_mut_ptr() as *mut c_void,
                     cryptor.page_size as c_int,
-                    p_start,
+                    p_start + base,
                 );
                 let short_read = rc == SQLITE_IOERR_SHORT_READ;
                 if rc != SQLITE_OK && !short_read {
                     return rc;
                 }
-                if page_no != 1
-                    && !short_read
-                    && let Err(e) = cryptor.decrypt(&mut page_buf, page_no)
-                {
+                if short_read {
+                    if page_no != 1 {
+                        page_buf.fill(0);
+                    }
+                } else if let Err(e) = cryptor.decrypt_any(&mut page_buf, page_no) {
                     if debug() {
                         eprintln!("sqlevfs: xWrite slow-path decrypt page {page_no}: {e}");
                     }
                     return SQLITE_IOERR_WRITE;
                 }
-            } else {
-                page_buf.copy_from_slice(&inp[in_cursor..in_cursor + seg_len]);
             }
 
             // Merge new plaintext bytes.
-            page_buf[in_page_off..in_page_off + seg_len]
-                .copy_from_slice(&inp[in_cursor..in_cursor + seg_len]);
-            in_cursor += seg_len;
+            page_buf[seg.in_page..seg.in_page + seg.len]
+                .copy_from_slice(&inp[in_cursor..in_cursor + seg.len]);
+            in_cursor += seg.len;
 
-            if page_no == 1 {
-                if cryptor.reserve_size <= u8::MAX as usize && page_buf.len() >= 21 {
-                    page_buf[20] = cryptor.reserve_size as u8;
-                }
-            } else if let Err(e) = cryptor.encrypt(&mut page_buf, page_no) {
+            if let Err(e) = cryptor.encrypt_any(&mut page_buf, page_no) {
                 if debug() {
                     eprintln!("sqlevfs: xWrite slow-path encrypt page {page_no}: {e}");
                 }
@@ -759,7 +1506,7 @@ unsafe extern "C" fn evfs_write(
                 inner,
                 page_buf.as_ptr() as *const c_void,
                 cryptor.page_size as c_int,
-                p_start,
+                p_start + base,
             );
             if rc != SQLITE_OK {
                 return rc;
@@ -857,38 +1604,71 @@ unsafe extern "C" fn evfs_lock(file: *mut sqlite3_file, lock_type: c_int) -> c_i
             return SQLITE_BUSY;
         }
 
-        ((*(*inner).pMethods).xLock.unwrap())(inner, lock_type)
+        let rc = ((*(*inner).pMethods).xLock.unwrap())(inner, lock_type);
+        if rc == SQLITE_OK && (*efile).is_main_db {
+            (*(*efile).global)
+                .secure_delete
+                .lock(efile as usize, lock_type);
+        }
+        rc
+    }
+}
+
+unsafe extern "C" fn evfs_unlock(file: *mut sqlite3_file, lock_type: c_int) -> c_int {
+    unsafe {
+        let efile = file as *mut EvfsFile;
+        let inner = (*efile).inner_file;
+        let rc = ((*(*inner).pMethods).xUnlock.unwrap())(inner, lock_type);
+        if rc == SQLITE_OK && (*efile).is_main_db {
+            (*(*efile).global)
+                .secure_delete
+                .lock(efile as usize, lock_type);
+        }
+        rc
     }
 }
 
 // -- Forwarded I/O methods -------------------------------------------
 
-macro_rules! forward_io {
-    ($name:ident ( $($arg:ident : $ty:ty),* ) -> c_int) => {
-        #[allow(non_snake_case)]
-        unsafe extern "C" fn $name(
-            file: *mut sqlite3_file,
-            $( $arg: $ty, )*
-        ) -> c_int {
-            unsafe {
-                let efile = file as *mut EvfsFile;
-                let inner = (*efile).inner_file;
-                ((*(*inner).pMethods).$name.unwrap())(inner, $( $arg, )*)
+unsafe extern "C" fn evfs_truncate(file: *mut sqlite3_file, size: i64) -> c_int {
+    if debug() {
+        eprintln!("sqlevfs: xTruncate: size {size}");
+    }
+    unsafe {
+        let efile = file as *mut EvfsFile;
+        if (*efile).read_only {
+            return SQLITE_READONLY;
+        }
+        let inner = (*efile).inner_file;
+        let new_size = size + (*efile).data_offset;
+        if secure_delete(efile) {
+            let mut cur_size = 0i64;
+            let rc = ((*(*inner).pMethods).xFileSize.unwrap())(inner, &mut cur_size);
+            if rc != SQLITE_OK {
+                return rc;
+            }
+            let rc = zero_range(inner, new_size, cur_size);
+            if rc != SQLITE_OK {
+                return rc;
             }
         }
-    };
+        ((*(*inner).pMethods).xTruncate.unwrap())(inner, new_size)
+    }
 }
 
-forward_io!(xTruncate(size: i64) -> c_int);
-forward_io!(xUnlock(lock_type: c_int) -> c_int);
-
 unsafe extern "C" fn evfs_file_size(file: *mut sqlite3_file, p_size: *mut i64) -> c_int {
     if debug() {
         eprintln!("sqlevfs: xFileSize");
     }
     unsafe {
         let efile = file as *mut EvfsFile;
-        ((*(*(*efile).inner_file).pMethods).xFileSize.unwrap())((*efile).inner_file, p_size)
+        let rc =
+            ((*(*(*efile).inner_file).pMethods).xFileSize.unwrap())((*efile).inner_file, p_size);
+        // Hide the embedded keyring block from SQLite.
+        if rc == SQLITE_OK && (*efile).data_offset != 0 {
+            *p_size = (*p_size - (*efile).data_offset).max(0);
+        }
+        rc
     }
 }
 
@@ -919,6 +1699,30 @@ unsafe extern "C" fn evfs_file_control(
         let inner = (*efile).inner_file;
         let cryptor = &*(*efile).cryptor;
 
+        // Track `PRAGMA secure_delete`, then let SQLite handle it as usual.
+        if op == SQLITE_FCNTL_PRAGMA && !p_arg.is_null() {
+            let args = p_arg as *mut *mut c_char;
+            let (name, value) = (*args.add(1), *args.add(2));
+            if !name.is_null()
+                && !value.is_null()
+                && CStr::from_ptr(name)
+                    .to_bytes()
+                    .eq_ignore_ascii_case(b"secure_delete")
+                && let Some(on) = CStr::from_ptr(value).to_str().ok().and_then(pragma_enables)
+            {
+                (*(*efile).global).secure_delete.set(efile as usize, on);
+            }
+        }
+
+        // The inner file must never map pages: SQLite would read the
+        // ciphertext straight out of the mapping.
+        if op == SQLITE_FCNTL_MMAP_SIZE {
+            if !p_arg.is_null() {
+                *(p_arg as *mut sqlite3_int64) = 0;
+            }
+            return SQLITE_OK;
+        }
+
         if op == SQLITE_FCNTL_RESERVE_BYTES {
             if debug() {
                 eprintln!(
@@ -1044,6 +1848,31 @@ unsafe extern "C" fn evfs_shm_unmap(file: *mut sqlite3_file, delete_flag: c_int)
     }
 }
 
+/// Memory-mapped reads are never offered: a mapping would expose the raw
+/// ciphertext. Returning no page makes SQLite fall back to `xRead`.
+unsafe extern "C" fn evfs_fetch(
+    _file: *mut sqlite3_file,
+    i_ofst: sqlite3_int64,
+    i_amt: c_int,
+    pp: *mut *mut c_void,
+) -> c_int {
+    if debug() {
+        eprintln!("sqlevfs: xFetch: offset={i_ofst} amt={i_amt} (mmap disabled)");
+    }
+    if !pp.is_null() {
+        unsafe { *pp = ptr::null_mut() };
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn evfs_unfetch(
+    _file: *mut sqlite3_file,
+    _i_ofst: sqlite3_int64,
+    _p: *mut c_void,
+) -> c_int {
+    SQLITE_OK
+}
+
 // -- Forwarded VFS methods -------------------------------------------
 //
 // The macro delegates to the inner VFS using the correct sqlite3_vfs
@@ -1067,13 +1896,34 @@ macro_rules! forward_vfs {
     };
 }
 
-forward_vfs!(evfs_delete        => xDelete(z_name: *const c_char, sync_dir: c_int) -> c_int);
 forward_vfs!(evfs_access        => xAccess(z_name: *const c_char, flags: c_int, p_res_out: *mut c_int) -> c_int);
 forward_vfs!(evfs_full_pathname => xFullPathname(z_name: *const c_char, n_out: c_int, z_out: *mut c_char) -> c_int);
 forward_vfs!(evfs_randomness    => xRandomness(n_byte: c_int, z_out: *mut c_char) -> c_int);
 forward_vfs!(evfs_sleep         => xSleep(microseconds: c_int) -> c_int);
 forward_vfs!(evfs_current_time  => xCurrentTime(p_time: *mut f64) -> c_int);
 
+unsafe extern "C" fn evfs_delete(
+    vfs: *mut sqlite3_vfs,
+    z_name: *const c_char,
+    sync_dir: c_int,
+) -> c_int {
+    if debug() {
+        eprintln!("sqlevfs: evfs_delete");
+    }
+    unsafe {
+        let global = &*((*vfs).pAppData as *const EvfsGlobal);
+        if !z_name.is_null()
+            && let Ok(name) = CStr::from_ptr(z_name).to_str()
+            && global.secure_delete.covers(Path::new(name))
+            && let Err(e) = overwrite_file(Path::new(name))
+            && debug()
+        {
+            eprintln!("sqlevfs: evfs_delete: overwrite {name}: {e}");
+        }
+        ((*global.inner_vfs).xDelete.unwrap())(global.inner_vfs, z_name, sync_dir)
+    }
+}
+
 unsafe extern "C" fn evfs_get_last_error(
     vfs: *mut sqlite3_vfs,
     n_buf: c_int,
@@ -1174,24 +2024,65 @@ pub struct EvfsConfig {
     pub reserve_size: usize,
     /// Pass `Some(handle)` to enable distributed replication.
     pub raft: Option<Arc<RaftHandle>>,
+    /// Fail every `xWrite`/`xTruncate` on the database, its journal and
+    /// WAL with `SQLITE_READONLY`.
+    pub read_only: bool,
+    /// Sidecar file or embedded block for the wrapped DEKs.
+    pub keyring_storage: KeyringStorage,
+    /// Sidecar location; `None` derives `<db>.evfs-keyring`.
+    pub keyring_path: Option<PathBuf>,
+    /// Registered VFS to delegate raw I/O to; `None` uses the default.
+    pub base_vfs: Option<String>,
+    /// Unregister a VFS already registered under the same name instead of
+    /// failing. Connections already open on it keep using it.
+    pub allow_replace: bool,
+    /// Seal page 1's header fields; see [`crate::crypto::header`].
+    pub encrypt_header: bool,
+    /// Open existing databases with the page size their header records;
+    /// `page_size` then applies to new databases only.
+    pub detect_page_size: bool,
+    /// Open existing databases with the reserve their header records;
+    /// `reserve_size` then applies to new databases only.
+    pub detect_reserve_size: bool,
+    /// Highest database page number `xRead`/`xWrite` will touch.
+    pub max_pages: u32,
 }
 
 pub fn register_evfs(name: &str, cfg: EvfsConfig) -> anyhow::Result<()> {
-    let inner_vfs = unsafe { sqlite3_vfs_find(ptr::null()) };
-    anyhow::ensure!(!inner_vfs.is_null(), "no default sqlite3 VFS found");
+    let c_name = CString::new(name)?;
+    let existing = unsafe { sqlite3_vfs_find(c_name.as_ptr()) };
+    anyhow::ensure!(
+        existing.is_null() || cfg.allow_replace,
+        "VFS '{name}' already registered"
+    );
+
+    let inner_vfs = match &cfg.base_vfs {
+        Some(base) => {
+            let c_base = CString::new(base.as_str())?;
+            let vfs = unsafe { sqlite3_vfs_find(c_base.as_ptr()) };
+            anyhow::ensure!(!vfs.is_null(), "base VFS {base:?} is not registered");
+            vfs
+        }
+        None => {
+            let vfs = unsafe { sqlite3_vfs_find(ptr::null()) };
+            anyhow::ensure!(!vfs.is_null(), "no default sqlite3 VFS found");
+            vfs
+        }
+    };
 
-    let cryptor = PageCryptor::new(cfg.keyring, cfg.page_size, cfg.reserve_size);
+    let cryptor = PageCryptor::new(cfg.keyring, cfg.page_size, cfg.reserve_size)
+        .with_encrypted_header(cfg.encrypt_header);
 
     let io_methods = sqlite3_io_methods {
-        iVersion: 2,
+        iVersion: 3,
         xClose: Some(evfs_close),
         xRead: Some(evfs_read),
         xWrite: Some(evfs_write),
-        xTruncate: Some(xTruncate),
+        xTruncate: Some(evfs_truncate),
         xSync: Some(evfs_sync),
         xFileSize: Some(evfs_file_size),
         xLock: Some(evfs_lock),
-        xUnlock: Some(xUnlock),
+        xUnlock: Some(evfs_unlock),
         xCheckReservedLock: Some(evfs_check_reserved_lock),
         xFileControl: Some(evfs_file_control),
         xSectorSize: Some(evfs_sector_size),
@@ -1200,18 +2091,26 @@ pub fn register_evfs(name: &str, cfg: EvfsConfig) -> anyhow::Result<()> {
         xShmLock: Some(evfs_shm_lock),
         xShmBarrier: Some(evfs_shm_barrier),
         xShmUnmap: Some(evfs_shm_unmap),
-        xFetch: None,
-        xUnfetch: None,
+        xFetch: Some(evfs_fetch),
+        xUnfetch: Some(evfs_unfetch),
     };
 
     let global = Box::leak(Box::new(EvfsGlobal {
         cryptor,
+        detect_page_size: cfg.detect_page_size,
+        detect_reserve_size: cfg.detect_reserve_size,
         inner_vfs,
         raft: cfg.raft,
+        read_only: cfg.read_only,
+        max_pages: cfg.max_pages,
+        keyring_storage: cfg.keyring_storage,
+        keyring_path: cfg.keyring_path,
         io_methods,
+        secure_delete: SecureDeleteSet::default(),
+        open_files: AtomicUsize::new(0),
+        databases: Mutex::new(HashMap::new()),
     }));
 
-    let c_name = CString::new(name)?;
     let sz_os_file = std::mem::size_of::<EvfsFile>() as c_int;
 
     let vfs = Box::leak(Box::new(sqlite3_vfs {
@@ -1239,20 +2138,59 @@ pub fn register_evfs(name: &str, cfg: EvfsConfig) -> anyhow::Result<()> {
         xNextSystemCall: None,
     }));
 
+    if !existing.is_null() {
+        // The old VFS is leaked like every evfs registration, so open
+        // connections on it stay valid.
+        unsafe { sqlite3_vfs_unregister(existing) };
+    }
     let rc = unsafe { sqlite3_vfs_register(vfs as *mut sqlite3_vfs, 0) };
     anyhow::ensure!(rc == SQLITE_OK, "sqlite3_vfs_register failed: {rc}");
 
     if debug() {
         eprintln!(
-            "sqlevfs: registered (page_size={}, reserve={}, raft={})",
+            "sqlevfs: registered (page_size={}, reserve={}, base={}, raft={}, read_only={}, keyring={:?})",
             cfg.page_size,
             cfg.reserve_size,
+            cfg.base_vfs.as_deref().unwrap_or("default"),
             global.raft.is_some(),
+            global.read_only,
+            global.keyring_storage,
         );
     }
     Ok(())
 }
 
+/// Unregister the evfs VFS `name` and free it, dropping its reference
+/// to the keyring. Fails if no VFS of that name exists, if it is not an
+/// evfs VFS, or while files opened through it are still open; close
+/// every connection using it first, and do not open new ones
+/// concurrently.
+pub fn unregister_evfs(name: &str) -> anyhow::Result<()> {
+    let c_name = CString::new(name)?;
+    let vfs = unsafe { sqlite3_vfs_find(c_name.as_ptr()) };
+    anyhow::ensure!(!vfs.is_null(), "VFS '{name}' is not registered");
+    let is_evfs = unsafe { (*vfs).xOpen }
+        .is_some_and(|f| ptr::fn_addr_eq(f, evfs_open as unsafe extern "C" fn(_, _, _, _, _) -> _));
+    anyhow::ensure!(is_evfs, "VFS '{name}' is not an evfs VFS");
+
+    let global = unsafe { (*vfs).pAppData as *mut EvfsGlobal };
+    let open = unsafe { (*global).open_files.load(Ordering::Acquire) };
+    anyhow::ensure!(open == 0, "VFS '{name}' still has {open} open file(s)");
+
+    let rc = unsafe { sqlite3_vfs_unregister(vfs) };
+    anyhow::ensure!(rc == SQLITE_OK, "sqlite3_vfs_unregister failed: {rc}");
+    unsafe {
+        drop(CString::from_raw((*vfs).zName as *mut c_char));
+        drop(Box::from_raw(vfs));
+        drop(Box::from_raw(global));
+    }
+
+    if debug() {
+        eprintln!("sqlevfs: unregistered {name}");
+    }
+    Ok(())
+}
+
 // -- Tests ------------------------------------------------------------
 
 #[cfg(test)]
@@ -1281,6 +2219,81 @@ mod tests {
         }
     }
 
+    #[test]
+    fn header_read_is_one_sub_page_segment() {
+        assert_eq!(
+            page_segments(0, 100, 4096),
+            vec![PageSegment {
+                page_no: 1,
+                in_page: 0,
+                len: 100
+            }]
+        );
+        assert!(!page_segments(0, 100, 4096)[0].is_whole_page(4096));
+    }
+
+    #[test]
+    fn unaligned_write_splits_at_the_page_boundary() {
+        let segments = page_segments(2 * 4096 - 5, 10, 4096);
+        assert_eq!(
+            segments,
+            vec![
+                PageSegment {
+                    page_no: 2,
+                    in_page: 4091,
+                    len: 5
+                },
+                PageSegment {
+                    page_no: 3,
+                    in_page: 0,
+                    len: 5
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn aligned_multi_page_range_is_whole_pages() {
+        let segments = page_segments(4096, 2 * 4096, 4096);
+        assert_eq!(segments.len(), 2);
+        assert!(segments.iter().all(|s| s.is_whole_page(4096)));
+    }
+
+    #[test]
+    fn zero_length_io_touches_no_page() {
+        assert!(page_segments(0, 0, 4096).is_empty());
+        assert!(page_segments(4100, 0, 4096).is_empty());
+    }
+
+    #[test]
+    fn max_pages_bounds_the_last_byte_touched() {
+        assert!(!beyond_max_pages(0, 4096, 4096, 1));
+        assert!(!beyond_max_pages(4096 - 100, 100, 4096, 1));
+        assert!(beyond_max_pages(4096 - 100, 101, 4096, 1));
+        assert!(beyond_max_pages(4096, 4096, 4096, 1));
+        assert!(!beyond_max_pages(4096, 0, 4096, 2));
+    }
+
+    #[test]
+    fn max_pages_rejects_absurd_offsets() {
+        let limit = crate::DEFAULT_MAX_PAGES;
+        // Page numbers past u32::MAX would wrap in page_no_for_offset.
+        assert!(beyond_max_pages(1 << 50, 4096, 4096, limit));
+        assert!(beyond_max_pages(i64::MAX - 10, 4096, 4096, limit));
+        assert!(!beyond_max_pages(
+            page_start_offset(limit, 4096),
+            4096,
+            4096,
+            limit
+        ));
+        assert!(beyond_max_pages(
+            page_start_offset(limit, 4096) + 4096,
+            4096,
+            4096,
+            limit
+        ));
+    }
+
     #[test]
     fn cstring_rejects_interior_null() {
         assert!(CString::new("evfs\0bad").is_err());
@@ -1290,15 +2303,15 @@ mod tests {
     #[test]
     fn io_methods_critical_slots_set() {
         let methods = sqlite3_io_methods {
-            iVersion: 2,
+            iVersion: 3,
             xClose: Some(evfs_close),
             xRead: Some(evfs_read),
             xWrite: Some(evfs_write),
-            xTruncate: Some(xTruncate),
+            xTruncate: Some(evfs_truncate),
             xSync: Some(evfs_sync),
             xFileSize: Some(evfs_file_size),
             xLock: Some(evfs_lock),
-            xUnlock: Some(xUnlock),
+            xUnlock: Some(evfs_unlock),
             xCheckReservedLock: Some(evfs_check_reserved_lock),
             xFileControl: Some(evfs_file_control),
             xSectorSize: Some(evfs_sector_size),
@@ -1307,14 +2320,16 @@ mod tests {
             xShmLock: Some(evfs_shm_lock),
             xShmBarrier: Some(evfs_shm_barrier),
             xShmUnmap: Some(evfs_shm_unmap),
-            xFetch: None,
-            xUnfetch: None,
+            xFetch: Some(evfs_fetch),
+            xUnfetch: Some(evfs_unfetch),
         };
         assert!(methods.xRead.is_some());
         assert!(methods.xWrite.is_some());
         assert!(methods.xSync.is_some());
         assert!(methods.xClose.is_some());
         assert!(methods.xLock.is_some());
+        assert!(methods.xFetch.is_some());
+        assert!(methods.xUnfetch.is_some());
     }
 
     #[test]
@@ -0,0 +1,222 @@
+// src/vfs/secure_delete.rs
+
+//! Best-effort overwriting of released bytes.
+//!
+//! `PRAGMA secure_delete=ON` on a database opened through evfs also
+//! makes the VFS zero what it gives back to the filesystem: the tail
+//! cut off by `xTruncate`, journals and WAL files before `xDelete`, and
+//! the keyring sidecar once the database itself has been deleted.
+//!
+//! On SSDs and copy-on-write filesystems the old blocks may survive
+//! anyway; this mainly helps spinning disks and compliance checklists.
+
+use std::{
+    collections::HashMap,
+    ffi::{c_int, c_void},
+    fs::OpenOptions,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use libsqlite3_sys::*;
+use parking_lot::Mutex;
+
+const CHUNK: usize = 64 * 1024;
+
+/// `secure_delete` as set on each open main-database handle, i.e. on
+/// each connection, with the lock the handle holds.
+#[derive(Default)]
+pub(crate) struct SecureDeleteSet(Mutex<HashMap<usize, Handle>>);
+
+struct Handle {
+    db_path: PathBuf,
+    on: bool,
+    lock: c_int,
+}
+
+impl SecureDeleteSet {
+    /// Track a main-database handle, with `secure_delete` off.
+    pub(crate) fn open(&self, handle: usize, db_path: &Path) {
+        self.0.lock().insert(
+            handle,
+            Handle {
+                db_path: db_path.to_path_buf(),
+                on: false,
+                lock: SQLITE_LOCK_NONE,
+            },
+        );
+    }
+
+    pub(crate) fn close(&self, handle: usize) {
+        self.0.lock().remove(&handle);
+    }
+
+    pub(crate) fn set(&self, handle: usize, on: bool) {
+        if let Some(h) = self.0.lock().get_mut(&handle) {
+            h.on = on;
+        }
+    }
+
+    /// Record the lock a main-database handle now holds.
+    pub(crate) fn lock(&self, handle: usize, lock: c_int) {
+        if let Some(h) = self.0.lock().get_mut(&handle) {
+            h.lock = lock;
+        }
+    }
+
+    /// Whether the main-database handle `handle` has `secure_delete` on.
+    pub(crate) fn is_on(&self, handle: usize) -> bool {
+        self.0.lock().get(&handle).is_some_and(|h| h.on)
+    }
+
+    /// Whether `path`, a journal or WAL, is overwritten. The connection
+    /// holding its database's write lock decides; a WAL database keeps
+    /// that lock in shared memory, so there any connection with
+    /// `secure_delete` on is enough.
+    pub(crate) fn covers(&self, path: &Path) -> bool {
+        let db_path = main_db_path(path);
+        let handles = self.0.lock();
+        let mut handles = handles.values().filter(|h| h.db_path == db_path);
+        match handles.clone().find(|h| h.lock >= SQLITE_LOCK_RESERVED) {
+            Some(writer) => writer.on,
+            None => handles.any(|h| h.on),
+        }
+    }
+}
+
+/// Map a `-journal`/`-wal` path to its main database.
+pub(crate) fn main_db_path(path: &Path) -> PathBuf {
+    let s = path.to_string_lossy();
+    let db = s
+        .strip_suffix("-journal")
+        .or_else(|| s.strip_suffix("-wal"))
+        .unwrap_or(&s);
+    PathBuf::from(db)
+}
+
+/// Parse a `secure_delete` pragma value. `FAST` only scrubs when it
+/// costs no extra I/O, so it does not turn on VFS overwriting.
+pub(crate) fn pragma_enables(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "on" | "true" | "yes" | "1" => Some(true),
+        "off" | "false" | "no" | "0" | "fast" | "2" => Some(false),
+        _ => None,
+    }
+}
+
+/// Zero `[from, to)` of an inner file through its own `xWrite`.
+pub(crate) unsafe fn zero_range(inner: *mut sqlite3_file, from: i64, to: i64) -> c_int {
+    let zeros = vec![0u8; CHUNK];
+    let mut offset = from;
+    while offset < to {
+        let n = (to - offset).min(CHUNK as i64) as c_int;
+        let rc = unsafe {
+            ((*(*inner).pMethods).xWrite.unwrap())(
+                inner,
+                zeros.as_ptr() as *const c_void,
+                n,
+                offset,
+            )
+        };
+        if rc != SQLITE_OK {
+            return rc;
+        }
+        offset += n as i64;
+    }
+    SQLITE_OK
+}
+
+/// Overwrite a file with zeros and flush it, leaving it in place.
+pub(crate) fn overwrite_file(path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let mut remaining = file.metadata()?.len();
+    let zeros = vec![0u8; CHUNK];
+    while remaining > 0 {
+        let n = remaining.min(CHUNK as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()
+}
+
+/// Overwrite a file, then remove it. A missing file is not an error.
+pub(crate) fn shred_file(path: &Path) -> io::Result<()> {
+    match overwrite_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        other => other?,
+    }
+    std::fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_and_wal_map_to_main_db() {
+        assert_eq!(main_db_path(Path::new("/d/a.db")), PathBuf::from("/d/a.db"));
+        assert_eq!(
+            main_db_path(Path::new("/d/a.db-journal")),
+            PathBuf::from("/d/a.db")
+        );
+        assert_eq!(
+            main_db_path(Path::new("/d/a.db-wal")),
+            PathBuf::from("/d/a.db")
+        );
+    }
+
+    #[test]
+    fn set_covers_journals_of_flagged_dbs() {
+        let set = SecureDeleteSet::default();
+        set.open(1, Path::new("/d/a.db"));
+        set.set(1, true);
+        assert!(set.covers(Path::new("/d/a.db-wal")));
+        assert!(!set.covers(Path::new("/d/b.db-journal")));
+        set.set(1, false);
+        assert!(!set.covers(Path::new("/d/a.db-journal")));
+        set.set(1, true);
+        set.close(1);
+        assert!(!set.covers(Path::new("/d/a.db-journal")));
+    }
+
+    #[test]
+    fn journal_follows_the_writing_connection() {
+        let set = SecureDeleteSet::default();
+        set.open(1, Path::new("/d/a.db"));
+        set.open(2, Path::new("/d/a.db"));
+        set.set(1, true);
+        assert!(set.is_on(1));
+        assert!(!set.is_on(2));
+
+        set.lock(2, SQLITE_LOCK_RESERVED);
+        assert!(!set.covers(Path::new("/d/a.db-journal")));
+        set.lock(2, SQLITE_LOCK_NONE);
+        set.lock(1, SQLITE_LOCK_EXCLUSIVE);
+        assert!(set.covers(Path::new("/d/a.db-journal")));
+    }
+
+    #[test]
+    fn pragma_values() {
+        assert_eq!(pragma_enables("ON"), Some(true));
+        assert_eq!(pragma_enables("1"), Some(true));
+        assert_eq!(pragma_enables("fast"), Some(false));
+        assert_eq!(pragma_enables("off"), Some(false));
+        assert_eq!(pragma_enables("maybe"), None);
+    }
+
+    #[test]
+    fn shred_zeroes_then_removes() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("secret");
+        std::fs::write(&path, vec![0xAB; CHUNK + 17])?;
+
+        overwrite_file(&path)?;
+        let data = std::fs::read(&path)?;
+        assert_eq!(data.len(), CHUNK + 17);
+        assert!(data.iter().all(|b| *b == 0));
+
+        shred_file(&path)?;
+        assert!(!path.exists());
+        shred_file(&path)
+    }
+}
@@ -1,12 +1,15 @@
 use std::io::Cursor;
 
+use rusqlite::{Connection, OpenFlags};
 use sqlevfs::{
+    EvfsBuilder,
+    Mode,
     backup,
     crypto::{keys::KeyScope, page},
     keyring::Keyring,
 };
 
-use crate::common::{make_provider, test_db_path};
+use crate::common::{make_provider, sqlite_api_is_available, test_db_path};
 
 #[test_log::test]
 fn test_backup_create_verify_restore_rotate() -> anyhow::Result<()> {
@@ -133,3 +136,54 @@ fn test_backup_create_verify_restore_rotate() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test_log::test]
+fn test_encrypt_plaintext_db_opens_under_evfs() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp = tempfile::TempDir::new()?;
+    let keyfile = test_db_path(&temp, "migrate.key");
+    std::fs::write(&keyfile, [0x44u8; 32])?;
+    let vfs_name = "evfs_migrate_test";
+    let keyring = EvfsBuilder::new(Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    })
+    .vfs_name(vfs_name)
+    .register()?;
+
+    let plain_path = test_db_path(&temp, "plain.db");
+    let plain = Connection::open(&plain_path)?;
+    plain.execute_batch("CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT);")?;
+    for id in 0..200 {
+        plain.execute(
+            "INSERT INTO notes (id, body) VALUES (?1, ?2)",
+            rusqlite::params![id, format!("plaintext-note-{id}")],
+        )?;
+    }
+    drop(plain);
+
+    let evfs_path = test_db_path(&temp, "migrated.db");
+    backup::encrypt_plaintext_db(&plain_path, &evfs_path, &keyring, 4096, 48)?;
+
+    let raw = std::fs::read(&evfs_path)?;
+    assert_eq!(&raw[..16], b"SQLite format 3\0");
+    assert_eq!(raw[20], 48);
+    assert!(!raw.windows(15).any(|w| w == b"plaintext-note-"));
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &evfs_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE,
+        vfs_name,
+    )?;
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM notes", [], |r| r.get(0))?;
+    assert_eq!(count, 200);
+    let body: String = conn.query_row("SELECT body FROM notes WHERE id = 123", [], |r| r.get(0))?;
+    assert_eq!(body, "plaintext-note-123");
+
+    // The source is left alone and the destination is never overwritten.
+    assert!(backup::encrypt_plaintext_db(&plain_path, &evfs_path, &keyring, 4096, 48).is_err());
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use std::fs;
 
-use sqlevfs::{EvfsBuilder, Mode};
+use sqlevfs::{EvfsBuilder, Mode, keyring::KeyringStorage};
 use tempfile::TempDir;
 
 #[test_log::test]
@@ -16,8 +16,10 @@ fn test_builder_device_key_with_keyfile() -> anyhow::Result<()> {
 
     let builder = EvfsBuilder::new(mode);
     assert_eq!(builder.name, "evfs");
-    assert_eq!(builder.page_size, 4096);
-    assert_eq!(builder.reserve_size, 48);
+    assert_eq!(builder.page_size, None);
+    assert_eq!(builder.effective_page_size(), 4096);
+    assert_eq!(builder.reserve_size, None);
+    assert_eq!(builder.effective_reserve_size(), 48);
 
     Ok(())
 }
@@ -30,7 +32,7 @@ fn test_builder_device_key_with_passphrase() {
     };
 
     let builder = EvfsBuilder::new(mode);
-    assert_eq!(builder.page_size, 4096);
+    assert_eq!(builder.effective_page_size(), 4096);
 }
 
 #[test_log::test]
@@ -53,7 +55,7 @@ fn test_builder_tenant_key() {
     };
 
     let builder = EvfsBuilder::new(mode);
-    assert_eq!(builder.page_size, 4096);
+    assert_eq!(builder.effective_page_size(), 4096);
 }
 
 #[test_log::test]
@@ -70,11 +72,92 @@ fn test_builder_chaining() -> anyhow::Result<()> {
     let builder = EvfsBuilder::new(mode)
         .page_size(8192)
         .reserve_size(64)
-        .vfs_name("custom_evfs");
+        .vfs_name("custom_evfs")
+        .base_vfs("memdb");
 
     assert_eq!(builder.name, "custom_evfs");
-    assert_eq!(builder.page_size, 8192);
-    assert_eq!(builder.reserve_size, 64);
+    assert_eq!(builder.page_size, Some(8192));
+    assert_eq!(builder.effective_page_size(), 8192);
+    assert_eq!(builder.reserve_size, Some(64));
+    assert_eq!(builder.effective_reserve_size(), 64);
+    assert_eq!(builder.base_vfs.as_deref(), Some("memdb"));
 
     Ok(())
 }
+
+#[test_log::test]
+fn test_builder_register_rejects_small_reserve() {
+    let mode = Mode::DeviceKey {
+        keyfile: None,
+        passphrase: Some("test_password".to_string()),
+    };
+
+    let Err(err) = EvfsBuilder::new(mode)
+        .reserve_size(10)
+        .vfs_name("evfs_small_reserve")
+        .register()
+    else {
+        panic!("register() accepted reserve_size(10)");
+    };
+    assert!(err.to_string().contains("reserve (10)"), "{err}");
+}
+
+#[test_log::test]
+fn test_builder_register_rejects_derived_keyring_with_a_per_process_kek() {
+    for (name, mode) in [
+        ("evfs_derived_ephemeral", Mode::Ephemeral),
+        (
+            "evfs_derived_cloud",
+            Mode::TenantKey {
+                key_id: "alias/test".to_string(),
+                endpoint: Some("http://127.0.0.1:1".to_string()),
+            },
+        ),
+    ] {
+        let Err(err) = EvfsBuilder::new(mode)
+            .keyring_storage(KeyringStorage::Derived)
+            .vfs_name(name)
+            .register()
+        else {
+            panic!("register() accepted a derived keyring for {name}");
+        };
+        assert!(err.to_string().contains("same in every process"), "{err}");
+    }
+}
+
+#[test_log::test]
+fn test_builder_default_reserve_adapts_to_page_size() {
+    let mode = || Mode::DeviceKey {
+        keyfile: None,
+        passphrase: Some("test_password".to_string()),
+    };
+
+    // Small pages get only the trailer; 4096 and up keep spare bytes.
+    let small = EvfsBuilder::new(mode()).page_size(1024);
+    assert_eq!(
+        small.effective_reserve_size(),
+        sqlevfs::crypto::page::VERSIONED_RESERVE
+    );
+    let large = EvfsBuilder::new(mode()).page_size(65536);
+    assert_eq!(large.effective_reserve_size(), 48);
+
+    // An explicit reserve always wins.
+    let explicit = EvfsBuilder::new(mode()).page_size(1024).reserve_size(40);
+    assert_eq!(explicit.effective_reserve_size(), 40);
+}
+
+#[test_log::test]
+fn test_builder_self_test() -> anyhow::Result<()> {
+    EvfsBuilder::new(Mode::Ephemeral).self_test()?;
+
+    let temp_dir = TempDir::new()?;
+    let missing = temp_dir.path().join("missing.key");
+    let err = EvfsBuilder::new(Mode::DeviceKey {
+        keyfile: Some(missing),
+        passphrase: None,
+    })
+    .self_test()
+    .unwrap_err();
+    assert!(err.to_string().contains("self-test"), "{err}");
+    Ok(())
+}
@@ -27,8 +27,8 @@ fn test_crypto_wrong_key_rejected() {
     let mut buf = vec![0xCDu8; 4096];
     page::encrypt_page(&mut buf, 1, &dek, reserve).expect("encrypt page");
 
-    let err = page::decrypt_page(&mut buf, 1, &wrong, reserve);
-    assert!(err.is_err());
+    let err = page::decrypt_page(&mut buf, 1, &wrong, reserve).expect_err("wrong key");
+    assert_eq!(err.downcast_ref(), Some(&page::PageError::AuthFailed));
 }
 
 #[test_log::test]
@@ -2,7 +2,14 @@ use std::fs;
 
 use bincode::config;
 use rusqlite::{Connection, OpenFlags};
-use sqlevfs::{EvfsBuilder, Mode, keyring::PersistedKeyring, policy};
+use sqlevfs::{
+    EvfsBuilder,
+    Mode,
+    crypto::keys::KeyScope,
+    keyring::{EMBEDDED_KEYRING_SIZE, KeyringStorage, PersistedKeyring},
+    policy,
+    vfs,
+};
 use tempfile::TempDir;
 
 use crate::common::{sqlite_api_is_available, test_db_path};
@@ -48,6 +55,8 @@ fn test_end_to_end_database_operations() -> anyhow::Result<()> {
             fallback: policy::TempStoreFallback::Memory,
         },
         enforce: policy::Enforce::Warn,
+        secure_delete: false,
+        temp_dir_override: None,
     };
     let _ = policy::apply_storage_policy(&conn, &db_path, &storage_policy)?;
 
@@ -152,6 +161,334 @@ fn test_reopening_encrypted_database() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test_log::test]
+fn test_default_reserve_on_small_pages_is_the_minimum() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let db_path = test_db_path(&temp_dir, "small_pages.db");
+
+    let builder = EvfsBuilder::new(Mode::DeviceKey {
+        keyfile: None,
+        passphrase: Some("small-pages".into()),
+    })
+    .vfs_name("evfs_small_pages")
+    .page_size(1024);
+    let reserve_size = builder.effective_reserve_size();
+    assert_eq!(reserve_size, sqlevfs::crypto::page::VERSIONED_RESERVE);
+    builder.register()?;
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs_small_pages",
+    )?;
+    conn.execute_batch("CREATE TABLE data (value TEXT); INSERT INTO data VALUES ('small');")?;
+    let value: String = conn.query_row("SELECT value FROM data", [], |row| row.get(0))?;
+    assert_eq!(value, "small");
+    conn.close().map_err(|(_, e)| e)?;
+
+    let bytes = std::fs::read(&db_path)?;
+    assert_eq!(bytes[20] as usize, reserve_size);
+    Ok(())
+}
+
+#[test_log::test]
+fn test_small_page_database_keeps_its_reserve_with_an_explicit_page_size() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let db_path = test_db_path(&temp_dir, "legacy_small_pages.db");
+    let mode = || Mode::DeviceKey {
+        keyfile: None,
+        passphrase: Some("legacy-small-pages".into()),
+    };
+
+    // Created under the old fixed default of 48.
+    EvfsBuilder::new(mode())
+        .vfs_name("evfs_legacy_48")
+        .page_size(1024)
+        .reserve_size(48)
+        .register()?;
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs_legacy_48",
+    )?;
+    conn.execute_batch("CREATE TABLE data (value TEXT); INSERT INTO data VALUES ('legacy');")?;
+    conn.close().map_err(|(_, e)| e)?;
+
+    // Reopened with only the page size set: the reserve comes from the
+    // header, not the smaller default.
+    EvfsBuilder::new(mode())
+        .vfs_name("evfs_legacy_default")
+        .page_size(1024)
+        .register()?;
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE,
+        "evfs_legacy_default",
+    )?;
+    let value: String = conn.query_row("SELECT value FROM data", [], |row| row.get(0))?;
+    assert_eq!(value, "legacy");
+    Ok(())
+}
+
+#[test_log::test]
+fn test_reopening_with_different_reserve_size_is_refused() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("reserve.key");
+    fs::write(&keyfile, vec![0xDE; 32])?;
+    let db_path = test_db_path(&temp_dir, "reserve.db");
+
+    for (name, reserve_size) in [("evfs_reserve48", 48), ("evfs_reserve32", 32)] {
+        EvfsBuilder::new(Mode::DeviceKey {
+            keyfile: Some(keyfile.clone()),
+            passphrase: None,
+        })
+        .vfs_name(name)
+        .reserve_size(reserve_size)
+        .register()?;
+    }
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs_reserve48",
+    )?;
+    conn.execute_batch("CREATE TABLE data (value TEXT); INSERT INTO data VALUES ('x');")?;
+    conn.close().map_err(|(_, e)| e)?;
+    assert_eq!(fs::read(&db_path)?[20], 48);
+
+    let err = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE,
+        "evfs_reserve32",
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::CannotOpen)
+    );
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY,
+        "evfs_reserve48",
+    )?;
+    let value: String = conn.query_row("SELECT value FROM data", [], |r| r.get(0))?;
+    assert_eq!(value, "x");
+    Ok(())
+}
+
+#[test_log::test]
+fn test_ephemeral_vfses_cannot_read_each_other() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    EvfsBuilder::new(Mode::Ephemeral)
+        .vfs_name("evfs_ephemeral_a")
+        .register()?;
+    EvfsBuilder::new(Mode::Ephemeral)
+        .vfs_name("evfs_ephemeral_b")
+        .register()?;
+
+    let mut paths = vec![];
+    for name in ["evfs_ephemeral_a", "evfs_ephemeral_b"] {
+        let db_path = test_db_path(&temp_dir, &format!("{name}.db"));
+        let conn = Connection::open_with_flags_and_vfs(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            name,
+        )?;
+        conn.execute_batch("CREATE TABLE scratch (v TEXT); INSERT INTO scratch VALUES ('tmp');")?;
+        let v: String = conn.query_row("SELECT v FROM scratch", [], |r| r.get(0))?;
+        assert_eq!(v, "tmp");
+        conn.close().map_err(|(_, e)| e)?;
+        paths.push(db_path);
+    }
+
+    for (path, other) in [
+        (&paths[0], "evfs_ephemeral_b"),
+        (&paths[1], "evfs_ephemeral_a"),
+    ] {
+        let read =
+            Connection::open_with_flags_and_vfs(path, OpenFlags::SQLITE_OPEN_READ_ONLY, other)
+                .and_then(|c| c.query_row("SELECT v FROM scratch", [], |r| r.get::<_, String>(0)));
+        assert!(read.is_err(), "{} readable through {other}", path.display());
+    }
+    Ok(())
+}
+
+#[test_log::test]
+fn test_read_only_vfs_rejects_writes() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("readonly.key");
+    fs::write(&keyfile, vec![0xD1; 32])?;
+
+    let db_path = test_db_path(&temp_dir, "readonly.db");
+
+    {
+        let mode = Mode::DeviceKey {
+            keyfile: Some(keyfile.clone()),
+            passphrase: None,
+        };
+
+        EvfsBuilder::new(mode)
+            .vfs_name("evfs_ro_writer")
+            .register()?;
+
+        let conn = Connection::open_with_flags_and_vfs(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            "evfs_ro_writer",
+        )?;
+        conn.execute_batch(
+            r#"
+            PRAGMA journal_mode = DELETE;
+            CREATE TABLE data (value TEXT);
+            INSERT INTO data VALUES ('original');
+            CREATE TABLE bulk (body BLOB);
+            WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 4000)
+            INSERT INTO bulk SELECT randomblob(256) FROM n;
+            "#,
+        )?;
+        conn.close().map_err(|(_, e)| e)?;
+    }
+
+    let before = fs::read(&db_path)?;
+
+    {
+        let mode = Mode::DeviceKey {
+            keyfile: Some(keyfile),
+            passphrase: None,
+        };
+
+        EvfsBuilder::new(mode)
+            .vfs_name("evfs_ro_reader")
+            .read_only(true)
+            .register()?;
+
+        // Read-write open flags: the VFS itself must still refuse writes.
+        let conn = Connection::open_with_flags_and_vfs(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE,
+            "evfs_ro_reader",
+        )?;
+
+        let value: String = conn.query_row("SELECT value FROM data", [], |row| row.get(0))?;
+        assert_eq!(value, "original");
+
+        let err = conn
+            .execute("INSERT INTO data VALUES ('tampered')", [])
+            .expect_err("insert through a read-only VFS must fail");
+        assert_eq!(
+            err.sqlite_error_code(),
+            Some(rusqlite::ErrorCode::ReadOnly),
+            "unexpected error: {err}"
+        );
+
+        // A sort too big for the cache spills to a temp file, and a temp
+        // table lives in one: neither is the read-only database.
+        conn.execute_batch("PRAGMA temp_store = FILE; PRAGMA cache_size = 8;")?;
+        let sorted: i64 = conn.query_row(
+            "SELECT count(*) FROM (SELECT DISTINCT body FROM bulk ORDER BY body DESC)",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(sorted, 4000);
+        conn.execute_batch("CREATE TEMP TABLE scratch AS SELECT body FROM bulk;")?;
+        let copied: i64 =
+            conn.query_row("SELECT count(*) FROM temp.scratch", [], |row| row.get(0))?;
+        assert_eq!(copied, 4000);
+
+        conn.close().map_err(|(_, e)| e)?;
+    }
+
+    assert_eq!(fs::read(&db_path)?, before);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn test_max_pages_rejects_writes_past_the_limit() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("max_pages.key");
+    fs::write(&keyfile, vec![0xD2; 32])?;
+
+    let db_path = test_db_path(&temp_dir, "max_pages.db");
+    let mode = Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    };
+    EvfsBuilder::new(mode)
+        .vfs_name("evfs_max_pages")
+        .max_pages(16)
+        .register()?;
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs_max_pages",
+    )?;
+    conn.execute_batch(
+        r#"
+        PRAGMA journal_mode = DELETE;
+        CREATE TABLE data (value BLOB);
+        INSERT INTO data VALUES (randomblob(100));
+        "#,
+    )?;
+
+    // A write at an absurd page number, as a hostile header could ask for.
+    let mut file: *mut rusqlite::ffi::sqlite3_file = std::ptr::null_mut();
+    let rc = unsafe {
+        rusqlite::ffi::sqlite3_file_control(
+            conn.handle(),
+            c"main".as_ptr(),
+            rusqlite::ffi::SQLITE_FCNTL_FILE_POINTER,
+            (&mut file as *mut *mut rusqlite::ffi::sqlite3_file).cast(),
+        )
+    };
+    assert_eq!(rc, rusqlite::ffi::SQLITE_OK);
+    let page = vec![0u8; 4096];
+    let rc =
+        unsafe { ((*(*file).pMethods).xWrite.unwrap())(file, page.as_ptr().cast(), 4096, 1 << 42) };
+    assert_eq!(rc, rusqlite::ffi::SQLITE_FULL);
+
+    // SQLite itself growing the file past the limit fails the same way.
+    let err = conn
+        .execute("INSERT INTO data VALUES (randomblob(128 * 1024))", [])
+        .expect_err("growing past max_pages must fail");
+    assert_eq!(
+        err.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DiskFull),
+        "unexpected error: {err}"
+    );
+    let rows: i64 = conn.query_row("SELECT COUNT(*) FROM data", [], |row| row.get(0))?;
+    assert_eq!(rows, 1);
+
+    conn.close().map_err(|(_, e)| e)?;
+    Ok(())
+}
+
 #[test_log::test]
 fn test_large_data_encryption() -> anyhow::Result<()> {
     if !sqlite_api_is_available() {
@@ -327,105 +664,1032 @@ fn test_keyring_persistence_via_sidecar() -> anyhow::Result<()> {
 }
 
 #[test_log::test]
-#[ignore]
-fn test_concurrent_access() -> anyhow::Result<()> {
+fn test_embedded_keyring_survives_copying_only_the_db() -> anyhow::Result<()> {
     if !sqlite_api_is_available() {
         eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
         return Ok(());
     }
-    use std::thread;
-
     let temp_dir = TempDir::new()?;
-    let keyfile = temp_dir.path().join("concurrent.key");
-    fs::write(&keyfile, vec![0x33; 32])?;
+    let keyfile = temp_dir.path().join("embedded.key");
+    fs::write(&keyfile, vec![0x42; 32])?;
 
-    let db_path = test_db_path(&temp_dir, "concurrent.db");
+    let db_path = test_db_path(&temp_dir, "embedded.db");
+    let vfs_name = "evfs_embedded_keyring";
 
     let mode = Mode::DeviceKey {
         keyfile: Some(keyfile),
         passphrase: None,
     };
-
     EvfsBuilder::new(mode)
-        .vfs_name("evfs_concurrent")
+        .vfs_name(vfs_name)
+        .keyring_storage(KeyringStorage::Embedded)
         .register()?;
 
     {
         let conn = Connection::open_with_flags_and_vfs(
             &db_path,
             OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
-            "evfs_concurrent",
+            vfs_name,
         )?;
-
-        conn.execute("CREATE TABLE counter (value INTEGER)", [])?;
-        conn.execute("INSERT INTO counter VALUES (0)", [])?;
+        conn.execute("CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT)", [])?;
+        conn.execute("INSERT INTO t (v) VALUES ('portable')", [])?;
         conn.close().map_err(|(_, e)| e)?;
     }
 
-    let db_path_clone = db_path.clone();
-    let handles: Vec<_> = (0..5)
-        .map(|_| {
-            let path = db_path_clone.clone();
-            thread::spawn(move || {
-                let conn = Connection::open_with_flags_and_vfs(
-                    &path,
-                    OpenFlags::SQLITE_OPEN_READ_ONLY,
-                    "evfs_concurrent",
-                )
-                .unwrap();
-
-                let value: i32 = conn
-                    .query_row("SELECT value FROM counter", [], |row| row.get(0))
-                    .unwrap();
+    assert!(
+        !db_path.with_extension("evfs-keyring").exists(),
+        "embedded mode must not write a sidecar"
+    );
 
-                assert_eq!(value, 0);
-            })
-        })
-        .collect();
+    let copy_dir = TempDir::new()?;
+    let copy_path = test_db_path(&copy_dir, "copied.db");
+    fs::copy(&db_path, &copy_path)?;
 
-    for handle in handles {
-        handle.join().unwrap();
-    }
+    let conn = Connection::open_with_flags_and_vfs(
+        &copy_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE,
+        vfs_name,
+    )?;
+    let v: String = conn.query_row("SELECT v FROM t WHERE id = 1", [], |r| r.get(0))?;
+    assert_eq!(v, "portable");
 
     Ok(())
 }
 
 #[test_log::test]
-fn test_wal_journal_mode_enabled_with_evfs() -> anyhow::Result<()> {
+fn test_embedded_keyring_journal_rolls_forward_on_open() -> anyhow::Result<()> {
     if !sqlite_api_is_available() {
         eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
         return Ok(());
     }
     let temp_dir = TempDir::new()?;
-    let keyfile = temp_dir.path().join("wal-mode.key");
-    fs::write(&keyfile, vec![0x77; 32])?;
+    let keyfile = temp_dir.path().join("embedded_journal.key");
+    fs::write(&keyfile, vec![0x43; 32])?;
 
-    let db_path = temp_dir.path().join("wal-mode.db");
-    let vfs_name = "evfs_wal_mode_test";
+    let db_path = test_db_path(&temp_dir, "embedded_journal.db");
+    let journal_path = temp_dir.path().join("embedded_journal.db-evfskr");
+    let vfs_name = "evfs_embedded_keyring_journal";
 
     let mode = Mode::DeviceKey {
         keyfile: Some(keyfile),
         passphrase: None,
     };
+    EvfsBuilder::new(mode)
+        .vfs_name(vfs_name)
+        .keyring_storage(KeyringStorage::Embedded)
+        .register()?;
 
-    EvfsBuilder::new(mode).vfs_name(vfs_name).register()?;
-
-    let conn = Connection::open_with_flags_and_vfs(
-        &db_path,
-        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
-        vfs_name,
-    )?;
+    {
+        let conn = Connection::open_with_flags_and_vfs(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            vfs_name,
+        )?;
+        conn.execute("CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT)", [])?;
+        conn.execute("INSERT INTO t (v) VALUES ('journaled')", [])?;
+        conn.close().map_err(|(_, e)| e)?;
+    }
+    assert!(
+        !journal_path.exists(),
+        "the journal is deleted once written"
+    );
+
+    // A crash after the journal committed, with the block torn.
+    let mut bytes = fs::read(&db_path)?;
+    let mut journal = bytes[..EMBEDDED_KEYRING_SIZE].to_vec();
+    journal.extend_from_slice(b"EVFSKRJC");
+    fs::write(&journal_path, &journal)?;
+    bytes[..EMBEDDED_KEYRING_SIZE].fill(0);
+    fs::write(&db_path, &bytes)?;
 
-    let mut stmt = conn.prepare("PRAGMA journal_mode = WAL")?;
-    let wal_after: String = stmt.query_row([], |r| r.get(0))?;
-    drop(stmt);
-    assert_eq!(wal_after.to_lowercase(), "wal");
+    {
+        let conn = Connection::open_with_flags_and_vfs(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE,
+            vfs_name,
+        )?;
+        let v: String = conn.query_row("SELECT v FROM t WHERE id = 1", [], |r| r.get(0))?;
+        assert_eq!(v, "journaled");
+    }
+    assert!(!journal_path.exists());
 
-    conn.execute("CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT)", [])?;
-    conn.execute("INSERT INTO t (v) VALUES ('ok')", [])?;
+    // A journal torn before its commit marker never touched the block.
+    fs::write(&journal_path, vec![0xAB; 100])?;
+    let conn =
+        Connection::open_with_flags_and_vfs(&db_path, OpenFlags::SQLITE_OPEN_READ_WRITE, vfs_name)?;
     let v: String = conn.query_row("SELECT v FROM t WHERE id = 1", [], |r| r.get(0))?;
-    assert_eq!(v, "ok");
+    assert_eq!(v, "journaled");
+    assert!(!journal_path.exists());
 
-    conn.close().map_err(|(_, e)| e)?;
+    Ok(())
+}
+
+#[test_log::test]
+fn test_derived_keyring_opens_with_the_passphrase_alone() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let db_path = test_db_path(&temp_dir, "derived.db");
+
+    // Two VFSes with their own keyrings stand in for two processes.
+    for vfs_name in ["evfs_derived_a", "evfs_derived_b"] {
+        EvfsBuilder::new(Mode::DeviceKey {
+            keyfile: None,
+            passphrase: Some("derived".into()),
+        })
+        .vfs_name(vfs_name)
+        .keyring_storage(KeyringStorage::Derived)
+        .register()?;
+    }
+
+    {
+        let conn = Connection::open_with_flags_and_vfs(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            "evfs_derived_a",
+        )?;
+        conn.execute_batch("CREATE TABLE t (v TEXT); INSERT INTO t VALUES ('derived');")?;
+        conn.close().map_err(|(_, e)| e)?;
+    }
+    assert!(
+        !db_path.with_extension("evfs-keyring").exists(),
+        "derived mode must not write a sidecar"
+    );
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY,
+        "evfs_derived_b",
+    )?;
+    let v: String = conn.query_row("SELECT v FROM t", [], |r| r.get(0))?;
+    assert_eq!(v, "derived");
+    Ok(())
+}
+
+#[test_log::test]
+fn test_page_size_is_detected_from_an_existing_database() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let db_path = test_db_path(&temp_dir, "large_pages.db");
+    let mode = || Mode::DeviceKey {
+        keyfile: None,
+        passphrase: Some("large-pages".into()),
+    };
+
+    EvfsBuilder::new(mode())
+        .vfs_name("evfs_8k_pages")
+        .page_size(8192)
+        .register()?;
+    {
+        let conn = Connection::open_with_flags_and_vfs(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            "evfs_8k_pages",
+        )?;
+        conn.execute_batch(
+            "CREATE TABLE t (v TEXT);
+             INSERT INTO t VALUES ('eight'), (hex(zeroblob(6000)));",
+        )?;
+        conn.close().map_err(|(_, e)| e)?;
+    }
+
+    // No page size: the VFS reads 8192 from the header.
+    EvfsBuilder::new(mode())
+        .vfs_name("evfs_detected_pages")
+        .register()?;
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE,
+        "evfs_detected_pages",
+    )?;
+    let page_size: u32 = conn.query_row("PRAGMA page_size", [], |r| r.get(0))?;
+    assert_eq!(page_size, 8192);
+    let v: String = conn.query_row("SELECT v FROM t WHERE rowid = 1", [], |r| r.get(0))?;
+    assert_eq!(v, "eight");
+    let n: i64 = conn.query_row("SELECT length(v) FROM t WHERE rowid = 2", [], |r| r.get(0))?;
+    assert_eq!(n, 12000);
+    conn.execute("INSERT INTO t VALUES ('more')", [])?;
+    let ok: String = conn.query_row("PRAGMA integrity_check", [], |r| r.get(0))?;
+    assert_eq!(ok, "ok");
+    Ok(())
+}
+
+#[test_log::test]
+fn test_encrypted_header_hides_the_schema_cookie() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let db_path = test_db_path(&temp_dir, "sealed_header.db");
+    EvfsBuilder::new(Mode::DeviceKey {
+        keyfile: None,
+        passphrase: Some("header".into()),
+    })
+    .vfs_name("evfs_sealed_header")
+    .encrypt_header(true)
+    .register()?;
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs_sealed_header",
+    )?;
+    conn.execute_batch(
+        "CREATE TABLE a (v TEXT); CREATE TABLE b (v TEXT); INSERT INTO a VALUES ('x');",
+    )?;
+    let cookie: u32 = conn.query_row("PRAGMA schema_version", [], |r| r.get(0))?;
+    conn.close().map_err(|(_, e)| e)?;
+
+    let raw = std::fs::read(&db_path)?;
+    assert!(raw.starts_with(b"SQLite format 3\0"));
+    assert_eq!(u16::from_be_bytes([raw[16], raw[17]]), 4096);
+    assert_ne!(
+        raw[40..44],
+        cookie.to_be_bytes(),
+        "schema cookie is plaintext"
+    );
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY,
+        "evfs_sealed_header",
+    )?;
+    let v: String = conn.query_row("SELECT v FROM a", [], |r| r.get(0))?;
+    assert_eq!(v, "x");
+    Ok(())
+}
+
+#[test_log::test]
+#[ignore]
+fn test_concurrent_access() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    use std::thread;
+
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("concurrent.key");
+    fs::write(&keyfile, vec![0x33; 32])?;
+
+    let db_path = test_db_path(&temp_dir, "concurrent.db");
+
+    let mode = Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    };
+
+    EvfsBuilder::new(mode)
+        .vfs_name("evfs_concurrent")
+        .register()?;
+
+    {
+        let conn = Connection::open_with_flags_and_vfs(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            "evfs_concurrent",
+        )?;
+
+        conn.execute("CREATE TABLE counter (value INTEGER)", [])?;
+        conn.execute("INSERT INTO counter VALUES (0)", [])?;
+        conn.close().map_err(|(_, e)| e)?;
+    }
+
+    let db_path_clone = db_path.clone();
+    let handles: Vec<_> = (0..5)
+        .map(|_| {
+            let path = db_path_clone.clone();
+            thread::spawn(move || {
+                let conn = Connection::open_with_flags_and_vfs(
+                    &path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY,
+                    "evfs_concurrent",
+                )
+                .unwrap();
+
+                let value: i32 = conn
+                    .query_row("SELECT value FROM counter", [], |row| row.get(0))
+                    .unwrap();
+
+                assert_eq!(value, 0);
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    Ok(())
+}
+
+#[test_log::test]
+fn test_wal_journal_mode_enabled_with_evfs() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("wal-mode.key");
+    fs::write(&keyfile, vec![0x77; 32])?;
+
+    let db_path = temp_dir.path().join("wal-mode.db");
+    let vfs_name = "evfs_wal_mode_test";
+
+    let mode = Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    };
+
+    EvfsBuilder::new(mode).vfs_name(vfs_name).register()?;
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        vfs_name,
+    )?;
+
+    let mut stmt = conn.prepare("PRAGMA journal_mode = WAL")?;
+    let wal_after: String = stmt.query_row([], |r| r.get(0))?;
+    drop(stmt);
+    assert_eq!(wal_after.to_lowercase(), "wal");
+
+    conn.execute("CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT)", [])?;
+    conn.execute("INSERT INTO t (v) VALUES ('ok')", [])?;
+    let v: String = conn.query_row("SELECT v FROM t WHERE id = 1", [], |r| r.get(0))?;
+    assert_eq!(v, "ok");
+
+    conn.close().map_err(|(_, e)| e)?;
+    Ok(())
+}
+
+#[test_log::test]
+fn test_wal_checkpoint_round_trips_encrypted_frames() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("checkpoint.key");
+    fs::write(&keyfile, vec![0x78; 32])?;
+    let vfs_name = "evfs_wal_checkpoint_test";
+
+    let mode = Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    };
+    EvfsBuilder::new(mode).vfs_name(vfs_name).register()?;
+    let open = |path: &std::path::Path| {
+        Connection::open_with_flags_and_vfs(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            vfs_name,
+        )
+    };
+
+    let db_path = temp_dir.path().join("checkpoint.db");
+    let wal_path = temp_dir.path().join("checkpoint.db-wal");
+    let conn = open(&db_path)?;
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA wal_autocheckpoint = 0;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, body TEXT);",
+    )?;
+    for id in 0..32 {
+        conn.execute(
+            "INSERT INTO t (id, body) VALUES (?1, ?2)",
+            rusqlite::params![id, format!("wal-row-{id}-").repeat(50)],
+        )?;
+    }
+
+    let wal = fs::read(&wal_path)?;
+    assert!(wal.len() > 32, "frames should still be in the WAL");
+    assert!(!wal.windows(7).any(|w| w == b"wal-row"));
+
+    // A copy taken before the checkpoint must recover from its WAL, which
+    // only works if every frame still matches SQLite's checksums.
+    let recovered = temp_dir.path().join("recovered.db");
+    fs::copy(&db_path, &recovered)?;
+    fs::write(temp_dir.path().join("recovered.db-wal"), &wal)?;
+    fs::copy(
+        temp_dir.path().join("checkpoint.evfs-keyring"),
+        temp_dir.path().join("recovered.evfs-keyring"),
+    )?;
+
+    let (busy, _, _): (i64, i64, i64) =
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |r| {
+            Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+        })?;
+    assert_eq!(busy, 0);
+    assert_eq!(fs::metadata(&wal_path)?.len(), 0);
+    assert!(!fs::read(&db_path)?.windows(7).any(|w| w == b"wal-row"));
+
+    let body: String = conn.query_row("SELECT body FROM t WHERE id = 9", [], |r| r.get(0))?;
+    assert_eq!(body, "wal-row-9-".repeat(50));
+    conn.close().map_err(|(_, e)| e)?;
+
+    for path in [&db_path, &recovered] {
+        let conn = open(path)?;
+        let n: i64 = conn.query_row("SELECT count(*) FROM t", [], |r| r.get(0))?;
+        assert_eq!(n, 32, "{}", path.display());
+        let check: String = conn.query_row("PRAGMA integrity_check", [], |r| r.get(0))?;
+        assert_eq!(check, "ok");
+    }
+    Ok(())
+}
+
+#[test_log::test]
+fn test_hot_journal_rollback_recovers_encrypted_pages() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("journal.key");
+    fs::write(&keyfile, vec![0x55; 32])?;
+    let vfs_name = "evfs_hot_journal_test";
+
+    let mode = Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    };
+    EvfsBuilder::new(mode).vfs_name(vfs_name).register()?;
+    let open = |path: &std::path::Path| {
+        Connection::open_with_flags_and_vfs(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            vfs_name,
+        )
+    };
+
+    let db_path = temp_dir.path().join("crash.db");
+    let conn = open(&db_path)?;
+    conn.execute_batch(
+        "PRAGMA journal_mode = DELETE;
+         CREATE TABLE t (id INTEGER PRIMARY KEY, body TEXT);",
+    )?;
+    for id in 0..64 {
+        conn.execute(
+            "INSERT INTO t (id, body) VALUES (?1, ?2)",
+            rusqlite::params![id, format!("before-row-{id}-").repeat(100)],
+        )?;
+    }
+
+    // Spill changed pages to the database mid-transaction, then snapshot
+    // the files as a crash would leave them.
+    conn.execute_batch(
+        "PRAGMA cache_size = 2;
+         BEGIN;
+         UPDATE t SET body = 'after';",
+    )?;
+    let recovered = temp_dir.path().join("recovered.db");
+    fs::copy(&db_path, &recovered)?;
+    let journal = fs::read(temp_dir.path().join("crash.db-journal"))?;
+    fs::write(temp_dir.path().join("recovered.db-journal"), &journal)?;
+    fs::copy(
+        temp_dir.path().join("crash.evfs-keyring"),
+        temp_dir.path().join("recovered.evfs-keyring"),
+    )?;
+    conn.execute_batch("ROLLBACK;")?;
+    conn.close().map_err(|(_, e)| e)?;
+
+    assert!(!journal.windows(10).any(|w| w == b"before-row"));
+
+    let conn = open(&recovered)?;
+    let body: String = conn.query_row("SELECT body FROM t WHERE id = 7", [], |r| r.get(0))?;
+    assert_eq!(body, "before-row-7-".repeat(100));
+    Ok(())
+}
+
+#[test_log::test]
+fn test_encrypt_off_uri_parameter() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("bypass.key");
+    fs::write(&keyfile, vec![0x66; 32])?;
+    let vfs_name = "evfs_bypass_test";
+    EvfsBuilder::new(Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    })
+    .vfs_name(vfs_name)
+    .register()?;
+    let open_bypassed = |path: &std::path::Path| {
+        Connection::open_with_flags_and_vfs(
+            format!("file:{}?evfs_encrypt=off", path.display()),
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_URI,
+            vfs_name,
+        )
+    };
+
+    // Allowed: a database that was created plaintext.
+    let plain = test_db_path(&temp_dir, "plain.db");
+    Connection::open(&plain)?.execute_batch(
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, body TEXT);
+         INSERT INTO t (body) VALUES ('plain');",
+    )?;
+    let conn = open_bypassed(&plain)?;
+    conn.execute("INSERT INTO t (body) VALUES ('via evfs')", [])?;
+    drop(conn);
+    let body: String =
+        Connection::open(&plain)?.query_row("SELECT body FROM t WHERE id = 2", [], |r| r.get(0))?;
+    assert_eq!(body, "via evfs");
+
+    // Refused: a database evfs has encrypted.
+    let encrypted = test_db_path(&temp_dir, "encrypted.db");
+    let conn = Connection::open_with_flags_and_vfs(
+        &encrypted,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        vfs_name,
+    )?;
+    conn.execute_batch("CREATE TABLE t (id INTEGER PRIMARY KEY, body TEXT);")?;
+    drop(conn);
+    let err = open_bypassed(&encrypted)
+        .and_then(|c| c.query_row("SELECT COUNT(*) FROM t", [], |r| r.get::<_, i64>(0)))
+        .unwrap_err();
+    assert_eq!(
+        err.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::CannotOpen)
+    );
+    Ok(())
+}
+
+#[test_log::test]
+fn test_keyring_path_override_round_trips() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let db_dir = TempDir::new()?;
+    let key_dir = TempDir::new()?;
+    let keyfile = key_dir.path().join("db.key");
+    fs::write(&keyfile, vec![0x77; 32])?;
+    let sidecar = key_dir.path().join("secure.evfs-keyring");
+    let vfs_name = "evfs_keyring_path_test";
+    EvfsBuilder::new(Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    })
+    .vfs_name(vfs_name)
+    .keyring_path(sidecar.clone())
+    .register()?;
+
+    let db_path = test_db_path(&db_dir, "app.db");
+    let open = || {
+        Connection::open_with_flags_and_vfs(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            vfs_name,
+        )
+    };
+    let conn = open()?;
+    conn.execute_batch(
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, body TEXT);
+         INSERT INTO t (body) VALUES ('kept apart');",
+    )?;
+    drop(conn);
+
+    assert!(sidecar.exists());
+    assert!(!db_path.with_extension("evfs-keyring").exists());
+
+    let body: String = open()?.query_row("SELECT body FROM t", [], |r| r.get(0))?;
+    assert_eq!(body, "kept apart");
+    Ok(())
+}
+
+#[test_log::test]
+fn test_mmap_size_pragma_still_reads_plaintext() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("mmap.key");
+    fs::write(&keyfile, vec![0xE1; 32])?;
+    let db_path = test_db_path(&temp_dir, "mmap.db");
+
+    let mode = Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    };
+    EvfsBuilder::new(mode).vfs_name("evfs_mmap").register()?;
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs_mmap",
+    )?;
+    conn.execute_batch(
+        r#"
+        CREATE TABLE t (id INTEGER PRIMARY KEY, body TEXT);
+        WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500)
+        INSERT INTO t (body) SELECT 'row ' || i FROM n;
+        "#,
+    )?;
+    conn.close().map_err(|(_, e)| e)?;
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY,
+        "evfs_mmap",
+    )?;
+    let mmap_size: i64 = conn.query_row("PRAGMA mmap_size = 268435456", [], |r| r.get(0))?;
+    assert_eq!(mmap_size, 0, "the VFS must refuse memory mapping");
+
+    let (count, last): (i64, String) = conn.query_row(
+        "SELECT COUNT(*), MAX(body) FROM t WHERE body LIKE 'row %'",
+        [],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )?;
+    assert_eq!(count, 500);
+    assert_eq!(last, "row 99");
+    Ok(())
+}
+
+#[test_log::test]
+fn test_secure_delete_shreds_sidecar_of_deleted_db() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("shred.key");
+    fs::write(&keyfile, vec![0xE2; 32])?;
+    let db_path = test_db_path(&temp_dir, "shred.db");
+    let sidecar = db_path.with_extension("evfs-keyring");
+
+    let mode = Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    };
+    EvfsBuilder::new(mode).vfs_name("evfs_shred").register()?;
+
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs_shred",
+    )?;
+    let storage_policy = policy::StoragePolicy {
+        secure_delete: true,
+        ..policy::StoragePolicy::default()
+    };
+    policy::apply_storage_policy(&conn, &db_path, &storage_policy)?;
+    conn.execute_batch("CREATE TABLE t (body TEXT); INSERT INTO t VALUES ('gone soon');")?;
+    assert!(sidecar.exists());
+
+    fs::remove_file(&db_path)?;
+    conn.close().map_err(|(_, e)| e)?;
+    assert!(!sidecar.exists(), "sidecar must be shredded with the db");
+    Ok(())
+}
+
+#[test_log::test]
+fn test_secure_delete_is_per_connection() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("per_conn.key");
+    fs::write(&keyfile, vec![0xE3; 32])?;
+    let db_path = test_db_path(&temp_dir, "per_conn.db");
+    let sidecar = db_path.with_extension("evfs-keyring");
+
+    let mode = Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    };
+    EvfsBuilder::new(mode)
+        .vfs_name("evfs_shred_per_conn")
+        .register()?;
+    let open = || {
+        Connection::open_with_flags_and_vfs(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            "evfs_shred_per_conn",
+        )
+    };
+
+    // A connection that turned it on and has gone does not leave it on
+    // for the next one.
+    let scrubbing = open()?;
+    scrubbing.execute_batch(
+        "PRAGMA secure_delete = ON;
+         CREATE TABLE t (body TEXT);
+         INSERT INTO t VALUES ('kept');",
+    )?;
+    scrubbing.close().map_err(|(_, e)| e)?;
+
+    let conn = open()?;
+    conn.execute_batch("INSERT INTO t VALUES ('also kept');")?;
+    fs::remove_file(&db_path)?;
+    conn.close().map_err(|(_, e)| e)?;
+    assert!(
+        sidecar.exists(),
+        "a connection without secure_delete must not shred the sidecar"
+    );
+    Ok(())
+}
+
+#[test_log::test]
+fn test_vfs_keys_every_page_under_the_database_scope() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("scopes.key");
+    fs::write(&keyfile, vec![0xE4; 32])?;
+    let db_path = test_db_path(&temp_dir, "scopes.db");
+
+    let mode = Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    };
+    let keyring = EvfsBuilder::new(mode).vfs_name("evfs_scopes").register()?;
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs_scopes",
+    )?;
+    conn.execute_batch(
+        "CREATE TABLE acme (body TEXT);
+         CREATE TABLE globex (body TEXT);
+         INSERT INTO acme VALUES (hex(zeroblob(20000)));
+         INSERT INTO globex VALUES ('small');",
+    )?;
+    conn.close().map_err(|(_, e)| e)?;
+
+    // Neither table, its overflow pages nor a tenant get a DEK of their own.
+    let scopes = keyring.scopes();
+    assert!(scopes.contains(&KeyScope::Database), "{scopes:?}");
+    assert!(
+        scopes.iter().all(|s| !matches!(
+            s,
+            KeyScope::Table(_) | KeyScope::Column { .. } | KeyScope::Tenant(_)
+        )),
+        "{scopes:?}"
+    );
+    Ok(())
+}
+
+#[test_log::test]
+fn test_gc_schema_keeps_the_keys_of_existing_tables() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("gc.key");
+    fs::write(&keyfile, vec![0xE5; 32])?;
+    let db_path = test_db_path(&temp_dir, "gc.db");
+
+    let mode = Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    };
+    let keyring = EvfsBuilder::new(mode).vfs_name("evfs_gc").register()?;
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs_gc",
+    )?;
+    conn.execute_batch(
+        "CREATE TABLE users (email TEXT);
+         CREATE TABLE orders (total INTEGER);",
+    )?;
+    let users = KeyScope::Table("users".into());
+    let email = KeyScope::Column {
+        table: "users".into(),
+        column: "email".into(),
+    };
+    let orders = KeyScope::Table("orders".into());
+    for scope in [&users, &email, &orders] {
+        keyring.dek_for(scope)?;
+    }
+
+    assert_eq!(keyring.gc_schema(&conn)?, 0);
+    conn.execute_batch("DROP TABLE orders;")?;
+    assert_eq!(keyring.gc_schema(&conn)?, 1);
+    let scopes = keyring.scopes();
+    assert!(
+        scopes.contains(&users) && scopes.contains(&email),
+        "{scopes:?}"
+    );
+    assert!(!scopes.contains(&orders), "{scopes:?}");
+    Ok(())
+}
+
+#[test_log::test]
+fn test_shred_scope_refuses_the_scope_vfs_pages_use() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp_dir = TempDir::new()?;
+    let keyfile = temp_dir.path().join("shred_scope.key");
+    fs::write(&keyfile, vec![0xE6; 32])?;
+    let db_path = test_db_path(&temp_dir, "shred_scope.db");
+
+    let mode = Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    };
+    let keyring = EvfsBuilder::new(mode)
+        .vfs_name("evfs_shred_scope")
+        .register()?;
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs_shred_scope",
+    )?;
+    conn.execute_batch("CREATE TABLE t (body TEXT); INSERT INTO t VALUES ('kept');")?;
+
+    let err = keyring
+        .shred_scope(&KeyScope::Database, None, policy::Enforce::Error)
+        .unwrap_err();
+    assert!(err.to_string().contains("the VFS encrypts"), "{err}");
+    let body: String = conn.query_row("SELECT body FROM t", [], |r| r.get(0))?;
+    assert_eq!(body, "kept");
+    Ok(())
+}
+
+#[test_log::test]
+fn test_stacking_over_memdb_vfs() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let mode = || Mode::DeviceKey {
+        keyfile: None,
+        passphrase: Some("stacked".into()),
+    };
+
+    let Err(err) = EvfsBuilder::new(mode())
+        .vfs_name("evfs_over_missing")
+        .base_vfs("no_such_vfs")
+        .register()
+    else {
+        panic!("register() accepted an unknown base VFS");
+    };
+    assert!(err.to_string().contains("no_such_vfs"), "{err}");
+
+    // The keyring rides inside the in-memory file, so nothing touches disk.
+    EvfsBuilder::new(mode())
+        .vfs_name("evfs_over_memdb")
+        .base_vfs("memdb")
+        .keyring_storage(KeyringStorage::Embedded)
+        .register()?;
+
+    let flags = OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE;
+    let conn = Connection::open_with_flags_and_vfs("/stacked.db", flags, "evfs_over_memdb")?;
+    conn.execute_batch("CREATE TABLE t (body TEXT); INSERT INTO t VALUES ('stacked secret');")?;
+
+    let reader = Connection::open_with_flags_and_vfs("/stacked.db", flags, "evfs_over_memdb")?;
+    let body: String = reader.query_row("SELECT body FROM t", [], |r| r.get(0))?;
+    assert_eq!(body, "stacked secret");
+
+    // Through memdb alone the same file is ciphertext.
+    let raw = Connection::open_with_flags_and_vfs("/stacked.db", flags, "memdb")?;
+    assert!(
+        raw.query_row("SELECT body FROM t", [], |r| r.get::<_, String>(0))
+            .is_err()
+    );
+    Ok(())
+}
+
+#[test_log::test]
+fn test_register_rejects_taken_vfs_name() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let dir = TempDir::new()?;
+    let mode = || Mode::DeviceKey {
+        keyfile: None,
+        passphrase: Some("twice".into()),
+    };
+    let builder = || EvfsBuilder::new(mode()).vfs_name("evfs_registered_twice");
+
+    builder().register()?;
+    let Err(err) = builder().register() else {
+        panic!("register() accepted a VFS name that is already taken");
+    };
+    assert_eq!(
+        err.to_string(),
+        "VFS 'evfs_registered_twice' already registered"
+    );
+
+    builder().allow_replace(true).register()?;
+    let db_path = test_db_path(&dir, "twice.db");
+    let conn = Connection::open_with_flags_and_vfs(
+        &db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        "evfs_registered_twice",
+    )?;
+    conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1);")?;
+    let x: i64 = conn.query_row("SELECT x FROM t", [], |r| r.get(0))?;
+    assert_eq!(x, 1);
+    Ok(())
+}
+
+#[test_log::test]
+fn test_unregister_frees_the_vfs_name() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let dir = TempDir::new()?;
+    let db_path = test_db_path(&dir, "unregister.db");
+    let flags = OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE;
+    let register = || {
+        EvfsBuilder::new(Mode::Ephemeral)
+            .vfs_name("evfs_unregister")
+            .register()
+    };
+
+    register()?;
+    let conn = Connection::open_with_flags_and_vfs(&db_path, flags, "evfs_unregister")?;
+    conn.execute_batch("CREATE TABLE t (x);")?;
+    let err = vfs::unregister_evfs("evfs_unregister").unwrap_err();
+    assert!(err.to_string().contains("open file"), "{err}");
+
+    drop(conn);
+    vfs::unregister_evfs("evfs_unregister")?;
+    assert!(Connection::open_with_flags_and_vfs(&db_path, flags, "evfs_unregister").is_err());
+    assert!(vfs::unregister_evfs("evfs_unregister").is_err());
+
+    // The name is free again, and foreign VFSes are left alone.
+    register()?;
+    vfs::unregister_evfs("evfs_unregister")?;
+    let err = vfs::unregister_evfs("memdb").unwrap_err();
+    assert!(err.to_string().contains("not an evfs VFS"), "{err}");
+    Ok(())
+}
+
+#[test_log::test]
+fn test_attached_database_uses_its_own_keyring() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let dir = TempDir::new()?;
+    let keyfile = dir.path().join("attach.key");
+    fs::write(&keyfile, vec![0x5A; 32])?;
+    EvfsBuilder::new(Mode::DeviceKey {
+        keyfile: Some(keyfile),
+        passphrase: None,
+    })
+    .vfs_name("evfs_attach")
+    .register()?;
+
+    let flags = OpenFlags::SQLITE_OPEN_READ_WRITE
+        | OpenFlags::SQLITE_OPEN_CREATE
+        | OpenFlags::SQLITE_OPEN_URI;
+    let open = |name: &str| {
+        Connection::open_with_flags_and_vfs(test_db_path(&dir, name), flags, "evfs_attach")
+    };
+    let main = open("main.db")?;
+    main.execute_batch(
+        "CREATE TABLE users (id INTEGER, name TEXT); INSERT INTO users VALUES (1, 'ada');",
+    )?;
+    let other = open("other.db")?;
+    other.execute_batch(
+        "CREATE TABLE orders (user_id INTEGER, item TEXT); INSERT INTO orders VALUES (1, 'book');",
+    )?;
+    drop(other);
+
+    let other_uri = format!(
+        "file:{}?vfs=evfs_attach",
+        test_db_path(&dir, "other.db").display()
+    );
+    main.execute("ATTACH DATABASE ?1 AS aux", [&other_uri])?;
+    let joined: (String, String) = main.query_row(
+        "SELECT name, item FROM users JOIN aux.orders ON orders.user_id = users.id",
+        [],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )?;
+    assert_eq!(joined, ("ada".into(), "book".into()));
+    main.execute_batch(
+        "INSERT INTO aux.orders VALUES (1, 'pen'); INSERT INTO users VALUES (2, 'bob');",
+    )?;
+    drop(main);
+
+    // Each file still decrypts on its own, under its own sidecar.
+    assert!(dir.path().join("main.evfs-keyring").exists());
+    assert!(dir.path().join("other.evfs-keyring").exists());
+    let users: i64 = open("main.db")?.query_row("SELECT count(*) FROM users", [], |r| r.get(0))?;
+    let orders: i64 =
+        open("other.db")?.query_row("SELECT count(*) FROM orders", [], |r| r.get(0))?;
+    assert_eq!((users, orders), (2, 2));
     Ok(())
 }
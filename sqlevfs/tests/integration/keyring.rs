@@ -1,10 +1,15 @@
+use std::sync::Arc;
+
 use bincode::config;
 use sqlevfs::{
     crypto::keys::KeyScope,
+    io::FileContext,
     keyring::{Keyring, PersistedKeyring},
+    kms::local::DeviceKeyProvider,
+    policy::Enforce,
 };
 
-use crate::common::{make_provider, test_db_path};
+use crate::common::{make_provider, sqlite_api_is_available, test_db_path};
 
 #[test_log::test]
 fn test_keyring_scope_resolution() {
@@ -69,3 +74,155 @@ fn test_keyring_sidecar_persistence_and_rewrap() {
 
     keyring.rewrap_all().expect("rewrap_all should succeed");
 }
+
+#[test_log::test]
+fn test_keyring_sidecar_shared_by_two_keyrings_keeps_all_deks() {
+    let temp = tempfile::TempDir::new().expect("temp dir");
+    let keyfile = test_db_path(&temp, "shared.key");
+    std::fs::write(&keyfile, [0x66u8; 32]).expect("write keyfile");
+    let db_path = test_db_path(&temp, "shared.db");
+
+    // Each keyring stands in for a separate process sharing the sidecar.
+    let handles: Vec<_> = ["a", "b"]
+        .into_iter()
+        .map(|prefix| {
+            let keyring = Keyring::new(make_provider(&keyfile));
+            keyring.set_sidecar_path(&db_path);
+            std::thread::spawn(move || {
+                (0..20)
+                    .map(|i| {
+                        let scope = KeyScope::Table(format!("{prefix}{i}"));
+                        let dek = keyring.dek_for(&scope).expect("dek");
+                        (scope, dek)
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let generated: Vec<_> = handles
+        .into_iter()
+        .flat_map(|h| h.join().expect("thread"))
+        .collect();
+
+    let reader = Keyring::new(make_provider(&keyfile));
+    reader.set_sidecar_path(&db_path);
+    for (scope, dek) in generated {
+        let reloaded = reader.dek_for(&scope).expect("reload dek");
+        assert_eq!(reloaded.as_bytes(), dek.as_bytes(), "{scope} was lost");
+    }
+}
+
+#[test_log::test]
+fn test_keyring_column_scopes_are_distinct_and_persist() {
+    let temp = tempfile::TempDir::new().expect("temp dir");
+    let keyfile = test_db_path(&temp, "columns.key");
+    std::fs::write(&keyfile, [0x44u8; 32]).expect("write keyfile");
+    let db_path = test_db_path(&temp, "columns.db");
+
+    let column = |name: &str| KeyScope::Column {
+        table: "users".into(),
+        column: name.into(),
+    };
+    let keyring = Keyring::new(make_provider(&keyfile));
+    keyring.set_sidecar_path(&db_path);
+    let ssn = keyring.dek_for(&column("ssn")).expect("ssn DEK");
+    let email = keyring.dek_for(&column("email")).expect("email DEK");
+    assert_ne!(ssn.as_bytes(), email.as_bytes());
+
+    let reopened = Keyring::new(make_provider(&keyfile));
+    reopened.set_sidecar_path(&db_path);
+    let mut scopes = reopened.scopes();
+    scopes.sort_by_key(ToString::to_string);
+    assert_eq!(scopes, vec![column("email"), column("ssn")]);
+    assert_eq!(
+        reopened.dek_for(&column("ssn")).expect("reload").as_bytes(),
+        ssn.as_bytes()
+    );
+}
+
+#[test_log::test]
+fn test_shredding_a_table_orphans_its_overflow_pages() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let temp = tempfile::TempDir::new()?;
+    let db_path = test_db_path(&temp, "overflow.db");
+    let conn = rusqlite::Connection::open(&db_path)?;
+    conn.execute_batch("PRAGMA page_size = 1024; CREATE TABLE docs (body BLOB);")?;
+    conn.execute("INSERT INTO docs VALUES (?1)", [vec![0x5Au8; 10_000]])?;
+    let root: u32 = conn.query_row(
+        "SELECT rootpage FROM sqlite_master WHERE name = 'docs'",
+        [],
+        |r| r.get(0),
+    )?;
+    drop(conn);
+
+    let file = std::fs::read(&db_path)?;
+    let page_count = (file.len() / 1024) as u32;
+    let keyfile = test_db_path(&temp, "overflow.key");
+    std::fs::write(&keyfile, [0x42u8; 32])?;
+    let mut ctx = FileContext {
+        keyring: Arc::new(Keyring::new(make_provider(&keyfile))),
+        page_size: 1024,
+        reserve_size: 0,
+        encrypt_enabled: true,
+        is_journal: false,
+        page_scope_map: None,
+    };
+    ctx.build_full_page_scope_map(&[("docs".into(), root)], |n| {
+        let at = (n as usize - 1) * 1024;
+        Ok(file[at..at + 1024].to_vec())
+    })?;
+
+    // The one row spills over ~10 overflow pages; all of them are `docs`'s.
+    let map = ctx.page_scope_map.as_ref().unwrap();
+    assert!(map.len() >= 10, "{map:?}");
+    let docs = KeyScope::Table("docs".into());
+    for page_no in 2..=page_count {
+        assert_eq!(map.get(&page_no), Some(&docs), "page {page_no}");
+    }
+
+    // The file was written without a reserve; encrypting needs one.
+    ctx.reserve_size = 48;
+    let mut pages: Vec<(u32, Vec<u8>)> =
+        (2..=page_count).map(|n| (n, vec![n as u8; 1024])).collect();
+    for (page_no, page) in &mut pages {
+        ctx.encrypt_page(page, *page_no)?;
+    }
+    ctx.keyring
+        .shred_scope(&docs, ctx.page_scope_map.as_ref(), Enforce::Warn)?;
+    for (page_no, page) in &mut pages {
+        assert!(ctx.decrypt_page(page, *page_no).is_err(), "page {page_no}");
+    }
+    Ok(())
+}
+
+#[test_log::test]
+fn test_derived_keyring_agrees_across_keyrings_without_a_sidecar() -> anyhow::Result<()> {
+    let temp = tempfile::TempDir::new()?;
+    let db_path = test_db_path(&temp, "derived.db");
+
+    // Each keyring stands in for a separate process with the passphrase.
+    let open = || {
+        let keyring = Keyring::new(Arc::new(DeviceKeyProvider::from_passphrase("derived")));
+        keyring.set_derived_path(&db_path);
+        keyring
+    };
+    let (a, b) = (open(), open());
+    for scope in [KeyScope::Database, KeyScope::Table("t".into())] {
+        assert_eq!(a.dek_for(&scope)?.as_bytes(), b.dek_for(&scope)?.as_bytes());
+    }
+    assert_ne!(
+        a.dek_for(&KeyScope::Database)?.as_bytes(),
+        a.dek_for(&KeyScope::Table("t".into()))?.as_bytes()
+    );
+    assert!(a.scopes().is_empty(), "derived DEKs are never persisted");
+    assert!(!db_path.with_extension("evfs-keyring").exists());
+
+    let err = a
+        .shred_scope(&KeyScope::Table("t".into()), None, Enforce::Error)
+        .unwrap_err();
+    assert!(err.to_string().contains("derived"), "{err}");
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use rusqlite::Connection;
+use sqlevfs::policy::{
+    Enforce,
+    JournalModeFallback,
+    JournalModePolicy,
+    StoragePolicy,
+    TempStoreFallback,
+    TempStorePolicy,
+    apply_storage_policy,
+    apply_storage_policy_plan,
+};
+use tempfile::TempDir;
+
+use crate::common::{sqlite_api_is_available, test_db_path};
+
+#[test_log::test]
+fn test_policy_plan_matches_applied_decisions() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    let policy = StoragePolicy {
+        journal_mode: JournalModePolicy::DeleteOnlyIfRamdisk {
+            fallback: JournalModeFallback::Memory,
+        },
+        temp_store: TempStorePolicy::FileOnlyIfRamdisk {
+            fallback: TempStoreFallback::Memory,
+        },
+        enforce: Enforce::Warn,
+        secure_delete: false,
+        temp_dir_override: None,
+    };
+
+    let mut dirs = vec![TempDir::new_in(env!("CARGO_TARGET_TMPDIR"))?];
+    if std::path::Path::new("/dev/shm").is_dir() {
+        dirs.push(TempDir::new_in("/dev/shm")?);
+    }
+
+    for dir in &dirs {
+        let db_path = test_db_path(dir, "policy.db");
+        let conn = Connection::open(&db_path)?;
+
+        let plan = apply_storage_policy_plan(&conn, &db_path, &policy)?;
+        let journal_before: String = conn.query_row("PRAGMA journal_mode", [], |r| r.get(0))?;
+        assert_eq!(
+            journal_before.to_lowercase(),
+            "delete",
+            "plan must not run PRAGMAs"
+        );
+
+        let applied = apply_storage_policy(&conn, &db_path, &policy)?;
+        assert_eq!(plan.db_dir_fstype, applied.db_dir_fstype);
+        assert_eq!(plan.applied_journal_mode, applied.applied_journal_mode);
+        assert_eq!(plan.applied_temp_store, applied.applied_temp_store);
+        assert!(plan.notes.iter().any(|n| n.starts_with("would apply")));
+    }
+    Ok(())
+}
+
+#[test_log::test]
+fn test_temp_dir_override_allows_file_temp_store_on_ramdisk() -> anyhow::Result<()> {
+    if !sqlite_api_is_available() {
+        eprintln!("skipping: sqlite extension API pointers are not initialized in this build");
+        return Ok(());
+    }
+    if !std::path::Path::new("/dev/shm").is_dir() {
+        eprintln!("skipping: no /dev/shm ramdisk");
+        return Ok(());
+    }
+    let ramdisk = TempDir::new_in("/dev/shm")?;
+    let policy = StoragePolicy {
+        temp_store: TempStorePolicy::FileOnlyIfRamdisk {
+            fallback: TempStoreFallback::Memory,
+        },
+        enforce: Enforce::Error,
+        temp_dir_override: Some(ramdisk.path().to_path_buf()),
+        ..StoragePolicy::default()
+    };
+
+    let dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"))?;
+    let db_path = test_db_path(&dir, "policy.db");
+    let conn = Connection::open(&db_path)?;
+    let report = apply_storage_policy(&conn, &db_path, &policy)?;
+
+    assert_eq!(report.temp_dir, ramdisk.path().canonicalize()?);
+    assert_eq!(report.applied_temp_store.as_deref(), Some("FILE"));
+    let temp_store: i64 = conn.query_row("PRAGMA temp_store", [], |r| r.get(0))?;
+    assert_eq!(temp_store, 1);
+    Ok(())
+}
@@ -8,9 +8,11 @@ use sqlevfs::vfs::consensus::{handle::RaftHandle, wal::WalRecord};
 
 use crate::common::{sqlite_api_is_available, wait_until};
 
+type AppliedFrames = Arc<Mutex<Vec<(i64, u32, Vec<u8>)>>>;
+
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn test_raft_replication_single_node_applies_committed_frame() -> anyhow::Result<()> {
-    let applied: Arc<Mutex<Vec<(i64, u32, Vec<u8>)>>> = Arc::new(Mutex::new(Vec::new()));
+    let applied: AppliedFrames = Arc::new(Mutex::new(Vec::new()));
     let applied_clone = applied.clone();
 
     let node = RaftHandle::start(
@@ -58,7 +60,7 @@ async fn test_raft_replication_single_node_applies_committed_frame() -> anyhow::
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn test_raft_replication_single_node_commits_in_order() -> anyhow::Result<()> {
-    let applied: Arc<Mutex<Vec<(i64, u32, Vec<u8>)>>> = Arc::new(Mutex::new(Vec::new()));
+    let applied: AppliedFrames = Arc::new(Mutex::new(Vec::new()));
     let applied_clone = applied.clone();
 
     let node = RaftHandle::start(
@@ -109,7 +111,7 @@ async fn test_raft_replication_single_node_commits_in_order() -> anyhow::Result<
 async fn test_raft_replication_from_one_instance_to_another() -> anyhow::Result<()> {
     use tokio::sync::mpsc;
 
-    let replica_applied: Arc<Mutex<Vec<(i64, u32, Vec<u8>)>>> = Arc::new(Mutex::new(Vec::new()));
+    let replica_applied: AppliedFrames = Arc::new(Mutex::new(Vec::new()));
     let replica_applied_clone = replica_applied.clone();
 
     let replica = RaftHandle::start(
@@ -188,8 +190,10 @@ async fn test_raft_replication_from_one_instance_to_another() -> anyhow::Result<
     })
     .await?;
 
-    let replica_applied = replica_applied.lock().expect("replica apply lock poisoned");
-    assert_eq!(replica_applied.as_slice(), &writes);
+    {
+        let replica_applied = replica_applied.lock().expect("replica apply lock poisoned");
+        assert_eq!(replica_applied.as_slice(), &writes);
+    }
 
     bridge.abort();
     let _ = bridge.await;
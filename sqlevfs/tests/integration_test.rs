@@ -21,3 +21,6 @@ mod keyring;
 
 #[path = "integration/backup.rs"]
 mod backup;
+
+#[path = "integration/policy.rs"]
+mod policy;
@@ -1,5 +1,20 @@
+use thiserror::Error;
+
 use crate::context::sec_ctx::SecurityContext;
 
+/// Pushes allowed on top of the base context unless `sec_meta` says otherwise.
+pub const DEFAULT_MAX_DEPTH: i64 = 64;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ContextStackError {
+    #[error("context stack overflow (max depth {0})")]
+    Overflow(i64),
+    #[error("context stack underflow")]
+    Underflow,
+    #[error("no context named '{0}' on the stack")]
+    NotFound(String),
+}
+
 #[derive(Debug, Clone)]
 pub struct ContextStack {
     stack: Vec<(Option<String>, SecurityContext)>, // (optional name, context)
@@ -35,6 +50,31 @@ impl ContextStack {
         }
     }
 
+    /// Number of contexts pushed on top of the base context.
+    pub fn depth(&self) -> usize {
+        self.stack.len() - 1
+    }
+
+    /// Push, refusing to grow past `max_depth` pushed contexts.
+    pub fn try_push(
+        &mut self,
+        name: Option<String>,
+        max_depth: i64,
+    ) -> Result<(), ContextStackError> {
+        if self.depth() as i64 >= max_depth {
+            return Err(ContextStackError::Overflow(max_depth));
+        }
+        self.push(name);
+        Ok(())
+    }
+
+    /// Pop, reporting an attempt to pop the base context as an underflow.
+    pub fn try_pop(&mut self) -> Result<SecurityContext, ContextStackError> {
+        self.pop()
+            .map(|(_, ctx)| ctx)
+            .ok_or(ContextStackError::Underflow)
+    }
+
     pub fn push_named(&mut self, name: &str) {
         self.push(Some(name.to_string()));
     }
@@ -51,6 +91,12 @@ impl ContextStack {
         }
     }
 
+    /// [`Self::pop_named`], reporting a name not on the stack as an error.
+    pub fn try_pop_named(&mut self, name: &str) -> Result<SecurityContext, ContextStackError> {
+        self.pop_named(name)
+            .ok_or_else(|| ContextStackError::NotFound(name.to_string()))
+    }
+
     /// Context used for access checks
     pub fn effective(&self) -> &SecurityContext {
         &self.stack.last().unwrap().1
@@ -115,6 +161,39 @@ mod tests {
         assert_eq!(stack.stack.len(), 1);
     }
 
+    #[test]
+    fn try_push_stops_at_max_depth() {
+        let mut stack = ContextStack::default();
+
+        assert!(stack.try_push(None, 2).is_ok());
+        assert!(stack.try_push(None, 2).is_ok());
+        assert_eq!(stack.try_push(None, 2), Err(ContextStackError::Overflow(2)));
+        assert_eq!(stack.depth(), 2);
+    }
+
+    #[test]
+    fn try_pop_reports_underflow_on_base() {
+        let mut stack = ContextStack::default();
+
+        stack.push(None);
+        assert!(stack.try_pop().is_ok());
+        assert_eq!(stack.try_pop(), Err(ContextStackError::Underflow));
+        assert_eq!(stack.depth(), 0);
+    }
+
+    #[test]
+    fn try_pop_named_reports_a_missing_name() {
+        let mut stack = ContextStack::default();
+
+        stack.push_named("import");
+        assert!(stack.try_pop_named("import").is_ok());
+        assert_eq!(
+            stack.try_pop_named("import"),
+            Err(ContextStackError::NotFound("import".to_string()))
+        );
+        assert_eq!(stack.depth(), 0);
+    }
+
     #[test]
     fn push_named_sets_name_correctly() {
         let mut stack = ContextStack::default();
@@ -8,7 +8,9 @@ use parking_lot::Mutex;
 
 use crate::context::{sec_ctx::SecurityContext, ctx_stack::ContextStack};
 
-/// Global map: db handle address -> SecurityContext
+/// Global map: db handle address -> SecurityContext. Each `sqlite3*`
+/// connection has its own entry, even when several share a database file;
+/// it is dropped when the connection closes (see [`release_context`]).
 pub static CONTEXTS: Lazy<Mutex<HashMap<usize, ContextStack>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
@@ -24,3 +26,15 @@ pub fn set_context_stack(db_ptr: usize, ctx: ContextStack) {
 pub fn effective_context(db_ptr: usize) -> SecurityContext {
     get_context_stack(db_ptr).effective().clone()
 }
+
+/// Run `f` against a connection's effective context in place, for per-row
+/// callers that can't afford a copy. `f` must not touch `CONTEXTS`.
+pub fn with_effective_context<R>(db_ptr: usize, f: impl FnOnce(&SecurityContext) -> R) -> R {
+    f(CONTEXTS.lock().entry(db_ptr).or_default().effective())
+}
+
+/// Forget the context of a closing connection, so a later connection that
+/// reuses the same handle address starts empty.
+pub fn release_context(db_ptr: usize) {
+    CONTEXTS.lock().remove(&db_ptr);
+}
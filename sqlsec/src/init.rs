@@ -2,12 +2,37 @@ use std::mem::forget;
 
 use rusqlite::{Connection, Result, ffi::sqlite3};
 
-use crate::register::register_functions_ffi;
+use crate::{
+    context::effective_context,
+    register::register_functions_ffi,
+    strict::{MARKER, enable_strict, is_compromised, strict_mode_requested},
+    views::refresh_views::refresh_views,
+};
 
-/// Initialize the database objects when extension loads via FFI.
-pub(crate) unsafe fn init_extension_ffi(db: *mut sqlite3) -> Result<()> {
-    let conn = unsafe { Connection::from_handle(db) }?;
+/// Whether a registered table is missing its TEMP view on this connection.
+///
+/// Secure views live in the temp schema, so a fresh connection starts without
+/// them even though `sec_meta` may claim the last refresh is current.
+fn views_missing(conn: &Connection) -> Result<bool> {
+    conn.query_row(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM sec_tables
+            WHERE logical_name NOT IN (
+                SELECT name FROM temp.sqlite_master WHERE type = 'view'
+            )
+        )
+        "#,
+        [],
+        |row| row.get(0),
+    )
+}
 
+/// Create the `sec_*` metadata tables and strict mode's marker and seed
+/// `sec_meta`, leaving any that already exist untouched. Run on every
+/// extension load, so a blank database is ready for its first
+/// `REGISTER SECURE TABLE`.
+pub fn sec_init_schema(conn: &Connection) -> Result<()> {
     conn.execute_batch(
         r#"
         CREATE TABLE IF NOT EXISTS sec_labels (
@@ -15,6 +40,11 @@ pub(crate) unsafe fn init_extension_ffi(db: *mut sqlite3) -> Result<()> {
             expr TEXT NOT NULL UNIQUE
         );
 
+        CREATE TABLE IF NOT EXISTS sec_label_names (
+            name     TEXT PRIMARY KEY,
+            label_id INTEGER NOT NULL REFERENCES sec_labels(id)
+        );
+
         CREATE TABLE IF NOT EXISTS sec_levels (
             attr_name   TEXT NOT NULL,
             level_name  TEXT NOT NULL,
@@ -46,14 +76,54 @@ pub(crate) unsafe fn init_extension_ffi(db: *mut sqlite3) -> Result<()> {
         INSERT OR IGNORE INTO sec_meta VALUES ('generation', 0);
         INSERT OR IGNORE INTO sec_meta VALUES ('last_refresh_generation', 0);
         INSERT OR IGNORE INTO sec_meta VALUES ('views_initialized', 0);
+        INSERT OR IGNORE INTO sec_meta VALUES ('max_context_depth', 64);
+        INSERT OR IGNORE INTO sec_meta VALUES ('enforce_policies', 0);
         "#,
     )?;
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {MARKER} (id INTEGER PRIMARY KEY);"
+    ))
+}
 
-    // Ensure we don’t close SQLite’s internal handle
-    forget(conn);
+/// Initialize the database objects when extension loads via FFI.
+pub(crate) unsafe fn init_extension_ffi(db: *mut sqlite3) -> Result<()> {
+    let mut conn = unsafe { Connection::from_handle(db) }?;
+
+    // Checked before the schema is (re)created, which would paper over
+    // missing tables. A connection that has already failed closed may not
+    // read the metadata to seed it, so it is left as found.
+    if strict_mode_requested() {
+        enable_strict(&conn)?;
+    }
+
+    let compromised = is_compromised(db as usize);
+    if !compromised {
+        sec_init_schema(&conn)?;
+    }
 
     // Register scalar functions
     register_functions_ffi(db);
 
-    Ok(())
+    // Rebuild views dropped with a previous connection's temp schema. The
+    // generation is invalidated first so that, should the rebuild fail, the
+    // write triggers' refresh guard still fires instead of trusting sec_meta.
+    let result = if compromised {
+        Ok(())
+    } else {
+        views_missing(&conn).and_then(|missing| {
+            if missing {
+                conn.execute(
+                    "UPDATE sec_meta SET value = -1 WHERE key = 'last_refresh_generation'",
+                    [],
+                )?;
+                let _ = refresh_views(&mut conn, &effective_context(db as usize));
+            }
+            Ok(())
+        })
+    };
+
+    // Ensure we don’t close SQLite’s internal handle
+    forget(conn);
+
+    result
 }
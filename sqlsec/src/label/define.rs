@@ -1,6 +1,6 @@
-use std::mem::forget;
+use std::{io::ErrorKind, mem::forget, sync::Arc};
 
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, Error, OptionalExtension, Result};
 
 use crate::label::{LABEL_CACHE, parse::parse};
 
@@ -16,7 +16,7 @@ pub fn define_label(conn: &Connection, expr: &str) -> Result<i64> {
     })?;
 
     if let Ok(label) = parse(expr) {
-        LABEL_CACHE.lock().insert(id, label);
+        LABEL_CACHE.lock().insert(id, Arc::new(label));
     }
 
     Ok(id)
@@ -29,3 +29,59 @@ pub fn define_label_raw(db_ptr: usize, expr: &str) -> Result<i64> {
     forget(conn);
     result
 }
+
+/// Define a label and bind `name` to it. Rebinding a name to another
+/// expression is refused: tables registered under the name keep the label
+/// id it resolved to at the time.
+pub fn define_named_label(conn: &Connection, name: &str, expr: &str) -> Result<i64> {
+    let bound: Option<String> = conn
+        .query_row(
+            r#"
+            SELECT l.expr FROM sec_label_names n
+            JOIN sec_labels l ON l.id = n.label_id
+            WHERE n.name = ?1
+            "#,
+            [name],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(other) = bound.filter(|other| other != expr) {
+        return Err(Error::UserFunctionError(Box::new(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("label {name} is already defined as '{other}'"),
+        ))));
+    }
+
+    let id = define_label(conn, expr)?;
+    conn.execute(
+        "INSERT OR IGNORE INTO sec_label_names (name, label_id) VALUES (?1, ?2)",
+        rusqlite::params![name, id],
+    )?;
+    Ok(id)
+}
+
+/// Define a named label from raw db pointer (for FFI)
+pub fn define_named_label_raw(db_ptr: usize, name: &str, expr: &str) -> Result<i64> {
+    let conn = unsafe { Connection::from_handle(db_ptr as *mut _)? };
+    let result = define_named_label(&conn, name, expr);
+    forget(conn);
+    result
+}
+
+/// The id of the label bound to `name`, if any.
+pub fn named_label_id(conn: &Connection, name: &str) -> Result<Option<i64>> {
+    conn.query_row(
+        "SELECT label_id FROM sec_label_names WHERE name = ?1",
+        [name],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Look up a named label from raw db pointer (for FFI)
+pub fn named_label_id_raw(db_ptr: usize, name: &str) -> Result<Option<i64>> {
+    let conn = unsafe { Connection::from_handle(db_ptr as *mut _)? };
+    let result = named_label_id(&conn, name);
+    forget(conn);
+    result
+}
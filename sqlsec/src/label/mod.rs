@@ -1,10 +1,14 @@
-use std::{collections::HashMap, sync::LazyLock};
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock},
+};
 
 use parking_lot::Mutex;
 
 pub mod define;
 pub mod evaluate;
 pub mod parse;
+pub mod validate;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompareOp {
@@ -30,8 +34,9 @@ pub struct Label {
     pub always_true: bool,
 }
 
-// Cache: label_id -> Label
-pub static LABEL_CACHE: LazyLock<Mutex<HashMap<i64, Label>>> =
+// Cache: label_id -> Label, parsed once and shared so per-row checks
+// don't copy it
+pub static LABEL_CACHE: LazyLock<Mutex<HashMap<i64, Arc<Label>>>> =
     LazyLock::new(|| Mutex::new(HashMap::new()));
 
 // Cache: attr_name -> (level_name -> level_value)
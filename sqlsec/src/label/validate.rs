@@ -0,0 +1,223 @@
+use thiserror::Error;
+
+/// Why a label expression was rejected. Columns are 1-based character
+/// positions in the expression as written.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LabelError {
+    #[error("label expression is empty")]
+    Empty,
+    #[error("unbalanced parenthesis at column {column}")]
+    UnbalancedParen { column: usize },
+    #[error("missing attribute name at column {column}")]
+    EmptyAttribute { column: usize },
+    #[error("missing value at column {column}")]
+    EmptyValue { column: usize },
+    #[error("unknown operator '{found}' at column {column}, expected one of = < <= > >=")]
+    UnknownOperator { column: usize, found: String },
+    #[error("unexpected '{found}' at column {column}")]
+    Unexpected { column: usize, found: String },
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+struct Cursor<'a> {
+    expr: &'a str,
+    pos: usize,
+}
+
+impl Cursor<'_> {
+    fn peek(&self) -> Option<char> {
+        self.expr[self.pos..].chars().next()
+    }
+
+    fn column(&self) -> usize {
+        self.expr[..self.pos].chars().count() + 1
+    }
+
+    fn found(&self) -> String {
+        self.peek()
+            .map_or_else(|| "end of expression".to_string(), String::from)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            return true;
+        }
+        false
+    }
+
+    fn ident(&mut self) -> &str {
+        let start = self.pos;
+        while let Some(c) = self.peek().filter(|c| is_ident_char(*c)) {
+            self.pos += c.len_utf8();
+        }
+        &self.expr[start..self.pos]
+    }
+
+    fn compare_op(&mut self) -> Result<(), LabelError> {
+        for op in [">=", "<=", ">", "<", "="] {
+            if self.expr[self.pos..].starts_with(op) {
+                self.pos += op.len();
+                return Ok(());
+            }
+        }
+        Err(LabelError::UnknownOperator {
+            column: self.column(),
+            found: self.found(),
+        })
+    }
+
+    /// `attr op value`
+    fn attr_req(&mut self) -> Result<(), LabelError> {
+        if self.ident().is_empty() {
+            return Err(match self.peek() {
+                Some('(' | ')') => LabelError::UnbalancedParen {
+                    column: self.column(),
+                },
+                _ => LabelError::EmptyAttribute {
+                    column: self.column(),
+                },
+            });
+        }
+        self.compare_op()?;
+        if self.ident().is_empty() {
+            return Err(LabelError::EmptyValue {
+                column: self.column(),
+            });
+        }
+        Ok(())
+    }
+
+    /// `attr_req` or `(attr_req|attr_req|...)`
+    fn clause(&mut self) -> Result<(), LabelError> {
+        let open = self.column();
+        if !self.eat('(') {
+            return self.attr_req();
+        }
+        loop {
+            self.attr_req()?;
+            if self.eat(')') {
+                return Ok(());
+            }
+            if !self.eat('|') {
+                return Err(match self.peek() {
+                    None | Some('&') => LabelError::UnbalancedParen { column: open },
+                    _ => self.unexpected(),
+                });
+            }
+        }
+    }
+
+    fn unexpected(&self) -> LabelError {
+        match self.peek() {
+            Some(')') => LabelError::UnbalancedParen {
+                column: self.column(),
+            },
+            Some(c) if !is_ident_char(c) && !"&|()".contains(c) => LabelError::UnknownOperator {
+                column: self.column(),
+                found: self.found(),
+            },
+            _ => LabelError::Unexpected {
+                column: self.column(),
+                found: self.found(),
+            },
+        }
+    }
+}
+
+/// Check that `expr` is a well-formed label: `true`, or `&`-joined
+/// clauses, each an `attr op value` requirement or a parenthesised
+/// `|`-list of them. Accepts exactly what [`parse`](super::parse::parse)
+/// does, but reports where the expression goes wrong.
+pub fn validate_label_expr(expr: &str) -> Result<(), LabelError> {
+    let trimmed = expr.trim();
+    if trimmed.is_empty() {
+        return Err(LabelError::Empty);
+    }
+    if trimmed == "true" {
+        return Ok(());
+    }
+
+    let mut cursor = Cursor {
+        expr: expr.trim_end(),
+        pos: expr.len() - expr.trim_start().len(),
+    };
+    loop {
+        cursor.clause()?;
+        if cursor.peek().is_none() {
+            return Ok(());
+        }
+        if !cursor.eat('&') {
+            return Err(cursor.unexpected());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::label::parse::parse;
+
+    #[test]
+    fn accepts_what_parse_accepts() {
+        for expr in [
+            "true",
+            " role=admin ",
+            "clearance>=secret",
+            "role=admin&team=finance",
+            "(role=admin|role=auditor)&clearance>=confidential",
+        ] {
+            assert_eq!(validate_label_expr(expr), Ok(()), "{expr}");
+            assert!(parse(expr).is_ok(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn rejects_with_position() {
+        let cases = [
+            ("", LabelError::Empty),
+            (
+                "role=admin&(team=finance",
+                LabelError::UnbalancedParen { column: 12 },
+            ),
+            ("role=admin)", LabelError::UnbalancedParen { column: 11 }),
+            ("=admin", LabelError::EmptyAttribute { column: 1 }),
+            ("role=admin&", LabelError::EmptyAttribute { column: 12 }),
+            ("role=", LabelError::EmptyValue { column: 6 }),
+            (
+                "role!=admin",
+                LabelError::UnknownOperator {
+                    column: 5,
+                    found: "!".into(),
+                },
+            ),
+            (
+                "role=admin^team=x",
+                LabelError::UnknownOperator {
+                    column: 11,
+                    found: "^".into(),
+                },
+            ),
+            (
+                "role=a|role=b",
+                LabelError::Unexpected {
+                    column: 7,
+                    found: "|".into(),
+                },
+            ),
+        ];
+        for (expr, err) in cases {
+            assert_eq!(validate_label_expr(expr), Err(err), "{expr}");
+            assert!(parse(expr).is_err(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn error_messages_name_the_column() {
+        let err = validate_label_expr("role=admin&(team=finance").unwrap_err();
+        assert_eq!(err.to_string(), "unbalanced parenthesis at column 12");
+    }
+}
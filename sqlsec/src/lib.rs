@@ -2,6 +2,7 @@ pub mod context;
 pub mod init;
 pub mod label;
 pub mod register;
+pub mod strict;
 pub mod views;
 
 use std::{
@@ -48,7 +49,7 @@ pub unsafe extern "C" fn sqlite3_sqlsec_init(
 /// Set the SQLite extension error message.
 ///
 /// Allocates a C string using `sqlite3_malloc` and writes its pointer to `pz_err_msg`.
-fn set_err_message(pz_err_msg: *mut *mut c_char, msg: &str) {
+pub(crate) fn set_err_message(pz_err_msg: *mut *mut c_char, msg: &str) {
     unsafe {
         if pz_err_msg.is_null() {
             return;
@@ -1,16 +1,24 @@
 use std::ffi::c_int;
 
 use rusqlite::ffi::{
+    SQLITE_SCHEMA,
     SQLITE_UTF8,
     sqlite3,
     sqlite3_context,
     sqlite3_context_db_handle,
     sqlite3_create_function_v2,
+    sqlite3_next_stmt,
+    sqlite3_result_error_code,
     sqlite3_result_int,
+    sqlite3_stmt_busy,
+    sqlite3_stmt_readonly,
     sqlite3_value,
 };
 
-use crate::register::{Sqlite3FunctionV2, sqlite_error};
+use crate::{
+    register::{Sqlite3FunctionV2, sqlite_error},
+    views::refresh_views::{refresh_views_if_stale_raw, views_stale},
+};
 
 pub struct AssertFresh;
 
@@ -32,6 +40,23 @@ impl Sqlite3FunctionV2 for AssertFresh {
     }
 }
 
+/// Whether any statement on this connection is mid-way through a write.
+///
+/// A refresh run underneath a writer would be rolled back along with it, so
+/// the lazy path is only taken for read-only statements.
+unsafe fn write_in_progress(db: *mut sqlite3) -> bool {
+    unsafe {
+        let mut stmt = sqlite3_next_stmt(db, std::ptr::null_mut());
+        while !stmt.is_null() {
+            if sqlite3_stmt_busy(stmt) != 0 && sqlite3_stmt_readonly(stmt) == 0 {
+                return true;
+            }
+            stmt = sqlite3_next_stmt(db, stmt);
+        }
+        false
+    }
+}
+
 pub(crate) extern "C" fn ffi_sec_assert_fresh(
     ctx: *mut sqlite3_context,
     argc: c_int,
@@ -43,8 +68,8 @@ pub(crate) extern "C" fn ffi_sec_assert_fresh(
             return;
         }
 
-        let db_ptr = sqlite3_context_db_handle(ctx) as usize;
-        let conn = match rusqlite::Connection::from_handle(db_ptr as *mut _) {
+        let db = sqlite3_context_db_handle(ctx);
+        let conn = match rusqlite::Connection::from_handle(db) {
             Ok(c) => c,
             Err(e) => {
                 sqlite_error(ctx, "assert_fresh", e);
@@ -52,32 +77,34 @@ pub(crate) extern "C" fn ffi_sec_assert_fresh(
             }
         };
 
-        let generation: i64 = conn
-            .query_row(
-                "SELECT value FROM sec_meta WHERE key = 'generation'",
-                [],
-                |r| r.get(0),
-            )
-            .unwrap_or(0);
-
-        let last_refresh: i64 = conn
-            .query_row(
-                "SELECT value FROM sec_meta WHERE key = 'last_refresh_generation'",
-                [],
-                |r| r.get(0),
-            )
-            .unwrap_or(-1);
+        let stale = views_stale(&conn).unwrap_or(true);
 
         std::mem::forget(conn);
 
-        if generation != last_refresh {
+        if !stale {
+            sqlite3_result_int(ctx, 1);
+            return;
+        }
+
+        if write_in_progress(db) {
             sqlite_error(
                 ctx,
                 "assert_fresh",
                 "security views are stale: call sec_refresh_views()",
             );
-        } else {
-            sqlite3_result_int(ctx, 1);
+            return;
+        }
+
+        // Rebuild the views, then report SQLITE_SCHEMA so sqlite3_step
+        // re-prepares the running statement against the fresh definitions.
+        match refresh_views_if_stale_raw(db as usize) {
+            Ok(_) => {
+                sqlite_error(ctx, "assert_fresh", "security views refreshed");
+                sqlite3_result_error_code(ctx, SQLITE_SCHEMA);
+            }
+            Err(e) => {
+                sqlite_error(ctx, "assert_fresh", e);
+            }
         }
     }
 }
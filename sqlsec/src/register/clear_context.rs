@@ -1,4 +1,4 @@
-use std::ffi::c_int;
+use std::ffi::{c_int, c_void};
 
 use rusqlite::ffi::{
     SQLITE_UTF8,
@@ -11,14 +11,18 @@ use rusqlite::ffi::{
 };
 
 use crate::{
-    context::{ctx_stack::ContextStack, set_context_stack},
+    context::{ctx_stack::ContextStack, release_context, set_context_stack},
     register::{Sqlite3FunctionV2, sqlite_error},
+    strict::release_strict,
     views::bump_generation::bump_generation_raw,
 };
 
 pub struct ClearContext;
 
 impl Sqlite3FunctionV2 for ClearContext {
+    /// SQLite runs the destructor when the connection closes (or the
+    /// extension is loaded into it again), which tears down its context and
+    /// strict-mode state.
     fn register(db: *mut sqlite3) {
         unsafe {
             sqlite3_create_function_v2(
@@ -26,16 +30,21 @@ impl Sqlite3FunctionV2 for ClearContext {
                 c"sec_clear_context".as_ptr(),
                 0,
                 SQLITE_UTF8,
-                std::ptr::null_mut(),
+                db as *mut c_void,
                 Some(ffi_sec_clear_context),
                 None,
                 None,
-                None,
+                Some(ffi_release_context),
             );
         }
     }
 }
 
+extern "C" fn ffi_release_context(db: *mut c_void) {
+    release_context(db as usize);
+    release_strict(db as usize);
+}
+
 pub(crate) extern "C" fn ffi_sec_clear_context(
     ctx: *mut sqlite3_context,
     argc: c_int,
@@ -12,7 +12,7 @@ use rusqlite::ffi::{
 };
 
 use crate::{
-    label::{define::define_label_raw, parse::parse},
+    label::{define::define_label_raw, parse::parse, validate::validate_label_expr},
     register::{Sqlite3FunctionV2, sqlite_error},
 };
 
@@ -55,7 +55,14 @@ pub(crate) extern "C" fn ffi_sec_define_label(
 
         let expr = CStr::from_ptr(expr_ptr as *const c_char).to_string_lossy();
 
-        // Validate parse
+        if let Err(e) = validate_label_expr(&expr) {
+            sqlite_error(
+                ctx,
+                "define_label",
+                format!("invalid label expression: {e}"),
+            );
+            return;
+        }
         if parse(&expr).is_err() {
             sqlite_error(ctx, "define_label", "invalid label expression");
             return;
@@ -1,10 +1,13 @@
 use std::{
     ffi::{CStr, c_char, c_int},
+    io::ErrorKind,
     mem::forget,
 };
 
 use rusqlite::{
     Connection,
+    Error,
+    OptionalExtension,
     Result,
     ffi::{
         SQLITE_NULL,
@@ -86,7 +89,26 @@ pub(crate) extern "C" fn ffi_sec_define_level(
     }
 }
 
+/// Define (or redefine) a level. Two names may not share a value within one
+/// attribute, which would make them indistinguishable in comparisons.
 pub fn define_level(conn: &Connection, attr: &str, name: &str, value: i64) -> Result<i64> {
+    let clash: Option<String> = conn
+        .query_row(
+            r#"
+            SELECT level_name FROM sec_levels
+            WHERE attr_name = ?1 AND level_value = ?3 AND level_name != ?2
+            "#,
+            rusqlite::params![attr, name, value],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(other) = clash {
+        return Err(Error::UserFunctionError(Box::new(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("{attr} level {value} is already defined as '{other}'"),
+        ))));
+    }
+
     conn.execute(
         r#"
         INSERT OR REPLACE INTO sec_levels (attr_name, level_name, level_value)
@@ -0,0 +1,88 @@
+use std::ffi::{CStr, c_char, c_int};
+
+use rusqlite::ffi::{
+    SQLITE_UTF8,
+    sqlite3,
+    sqlite3_context,
+    sqlite3_context_db_handle,
+    sqlite3_create_function_v2,
+    sqlite3_result_int64,
+    sqlite3_value,
+    sqlite3_value_text,
+};
+
+use crate::{
+    label::{define::define_named_label_raw, parse::parse, validate::validate_label_expr},
+    register::{Sqlite3FunctionV2, sqlite_error},
+};
+
+pub struct DefineNamedLabel;
+
+impl Sqlite3FunctionV2 for DefineNamedLabel {
+    fn register(db: *mut sqlite3) {
+        unsafe {
+            sqlite3_create_function_v2(
+                db,
+                c"sec_define_named_label".as_ptr(),
+                2,
+                SQLITE_UTF8,
+                std::ptr::null_mut(),
+                Some(ffi_sec_define_named_label),
+                None,
+                None,
+                None,
+            );
+        }
+    }
+}
+
+/// Like `sec_define_label`, also binding a name that `sec_label_id` resolves.
+pub(crate) extern "C" fn ffi_sec_define_named_label(
+    ctx: *mut sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) {
+    unsafe {
+        if argc != 2 {
+            sqlite_error(ctx, "define_named_label", "expected 2 arguments");
+            return;
+        }
+
+        let name_ptr = sqlite3_value_text(*argv);
+        let expr_ptr = sqlite3_value_text(*argv.add(1));
+        if name_ptr.is_null() {
+            sqlite_error(ctx, "define_named_label", "NULL argument 1 'name'");
+            return;
+        }
+        if expr_ptr.is_null() {
+            sqlite_error(ctx, "define_named_label", "NULL argument 2 'expr'");
+            return;
+        }
+
+        let name = CStr::from_ptr(name_ptr as *const c_char).to_string_lossy();
+        let expr = CStr::from_ptr(expr_ptr as *const c_char).to_string_lossy();
+
+        if name.is_empty() {
+            sqlite_error(ctx, "define_named_label", "empty label name");
+            return;
+        }
+        if let Err(e) = validate_label_expr(&expr) {
+            sqlite_error(
+                ctx,
+                "define_named_label",
+                format!("invalid label expression: {e}"),
+            );
+            return;
+        }
+        if parse(&expr).is_err() {
+            sqlite_error(ctx, "define_named_label", "invalid label expression");
+            return;
+        }
+
+        let db_ptr = sqlite3_context_db_handle(ctx) as usize;
+        match define_named_label_raw(db_ptr, &name, &expr) {
+            Ok(id) => sqlite3_result_int64(ctx, id),
+            Err(e) => sqlite_error(ctx, "define_named_label", e),
+        }
+    }
+}
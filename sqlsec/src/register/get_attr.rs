@@ -0,0 +1,73 @@
+use std::ffi::{CStr, c_char, c_int};
+
+use rusqlite::ffi::{
+    SQLITE_TRANSIENT,
+    SQLITE_UTF8,
+    sqlite3,
+    sqlite3_context,
+    sqlite3_context_db_handle,
+    sqlite3_create_function_v2,
+    sqlite3_result_null,
+    sqlite3_result_text,
+    sqlite3_value,
+    sqlite3_value_text,
+};
+
+use crate::{
+    context::effective_context,
+    register::{Sqlite3FunctionV2, sqlite_error},
+};
+
+pub struct GetAttr;
+
+impl Sqlite3FunctionV2 for GetAttr {
+    fn register(db: *mut sqlite3) {
+        unsafe {
+            sqlite3_create_function_v2(
+                db,
+                c"sec_get_attr".as_ptr(),
+                1,
+                SQLITE_UTF8,
+                std::ptr::null_mut(),
+                Some(ffi_sec_get_attr),
+                None,
+                None,
+                None,
+            );
+        }
+    }
+}
+
+/// Returns the attribute's value, or NULL when it is unset or has several
+/// values (attributes are multi-valued, so there is no single answer).
+pub(crate) extern "C" fn ffi_sec_get_attr(
+    ctx: *mut sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) {
+    unsafe {
+        if argc != 1 {
+            sqlite_error(ctx, "get_attr", "expected 1 argument");
+            return;
+        }
+
+        let key = sqlite3_value_text(*argv);
+        if key.is_null() {
+            sqlite_error(ctx, "get_attr", "NULL argument 1 'key'");
+            return;
+        }
+        let key = CStr::from_ptr(key as *const c_char).to_string_lossy();
+
+        let db_ptr = sqlite3_context_db_handle(ctx) as usize;
+        let sec_ctx = effective_context(db_ptr);
+        match sec_ctx.get_attrs(&key).as_slice() {
+            [value] => sqlite3_result_text(
+                ctx,
+                value.as_ptr() as *const c_char,
+                value.len() as c_int,
+                SQLITE_TRANSIENT(),
+            ),
+            _ => sqlite3_result_null(ctx),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::ffi::{CStr, c_char, c_int};
+
+use rusqlite::ffi::{
+    SQLITE_UTF8,
+    sqlite3,
+    sqlite3_context,
+    sqlite3_context_db_handle,
+    sqlite3_create_function_v2,
+    sqlite3_result_int64,
+    sqlite3_value,
+    sqlite3_value_text,
+};
+
+use crate::{
+    label::define::named_label_id_raw,
+    register::{Sqlite3FunctionV2, sqlite_error},
+};
+
+pub struct LabelId;
+
+impl Sqlite3FunctionV2 for LabelId {
+    fn register(db: *mut sqlite3) {
+        unsafe {
+            sqlite3_create_function_v2(
+                db,
+                c"sec_label_id".as_ptr(),
+                1,
+                SQLITE_UTF8,
+                std::ptr::null_mut(),
+                Some(ffi_sec_label_id),
+                None,
+                None,
+                None,
+            );
+        }
+    }
+}
+
+/// Returns the id of a label defined with `sec_define_named_label`. An
+/// unknown name is an error rather than NULL, which would register a table
+/// or column with no label at all.
+pub(crate) extern "C" fn ffi_sec_label_id(
+    ctx: *mut sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) {
+    unsafe {
+        if argc != 1 {
+            sqlite_error(ctx, "label_id", "expected 1 argument");
+            return;
+        }
+
+        let name = sqlite3_value_text(*argv);
+        if name.is_null() {
+            sqlite_error(ctx, "label_id", "NULL argument 1 'name'");
+            return;
+        }
+        let name = CStr::from_ptr(name as *const c_char).to_string_lossy();
+
+        let db_ptr = sqlite3_context_db_handle(ctx) as usize;
+        match named_label_id_raw(db_ptr, &name) {
+            Ok(Some(id)) => sqlite3_result_int64(ctx, id),
+            Ok(None) => sqlite_error(ctx, "label_id", format!("unknown label {name}")),
+            Err(e) => sqlite_error(ctx, "label_id", e),
+        }
+    }
+}
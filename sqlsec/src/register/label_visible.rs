@@ -14,8 +14,8 @@ use rusqlite::ffi::{
 };
 
 use crate::{
-    context::effective_context,
-    label::evaluate::evaluate_by_id,
+    context::with_effective_context,
+    label::evaluate::label_by_id,
     register::{Sqlite3FunctionV2, sqlite_error},
 };
 
@@ -60,16 +60,15 @@ pub(crate) extern "C" fn ffi_sec_label_visible(
 
         let visible = match label_id {
             None => true,
-            Some(id) => {
-                let sec_ctx = effective_context(db_ptr);
-                match evaluate_by_id(db_ptr, id, &sec_ctx) {
-                    Ok(res) => res,
-                    Err(e) => {
-                        sqlite_error(ctx, "label_visible", e);
-                        return;
-                    }
+            // Called once per row: the label comes from the cache and the
+            // context is borrowed, so neither is copied.
+            Some(id) => match label_by_id(db_ptr, id) {
+                Ok(label) => with_effective_context(db_ptr, |sec_ctx| label.evaluate(sec_ctx)),
+                Err(e) => {
+                    sqlite_error(ctx, "label_visible", e);
+                    return;
                 }
-            }
+            },
         };
 
         sqlite3_result_int(ctx, if visible { 1 } else { 0 });
@@ -1,13 +1,19 @@
 pub mod assert_fresh;
+pub mod clear_attr;
 pub mod clear_context;
 pub mod define_label;
 pub mod define_level;
+pub mod define_named_label;
+pub mod get_attr;
+pub mod label_id;
 pub mod label_visible;
 pub mod pop_context;
 pub mod push_context;
 pub mod refresh_views;
 pub mod register_table;
 pub mod set_attr;
+pub mod show_context;
+pub mod tables_info;
 
 use std::{ffi::CString, fmt::Display};
 
@@ -15,15 +21,21 @@ use rusqlite::ffi::{sqlite3, sqlite3_context, sqlite3_result_error};
 
 use crate::register::{
     assert_fresh::AssertFresh,
+    clear_attr::ClearAttr,
     clear_context::ClearContext,
     define_label::DefineLabel,
     define_level::DefineLevel,
+    define_named_label::DefineNamedLabel,
+    get_attr::GetAttr,
+    label_id::LabelId,
     label_visible::LabelVisible,
     pop_context::PopContext,
     push_context::PushContext,
     refresh_views::RefreshViews,
     register_table::RegisterTable,
     set_attr::SetAttr,
+    show_context::ShowContext,
+    tables_info::TablesInfo,
 };
 
 fn sqlite_error(ctx: *mut sqlite3_context, prefix: &str, e: impl Display) {
@@ -40,13 +52,19 @@ trait Sqlite3FunctionV2 {
 /// Register all scalar functions using raw FFI
 pub(crate) fn register_functions_ffi(db: *mut sqlite3) {
     AssertFresh::register(db);
+    ClearAttr::register(db);
     ClearContext::register(db);
     DefineLabel::register(db);
+    DefineNamedLabel::register(db);
     DefineLevel::register(db);
+    GetAttr::register(db);
+    LabelId::register(db);
     PopContext::register(db);
     PushContext::register(db);
     RefreshViews::register(db);
     RegisterTable::register(db);
     LabelVisible::register(db);
     SetAttr::register(db);
+    ShowContext::register(db);
+    TablesInfo::register(db);
 }
@@ -1,4 +1,4 @@
-use std::ffi::c_int;
+use std::ffi::{CStr, c_char, c_int};
 
 use rusqlite::ffi::{
     SQLITE_UTF8,
@@ -8,6 +8,7 @@ use rusqlite::ffi::{
     sqlite3_create_function_v2,
     sqlite3_result_int64,
     sqlite3_value,
+    sqlite3_value_text,
 };
 
 use crate::{
@@ -24,7 +25,7 @@ impl Sqlite3FunctionV2 for PopContext {
             sqlite3_create_function_v2(
                 db,
                 c"sec_pop_context".as_ptr(),
-                0,
+                -1,
                 SQLITE_UTF8,
                 std::ptr::null_mut(),
                 Some(ffi_sec_pop_context),
@@ -36,17 +37,34 @@ impl Sqlite3FunctionV2 for PopContext {
     }
 }
 
+/// `sec_pop_context()` pops the top context; `sec_pop_context(name)` pops
+/// the one pushed under `name`.
 pub(crate) extern "C" fn ffi_sec_pop_context(
     ctx: *mut sqlite3_context,
-    _argc: c_int,
-    _argv: *mut *mut sqlite3_value,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
 ) {
     unsafe {
         let db_ptr = sqlite3_context_db_handle(ctx) as usize;
         let mut stack = get_context_stack(db_ptr);
 
-        if stack.pop().is_none() {
-            sqlite_error(ctx, "pop_context", "cannot pop base context");
+        let name = if argc == 1 {
+            let name_ptr = sqlite3_value_text(*argv);
+            if name_ptr.is_null() {
+                sqlite_error(ctx, "pop_context", "NULL argument 1 'name'");
+                return;
+            }
+            Some(CStr::from_ptr(name_ptr as *const c_char).to_string_lossy())
+        } else {
+            None
+        };
+
+        let popped = match name {
+            Some(name) => stack.try_pop_named(&name),
+            None => stack.try_pop(),
+        };
+        if let Err(e) = popped {
+            sqlite_error(ctx, "pop_context", e);
             return;
         }
         set_context_stack(db_ptr, stack);
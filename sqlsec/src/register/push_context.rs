@@ -1,18 +1,26 @@
-use std::ffi::{CStr, c_char, c_int};
+use std::{
+    ffi::{CStr, c_char, c_int},
+    mem::forget,
+};
 
-use rusqlite::ffi::{
-    SQLITE_UTF8,
-    sqlite3,
-    sqlite3_context,
-    sqlite3_context_db_handle,
-    sqlite3_create_function_v2,
-    sqlite3_result_int64,
-    sqlite3_value,
-    sqlite3_value_text,
+use rusqlite::{
+    Connection,
+    OptionalExtension,
+    Result,
+    ffi::{
+        SQLITE_UTF8,
+        sqlite3,
+        sqlite3_context,
+        sqlite3_context_db_handle,
+        sqlite3_create_function_v2,
+        sqlite3_result_int64,
+        sqlite3_value,
+        sqlite3_value_text,
+    },
 };
 
 use crate::{
-    context::{get_context_stack, set_context_stack},
+    context::{ctx_stack::DEFAULT_MAX_DEPTH, get_context_stack, set_context_stack},
     register::{Sqlite3FunctionV2, sqlite_error},
     views::bump_generation::bump_generation_raw,
 };
@@ -61,7 +69,17 @@ pub(crate) extern "C" fn ffi_sec_push_context(
             None
         };
 
-        stack.push(name);
+        let max_depth = match max_context_depth_raw(db_ptr) {
+            Ok(max_depth) => max_depth,
+            Err(e) => {
+                sqlite_error(ctx, "push_context", e);
+                return;
+            }
+        };
+        if let Err(e) = stack.try_push(name, max_depth) {
+            sqlite_error(ctx, "push_context", e);
+            return;
+        }
         set_context_stack(db_ptr, stack);
 
         match bump_generation_raw(db_ptr) {
@@ -72,3 +90,23 @@ pub(crate) extern "C" fn ffi_sec_push_context(
         }
     }
 }
+
+/// The `max_context_depth` setting in `sec_meta`, or the default if unset.
+pub fn max_context_depth(conn: &Connection) -> Result<i64> {
+    let depth = conn
+        .query_row(
+            "SELECT value FROM sec_meta WHERE key = 'max_context_depth'",
+            [],
+            |row| row.get::<_, Option<i64>>(0),
+        )
+        .optional()?
+        .flatten();
+    Ok(depth.unwrap_or(DEFAULT_MAX_DEPTH))
+}
+
+fn max_context_depth_raw(db_ptr: usize) -> Result<i64> {
+    let conn = unsafe { Connection::from_handle(db_ptr as *mut _)? };
+    let result = max_context_depth(&conn);
+    forget(conn);
+    result
+}
@@ -27,7 +27,7 @@ impl Sqlite3FunctionV2 for RegisterTable {
             sqlite3_create_function_v2(
                 db,
                 c"sec_register_table".as_ptr(),
-                5,
+                -1,
                 SQLITE_UTF8,
                 std::ptr::null_mut(),
                 Some(ffi_sec_register_table),
@@ -45,8 +45,8 @@ pub(crate) extern "C" fn ffi_sec_register_table(
     argv: *mut *mut sqlite3_value,
 ) {
     unsafe {
-        if argc != 5 {
-            sqlite_error(ctx, "register_table", "expected 5 arguments");
+        if argc != 5 && argc != 6 {
+            sqlite_error(ctx, "register_table", "expected 5 or 6 arguments");
             return;
         }
 
@@ -64,6 +64,10 @@ pub(crate) extern "C" fn ffi_sec_register_table(
         } else {
             Some(sqlite3_value_int64(*argv.add(4)))
         };
+        // Optional 6th argument; without it implicit labels stay allowed.
+        let allow_implicit_label = argc < 6
+            || sqlite3_value_type(*argv.add(5)) == SQLITE_NULL
+            || sqlite3_value_int64(*argv.add(5)) != 0;
 
         if logical_ptr.is_null() {
             sqlite_error(ctx, "register_table", "NULL argument 1 'logical'");
@@ -90,6 +94,7 @@ pub(crate) extern "C" fn ffi_sec_register_table(
             &row_col,
             table_label_id,
             insert_label_id,
+            allow_implicit_label,
         ) {
             Ok(_) => sqlite3_result_int(ctx, 1),
             Err(e) => {
@@ -0,0 +1,267 @@
+use std::{
+    collections::HashMap,
+    ffi::{c_char, c_int, c_void},
+    ptr,
+    sync::LazyLock,
+};
+
+use rusqlite::ffi::{
+    SQLITE_OK,
+    SQLITE_TRANSIENT,
+    sqlite3,
+    sqlite3_context,
+    sqlite3_create_module_v2,
+    sqlite3_declare_vtab,
+    sqlite3_index_info,
+    sqlite3_int64,
+    sqlite3_module,
+    sqlite3_result_int64,
+    sqlite3_result_null,
+    sqlite3_result_text,
+    sqlite3_value,
+    sqlite3_vtab,
+    sqlite3_vtab_cursor,
+};
+
+use crate::{
+    context::{effective_context, sec_ctx::SecurityContext},
+    label::LEVELS_CACHE,
+    register::Sqlite3FunctionV2,
+};
+
+/// `sec_context`: an eponymous table-valued function listing the effective
+/// context as `(key, value, level)` rows. `level` is the value's
+/// `sec_define_level` rank for that attribute, or NULL if it has none.
+pub struct ShowContext;
+
+impl Sqlite3FunctionV2 for ShowContext {
+    fn register(db: *mut sqlite3) {
+        unsafe {
+            sqlite3_create_module_v2(db, c"sec_context".as_ptr(), &*MODULE, ptr::null_mut(), None);
+        }
+    }
+}
+
+static MODULE: LazyLock<sqlite3_module> = LazyLock::new(|| sqlite3_module {
+    iVersion: 1,
+    // No xCreate: the table is eponymous-only and cannot be CREATE VIRTUAL TABLEd.
+    xCreate: None,
+    xConnect: Some(x_connect),
+    xBestIndex: Some(x_best_index),
+    xDisconnect: Some(x_disconnect),
+    xDestroy: Some(x_disconnect),
+    xOpen: Some(x_open),
+    xClose: Some(x_close),
+    xFilter: Some(x_filter),
+    xNext: Some(x_next),
+    xEof: Some(x_eof),
+    xColumn: Some(x_column),
+    xRowid: Some(x_rowid),
+    xUpdate: None,
+    xBegin: None,
+    xSync: None,
+    xCommit: None,
+    xRollback: None,
+    xFindFunction: None,
+    xRename: None,
+    xSavepoint: None,
+    xRelease: None,
+    xRollbackTo: None,
+    xShadowName: None,
+});
+
+#[repr(C)]
+struct ContextTable {
+    base: sqlite3_vtab,
+    db: *mut sqlite3,
+}
+
+#[repr(C)]
+struct ContextCursor {
+    base: sqlite3_vtab_cursor,
+    rows: Vec<ContextRow>,
+    pos: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ContextRow {
+    key: String,
+    value: String,
+    level: Option<i64>,
+}
+
+/// Flatten a context into sorted rows, resolving each value's level.
+fn context_rows(
+    ctx: &SecurityContext,
+    levels: &HashMap<String, HashMap<String, i64>>,
+) -> Vec<ContextRow> {
+    let mut rows: Vec<ContextRow> = ctx
+        .attrs
+        .iter()
+        .flat_map(|(key, values)| {
+            values.iter().map(move |value| ContextRow {
+                key: key.clone(),
+                value: value.clone(),
+                level: levels.get(key).and_then(|l| l.get(value)).copied(),
+            })
+        })
+        .collect();
+    rows.sort_by(|a, b| (&a.key, &a.value).cmp(&(&b.key, &b.value)));
+    rows
+}
+
+unsafe extern "C" fn x_connect(
+    db: *mut sqlite3,
+    _aux: *mut c_void,
+    _argc: c_int,
+    _argv: *const *const c_char,
+    pp_vtab: *mut *mut sqlite3_vtab,
+    _err: *mut *mut c_char,
+) -> c_int {
+    unsafe {
+        let rc = sqlite3_declare_vtab(
+            db,
+            c"CREATE TABLE x(key TEXT, value TEXT, level INTEGER)".as_ptr(),
+        );
+        if rc != SQLITE_OK {
+            return rc;
+        }
+        let table = Box::new(ContextTable {
+            base: std::mem::zeroed(),
+            db,
+        });
+        *pp_vtab = Box::into_raw(table) as *mut sqlite3_vtab;
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_best_index(
+    _vtab: *mut sqlite3_vtab,
+    info: *mut sqlite3_index_info,
+) -> c_int {
+    unsafe {
+        (*info).estimatedCost = 10.0;
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_disconnect(vtab: *mut sqlite3_vtab) -> c_int {
+    drop(unsafe { Box::from_raw(vtab as *mut ContextTable) });
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_open(
+    _vtab: *mut sqlite3_vtab,
+    pp_cursor: *mut *mut sqlite3_vtab_cursor,
+) -> c_int {
+    let cursor = Box::new(ContextCursor {
+        base: unsafe { std::mem::zeroed() },
+        rows: Vec::new(),
+        pos: 0,
+    });
+    unsafe {
+        *pp_cursor = Box::into_raw(cursor) as *mut sqlite3_vtab_cursor;
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_close(cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    drop(unsafe { Box::from_raw(cursor as *mut ContextCursor) });
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_filter(
+    cursor: *mut sqlite3_vtab_cursor,
+    _idx_num: c_int,
+    _idx_str: *const c_char,
+    _argc: c_int,
+    _argv: *mut *mut sqlite3_value,
+) -> c_int {
+    unsafe {
+        let cursor = &mut *(cursor as *mut ContextCursor);
+        let db = (*(cursor.base.pVtab as *mut ContextTable)).db;
+        cursor.rows = context_rows(&effective_context(db as usize), &LEVELS_CACHE.lock());
+        cursor.pos = 0;
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_next(cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    unsafe {
+        (*(cursor as *mut ContextCursor)).pos += 1;
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_eof(cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    let cursor = unsafe { &*(cursor as *mut ContextCursor) };
+    (cursor.pos >= cursor.rows.len()) as c_int
+}
+
+unsafe extern "C" fn x_column(
+    cursor: *mut sqlite3_vtab_cursor,
+    ctx: *mut sqlite3_context,
+    col: c_int,
+) -> c_int {
+    unsafe {
+        let cursor = &*(cursor as *mut ContextCursor);
+        let row = &cursor.rows[cursor.pos];
+        let text = match col {
+            0 => &row.key,
+            1 => &row.value,
+            _ => {
+                match row.level {
+                    Some(level) => sqlite3_result_int64(ctx, level),
+                    None => sqlite3_result_null(ctx),
+                }
+                return SQLITE_OK;
+            }
+        };
+        sqlite3_result_text(
+            ctx,
+            text.as_ptr() as *const c_char,
+            text.len() as c_int,
+            SQLITE_TRANSIENT(),
+        );
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_rowid(cursor: *mut sqlite3_vtab_cursor, rowid: *mut sqlite3_int64) -> c_int {
+    unsafe {
+        *rowid = (*(cursor as *mut ContextCursor)).pos as sqlite3_int64;
+    }
+    SQLITE_OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_are_sorted_and_carry_levels() {
+        let mut ctx = SecurityContext::default();
+        ctx.set_attr("team", "eng");
+        ctx.set_attr("clearance", "secret");
+        ctx.set_attr("role", "admin");
+
+        let levels = HashMap::from([(
+            "clearance".to_string(),
+            HashMap::from([("secret".to_string(), 2)]),
+        )]);
+
+        let rows = context_rows(&ctx, &levels);
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|r| (r.key.as_str(), r.value.as_str(), r.level))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("clearance", "secret", Some(2)),
+                ("role", "admin", None),
+                ("team", "eng", None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    ffi::{c_char, c_int, c_void},
+    ptr,
+    sync::LazyLock,
+};
+
+use rusqlite::{
+    Connection,
+    Result,
+    ffi::{
+        SQLITE_ERROR,
+        SQLITE_OK,
+        SQLITE_TRANSIENT,
+        sqlite3,
+        sqlite3_context,
+        sqlite3_create_module_v2,
+        sqlite3_declare_vtab,
+        sqlite3_index_info,
+        sqlite3_int64,
+        sqlite3_module,
+        sqlite3_result_int64,
+        sqlite3_result_null,
+        sqlite3_result_text,
+        sqlite3_value,
+        sqlite3_vtab,
+        sqlite3_vtab_cursor,
+    },
+};
+
+use crate::{register::Sqlite3FunctionV2, set_err_message};
+
+/// `sec_tables_info`: an eponymous table-valued function listing every
+/// registered table's `sec_tables` row. Strict mode's authorizer refuses it
+/// once the connection has failed closed.
+pub struct TablesInfo;
+
+impl Sqlite3FunctionV2 for TablesInfo {
+    fn register(db: *mut sqlite3) {
+        unsafe {
+            sqlite3_create_module_v2(
+                db,
+                c"sec_tables_info".as_ptr(),
+                &*MODULE,
+                ptr::null_mut(),
+                None,
+            );
+        }
+    }
+}
+
+static MODULE: LazyLock<sqlite3_module> = LazyLock::new(|| sqlite3_module {
+    iVersion: 1,
+    // No xCreate: the table is eponymous-only and cannot be CREATE VIRTUAL TABLEd.
+    xCreate: None,
+    xConnect: Some(x_connect),
+    xBestIndex: Some(x_best_index),
+    xDisconnect: Some(x_disconnect),
+    xDestroy: Some(x_disconnect),
+    xOpen: Some(x_open),
+    xClose: Some(x_close),
+    xFilter: Some(x_filter),
+    xNext: Some(x_next),
+    xEof: Some(x_eof),
+    xColumn: Some(x_column),
+    xRowid: Some(x_rowid),
+    xUpdate: None,
+    xBegin: None,
+    xSync: None,
+    xCommit: None,
+    xRollback: None,
+    xFindFunction: None,
+    xRename: None,
+    xSavepoint: None,
+    xRelease: None,
+    xRollbackTo: None,
+    xShadowName: None,
+});
+
+#[repr(C)]
+struct TablesInfoTable {
+    base: sqlite3_vtab,
+    db: *mut sqlite3,
+}
+
+#[repr(C)]
+struct TablesInfoCursor {
+    base: sqlite3_vtab_cursor,
+    rows: Vec<TableRow>,
+    pos: usize,
+}
+
+#[derive(Debug)]
+struct TableRow {
+    logical_name: String,
+    physical_name: String,
+    row_label_col: String,
+    table_label_id: Option<i64>,
+    insert_label_id: Option<i64>,
+    allow_implicit_label: Option<i64>,
+}
+
+fn table_rows(conn: &Connection) -> Result<Vec<TableRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT logical_name, physical_name, row_label_col, table_label_id,
+                insert_label_id, allow_implicit_label
+         FROM sec_tables
+         ORDER BY logical_name",
+    )?;
+    stmt.query_map([], |row| {
+        Ok(TableRow {
+            logical_name: row.get(0)?,
+            physical_name: row.get(1)?,
+            row_label_col: row.get(2)?,
+            table_label_id: row.get(3)?,
+            insert_label_id: row.get(4)?,
+            allow_implicit_label: row.get(5)?,
+        })
+    })?
+    .collect()
+}
+
+unsafe extern "C" fn x_connect(
+    db: *mut sqlite3,
+    _aux: *mut c_void,
+    _argc: c_int,
+    _argv: *const *const c_char,
+    pp_vtab: *mut *mut sqlite3_vtab,
+    _err: *mut *mut c_char,
+) -> c_int {
+    unsafe {
+        let rc = sqlite3_declare_vtab(
+            db,
+            c"CREATE TABLE x(logical_name TEXT, physical_name TEXT, row_label_col TEXT, \
+               table_label_id INTEGER, insert_label_id INTEGER, allow_implicit_label INTEGER)"
+                .as_ptr(),
+        );
+        if rc != SQLITE_OK {
+            return rc;
+        }
+        let table = Box::new(TablesInfoTable {
+            base: std::mem::zeroed(),
+            db,
+        });
+        *pp_vtab = Box::into_raw(table) as *mut sqlite3_vtab;
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_best_index(
+    _vtab: *mut sqlite3_vtab,
+    info: *mut sqlite3_index_info,
+) -> c_int {
+    unsafe {
+        (*info).estimatedCost = 10.0;
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_disconnect(vtab: *mut sqlite3_vtab) -> c_int {
+    drop(unsafe { Box::from_raw(vtab as *mut TablesInfoTable) });
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_open(
+    _vtab: *mut sqlite3_vtab,
+    pp_cursor: *mut *mut sqlite3_vtab_cursor,
+) -> c_int {
+    let cursor = Box::new(TablesInfoCursor {
+        base: unsafe { std::mem::zeroed() },
+        rows: Vec::new(),
+        pos: 0,
+    });
+    unsafe {
+        *pp_cursor = Box::into_raw(cursor) as *mut sqlite3_vtab_cursor;
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_close(cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    drop(unsafe { Box::from_raw(cursor as *mut TablesInfoCursor) });
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_filter(
+    cursor: *mut sqlite3_vtab_cursor,
+    _idx_num: c_int,
+    _idx_str: *const c_char,
+    _argc: c_int,
+    _argv: *mut *mut sqlite3_value,
+) -> c_int {
+    unsafe {
+        let cursor = &mut *(cursor as *mut TablesInfoCursor);
+        let vtab = cursor.base.pVtab;
+        let rows = Connection::from_handle((*(vtab as *mut TablesInfoTable)).db)
+            .and_then(|conn| table_rows(&conn));
+        match rows {
+            Ok(rows) => cursor.rows = rows,
+            Err(e) => {
+                set_err_message(&mut (*vtab).zErrMsg, &format!("sec_tables_info: {e}"));
+                return SQLITE_ERROR;
+            }
+        }
+        cursor.pos = 0;
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_next(cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    unsafe {
+        (*(cursor as *mut TablesInfoCursor)).pos += 1;
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_eof(cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    let cursor = unsafe { &*(cursor as *mut TablesInfoCursor) };
+    (cursor.pos >= cursor.rows.len()) as c_int
+}
+
+unsafe extern "C" fn x_column(
+    cursor: *mut sqlite3_vtab_cursor,
+    ctx: *mut sqlite3_context,
+    col: c_int,
+) -> c_int {
+    unsafe {
+        let cursor = &*(cursor as *mut TablesInfoCursor);
+        let row = &cursor.rows[cursor.pos];
+        let text = match col {
+            0 => &row.logical_name,
+            1 => &row.physical_name,
+            2 => &row.row_label_col,
+            _ => {
+                let int = match col {
+                    3 => row.table_label_id,
+                    4 => row.insert_label_id,
+                    _ => row.allow_implicit_label,
+                };
+                match int {
+                    Some(v) => sqlite3_result_int64(ctx, v),
+                    None => sqlite3_result_null(ctx),
+                }
+                return SQLITE_OK;
+            }
+        };
+        sqlite3_result_text(
+            ctx,
+            text.as_ptr() as *const c_char,
+            text.len() as c_int,
+            SQLITE_TRANSIENT(),
+        );
+    }
+    SQLITE_OK
+}
+
+unsafe extern "C" fn x_rowid(cursor: *mut sqlite3_vtab_cursor, rowid: *mut sqlite3_int64) -> c_int {
+    unsafe {
+        *rowid = (*(cursor as *mut TablesInfoCursor)).pos as sqlite3_int64;
+    }
+    SQLITE_OK
+}
@@ -0,0 +1,250 @@
+//! Strict mode, enabled by setting `SQLSEC_STRICT` when the extension is
+//! loaded. A connection whose `sec_*` metadata is missing or malformed at
+//! load time, or is dropped or altered through it later, fails closed: an
+//! authorizer refuses every read and write of the registered physical
+//! tables, or of every non-metadata table when it can no longer tell which
+//! those are.
+//!
+//! Damage done by another connection is noticed on this one's next load
+//! or view refresh.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{CStr, c_char, c_int, c_void},
+};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rusqlite::{
+    Connection,
+    Result,
+    ffi::{
+        SQLITE_ALTER_TABLE,
+        SQLITE_DELETE,
+        SQLITE_DENY,
+        SQLITE_DROP_TABLE,
+        SQLITE_INSERT,
+        SQLITE_OK,
+        SQLITE_READ,
+        SQLITE_UPDATE,
+        sqlite3_set_authorizer,
+    },
+};
+
+/// The metadata tables and the columns sqlsec relies on.
+const SEC_SCHEMA: &[(&str, &[&str])] = &[
+    ("sec_labels", &["id", "expr"]),
+    ("sec_levels", &["attr_name", "level_name", "level_value"]),
+    (
+        "sec_tables",
+        &[
+            "logical_name",
+            "physical_name",
+            "row_label_col",
+            "table_label_id",
+            "insert_label_id",
+            "allow_implicit_label",
+        ],
+    ),
+    (
+        "sec_columns",
+        &[
+            "logical_table",
+            "column_name",
+            "read_label_id",
+            "update_label_id",
+        ],
+    ),
+    ("sec_meta", &["key", "value"]),
+];
+
+/// Created with the metadata and never dropped by sqlsec, so a database
+/// that has lost every `sec_*` table can be told from a blank one.
+pub(crate) const MARKER: &str = "sqlsec_initialized";
+
+/// The inventory of registered tables, which is only as trustworthy as
+/// `sec_tables` and so is refused once a connection has failed closed.
+const TABLES_INFO: &str = "sec_tables_info";
+
+#[derive(Debug, Default)]
+struct StrictState {
+    /// Lower-cased physical tables to deny once compromised; `None` when
+    /// `sec_tables` was unreadable, which denies every table.
+    protected: Option<HashSet<String>>,
+    compromised: Option<String>,
+}
+
+/// Global map: db handle address -> strict state, for connections loaded in
+/// strict mode only.
+static STRICT: Lazy<Mutex<HashMap<usize, StrictState>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether `SQLSEC_STRICT` asks for strict mode (any value but `0`).
+pub fn strict_mode_requested() -> bool {
+    std::env::var("SQLSEC_STRICT").is_ok_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Whether the database was initialized before: it has the marker, or a
+/// physical table named the way registered tables are (`__sec_*`).
+fn was_initialized(conn: &Connection) -> Result<bool> {
+    conn.query_row(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM main.sqlite_master
+            WHERE type = 'table'
+              AND (name = ?1 COLLATE NOCASE OR name LIKE '\_\_sec\_%' ESCAPE '\')
+        )
+        "#,
+        [MARKER],
+        |row| row.get(0),
+    )
+}
+
+/// What is wrong with the `sec_*` schema, if anything. A database with none
+/// of the tables is fine only if it was never initialized.
+pub fn schema_problem(conn: &Connection) -> Result<Option<String>> {
+    let mut missing = vec![];
+    for (table, required) in SEC_SCHEMA {
+        let mut stmt = conn.prepare(&format!("PRAGMA main.table_info({table})"))?;
+        let cols = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<_>>>()?;
+        if cols.is_empty() {
+            missing.push(*table);
+        } else if let Some(col) = required
+            .iter()
+            .find(|c| !cols.iter().any(|have| have == *c))
+        {
+            return Ok(Some(format!("{table} has no column {col}")));
+        }
+    }
+
+    if missing.is_empty() || (missing.len() == SEC_SCHEMA.len() && !was_initialized(conn)?) {
+        Ok(None)
+    } else {
+        Ok(Some(format!("missing {}", missing.join(", "))))
+    }
+}
+
+/// Put a connection into strict mode: check its schema, remember which
+/// physical tables it protects and install the authorizer. Loading the
+/// extension again keeps an earlier compromise.
+pub fn enable_strict(conn: &Connection) -> Result<()> {
+    let db = unsafe { conn.handle() };
+    let problem = schema_problem(conn)?;
+    let protected = registered_tables(conn).ok();
+
+    let mut strict = STRICT.lock();
+    let state = strict.entry(db as usize).or_default();
+    state.protected = protected;
+    if state.compromised.is_none() {
+        state.compromised = problem;
+    }
+    drop(strict);
+
+    unsafe { sqlite3_set_authorizer(db, Some(ffi_strict_authorizer), db as *mut c_void) };
+    Ok(())
+}
+
+/// Whether a strict connection has failed closed.
+pub fn is_compromised(db_ptr: usize) -> bool {
+    STRICT
+        .lock()
+        .get(&db_ptr)
+        .is_some_and(|s| s.compromised.is_some())
+}
+
+/// Re-check a strict connection's schema and registered tables, e.g. on a
+/// view refresh. No-op for connections not in strict mode.
+pub fn recheck(conn: &Connection) -> Result<()> {
+    let db_ptr = unsafe { conn.handle() } as usize;
+    if !STRICT.lock().contains_key(&db_ptr) {
+        return Ok(());
+    }
+
+    let problem = schema_problem(conn)?;
+    let protected = registered_tables(conn).ok();
+    if let Some(state) = STRICT.lock().get_mut(&db_ptr) {
+        if state.compromised.is_none() {
+            state.compromised = problem;
+        }
+        // Never forget tables: a half-dropped schema may list fewer.
+        match (&mut state.protected, protected) {
+            (Some(known), Some(now)) => known.extend(now),
+            (known @ None, Some(now)) if state.compromised.is_none() => *known = Some(now),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Add a newly registered physical table to a strict connection's set.
+pub fn protect(conn: &Connection, physical: &str) {
+    let db_ptr = unsafe { conn.handle() } as usize;
+    if let Some(Some(protected)) = STRICT.lock().get_mut(&db_ptr).map(|s| s.protected.as_mut()) {
+        protected.insert(physical.to_lowercase());
+    }
+}
+
+/// Forget a closing connection's strict state.
+pub fn release_strict(db_ptr: usize) {
+    STRICT.lock().remove(&db_ptr);
+}
+
+fn registered_tables(conn: &Connection) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT physical_name FROM sec_tables")?;
+    stmt.query_map([], |row| row.get::<_, String>(0))?
+        .map(|name| name.map(|n| n.to_lowercase()))
+        .collect()
+}
+
+fn is_sec_table(name: &str) -> bool {
+    SEC_SCHEMA.iter().any(|(t, _)| t.eq_ignore_ascii_case(name))
+}
+
+/// The authorizer may not run SQL, so it works from the in-memory state:
+/// DDL against a `sec_*` table marks the connection compromised, after
+/// which access to protected tables is denied.
+extern "C" fn ffi_strict_authorizer(
+    user: *mut c_void,
+    action: c_int,
+    arg1: *const c_char,
+    arg2: *const c_char,
+    _db_name: *const c_char,
+    _trigger_or_view: *const c_char,
+) -> c_int {
+    let name =
+        |p: *const c_char| (!p.is_null()).then(|| unsafe { CStr::from_ptr(p) }.to_string_lossy());
+    let db_ptr = user as usize;
+    let mut strict = STRICT.lock();
+    let Some(state) = strict.get_mut(&db_ptr) else {
+        return SQLITE_OK;
+    };
+
+    let altered = match action {
+        SQLITE_DROP_TABLE => name(arg1),
+        SQLITE_ALTER_TABLE => name(arg2),
+        _ => None,
+    };
+    if let Some(table) = altered.filter(|t| is_sec_table(t) || t.eq_ignore_ascii_case(MARKER))
+        && state.compromised.is_none()
+    {
+        state.compromised = Some(format!("{table} was dropped or altered"));
+    }
+
+    let accessed = match action {
+        SQLITE_READ | SQLITE_INSERT | SQLITE_UPDATE | SQLITE_DELETE => name(arg1),
+        _ => None,
+    };
+    match (&state.compromised, accessed) {
+        (Some(_), Some(table)) if !table.starts_with("sqlite_") => {
+            let denied = table.eq_ignore_ascii_case(TABLES_INFO)
+                || match &state.protected {
+                    Some(protected) => protected.contains(&table.to_lowercase()),
+                    // The metadata itself stays reachable so loading can finish.
+                    None => !is_sec_table(&table),
+                };
+            if denied { SQLITE_DENY } else { SQLITE_OK }
+        }
+        _ => SQLITE_OK,
+    }
+}
@@ -11,7 +11,9 @@ use rusqlite::{Connection, Error, Result};
 pub struct SecTable {
     logical_name: String,
     physical_name: String,
-    row_label_col: String,
+    /// One or more label columns; a row is visible only when every one of
+    /// their labels is.
+    row_label_cols: Vec<String>,
     table_label_id: Option<i64>,
     insert_label_id: Option<i64>,
 }
@@ -23,23 +25,114 @@ pub struct SecColumn {
     update_label_id: Option<i64>,
 }
 
+/// Quote `name` as an SQL identifier, doubling any embedded `"`.
+fn escape_sql_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Escape `s` for use inside a single-quoted SQL string literal.
+fn escape_sql_string(s: &str) -> String {
+    s.replace('\'', "''")
+}
+
+/// Split a `sec_tables.row_label_col` value: one column, or several joined
+/// with `,` for a composite row label. A name in double quotes may hold
+/// `,` itself, with any `"` in it doubled.
+fn split_row_label_cols(stored: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut quoted = false;
+    for c in stored.chars() {
+        if c == ',' && !quoted {
+            parts.push(String::new());
+            continue;
+        }
+        if c == '"' {
+            quoted = !quoted;
+        }
+        parts.last_mut().unwrap().push(c);
+    }
+    parts
+        .iter()
+        .map(|part| {
+            let part = part.trim();
+            match part.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+                Some(inner) => inner.replace("\"\"", "\""),
+                None => part.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Inverse of [`split_row_label_cols`]: names holding `,` or `"` are
+/// quoted, the rest stored as they are.
+fn join_row_label_cols(cols: &[String]) -> String {
+    cols.iter()
+        .map(|c| {
+            if c.contains([',', '"']) {
+                escape_sql_ident(c)
+            } else {
+                c.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// `sec_label_visible(..)` over every row label column, ANDed together.
+/// `prefix` qualifies the columns, e.g. `"NEW."` inside a trigger.
+fn row_visible_expr(cols: &[String], prefix: &str) -> String {
+    cols.iter()
+        .map(|c| format!("sec_label_visible({prefix}{})", escape_sql_ident(c)))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// `(name, USING expression)` of the `CREATE POLICY` policies on `table`
+/// for `operation` (`SELECT`, `UPDATE` or `DELETE`) or `ALL`, once
+/// `enforce_policies` is set in `sec_meta`. The policies live in sqlshim's
+/// `__sqlshim_policies`, which only exists after the first `CREATE POLICY`.
+fn policies(conn: &Connection, table: &str, operation: &str) -> Result<Vec<(String, String)>> {
+    let enforce: i64 = conn
+        .query_row(
+            "SELECT value FROM sec_meta WHERE key = 'enforce_policies'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap_or(0);
+    let stored: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master
+                        WHERE type = 'table' AND name = '__sqlshim_policies')",
+        [],
+        |r| r.get(0),
+    )?;
+    if enforce == 0 || !stored {
+        return Ok(vec![]);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT name, expr FROM __sqlshim_policies
+         WHERE table_name = ?1 COLLATE NOCASE AND operation IN (?2, 'ALL')
+         ORDER BY name",
+    )?;
+    stmt.query_map([table, operation], |r| Ok((r.get(0)?, r.get(1)?)))?
+        .collect()
+}
+
 fn get_physical_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
-    let mut stmt = conn.prepare(&format!("PRAGMA table_info(\"{}\")", table))?;
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", escape_sql_ident(table)))?;
     let cols = stmt
         .query_map([], |row| row.get::<_, String>(1))?
         .collect::<Result<Vec<_>>>()?;
 
     if cols.is_empty() {
-        return Err(invalid(format!(
-            "table '{table}' does not exist",
-        )));
+        return Err(invalid(format!("table '{table}' does not exist",)));
     }
 
     Ok(cols)
 }
 
 fn get_primary_key_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
-    let mut stmt = conn.prepare(&format!("PRAGMA table_info(\"{}\")", table))?;
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", escape_sql_ident(table)))?;
 
     let mut pk_cols: Vec<(i64, String)> = Vec::new();
 
@@ -104,7 +197,7 @@ fn get_sec_tables(conn: &Connection) -> Result<Vec<SecTable>> {
             Ok(SecTable {
                 logical_name: row.get(0)?,
                 physical_name: row.get(1)?,
-                row_label_col: row.get(2)?,
+                row_label_cols: split_row_label_cols(&row.get::<_, String>(2)?),
                 table_label_id: row.get(3)?,
                 insert_label_id: row.get(4)?,
             })
@@ -113,3 +206,49 @@ fn get_sec_tables(conn: &Connection) -> Result<Vec<SecTable>> {
 
     Ok(tables)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_sql_ident_doubles_quotes() {
+        assert_eq!(escape_sql_ident("plain"), r#""plain""#);
+        assert_eq!(
+            escape_sql_ident(r#"a" ; DROP TABLE t; --"#),
+            r#""a"" ; DROP TABLE t; --""#
+        );
+    }
+
+    #[test]
+    fn escape_sql_string_doubles_single_quotes() {
+        assert_eq!(escape_sql_string("it's"), "it''s");
+    }
+
+    #[test]
+    fn composite_row_label_requires_every_label() {
+        let cols = split_row_label_cols("dept_label, clearance_label");
+        assert_eq!(cols, ["dept_label", "clearance_label"]);
+        assert_eq!(
+            row_visible_expr(&cols, "NEW."),
+            r#"sec_label_visible(NEW."dept_label") AND sec_label_visible(NEW."clearance_label")"#
+        );
+        assert_eq!(
+            row_visible_expr(&split_row_label_cols("lbl"), ""),
+            r#"sec_label_visible("lbl")"#
+        );
+    }
+
+    #[test]
+    fn row_label_cols_may_hold_commas_and_quotes() {
+        let cols = [
+            "dept,label".to_string(),
+            "say \"hi\"".to_string(),
+            "lbl".to_string(),
+        ];
+        let stored = join_row_label_cols(&cols);
+        assert_eq!(stored, r#""dept,label","say ""hi""",lbl"#);
+        assert_eq!(split_row_label_cols(&stored), cols);
+        assert_eq!(split_row_label_cols(r#" "a,b" , c"#), ["a,b", "c"]);
+    }
+}
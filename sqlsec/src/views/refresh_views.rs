@@ -5,7 +5,16 @@ use rusqlite::{Connection, Error, Result};
 use crate::{
     context::{effective_context, sec_ctx::SecurityContext},
     label::evaluate::{is_visible_conn, load_levels},
-    views::{SecTable, get_sec_columns, get_sec_tables, write_triggers::create_write_triggers},
+    strict::recheck,
+    views::{
+        SecTable,
+        escape_sql_ident,
+        get_sec_columns,
+        get_sec_tables,
+        policies,
+        row_visible_expr,
+        write_triggers::create_write_triggers,
+    },
 };
 
 fn refresh_err(err: Error, table: &str) -> Error {
@@ -17,9 +26,11 @@ fn refresh_err(err: Error, table: &str) -> Error {
 
 /// Refresh views using Connection reference
 pub fn refresh_views(conn: &mut Connection, ctx: &SecurityContext) -> Result<()> {
+    recheck(conn)?;
     load_levels(conn)?;
 
-    let tx = conn.transaction()?; // BEGIN
+    // SAVEPOINT rather than BEGIN so a refresh may nest inside a caller's transaction
+    let tx = conn.savepoint()?;
 
     let tables = get_sec_tables(&tx)?;
 
@@ -38,7 +49,7 @@ pub fn refresh_views(conn: &mut Connection, ctx: &SecurityContext) -> Result<()>
         "#,
     )?;
 
-    tx.commit()?; // COMMIT
+    tx.commit()?; // RELEASE
     Ok(())
 }
 
@@ -54,13 +65,54 @@ pub fn refresh_views_raw(db_ptr: usize) -> Result<()> {
     result
 }
 
+/// Check whether the context generation has moved since the last refresh
+pub fn views_stale(conn: &Connection) -> Result<bool> {
+    let generation: i64 = conn
+        .query_row(
+            "SELECT value FROM sec_meta WHERE key = 'generation'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap_or(0);
+
+    let last_refresh: i64 = conn
+        .query_row(
+            "SELECT value FROM sec_meta WHERE key = 'last_refresh_generation'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap_or(-1);
+
+    Ok(generation != last_refresh)
+}
+
+/// Refresh views only if they are stale, returning whether a refresh happened
+pub fn refresh_views_if_stale(conn: &mut Connection, ctx: &SecurityContext) -> Result<bool> {
+    if !views_stale(conn)? {
+        return Ok(false);
+    }
+    refresh_views(conn, ctx)?;
+    Ok(true)
+}
+
+/// Lazily refresh views from raw pointer (for FFI)
+pub fn refresh_views_if_stale_raw(db_ptr: usize) -> Result<bool> {
+    let mut conn = unsafe { Connection::from_handle(db_ptr as *mut _)? };
+
+    let ctx = effective_context(db_ptr);
+
+    let result = refresh_views_if_stale(&mut conn, &ctx);
+
+    forget(conn);
+    result
+}
+
 fn refresh_single_view(conn: &Connection, table: &SecTable, ctx: &SecurityContext) -> Result<()> {
+    let view = escape_sql_ident(&table.logical_name);
+
     // Check table-level visibility
     if !is_visible_conn(conn, table.table_label_id, ctx) {
-        conn.execute(
-            &format!("DROP VIEW IF EXISTS \"{}\"", table.logical_name),
-            [],
-        )?;
+        conn.execute(&format!("DROP VIEW IF EXISTS {view}"), [])?;
         return Ok(());
     }
 
@@ -73,35 +125,34 @@ fn refresh_single_view(conn: &Connection, table: &SecTable, ctx: &SecurityContex
         .collect();
 
     if visible_columns.is_empty() {
-        conn.execute(
-            &format!("DROP VIEW IF EXISTS \"{}\"", table.logical_name),
-            [],
-        )?;
+        conn.execute(&format!("DROP VIEW IF EXISTS {view}"), [])?;
         return Ok(());
     }
 
     // Build SELECT list
     let select_cols = visible_columns
         .iter()
-        .map(|c| format!("\"{}\"", c))
+        .map(|c| escape_sql_ident(c))
         .collect::<Vec<_>>()
         .join(", ");
 
+    let policies: String = policies(conn, &table.logical_name, "SELECT")?
+        .iter()
+        .map(|(_, expr)| format!("\n          AND ({expr})"))
+        .collect();
+
     // Build the view DDL
     let view_sql = format!(
         r#"
-        DROP VIEW IF EXISTS "{}";
-        CREATE TEMP VIEW "{}" AS
-        SELECT {}
-        FROM "{}"
+        DROP VIEW IF EXISTS {view};
+        CREATE TEMP VIEW {view} AS
+        SELECT {select_cols}
+        FROM {physical}
         WHERE sec_assert_fresh()
-          AND sec_label_visible("{}");
+          AND {row_visible}{policies};
         "#,
-        table.logical_name,
-        table.logical_name,
-        select_cols,
-        table.physical_name,
-        table.row_label_col
+        physical = escape_sql_ident(&table.physical_name),
+        row_visible = row_visible_expr(&table.row_label_cols, ""),
     );
 
     conn.execute_batch(&view_sql)?;
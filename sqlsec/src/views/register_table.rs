@@ -2,7 +2,17 @@ use std::mem::forget;
 
 use rusqlite::{Connection, Result};
 
-use crate::views::{get_physical_columns, get_primary_key_columns, invalid};
+use crate::{
+    init::sec_init_schema,
+    strict::protect,
+    views::{
+        get_physical_columns,
+        get_primary_key_columns,
+        invalid,
+        join_row_label_cols,
+        split_row_label_cols,
+    },
+};
 
 fn is_without_rowid(conn: &Connection, table: &str) -> Result<bool> {
     let sql: Option<String> = conn.query_row(
@@ -17,6 +27,10 @@ fn is_without_rowid(conn: &Connection, table: &str) -> Result<bool> {
 }
 
 /// Register a table using Connection reference
+///
+/// `row_label_col` may name several columns separated by `,`, forming a
+/// composite row label: a row is visible only if each column's label is.
+/// A name holding `,` goes in double quotes.
 pub fn register_table(
     conn: &Connection,
     logical: &str,
@@ -24,16 +38,20 @@ pub fn register_table(
     row_label_col: &str,
     table_label_id: Option<i64>,
     insert_label_id: Option<i64>,
+    allow_implicit_label: bool,
 ) -> Result<()> {
     // 1. Physical table exists (implicit via PRAGMA failure)
     let cols = get_physical_columns(conn, physical)?;
 
-    // 2. Row label column exists
-    if !cols.iter().any(|c| c == row_label_col) {
-        return Err(invalid(format!(
-            "row label column '{row_label_col}' does not exist, candidates are: {}",
-            cols.join(", ")
-        )));
+    // 2. Row label column(s) exist
+    let row_label_cols = split_row_label_cols(row_label_col);
+    for label_col in &row_label_cols {
+        if !cols.iter().any(|c| c == label_col) {
+            return Err(invalid(format!(
+                "row label column '{label_col}' does not exist, candidates are: {}",
+                cols.join(", ")
+            )));
+        }
     }
 
     // 3. Primary key exists
@@ -61,20 +79,26 @@ pub fn register_table(
 
     // ---- safe to register ----
 
+    // Normally done on load; cheap enough to repeat in case it was not.
+    sec_init_schema(conn)?;
+
     conn.execute(
         r#"
         INSERT OR REPLACE INTO sec_tables
-        (logical_name, physical_name, row_label_col, table_label_id, insert_label_id)
-        VALUES (?1, ?2, ?3, ?4, ?5)
+        (logical_name, physical_name, row_label_col, table_label_id, insert_label_id,
+         allow_implicit_label)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
         "#,
         rusqlite::params![
             logical,
             physical,
-            row_label_col,
+            join_row_label_cols(&row_label_cols),
             table_label_id,
-            insert_label_id
+            insert_label_id,
+            allow_implicit_label
         ],
     )?;
+    protect(conn, physical);
 
     for col in cols {
         conn.execute(
@@ -97,6 +121,7 @@ pub fn register_table_raw(
     row_label_col: &str,
     table_label_id: Option<i64>,
     insert_label_id: Option<i64>,
+    allow_implicit_label: bool,
 ) -> Result<()> {
     let conn = unsafe { Connection::from_handle(db_ptr as *mut _)? };
     let result = register_table(
@@ -106,6 +131,7 @@ pub fn register_table_raw(
         row_label_col,
         table_label_id,
         insert_label_id,
+        allow_implicit_label,
     );
     forget(conn);
     result
@@ -3,7 +3,17 @@ use rusqlite::{Connection, Result};
 use crate::{
     context::effective_context,
     label::evaluate::is_visible_conn,
-    views::{SecTable, get_primary_key_columns, get_sec_columns, invalid},
+    views::{
+        SecColumn,
+        SecTable,
+        escape_sql_ident,
+        escape_sql_string,
+        get_primary_key_columns,
+        get_sec_columns,
+        invalid,
+        policies,
+        row_visible_expr,
+    },
 };
 
 pub fn create_write_triggers(
@@ -20,25 +30,29 @@ pub fn create_write_triggers(
 
 fn create_delete_trigger(conn: &Connection, table: &SecTable) -> Result<(), rusqlite::Error> {
     let logical = &table.logical_name;
-    let physical = &table.physical_name;
-    let row_label_col = &table.row_label_col;
+    let trigger = escape_sql_ident(&format!("{logical}_sec_del"));
+    let view = escape_sql_ident(logical);
+    let physical = escape_sql_ident(&table.physical_name);
+    let row_visible = row_visible_expr(&table.row_label_cols, "");
 
-    let pk_cols = pk_cols(conn, physical)?;
+    let pk_cols = pk_cols(conn, &table.physical_name)?;
     let pk_where_old = pk_where_old(&pk_cols);
 
     let refesh_guard = refresh_guard();
+    let policy_guards = policy_guards(conn, table, "DELETE", &pk_where_old)?;
 
     let delete_trigger = format!(
         r#"
-        DROP TRIGGER IF EXISTS "{logical}_sec_del";
-        CREATE TEMP TRIGGER "{logical}_sec_del"
-        INSTEAD OF DELETE ON "{logical}"
+        DROP TRIGGER IF EXISTS {trigger};
+        CREATE TEMP TRIGGER {trigger}
+        INSTEAD OF DELETE ON {view}
         BEGIN
             {refesh_guard}
+            {policy_guards}
 
-            DELETE FROM "{physical}"
+            DELETE FROM {physical}
             WHERE {pk_where_old}
-              AND sec_label_visible("{row_label_col}");
+              AND {row_visible};
         END;
         "#
     );
@@ -54,38 +68,45 @@ fn create_update_trigger(
     visible_cols: &[&str],
 ) -> Result<(), rusqlite::Error> {
     let logical = &table.logical_name;
-    let physical = &table.physical_name;
-    let row_label_col = &table.row_label_col;
+    let trigger = escape_sql_ident(&format!("{logical}_sec_upd"));
+    let view = escape_sql_ident(logical);
+    let physical = escape_sql_ident(&table.physical_name);
 
     let update_sets = visible_cols
         .iter()
-        .map(|c| format!("\"{}\" = NEW.\"{}\"", c, c))
+        .map(|c| {
+            let c = escape_sql_ident(c);
+            format!("{c} = NEW.{c}")
+        })
         .collect::<Vec<_>>()
         .join(", ");
 
-    let pk_cols = pk_cols(conn, physical)?;
+    let pk_cols = pk_cols(conn, &table.physical_name)?;
     let pk_where_old = pk_where_old(&pk_cols);
 
     let refresh_guard = refresh_guard();
     let update_pk_guard = update_pk_guard(pk_cols);
-    let update_label_guard = update_label_guard(row_label_col);
+    let update_label_guard = per_label_col(&table.row_label_cols, update_label_guard);
+    let row_visible = row_visible_expr(&table.row_label_cols, "");
     let column_policy_guards = column_update_policy_guards(conn, logical)?;
+    let policy_guards = policy_guards(conn, table, "UPDATE", &pk_where_old)?;
 
     let update_trigger = format!(
         r#"
-        DROP TRIGGER IF EXISTS "{logical}_sec_upd";
-        CREATE TEMP TRIGGER "{logical}_sec_upd"
-        INSTEAD OF UPDATE ON "{logical}"
+        DROP TRIGGER IF EXISTS {trigger};
+        CREATE TEMP TRIGGER {trigger}
+        INSTEAD OF UPDATE ON {view}
         BEGIN
             {refresh_guard}
             {update_pk_guard}
             {update_label_guard}
             {column_policy_guards}
+            {policy_guards}
 
-            UPDATE "{physical}"
+            UPDATE {physical}
             SET {update_sets}
             WHERE {pk_where_old}
-              AND sec_label_visible("{row_label_col}");
+              AND {row_visible};
         END;
         "#
     );
@@ -101,17 +122,19 @@ fn create_insert_trigger(
     visible_cols: &[&str],
 ) -> Result<(), rusqlite::Error> {
     let logical = &table.logical_name;
-    let physical = &table.physical_name;
-    let row_label_col = &table.row_label_col;
+    let trigger = escape_sql_ident(&format!("{logical}_sec_ins"));
+    let view = escape_sql_ident(logical);
+    let physical = escape_sql_ident(&table.physical_name);
+    let escaped_logical = escape_sql_string(logical);
 
     let insert_cols = visible_cols
         .iter()
-        .map(|c| format!("\"{}\"", c))
+        .map(|c| escape_sql_ident(c))
         .collect::<Vec<_>>()
         .join(", ");
     let insert_vals = visible_cols
         .iter()
-        .map(|c| format!("NEW.\"{}\"", c))
+        .map(|c| format!("NEW.{}", escape_sql_ident(c)))
         .collect::<Vec<_>>()
         .join(", ");
     let row_label_assignment = if table.insert_label_id.is_some() {
@@ -120,14 +143,14 @@ fn create_insert_trigger(
                 (
                     SELECT insert_label_id
                     FROM sec_tables
-                    WHERE logical_name = '{logical}'
+                    WHERE logical_name = '{escaped_logical}'
                       AND insert_label_id IS NOT NULL
                       AND sec_label_visible(insert_label_id)
                 ),
                 (
                     SELECT table_label_id
                     FROM sec_tables
-                    WHERE logical_name = '{logical}'
+                    WHERE logical_name = '{escaped_logical}'
                 ),
                 1
             )"#
@@ -139,22 +162,32 @@ fn create_insert_trigger(
     };
 
     let refesh_guard = refresh_guard();
-    let implicit_label_guard = implicit_label_guard(logical, row_label_col);
-    let label_visible_guard = label_visible_guard(row_label_col);
+    let implicit_label_guard =
+        per_label_col(&table.row_label_cols, |c| implicit_label_guard(logical, c));
+    let label_visible_guard = per_label_col(&table.row_label_cols, label_visible_guard);
+    let column_insert_guards = column_insert_policy_guards(conn, logical, visible_cols)?;
+    let row_label_cols = table
+        .row_label_cols
+        .iter()
+        .map(|c| escape_sql_ident(c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let row_label_assignments = vec![row_label_assignment; table.row_label_cols.len()].join(", ");
 
     let insert_trigger = format!(
         r#"
-        DROP TRIGGER IF EXISTS "{logical}_sec_ins";
-        CREATE TEMP TRIGGER "{logical}_sec_ins"
-        INSTEAD OF INSERT ON "{logical}"
+        DROP TRIGGER IF EXISTS {trigger};
+        CREATE TEMP TRIGGER {trigger}
+        INSTEAD OF INSERT ON {view}
         BEGIN
             {refesh_guard}
             {implicit_label_guard}
             {label_visible_guard}
+            {column_insert_guards}
 
-            INSERT INTO "{physical}" ("{row_label_col}", {insert_cols})
+            INSERT INTO {physical} ({row_label_cols}, {insert_cols})
             VALUES (
-                {row_label_assignment},
+                {row_label_assignments},
                 {insert_vals}
             );
         END;
@@ -169,7 +202,10 @@ fn create_insert_trigger(
 fn update_pk_guard(pk_cols: Vec<String>) -> String {
     let pk_updated = pk_cols
         .iter()
-        .map(|col| format!("OLD.\"{col}\" != NEW.\"{col}\""))
+        .map(|col| {
+            let col = escape_sql_ident(col);
+            format!("OLD.{col} != NEW.{col}")
+        })
         .collect::<Vec<_>>()
         .join(" OR ");
     format!(
@@ -181,43 +217,87 @@ fn update_pk_guard(pk_cols: Vec<String>) -> String {
     )
 }
 
-fn label_visible_guard(row_label_col: &String) -> String {
+/// Repeat a per-column guard for each column of a (possibly composite)
+/// row label.
+fn per_label_col(cols: &[String], guard: impl Fn(&str) -> String) -> String {
+    cols.iter().map(|c| guard(c)).collect()
+}
+
+fn label_visible_guard(row_label_col: &str) -> String {
+    let col = escape_sql_ident(row_label_col);
+    let escaped_col = escape_sql_string(row_label_col);
     format!(
         r#"
         SELECT CASE
-            WHEN NEW."{row_label_col}" IS NOT NULL
-             AND NOT sec_label_visible(NEW."{row_label_col}")
-            THEN RAISE(ABORT, 'row_label_col {row_label_col} not visible')
+            WHEN NEW.{col} IS NOT NULL
+             AND NOT sec_label_visible(NEW.{col})
+            THEN RAISE(ABORT, 'row_label_col {escaped_col} not visible')
         END;
         "#
     )
 }
 
-fn update_label_guard(row_label_col: &String) -> String {
+fn update_label_guard(row_label_col: &str) -> String {
+    let col = escape_sql_ident(row_label_col);
+    let escaped_col = escape_sql_string(row_label_col);
     format!(
         r#"
         SELECT CASE
-            WHEN NEW."{row_label_col}" != OLD."{row_label_col}"
-            THEN RAISE(ABORT, 'cannot update raw_label_col {row_label_col}')
+            WHEN NEW.{col} != OLD.{col}
+            THEN RAISE(ABORT, 'cannot update raw_label_col {escaped_col}')
         END;
         "#,
     )
 }
 
-fn implicit_label_guard(logical: &String, row_label_col: &String) -> String {
+fn implicit_label_guard(logical: &str, row_label_col: &str) -> String {
+    let col = escape_sql_ident(row_label_col);
+    let escaped_col = escape_sql_string(row_label_col);
+    let escaped_logical = escape_sql_string(logical);
     format!(
         r#"
         SELECT CASE
-            WHEN NEW."{row_label_col}" IS NULL
+            WHEN NEW.{col} IS NULL
              AND (SELECT allow_implicit_label
                   FROM sec_tables
-                  WHERE logical_name = '{logical}') = 0
-            THEN RAISE(ABORT, 'implicit row_label_col {row_label_col} not allowed')
+                  WHERE logical_name = '{escaped_logical}') = 0
+            THEN RAISE(ABORT, 'implicit row_label_col {escaped_col} not allowed')
         END;
         "#
     )
 }
 
+/// Abort an `operation` on a visible row that one of the table's
+/// `CREATE POLICY` expressions denies. Each expression is checked against
+/// the stored (OLD) row, so it may name any physical column.
+fn policy_guards(
+    conn: &Connection,
+    table: &SecTable,
+    operation: &str,
+    pk_where_old: &str,
+) -> Result<String, rusqlite::Error> {
+    let physical = escape_sql_ident(&table.physical_name);
+    let guards = policies(conn, &table.logical_name, operation)?
+        .iter()
+        .map(|(name, expr)| {
+            let escaped_name = escape_sql_string(name);
+            format!(
+                r#"
+            SELECT CASE
+                WHEN NOT EXISTS (
+                    SELECT 1 FROM {physical}
+                    WHERE {pk_where_old}
+                      AND ({expr})
+                )
+                THEN RAISE(ABORT, '{operation} denied by policy {escaped_name}')
+            END;
+            "#
+            )
+        })
+        .collect();
+    Ok(guards)
+}
+
 fn refresh_guard() -> &'static str {
     (r#"
     SELECT CASE
@@ -242,7 +322,10 @@ fn pk_where_old(pk_cols: &[String]) -> String {
     let pk_cols: &[String] = pk_cols;
     pk_cols
         .iter()
-        .map(|col| format!("\"{col}\" = OLD.\"{col}\""))
+        .map(|col| {
+            let col = escape_sql_ident(col);
+            format!("{col} = OLD.{col}")
+        })
         .collect::<Vec<_>>()
         .join(" AND ")
 }
@@ -254,34 +337,63 @@ fn trigger_err(err: rusqlite::Error, table: &str, kind: &str) -> rusqlite::Error
     ))))
 }
 
+/// Columns with an update policy the current context does not satisfy.
+fn unwritable_columns(conn: &Connection, logical: &str) -> Result<Vec<SecColumn>, rusqlite::Error> {
+    let ctx = effective_context(unsafe { conn.handle() as usize });
+    let all_columns = get_sec_columns(conn, logical)?;
+
+    Ok(all_columns
+        .into_iter()
+        .filter(|c| c.update_label_id.is_some() && !is_visible_conn(conn, c.update_label_id, &ctx))
+        .collect())
+}
+
+/// Abort an INSERT that gives a value to a column the current context may
+/// not write, rather than storing a value an UPDATE would refuse.
+fn column_insert_policy_guards(
+    conn: &Connection,
+    logical: &str,
+    visible_cols: &[&str],
+) -> Result<String, rusqlite::Error> {
+    let guards = unwritable_columns(conn, logical)?
+        .iter()
+        .filter(|c| visible_cols.contains(&c.column_name.as_str()))
+        .map(|col| {
+            let col_name = escape_sql_ident(&col.column_name);
+            let escaped_col_name = escape_sql_string(&col.column_name);
+            format!(
+                r#"
+            SELECT CASE
+                WHEN NEW.{col_name} IS NOT NULL
+                THEN RAISE(ABORT, 'column {escaped_col_name} not writable in current context')
+            END;
+            "#
+            )
+        })
+        .collect::<Vec<_>>();
+
+    Ok(guards.join("\n"))
+}
+
 fn column_update_policy_guards(
     conn: &Connection,
     logical: &str,
 ) -> Result<String, rusqlite::Error> {
     let mut guards = Vec::new();
 
-    let ctx = effective_context(unsafe { conn.handle() as usize });
-    let all_columns = get_sec_columns(conn, logical)?;
-
     // Generate guards for columns that have a policy AND the user doesn't satisfy it
-    let protected_columns = all_columns
-        .iter()
-        .filter(|c| {
-            c.update_label_id.is_some() 
-                && !is_visible_conn(conn, c.update_label_id, &ctx)
-        });
-
-    for col in protected_columns {
-        let col_name = &col.column_name;
+    for col in unwritable_columns(conn, logical)? {
+        let col_name = escape_sql_ident(&col.column_name);
+        let escaped_col_name = escape_sql_string(&col.column_name);
         guards.push(format!(
             r#"
             SELECT CASE
-                WHEN OLD."{col_name}" IS NOT NEW."{col_name}"
-                THEN RAISE(ABORT, 'update denied on column {col_name}')
+                WHEN OLD.{col_name} IS NOT NEW.{col_name}
+                THEN RAISE(ABORT, 'update denied on column {escaped_col_name}')
             END;
             "#
         ));
     }
 
     Ok(guards.join("\n"))
-}
\ No newline at end of file
+}
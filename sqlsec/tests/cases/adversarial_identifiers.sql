@@ -0,0 +1,44 @@
+.output /dev/null
+
+CREATE TABLE victim (id INTEGER PRIMARY KEY);
+INSERT INTO victim VALUES (1);
+
+CREATE TABLE "__sec_x"" ; DROP TABLE victim; --" (
+    "id""pk"               INTEGER PRIMARY KEY,
+    "lbl'); DROP TABLE victim; --" INTEGER,
+    "a"" ; DROP TABLE victim; --"  TEXT,
+    "it's"                 TEXT
+);
+INSERT INTO "__sec_x"" ; DROP TABLE victim; --" VALUES (1, 1, 'one', 'uno');
+
+.load ./target/debug/libsqlsec
+SELECT sec_define_label('true'); -- everyone
+SELECT sec_define_label('role=admin');
+SELECT sec_register_table(
+    'evil" ; DROP TABLE victim; --',
+    '__sec_x" ; DROP TABLE victim; --',
+    'lbl''); DROP TABLE victim; --',
+    NULL,
+    NULL
+);
+UPDATE sec_columns SET update_label_id = sec_define_label('role=admin') WHERE column_name = 'it''s';
+
+.output /dev/null
+SELECT sec_clear_context();
+SELECT sec_set_attr('role', 'user');
+SELECT sec_refresh_views();
+.output stdout
+
+.print ------------------------------------------------------------
+.print [Adversarial identifiers are quoted, not executed]
+INSERT INTO "evil"" ; DROP TABLE victim; --" ("id""pk", "a"" ; DROP TABLE victim; --", "it's")
+VALUES (2, 'two', 'dos');
+UPDATE "evil"" ; DROP TABLE victim; --" SET "a"" ; DROP TABLE victim; --" = 'ONE' WHERE "id""pk" = 1;
+DELETE FROM "evil"" ; DROP TABLE victim; --" WHERE "id""pk" = 2;
+SELECT * FROM "evil"" ; DROP TABLE victim; --";
+
+.print Column update policy still applies:
+UPDATE "evil"" ; DROP TABLE victim; --" SET "it's" = 'eins' WHERE "id""pk" = 1;
+
+.print Victim table survives:
+SELECT * FROM victim;
@@ -0,0 +1,51 @@
+.output /dev/null
+
+CREATE TABLE __sec_ledger (
+    id           INTEGER PRIMARY KEY,
+    row_label_id INTEGER NOT NULL,
+    amount       INTEGER
+);
+CREATE TABLE __sec_invoices (
+    id           INTEGER PRIMARY KEY,
+    row_label_id INTEGER NOT NULL,
+    amount       INTEGER
+);
+INSERT INTO __sec_ledger VALUES (1, 1, 100);
+INSERT INTO __sec_invoices VALUES (1, 1, 250);
+
+.load ./target/debug/libsqlsec
+
+SELECT sec_define_label('true');
+SELECT sec_define_named_label('finance_read', 'role=finance');
+
+-- Both tables share one label by name
+SELECT sec_register_table('ledger', '__sec_ledger', 'row_label_id', sec_label_id('finance_read'), NULL);
+SELECT sec_register_table('invoices', '__sec_invoices', 'row_label_id', sec_label_id('finance_read'), NULL);
+.output stdout
+
+.print ------------------------------------------------------------
+.print [Named label resolves to one label id]
+SELECT n.name, l.expr, n.label_id = sec_label_id('finance_read') AS same_id
+FROM sec_label_names n JOIN sec_labels l ON l.id = n.label_id;
+SELECT logical_name, table_label_id = sec_label_id('finance_read') AS uses_named_label
+FROM sec_tables_info;
+
+.print ------------------------------------------------------------
+.print [Redefining with the same expression is a no-op]
+SELECT sec_define_named_label('finance_read', 'role=finance') = sec_label_id('finance_read') AS unchanged;
+
+.output /dev/null
+SELECT sec_clear_context();
+SELECT sec_set_attr('role', 'finance');
+SELECT sec_refresh_views();
+.output stdout
+
+.print ------------------------------------------------------------
+.print [role=finance sees both tables]
+SELECT * FROM ledger;
+SELECT * FROM invoices;
+
+.print ------------------------------------------------------------
+.print [Rebinding a name or using an unknown one fails]
+SELECT sec_define_named_label('finance_read', 'role=admin');
+SELECT sec_label_id('finance_write');
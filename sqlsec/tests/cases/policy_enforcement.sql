@@ -0,0 +1,47 @@
+.output /dev/null
+
+CREATE TABLE __sec_staff (
+    id           INTEGER PRIMARY KEY,
+    row_label_id INTEGER,
+    name         TEXT,
+    department   TEXT
+);
+
+-- As written by sqlshim's CREATE POLICY.
+CREATE TABLE __sqlshim_policies (
+    name TEXT NOT NULL,
+    table_name TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    label_id INTEGER,
+    expr TEXT NOT NULL,
+    PRIMARY KEY (name, table_name, operation)
+);
+INSERT INTO __sqlshim_policies VALUES
+    ('finance_only', 'staff', 'SELECT', NULL, 'department = ''finance'''),
+    ('no_interns',   'staff', 'ALL',    NULL, 'name NOT LIKE ''intern%'''),
+    ('writes',       'staff', 'UPDATE', NULL, '0');
+
+.load ./target/debug/libsqlsec
+
+SELECT sec_register_table('staff', '__sec_staff', 'row_label_id', NULL, NULL);
+
+INSERT INTO __sec_staff VALUES
+    (1, NULL, 'alice',      'finance'),
+    (2, NULL, 'bob',        'engineering'),
+    (3, NULL, 'intern_cat', 'finance');
+
+SELECT sec_refresh_views();
+.output stdout
+
+.print ------------------------------------------------------------
+.print [Policies stored but not enforced]
+SELECT name FROM staff ORDER BY id;
+
+.output /dev/null
+UPDATE sec_meta SET value = 1 WHERE key = 'enforce_policies';
+SELECT sec_refresh_views();
+.output stdout
+
+.print ------------------------------------------------------------
+.print [SELECT and ALL policies ANDed into the view]
+SELECT name FROM staff ORDER BY id;
@@ -0,0 +1,29 @@
+.output /dev/null
+
+.open --new target/reconnect_test.db
+.load ./target/debug/libsqlsec
+
+SELECT sec_define_label('true');
+
+CREATE TABLE __sec_notes (
+    id           INTEGER PRIMARY KEY,
+    row_label_id INTEGER NOT NULL,
+    body         TEXT
+);
+INSERT INTO __sec_notes VALUES (1, 1, 'first');
+
+SELECT sec_register_table('notes', '__sec_notes', 'row_label_id', NULL, NULL);
+SELECT sec_refresh_views();
+
+-- Reconnect: the TEMP view and triggers are gone, sec_meta is not
+.open target/reconnect_test.db
+.load ./target/debug/libsqlsec
+.output stdout
+
+.print ------------------------------------------------------------
+.print [Secure view is rebuilt on reconnect]
+SELECT * FROM notes;
+
+.print Write through the view without a manual refresh:
+INSERT INTO notes (id, body) VALUES (2, 'second');
+SELECT * FROM __sec_notes;
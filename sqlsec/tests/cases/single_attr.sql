@@ -0,0 +1,28 @@
+.output /dev/null
+
+.load ./target/debug/libsqlsec
+
+SELECT sec_clear_context();
+SELECT sec_set_attr('tenant', 'acme');
+.output stdout
+
+.print ------------------------------------------------------------
+.print [Single value]
+SELECT sec_get_attr('tenant') AS tenant;
+
+.output /dev/null
+SELECT sec_set_attr('tenant', 'globex');
+.output stdout
+
+.print ------------------------------------------------------------
+.print [Several values]
+SELECT sec_get_attr('tenant') IS NULL AS ambiguous;
+
+.output /dev/null
+SELECT sec_clear_attr('tenant');
+SELECT sec_set_attr('tenant', 'globex');
+.output stdout
+
+.print ------------------------------------------------------------
+.print [Cleared and replaced]
+SELECT sec_get_attr('tenant') AS tenant;
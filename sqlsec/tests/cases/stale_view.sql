@@ -41,4 +41,18 @@ SELECT sec_set_attr('role', 'manager');
 
 .print ------------------------------------------------------------
 .print [Manager]
-SELECT * FROM employees;
\ No newline at end of file
+SELECT * FROM employees;
+.output /dev/null
+SELECT sec_clear_context();
+SELECT sec_set_attr('role', 'finance');
+.output stdout
+
+.print ------------------------------------------------------------
+.print [Finance write before refresh]
+DELETE FROM employees WHERE id = 1;
+
+.print ------------------------------------------------------------
+.print [Finance]
+SELECT * FROM employees;
+DELETE FROM employees WHERE id = 1;
+SELECT * FROM employees;
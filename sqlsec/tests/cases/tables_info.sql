@@ -0,0 +1,26 @@
+.output /dev/null
+
+CREATE TABLE __sec_accounts (
+    id           INTEGER PRIMARY KEY,
+    row_label_id INTEGER NOT NULL,
+    owner        TEXT
+);
+CREATE TABLE __sec_orders (
+    id           INTEGER PRIMARY KEY,
+    row_label_id INTEGER NOT NULL,
+    total        INTEGER
+);
+
+.load ./target/debug/libsqlsec
+
+SELECT sec_define_label('true');
+SELECT sec_define_label('role=admin');
+
+SELECT sec_register_table('orders', '__sec_orders', 'row_label_id', NULL, 2, 0);
+SELECT sec_register_table('accounts', '__sec_accounts', 'row_label_id', 2, NULL);
+.output stdout
+
+.print ------------------------------------------------------------
+.print [Every registered table with its label config]
+SELECT * FROM sec_tables_info;
+
@@ -48,6 +48,17 @@ UPDATE employees SET salary = 999999 WHERE id = 1;
 .print [Developer trying to update title - should fail]
 UPDATE employees SET title = 'CEO' WHERE id = 1;
 
+.print ------------------------------------------------------------
+.print [Developer inserting a title - should fail]
+INSERT INTO employees (id, row_label_id, name, department, title)
+VALUES (4, 1, 'Dana', 'Sales', 'CEO');
+
+.print ------------------------------------------------------------
+.print [Developer inserting without salary or title - should succeed]
+INSERT INTO employees (id, row_label_id, name, department)
+VALUES (4, 1, 'Dana', 'Sales');
+SELECT id, name, salary, title FROM employees WHERE id = 4;
+
 .output /dev/null
 SELECT sec_clear_context();
 SELECT sec_set_attr('role', 'manager');
@@ -1,20 +1,100 @@
 use std::{
+    collections::HashMap,
     ffi::{CStr, CString},
     slice::from_raw_parts,
+    sync::{
+        LazyLock,
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
 };
 
 use libc::{RTLD_NEXT, c_char, c_int, c_void};
 
-use crate::{
-    Exec,
-    ExecCallback,
-    PrepareV2,
-    PrepareV3,
-    Sqlite3,
-    SqliteStmt,
-    debug,
-    parse_and_rewrite,
-};
+use crate::rewrite_statement;
+
+type Sqlite3 = c_void;
+type SqliteStmt = c_void;
+type ExecCallback = Option<
+    unsafe extern "C" fn(
+        arg: *mut c_void,
+        argc: c_int,
+        argv: *mut *mut c_char,
+        col_names: *mut *mut c_char,
+    ) -> c_int,
+>;
+
+/// Legacy `sqlite3_prepare`: same shape as v2.
+type PrepareV1 = unsafe extern "C" fn(
+    db: *mut Sqlite3,
+    z_sql: *const c_char,
+    n_byte: c_int,
+    pp_stmt: *mut *mut SqliteStmt,
+    pz_tail: *mut *const c_char,
+) -> c_int;
+
+type PrepareV2 = unsafe extern "C" fn(
+    db: *mut Sqlite3,
+    z_sql: *const c_char,
+    n_byte: c_int,
+    pp_stmt: *mut *mut SqliteStmt,
+    pz_tail: *mut *const c_char,
+) -> c_int;
+
+type PrepareV3 = unsafe extern "C" fn(
+    db: *mut Sqlite3,
+    z_sql: *const c_char,
+    n_byte: c_int,
+    prep_flags: u32,
+    pp_stmt: *mut *mut SqliteStmt,
+    pz_tail: *mut *const c_char,
+) -> c_int;
+
+/// `sqlite3_prepare16_v2`: UTF-16 SQL, with `n_byte` still in bytes.
+type Prepare16V2 = unsafe extern "C" fn(
+    db: *mut Sqlite3,
+    z_sql: *const c_void,
+    n_byte: c_int,
+    pp_stmt: *mut *mut SqliteStmt,
+    pz_tail: *mut *const c_void,
+) -> c_int;
+
+type Prepare16V3 = unsafe extern "C" fn(
+    db: *mut Sqlite3,
+    z_sql: *const c_void,
+    n_byte: c_int,
+    prep_flags: u32,
+    pp_stmt: *mut *mut SqliteStmt,
+    pz_tail: *mut *const c_void,
+) -> c_int;
+
+type Step = unsafe extern "C" fn(stmt: *mut SqliteStmt) -> c_int;
+
+type Finalize = unsafe extern "C" fn(stmt: *mut SqliteStmt) -> c_int;
+
+type DbHandle = unsafe extern "C" fn(stmt: *mut SqliteStmt) -> *mut Sqlite3;
+
+type Complete = unsafe extern "C" fn(sql: *const c_char) -> c_int;
+
+type Exec = unsafe extern "C" fn(
+    db: *mut Sqlite3,
+    sql: *const c_char,
+    callback: ExecCallback,
+    arg: *mut c_void,
+    errmsg: *mut *mut c_char,
+) -> c_int;
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_DONE: c_int = 101;
+
+pub(crate) unsafe fn resolve_prepare() -> PrepareV1 {
+    let cname = CString::new("sqlite3_prepare").unwrap();
+    let addr = unsafe { libc::dlsym(RTLD_NEXT, cname.as_ptr()) };
+    if addr.is_null() {
+        panic!("sqlshim: could not resolve sqlite3_prepare");
+    }
+    unsafe { std::mem::transmute(addr) }
+}
 
 pub(crate) unsafe fn resolve_prepare_v2() -> PrepareV2 {
     let cname = CString::new("sqlite3_prepare_v2").unwrap();
@@ -34,6 +114,60 @@ pub(crate) unsafe fn resolve_prepare_v3() -> PrepareV3 {
     unsafe { std::mem::transmute(addr) }
 }
 
+pub(crate) unsafe fn resolve_prepare16_v2() -> Prepare16V2 {
+    let cname = CString::new("sqlite3_prepare16_v2").unwrap();
+    let addr = unsafe { libc::dlsym(RTLD_NEXT, cname.as_ptr()) };
+    if addr.is_null() {
+        panic!("sqlshim: could not resolve sqlite3_prepare16_v2");
+    }
+    unsafe { std::mem::transmute(addr) }
+}
+
+pub(crate) unsafe fn resolve_prepare16_v3() -> Prepare16V3 {
+    let cname = CString::new("sqlite3_prepare16_v3").unwrap();
+    let addr = unsafe { libc::dlsym(RTLD_NEXT, cname.as_ptr()) };
+    if addr.is_null() {
+        panic!("sqlshim: could not resolve sqlite3_prepare16_v3");
+    }
+    unsafe { std::mem::transmute(addr) }
+}
+
+pub(crate) unsafe fn resolve_step() -> Step {
+    let cname = CString::new("sqlite3_step").unwrap();
+    let addr = unsafe { libc::dlsym(RTLD_NEXT, cname.as_ptr()) };
+    if addr.is_null() {
+        panic!("sqlshim: could not resolve sqlite3_step");
+    }
+    unsafe { std::mem::transmute(addr) }
+}
+
+pub(crate) unsafe fn resolve_finalize() -> Finalize {
+    let cname = CString::new("sqlite3_finalize").unwrap();
+    let addr = unsafe { libc::dlsym(RTLD_NEXT, cname.as_ptr()) };
+    if addr.is_null() {
+        panic!("sqlshim: could not resolve sqlite3_finalize");
+    }
+    unsafe { std::mem::transmute(addr) }
+}
+
+pub(crate) unsafe fn resolve_db_handle() -> DbHandle {
+    let cname = CString::new("sqlite3_db_handle").unwrap();
+    let addr = unsafe { libc::dlsym(RTLD_NEXT, cname.as_ptr()) };
+    if addr.is_null() {
+        panic!("sqlshim: could not resolve sqlite3_db_handle");
+    }
+    unsafe { std::mem::transmute(addr) }
+}
+
+pub(crate) unsafe fn resolve_complete() -> Complete {
+    let cname = CString::new("sqlite3_complete").unwrap();
+    let addr = unsafe { libc::dlsym(RTLD_NEXT, cname.as_ptr()) };
+    if addr.is_null() {
+        panic!("sqlshim: could not resolve sqlite3_complete");
+    }
+    unsafe { std::mem::transmute(addr) }
+}
+
 pub(crate) unsafe fn resolve_exec() -> Exec {
     let cname = CString::new("sqlite3_exec").unwrap();
     let addr = unsafe { libc::dlsym(RTLD_NEXT, cname.as_ptr()) };
@@ -43,6 +177,37 @@ pub(crate) unsafe fn resolve_exec() -> Exec {
     unsafe { std::mem::transmute(addr) }
 }
 
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sqlite3_prepare(
+    db: *mut Sqlite3,
+    z_sql: *const c_char,
+    n_byte: c_int,
+    pp_stmt: *mut *mut SqliteStmt,
+    pz_tail: *mut *const c_char,
+) -> c_int {
+    let real = unsafe { resolve_prepare() };
+    let sql = sql_from_prepare_args(z_sql, n_byte);
+
+    if let Some(sql) = sql.as_deref()
+        && let Some((rewrite, consumed)) = rewrite_statement("prepare", sql)
+        && let Some(csql) = rewritten_cstring("prepare", rewrite.sql)
+    {
+        unsafe { set_tail(pz_tail, z_sql, consumed) };
+        return unsafe {
+            prepare_rewritten(
+                db,
+                &csql,
+                rewrite.undo,
+                false,
+                pp_stmt,
+                |sql, stmt, tail| real(db, sql, -1, stmt, tail),
+            )
+        };
+    }
+
+    unsafe { real(db, z_sql, n_byte, pp_stmt, pz_tail) }
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sqlite3_prepare_v2(
     db: *mut Sqlite3,
@@ -54,17 +219,16 @@ pub unsafe extern "C" fn sqlite3_prepare_v2(
     let real = unsafe { resolve_prepare_v2() };
     let sql = sql_from_prepare_args(z_sql, n_byte);
 
-    if let Some(sql) = sql.as_deref() {
-        if let Some(new_sql) = parse_and_rewrite(sql) {
-            if debug() {
-                eprintln!("sqlshim: prepare_v2 rewrite!");
-                eprintln!("  original: {}", sql.trim());
-                eprintln!("  rewritten: {}", new_sql.trim());
-            }
-            if let Ok(csql) = CString::new(new_sql) {
-                return unsafe { real(db, csql.as_ptr(), -1, pp_stmt, pz_tail) };
-            }
-        }
+    if let Some(sql) = sql.as_deref()
+        && let Some((rewrite, consumed)) = rewrite_statement("prepare_v2", sql)
+        && let Some(csql) = rewritten_cstring("prepare_v2", rewrite.sql)
+    {
+        unsafe { set_tail(pz_tail, z_sql, consumed) };
+        return unsafe {
+            prepare_rewritten(db, &csql, rewrite.undo, true, pp_stmt, |sql, stmt, tail| {
+                real(db, sql, -1, stmt, tail)
+            })
+        };
     }
 
     unsafe { real(db, z_sql, n_byte, pp_stmt, pz_tail) }
@@ -82,17 +246,73 @@ pub unsafe extern "C" fn sqlite3_prepare_v3(
     let real = unsafe { resolve_prepare_v3() };
     let sql = sql_from_prepare_args(z_sql, n_byte);
 
-    if let Some(sql) = sql.as_deref() {
-        if let Some(new_sql) = parse_and_rewrite(sql) {
-            if debug() {
-                eprintln!("sqlshim: prepare_v3 rewrite!");
-                eprintln!("  original: {}", sql.trim());
-                eprintln!("  rewritten: {}", new_sql.trim());
-            }
-            if let Ok(csql) = CString::new(new_sql) {
-                return unsafe { real(db, csql.as_ptr(), -1, prep_flags, pp_stmt, pz_tail) };
-            }
-        }
+    if let Some(sql) = sql.as_deref()
+        && let Some((rewrite, consumed)) = rewrite_statement("prepare_v3", sql)
+        && let Some(csql) = rewritten_cstring("prepare_v3", rewrite.sql)
+    {
+        unsafe { set_tail(pz_tail, z_sql, consumed) };
+        return unsafe {
+            prepare_rewritten(db, &csql, rewrite.undo, true, pp_stmt, |sql, stmt, tail| {
+                real(db, sql, -1, prep_flags, stmt, tail)
+            })
+        };
+    }
+
+    unsafe { real(db, z_sql, n_byte, prep_flags, pp_stmt, pz_tail) }
+}
+
+/// UTF-16 SQL is decoded for the parser; a rewritten statement is handed
+/// to the UTF-8 `sqlite3_prepare_v2`, which yields the same statement.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sqlite3_prepare16_v2(
+    db: *mut Sqlite3,
+    z_sql: *const c_void,
+    n_byte: c_int,
+    pp_stmt: *mut *mut SqliteStmt,
+    pz_tail: *mut *const c_void,
+) -> c_int {
+    let real = unsafe { resolve_prepare16_v2() };
+    let sql = unsafe { sql_from_prepare16_args(z_sql, n_byte) };
+
+    if let Some(sql) = sql.as_deref()
+        && let Some((rewrite, consumed)) = rewrite_statement("prepare16_v2", sql)
+        && let Some(csql) = rewritten_cstring("prepare16_v2", rewrite.sql)
+    {
+        unsafe { set_tail16(pz_tail, z_sql, &sql[..consumed]) };
+        let prepare = unsafe { resolve_prepare_v2() };
+        return unsafe {
+            prepare_rewritten(db, &csql, rewrite.undo, true, pp_stmt, |sql, stmt, tail| {
+                prepare(db, sql, -1, stmt, tail)
+            })
+        };
+    }
+
+    unsafe { real(db, z_sql, n_byte, pp_stmt, pz_tail) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sqlite3_prepare16_v3(
+    db: *mut Sqlite3,
+    z_sql: *const c_void,
+    n_byte: c_int,
+    prep_flags: u32,
+    pp_stmt: *mut *mut SqliteStmt,
+    pz_tail: *mut *const c_void,
+) -> c_int {
+    let real = unsafe { resolve_prepare16_v3() };
+    let sql = unsafe { sql_from_prepare16_args(z_sql, n_byte) };
+
+    if let Some(sql) = sql.as_deref()
+        && let Some((rewrite, consumed)) = rewrite_statement("prepare16_v3", sql)
+        && let Some(csql) = rewritten_cstring("prepare16_v3", rewrite.sql)
+    {
+        unsafe { set_tail16(pz_tail, z_sql, &sql[..consumed]) };
+        let prepare = unsafe { resolve_prepare_v3() };
+        return unsafe {
+            prepare_rewritten(db, &csql, rewrite.undo, true, pp_stmt, |sql, stmt, tail| {
+                prepare(db, sql, -1, prep_flags, stmt, tail)
+            })
+        };
     }
 
     unsafe { real(db, z_sql, n_byte, prep_flags, pp_stmt, pz_tail) }
@@ -110,23 +330,249 @@ pub unsafe extern "C" fn sqlite3_exec(
     if sql.is_null() {
         return unsafe { real(db, sql, callback, arg, errmsg) };
     }
+    run_pending_undo(db);
     let sql_str = unsafe { CStr::from_ptr(sql).to_string_lossy() };
 
-    // sqlite3_exec can contain multiple statements - we need to handle each
-    // For now, try to rewrite the whole thing if it's a single custom statement
-    if let Some(new_sql) = parse_and_rewrite(&sql_str) {
-        if debug() {
-            eprintln!("sqlshim: exec rewrite!");
-            eprintln!("  original: {}", sql_str.trim());
-            eprintln!("  rewritten: {}", new_sql.trim());
+    // A leading custom statement runs as its rewrite; whatever follows it
+    // is exec'd in turn, so later statements, custom or not, still run.
+    if let Some((rewrite, consumed)) = rewrite_statement("exec", &sql_str)
+        && let Some(csql) = rewritten_cstring("exec", rewrite.sql)
+    {
+        let rc = unsafe { real(db, csql.as_ptr(), callback, arg, errmsg) };
+        if rc != SQLITE_OK {
+            defer_undo(db, rewrite.undo);
+            return rc;
+        }
+        let rest = &sql_str[consumed..];
+        if rest.trim().is_empty() {
+            return rc;
         }
-        let csql = CString::new(new_sql).unwrap();
-        return unsafe { real(db, csql.as_ptr(), callback, arg, errmsg) };
+        let rest = CString::new(rest).expect("no interior NUL in a CStr");
+        return unsafe { sqlite3_exec(db, rest.as_ptr(), callback, arg, errmsg) };
     }
 
     unsafe { real(db, sql, callback, arg, errmsg) }
 }
 
+/// Convert rewritten SQL for SQLite. An interior NUL (e.g. smuggled in
+/// through a label or context value) can't be passed on, so the caller
+/// falls back to the original statement instead.
+fn rewritten_cstring(via: &str, sql: String) -> Option<CString> {
+    CString::new(sql)
+        .inspect_err(|e| crate::log::discarded(via, &e.to_string()))
+        .ok()
+}
+
+/// Point the caller's tail just past the rewritten statement in their own
+/// buffer, so any statements after it are still prepared.
+unsafe fn set_tail(pz_tail: *mut *const c_char, z_sql: *const c_char, consumed: usize) {
+    if !pz_tail.is_null() {
+        unsafe { *pz_tail = z_sql.add(consumed) };
+    }
+}
+
+/// UTF-16 counterpart of [`set_tail`]: `consumed` is the rewritten prefix
+/// of the decoded SQL, re-measured in UTF-16 code units.
+unsafe fn set_tail16(pz_tail: *mut *const c_void, z_sql: *const c_void, consumed: &str) {
+    if !pz_tail.is_null() {
+        let units = consumed.encode_utf16().count();
+        unsafe { *pz_tail = z_sql.cast::<u16>().add(units).cast() };
+    }
+}
+
+/// Leading statements of a multi-statement rewrite, keyed by the statement
+/// handed back in their place and run on its first step.
+struct Deferred {
+    db: usize,
+    sql: CString,
+    /// The rewrite's [`Rewrite::undo`], should `sql` fail.
+    undo: Option<String>,
+    /// Whether the statement is a [`STAND_IN`], with nothing left to step.
+    stand_in: bool,
+}
+
+static DEFERRED: LazyLock<Mutex<HashMap<usize, Deferred>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Resolved once: every statement's steps and finalize pass through here.
+static REAL_STEP: LazyLock<Step> = LazyLock::new(|| unsafe { resolve_step() });
+static REAL_FINALIZE: LazyLock<Finalize> = LazyLock::new(|| unsafe { resolve_finalize() });
+
+/// Number of entries in [`DEFERRED`], so steps skip its lock while there
+/// are none.
+static PENDING_DEFERRED: AtomicUsize = AtomicUsize::new(0);
+
+/// Undo SQL of rewrites that failed part way, keyed by connection and run
+/// before its next statement. Running it straight away would replace the
+/// error the caller is about to read.
+static UNDO: LazyLock<Mutex<HashMap<usize, CString>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Number of entries in [`UNDO`], so statements skip its lock while there
+/// are none.
+static PENDING_UNDO: AtomicUsize = AtomicUsize::new(0);
+
+static REAL_DB_HANDLE: LazyLock<DbHandle> = LazyLock::new(|| unsafe { resolve_db_handle() });
+
+/// Queue a failed rewrite's undo to run before `db`'s next statement.
+fn defer_undo(db: *mut Sqlite3, undo: Option<String>) {
+    let Some(undo) = undo.and_then(|undo| CString::new(undo).ok()) else {
+        return;
+    };
+    if UNDO.lock().unwrap().insert(db as usize, undo).is_none() {
+        PENDING_UNDO.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// Run the undo queued for `db`, if any. Its own failure is not reported:
+/// the statement it undoes has already failed.
+fn run_pending_undo(db: *mut Sqlite3) {
+    if PENDING_UNDO.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    let Some(undo) = UNDO.lock().unwrap().remove(&(db as usize)) else {
+        return;
+    };
+    PENDING_UNDO.fetch_sub(1, Ordering::Release);
+    let exec = unsafe { resolve_exec() };
+    unsafe {
+        exec(
+            db,
+            undo.as_ptr(),
+            None,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+}
+
+/// Stand-in handed back when the last statement of a rewrite only prepares
+/// once the ones before it have run: no rows, and nothing done until then.
+const STAND_IN: &CStr = c"SELECT NULL WHERE 0";
+
+/// Byte offset in `sql` where its last statement starts, if there is more
+/// than one. `sqlite3_complete` knows where a trigger body's `;` ends.
+fn last_statement_start(sql: &CStr) -> Option<usize> {
+    let complete = unsafe { resolve_complete() };
+    let bytes = sql.to_bytes();
+    let mut last = None;
+    for (i, _) in bytes.iter().enumerate().filter(|(_, b)| **b == b';') {
+        let rest = &bytes[i + 1..];
+        if rest.iter().all(u8::is_ascii_whitespace) {
+            break;
+        }
+        let prefix = CString::new(&bytes[..=i]).expect("no interior NUL in a CStr");
+        if unsafe { complete(prefix.as_ptr()) } != 0 {
+            last = Some(i + 1);
+        }
+    }
+    last
+}
+
+/// Prepare a rewritten statement. A rewrite may expand into several
+/// statements (e.g. a table plus its triggers); only the last is handed
+/// back, and the ones before it run on its first step, so preparing a
+/// statement that is never stepped changes nothing. When the last one
+/// can't be prepared until they have run, or `reprepares` is false (legacy
+/// `sqlite3_prepare` can't recover from the schema changing under it), the
+/// whole rewrite runs on the first step of a [`STAND_IN`].
+unsafe fn prepare_rewritten(
+    db: *mut Sqlite3,
+    csql: &CStr,
+    undo: Option<String>,
+    reprepares: bool,
+    pp_stmt: *mut *mut SqliteStmt,
+    mut prepare: impl FnMut(*const c_char, *mut *mut SqliteStmt, *mut *const c_char) -> c_int,
+) -> c_int {
+    let mut tail: *const c_char = std::ptr::null();
+    let Some(start) = last_statement_start(csql) else {
+        return prepare(csql.as_ptr(), pp_stmt, &mut tail);
+    };
+    let bytes = csql.to_bytes();
+    let (leading, last) = bytes.split_at(start);
+    let last = CString::new(last).expect("no interior NUL in a CStr");
+
+    let (deferred, stand_in) =
+        if reprepares && prepare(last.as_ptr(), pp_stmt, &mut tail) == SQLITE_OK {
+            (
+                CString::new(leading).expect("no interior NUL in a CStr"),
+                false,
+            )
+        } else {
+            let rc = prepare(STAND_IN.as_ptr(), pp_stmt, &mut tail);
+            if rc != SQLITE_OK {
+                return rc;
+            }
+            (csql.to_owned(), true)
+        };
+
+    let stmt = unsafe { *pp_stmt };
+    if !stmt.is_null() {
+        let mut pending = DEFERRED.lock().unwrap();
+        let previous = pending.insert(
+            stmt as usize,
+            Deferred {
+                db: db as usize,
+                sql: deferred,
+                undo,
+                stand_in,
+            },
+        );
+        if previous.is_none() {
+            PENDING_DEFERRED.fetch_add(1, Ordering::Release);
+        }
+    }
+    SQLITE_OK
+}
+
+/// The deferred statements for `stmt`, if it has not been stepped yet.
+fn take_deferred(stmt: *mut SqliteStmt) -> Option<Deferred> {
+    if PENDING_DEFERRED.load(Ordering::Acquire) == 0 {
+        return None;
+    }
+    let deferred = DEFERRED.lock().unwrap().remove(&(stmt as usize));
+    if deferred.is_some() {
+        PENDING_DEFERRED.fetch_sub(1, Ordering::Release);
+    }
+    deferred
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sqlite3_step(stmt: *mut SqliteStmt) -> c_int {
+    if PENDING_UNDO.load(Ordering::Acquire) != 0 {
+        run_pending_undo(unsafe { REAL_DB_HANDLE(stmt) });
+    }
+    if let Some(deferred) = take_deferred(stmt) {
+        let exec = unsafe { resolve_exec() };
+        let rc = unsafe {
+            exec(
+                deferred.db as *mut Sqlite3,
+                deferred.sql.as_ptr(),
+                None,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        if rc != SQLITE_OK {
+            defer_undo(deferred.db as *mut Sqlite3, deferred.undo);
+            return rc;
+        }
+        // Stepping the stand-in itself would only find the schema changed
+        // under it, which legacy `sqlite3_prepare` statements report.
+        if deferred.stand_in {
+            return SQLITE_DONE;
+        }
+    }
+    unsafe { REAL_STEP(stmt) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sqlite3_finalize(stmt: *mut SqliteStmt) -> c_int {
+    // Forget statements never stepped, before SQLite reuses the address.
+    take_deferred(stmt);
+    unsafe { REAL_FINALIZE(stmt) }
+}
+
 fn sql_from_prepare_args(z_sql: *const c_char, n_byte: c_int) -> Option<String> {
     if z_sql.is_null() {
         return None;
@@ -143,10 +589,106 @@ fn sql_from_prepare_args(z_sql: *const c_char, n_byte: c_int) -> Option<String>
     std::str::from_utf8(bytes).ok().map(str::to_owned)
 }
 
+/// Decode native-endian UTF-16 SQL. As in SQLite, a non-negative `n_byte`
+/// is a length in bytes (an odd trailing byte is ignored) and the text
+/// also stops at the first NUL code unit.
+unsafe fn sql_from_prepare16_args(z_sql: *const c_void, n_byte: c_int) -> Option<String> {
+    if z_sql.is_null() {
+        return None;
+    }
+
+    let units = z_sql.cast::<u16>();
+    let max = if n_byte < 0 {
+        usize::MAX
+    } else {
+        n_byte as usize / 2
+    };
+    let mut len = 0;
+    while len < max && unsafe { units.add(len).read_unaligned() } != 0 {
+        len += 1;
+    }
+    let sql: Vec<u16> = (0..len)
+        .map(|i| unsafe { units.add(i).read_unaligned() })
+        .collect();
+
+    String::from_utf16(&sql).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn rewritten_cstring_rejects_interior_nul_without_panicking() {
+        let rewritten = crate::rewrite_sql("DEFINE LABEL 'role=a\0b';").unwrap();
+        assert!(rewritten.contains('\0'));
+        assert_eq!(rewritten_cstring("exec", rewritten), None);
+        assert!(rewritten_cstring("exec", "SELECT 1".to_string()).is_some());
+    }
+
+    #[test]
+    fn prepare_interposers_match_the_sqlite_signatures() {
+        let _: PrepareV1 = sqlite3_prepare;
+        let _: PrepareV2 = sqlite3_prepare_v2;
+        let _: PrepareV3 = sqlite3_prepare_v3;
+        let _: Prepare16V2 = sqlite3_prepare16_v2;
+        let _: Prepare16V3 = sqlite3_prepare16_v3;
+        let _: Step = sqlite3_step;
+        let _: Finalize = sqlite3_finalize;
+    }
+
+    fn utf16(sql: &str) -> Vec<u16> {
+        sql.encode_utf16().chain([0]).collect()
+    }
+
+    #[test]
+    fn sql_from_prepare16_args_counts_n_byte_in_bytes() {
+        let sql = utf16("DEFINE LABEL 'team=🚀'; SELECT 1");
+        let ptr = sql.as_ptr().cast();
+        let decode = |n_byte| unsafe { sql_from_prepare16_args(ptr, n_byte) };
+
+        assert_eq!(
+            decode(-1).as_deref(),
+            Some("DEFINE LABEL 'team=🚀'; SELECT 1")
+        );
+        assert_eq!(decode(2 * 6).as_deref(), Some("DEFINE"));
+        assert_eq!(decode(2 * 6 + 1).as_deref(), Some("DEFINE"));
+        assert_eq!(decode(0).as_deref(), Some(""));
+        assert_eq!(
+            unsafe { sql_from_prepare16_args(std::ptr::null(), -1) },
+            None
+        );
+    }
+
+    #[test]
+    fn sql_from_prepare16_args_stops_at_nul_and_rejects_lone_surrogates() {
+        let sql = utf16("SELECT 1\0DROP");
+        assert_eq!(
+            unsafe { sql_from_prepare16_args(sql.as_ptr().cast(), 2 * sql.len() as c_int) }
+                .as_deref(),
+            Some("SELECT 1")
+        );
+
+        let lone = [0xd800u16, 0];
+        assert_eq!(
+            unsafe { sql_from_prepare16_args(lone.as_ptr().cast(), -1) },
+            None
+        );
+    }
+
+    #[test]
+    fn utf16_define_label_is_rewritten_with_tail_past_it() {
+        let sql = utf16("DEFINE LABEL 'team=🚀';\nSELECT 1;");
+        let decoded = unsafe { sql_from_prepare16_args(sql.as_ptr().cast(), -1) }.unwrap();
+        let (rewritten, consumed) = rewrite_statement("prepare16_v2", &decoded).unwrap();
+        assert_eq!(rewritten.sql, "SELECT sec_define_label('team=🚀');");
+
+        let mut tail: *const c_void = std::ptr::null();
+        unsafe { set_tail16(&mut tail, sql.as_ptr().cast(), &decoded[..consumed]) };
+        let rest = unsafe { sql_from_prepare16_args(tail, -1) };
+        assert_eq!(rest.as_deref(), Some("\nSELECT 1;"));
+    }
+
     #[test]
     fn sql_from_prepare_args_handles_null_pointer() {
         assert_eq!(sql_from_prepare_args(std::ptr::null(), -1), None);
@@ -1,64 +1,86 @@
+#[cfg(feature = "preload")]
 mod ffi;
+mod log;
 pub mod parser;
 pub mod plugin;
 pub mod rewriter;
 pub mod statement;
 
-use libc::{c_char, c_int, c_void};
-
-type Sqlite3 = c_void;
-type SqliteStmt = c_void;
-type ExecCallback = Option<
-    unsafe extern "C" fn(
-        arg: *mut c_void,
-        argc: c_int,
-        argv: *mut *mut c_char,
-        col_names: *mut *mut c_char,
-    ) -> c_int,
->;
-
-type PrepareV2 = unsafe extern "C" fn(
-    db: *mut Sqlite3,
-    z_sql: *const c_char,
-    n_byte: c_int,
-    pp_stmt: *mut *mut SqliteStmt,
-    pz_tail: *mut *const c_char,
-) -> c_int;
-
-type PrepareV3 = unsafe extern "C" fn(
-    db: *mut Sqlite3,
-    z_sql: *const c_char,
-    n_byte: c_int,
-    prep_flags: u32,
-    pp_stmt: *mut *mut SqliteStmt,
-    pz_tail: *mut *const c_char,
-) -> c_int;
-
-type Exec = unsafe extern "C" fn(
-    db: *mut Sqlite3,
-    sql: *const c_char,
-    callback: ExecCallback,
-    arg: *mut c_void,
-    errmsg: *mut *mut c_char,
-) -> c_int;
-
-fn debug() -> bool {
-    std::env::var("SQLSHIM_DEBUG").is_ok()
+use crate::parser::Rewrite;
+
+/// Rewrite `sql` if it starts with one of the custom statements, e.g.
+/// `DEFINE LABEL` or `SET CONTEXT`, returning the plain SQL to run in its
+/// place. This is the same rewrite the preloaded shim applies, for
+/// applications that would rather call it before handing SQL to their own
+/// driver. Anything else, a statement excluded by `SQLSHIM_ALLOW`, or one
+/// whose rewrite fails `SQLSHIM_VALIDATE`, gives `None`.
+///
+/// ```
+/// assert_eq!(
+///     sqlshim::rewrite_sql("DEFINE LABEL 'x'").as_deref(),
+///     Some("SELECT sec_define_label('x');"),
+/// );
+/// assert_eq!(sqlshim::rewrite_sql("SELECT 1"), None);
+/// ```
+pub fn rewrite_sql(sql: &str) -> Option<String> {
+    rewrite_statement("rewrite_sql", sql).map(|(rewrite, _)| rewrite.sql)
 }
 
-fn parse_and_rewrite(sql: &str) -> Option<String> {
-    let result = parser::parse_rewrite(sql).map(|stmt| {
-        if debug() {
-            eprintln!("sqlshim: rewrite: {}", stmt);
-        }
-        stmt
-    });
+/// Whether statements from `plugin` (e.g. `SET CONTEXT`) may be rewritten,
+/// given the comma-separated `SQLSHIM_ALLOW` list. Without a list every
+/// statement is allowed.
+fn is_allowed(allow: Option<&str>, plugin: &str) -> bool {
+    allow.is_none_or(|list| {
+        list.split(',').any(|kind| {
+            kind.split_whitespace()
+                .map(str::to_uppercase)
+                .eq(plugin.split_whitespace())
+        })
+    })
+}
+
+/// Whether `SQLSHIM_VALIDATE` asks for each rewrite to be re-parsed before
+/// it reaches SQLite.
+fn validate_requested() -> bool {
+    std::env::var_os("SQLSHIM_VALIDATE").is_some()
+}
 
-    if debug() && result.is_none() {
-        eprintln!("sqlshim: passthrough: {}", sql.trim());
+/// The SQL to hand SQLite in place of `original`. With `validate`, a rewrite
+/// that does not parse is logged and dropped, so SQLite is given the original
+/// statement and reports its own error rather than one in SQL the
+/// application never wrote.
+fn accept_rewrite(via: &str, original: &str, rewrite: Rewrite, validate: bool) -> Option<Rewrite> {
+    if validate && let Err(e) = parser::validate_rewrite(&rewrite.sql) {
+        log::discarded(
+            via,
+            &format!(
+                "{} rewrite does not parse ({e}): {}",
+                rewrite.plugin,
+                rewrite.sql.trim()
+            ),
+        );
+        return None;
     }
+    log::rewrite(via, &rewrite.plugin, original, &rewrite.sql);
+    Some(rewrite)
+}
 
-    result
+/// Rewrite the leading statement of `sql`, also returning how many bytes of
+/// `sql` it spans so the rest can be handed back to SQLite as the tail.
+/// `via` names the intercepted entry point for the rewrite log.
+fn rewrite_statement(via: &str, sql: &str) -> Option<(Rewrite, usize)> {
+    let allow = std::env::var("SQLSHIM_ALLOW").ok();
+    match parser::parse_rewrite_statement(sql) {
+        Some((rewrite, len)) if is_allowed(allow.as_deref(), &rewrite.plugin) => {
+            accept_rewrite(via, &sql[..len], rewrite, validate_requested()).map(|r| (r, len))
+        }
+        // Statements off the allowlist reach SQLite untouched, which rejects
+        // them as unknown syntax.
+        _ => {
+            log::passthrough(via, sql);
+            None
+        }
+    }
 }
 
 #[cfg(test)]
@@ -98,26 +120,216 @@ mod tests {
             statement::CustomStatement::CreatePolicy(p) => {
                 assert_eq!(p.name, "test_pol");
                 assert_eq!(p.table, "users");
-                assert_eq!(p.operation, Some(PolicyOperation::Select));
+                assert_eq!(p.operations, vec![PolicyOperation::Select]);
                 assert_eq!(p.using_expr, "role = 'admin'");
             }
             _ => panic!("Expected CreatePolicy"),
         }
     }
 
+    #[test]
+    fn test_parse_create_policy_operation_list() {
+        let sql = "CREATE POLICY p ON t FOR SELECT, UPDATE USING (has_role('admin'));";
+        match parser::parse(sql).unwrap() {
+            statement::CustomStatement::CreatePolicy(p) => {
+                assert_eq!(
+                    p.operations,
+                    vec![PolicyOperation::Select, PolicyOperation::Update]
+                );
+            }
+            _ => panic!("Expected CreatePolicy"),
+        }
+
+        match parser::parse("CREATE POLICY p ON t USING (1);").unwrap() {
+            statement::CustomStatement::CreatePolicy(p) => {
+                assert_eq!(p.operations, vec![PolicyOperation::All]);
+            }
+            _ => panic!("Expected CreatePolicy"),
+        }
+    }
+
+    fn using_expr(sql: &str) -> String {
+        match parser::parse(sql).unwrap() {
+            statement::CustomStatement::CreatePolicy(p) => p.using_expr,
+            _ => panic!("Expected CreatePolicy"),
+        }
+    }
+
+    #[test]
+    fn test_parse_create_policy_nested_using() {
+        assert_eq!(
+            using_expr("CREATE POLICY p ON t USING (has_role('a') AND (x OR y));"),
+            "has_role ( 'a' ) AND ( x OR y )"
+        );
+        assert_eq!(
+            using_expr("CREATE POLICY p ON t USING ((a) OR (b));"),
+            "( a ) OR ( b )"
+        );
+        assert_eq!(
+            using_expr(
+                "CREATE POLICY p ON t FOR SELECT USING \
+                 (((a = 1 OR (b = 2 AND (c = 3 OR (d = 4))))));"
+            ),
+            "( ( a = 1 OR ( b = 2 AND ( c = 3 OR ( d = 4 ) ) ) ) )"
+        );
+    }
+
+    #[test]
+    fn test_parse_create_policy_function_heavy_using() {
+        assert_eq!(
+            using_expr(
+                "CREATE POLICY p ON t USING \
+                 (coalesce(lower(trim(owner)), upper(substr(name, 1, 3))) = sec_get_attr('user') \
+                  AND has_project_membership(ifnull(project_id, max(0, min(1, 2)))));"
+            ),
+            "coalesce ( lower ( trim ( owner ) ) , upper ( substr ( name , 1 , 3 ) ) ) = \
+             sec_get_attr ( 'user' ) AND has_project_membership ( ifnull ( project_id , max ( 0 , \
+             min ( 1 , 2 ) ) ) )"
+        );
+        // Parens inside string literals are not counted.
+        assert_eq!(
+            using_expr("CREATE POLICY p ON t USING (note <> ')' AND tag = '((');"),
+            "note <> ')' AND tag = '(('"
+        );
+    }
+
+    #[test]
+    fn test_parse_create_policy_rejects_trailing_tokens() {
+        for sql in [
+            "CREATE POLICY p ON t USING (a) OR (b);",
+            "CREATE POLICY p ON t USING (a));",
+            "CREATE POLICY p ON t USING ((a);",
+        ] {
+            assert!(
+                parser::CustomParser::new(sql, &plugin::PLUGIN_REGISTRY)
+                    .unwrap()
+                    .parse()
+                    .is_err(),
+                "{sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rewrite_create_policy_row_per_operation() {
+        let sql = "CREATE POLICY p ON t FOR SELECT, UPDATE, SELECT USING (has_role('admin'));";
+        let rewritten = rewrite_sql(sql).unwrap();
+        assert!(rewritten.contains("('p', 't', 'SELECT', NULL, 'has_role ( ''admin'' )')"));
+        assert!(rewritten.contains("('p', 't', 'UPDATE', NULL, 'has_role ( ''admin'' )')"));
+        assert_eq!(rewritten.matches("'SELECT'").count(), 1);
+        // Tables keyed by (name, table_name) alone are rebuilt.
+        assert!(rewritten.contains("PRIMARY KEY (name, table_name, operation)"));
+        assert!(
+            rewritten.contains("ALTER TABLE __sqlshim_policies_new RENAME TO __sqlshim_policies;")
+        );
+    }
+
+    #[test]
+    fn test_create_policy_if_not_exists() {
+        let sql = "CREATE POLICY IF NOT EXISTS p ON t USING (1);";
+        match parser::parse(sql).unwrap() {
+            statement::CustomStatement::CreatePolicy(p) => {
+                assert!(p.if_not_exists);
+                assert_eq!(p.name, "p");
+            }
+            _ => panic!("Expected CreatePolicy"),
+        }
+        let rewritten = rewrite_sql(sql).unwrap();
+        assert!(rewritten.contains("INSERT OR IGNORE INTO __sqlshim_policies"));
+        assert!(!rewritten.contains("SELECT name, table_name, 'DUPLICATE'"));
+
+        let rewritten = rewrite_sql("CREATE POLICY p ON t USING (1);").unwrap();
+        assert!(rewritten.contains("'DUPLICATE'"));
+        assert!(!rewritten.contains("CREATE VIEW"));
+        assert!(!rewritten.contains("CREATE TRIGGER"));
+        assert!(!rewritten.contains("DELETE FROM __sqlshim_policies"));
+    }
+
     #[test]
     fn test_parse_set_context() {
         let sql = "SET CONTEXT role = 'admin';";
         let stmt = parser::parse(sql).unwrap();
         match stmt {
             statement::CustomStatement::SetContext(s) => {
-                assert_eq!(s.key, "role");
-                assert_eq!(s.value, "admin");
+                assert_eq!(s.attrs, vec![("role".into(), "admin".into())]);
+            }
+            _ => panic!("Expected SetContext"),
+        }
+    }
+
+    #[test]
+    fn test_parse_set_context_multiple() {
+        let sql = "SET CONTEXT role = 'admin', team = 'finance', clearance = 'secret';";
+        let stmt = parser::parse(sql).unwrap();
+        match stmt {
+            statement::CustomStatement::SetContext(s) => {
+                assert_eq!(
+                    s.attrs,
+                    vec![
+                        ("role".into(), "admin".into()),
+                        ("team".into(), "finance".into()),
+                        ("clearance".into(), "secret".into()),
+                    ]
+                );
             }
             _ => panic!("Expected SetContext"),
         }
     }
 
+    #[test]
+    fn test_rewrite_set_context_multiple_refreshes_once() {
+        let sql = "SET CONTEXT role = 'admin', team = 'finance';";
+        let rewritten = rewrite_sql(sql).unwrap();
+        assert_eq!(rewritten.matches("sec_set_attr").count(), 2);
+        assert_eq!(rewritten.matches("sec_refresh_views").count(), 1);
+    }
+
+    #[test]
+    fn test_parse_set_context_from_json() {
+        let sql = r#"SET CONTEXT FROM '{"role":"admin","team":"finance","clearance":2}';"#;
+        let stmt = parser::parse(sql).unwrap();
+        match stmt {
+            statement::CustomStatement::SetContextFromJson(s) => {
+                assert_eq!(
+                    s.attrs,
+                    vec![
+                        ("clearance".into(), "2".into()),
+                        ("role".into(), "admin".into()),
+                        ("team".into(), "finance".into()),
+                    ]
+                );
+            }
+            _ => panic!("Expected SetContextFromJson"),
+        }
+    }
+
+    #[test]
+    fn test_rewrite_set_context_from_json_refreshes_once() {
+        let sql = r#"SET CONTEXT FROM '{"role":"o''brien","team":"finance"}';"#;
+        let rewritten = rewrite_sql(sql).unwrap();
+        assert_eq!(rewritten.matches("sec_set_attr").count(), 2);
+        assert!(rewritten.contains("sec_set_attr('role', 'o''brien')"));
+        assert_eq!(rewritten.matches("sec_refresh_views").count(), 1);
+    }
+
+    #[test]
+    fn test_parse_set_context_from_json_rejects_malformed() {
+        for sql in [
+            r#"SET CONTEXT FROM '{"role":';"#,
+            r#"SET CONTEXT FROM '["admin"]';"#,
+            r#"SET CONTEXT FROM '{"groups":["a","b"]}';"#,
+            r#"SET CONTEXT FROM '{"role":{"name":"admin"}}';"#,
+        ] {
+            assert!(
+                parser::CustomParser::new(sql, &plugin::PLUGIN_REGISTRY)
+                    .unwrap()
+                    .parse()
+                    .is_err(),
+                "{sql}"
+            );
+        }
+    }
+
     #[test]
     fn test_passthrough_normal_sql() {
         let sql = "SELECT * FROM users WHERE id = 1;";
@@ -127,14 +339,692 @@ mod tests {
     #[test]
     fn test_parse_rewrite_passthrough_normal_sql() {
         let sql = "BEGIN IMMEDIATE;";
-        assert!(parse_and_rewrite(sql).is_none());
+        assert!(rewrite_sql(sql).is_none());
     }
 
     #[test]
     fn test_rewrite_define_label() {
         let sql = "DEFINE LABEL 'role=admin';";
-        let rewritten = parse_and_rewrite(sql).unwrap();
+        let rewritten = rewrite_sql(sql).unwrap();
         assert!(rewritten.contains("sec_define_label"));
         assert!(rewritten.contains("role=admin"));
     }
+
+    #[test]
+    fn test_define_label_as_name() {
+        let stmt = parser::parse("DEFINE LABEL 'role=finance' AS finance_read;").unwrap();
+        match stmt {
+            statement::CustomStatement::DefineLabel(s) => {
+                assert_eq!(s.expr, "role=finance");
+                assert_eq!(s.name.as_deref(), Some("finance_read"));
+            }
+            _ => panic!("Expected DefineLabel"),
+        }
+        assert_eq!(
+            rewrite_sql("DEFINE LABEL 'role=finance' AS finance_read;").as_deref(),
+            Some("SELECT sec_define_named_label('finance_read', 'role=finance');")
+        );
+    }
+
+    #[test]
+    fn test_named_labels_are_looked_up_by_name() {
+        let rewritten = rewrite_sql(
+            "REGISTER SECURE TABLE ledger ON __ledger WITH ROW LABEL lbl \
+             TABLE LABEL finance_read INSERT LABEL 'role=clerk';",
+        )
+        .unwrap();
+        assert!(
+            rewritten.contains(
+                "sec_register_table('ledger', '__ledger', 'lbl', \
+                 sec_label_id('finance_read'), sec_define_label('role=clerk'), 0)"
+            ),
+            "{rewritten}"
+        );
+
+        let rewritten =
+            rewrite_sql("SET COLUMN SECURITY ledger.amount READ finance_read;").unwrap();
+        assert!(
+            rewritten.contains("SET read_label_id = sec_label_id('finance_read')"),
+            "{rewritten}"
+        );
+    }
+
+    #[test]
+    fn test_parse_set_column_security_none() {
+        let sql = "SET COLUMN SECURITY employees.salary READ NONE UPDATE 'role=hr';";
+        let stmt = parser::parse(sql).unwrap();
+        match stmt {
+            statement::CustomStatement::SetColumnSecurity(s) => {
+                assert_eq!(s.read_label, Some(LabelChange::Clear));
+                assert_eq!(
+                    s.update_label,
+                    Some(LabelChange::Set(LabelRef::Expr("role=hr".into())))
+                );
+            }
+            _ => panic!("Expected SetColumnSecurity"),
+        }
+    }
+
+    #[test]
+    fn test_rewrite_set_column_security_none_clears_label() {
+        let sql = "SET COLUMN SECURITY employees.salary READ NONE;";
+        let rewritten = rewrite_sql(sql).unwrap();
+        assert!(rewritten.contains("SET read_label_id = NULL"));
+        assert!(!rewritten.contains("sec_define_label"));
+        assert!(!rewritten.contains("update_label_id"));
+    }
+
+    #[test]
+    fn test_parse_create_changefeed() {
+        let sql = "CREATE CHANGEFEED orders_feed ON orders (id, status) WHERE status = 'open';";
+        let stmt = parser::parse(sql).unwrap();
+        match stmt {
+            statement::CustomStatement::CreateChangefeed(s) => {
+                assert_eq!(s.name, "orders_feed");
+                assert_eq!(s.table, "orders");
+                assert_eq!(s.columns, vec!["id", "status"]);
+                assert_eq!(s.filter.as_deref(), Some("status = 'open'"));
+            }
+            _ => panic!("Expected CreateChangefeed"),
+        }
+    }
+
+    #[test]
+    fn test_rewrite_create_changefeed_qualifies_filter() {
+        let sql = "CREATE CHANGEFEED orders_feed ON orders (id) WHERE status IN ('open', 'held') AND lower(region) = 'eu';";
+        let rewritten = rewrite_sql(sql).unwrap();
+        assert!(rewritten.contains(r#"CREATE TABLE IF NOT EXISTS "orders_feed_outbox""#));
+        assert!(
+            rewritten
+                .contains("WHEN NEW.status IN ( 'open' , 'held' ) AND lower ( NEW.region ) = 'eu'")
+        );
+        assert!(rewritten.contains("WHEN OLD.status IN"));
+        assert!(rewritten.contains(r#"json_object('id', OLD."id")"#));
+    }
+
+    #[test]
+    fn test_rewrite_changefeed_quotes_identifiers() {
+        let rewritten = rewrite_sql(r#"CREATE CHANGEFEED "f; --" ON "order s" ("it's");"#).unwrap();
+        assert!(rewritten.contains(r#"CREATE TABLE IF NOT EXISTS "f; --_outbox""#));
+        assert!(rewritten.contains(r#"AFTER INSERT ON "order s""#));
+        assert!(rewritten.contains(r#"json_object('it''s', NEW."it's")"#));
+
+        let rewritten = rewrite_sql(r#"DROP CHANGEFEED "f""x";"#).unwrap();
+        assert!(rewritten.contains(r#"DROP TRIGGER IF EXISTS "f""x_changefeed_update""#));
+        assert!(rewritten.contains(r#"DROP TABLE IF EXISTS "f""x_outbox""#));
+    }
+
+    #[test]
+    fn test_rewrite_drop_changefeed_keep_outbox() {
+        let rewritten = rewrite_sql("DROP CHANGEFEED orders_feed;").unwrap();
+        assert!(rewritten.contains(r#"DROP TRIGGER IF EXISTS "orders_feed_changefeed_insert""#));
+        assert!(rewritten.contains(r#"DROP TABLE IF EXISTS "orders_feed_outbox""#));
+
+        let rewritten = rewrite_sql("DROP CHANGEFEED orders_feed KEEP OUTBOX;").unwrap();
+        assert!(rewritten.contains(r#"DROP TRIGGER IF EXISTS "orders_feed_changefeed_delete""#));
+        assert!(!rewritten.contains("DROP TABLE"));
+    }
+
+    #[test]
+    fn test_rewrite_statement_reports_consumed_len() {
+        let sql = "SET CONTEXT role = 'admin';\n  SELECT 1;";
+        let (_, consumed) = rewrite_statement("prepare_v2", sql).unwrap();
+        assert_eq!(&sql[consumed..], "\n  SELECT 1;");
+
+        let sql = "CLEAR CONTEXT";
+        let (_, consumed) = rewrite_statement("prepare_v2", sql).unwrap();
+        assert_eq!(consumed, sql.len());
+    }
+
+    #[test]
+    fn test_trailing_junk_after_a_custom_statement_is_not_rewritten() {
+        for sql in [
+            "SET TENANT = 'a' garbage",
+            "SET TENANT = 'a' garbage; SELECT 1;",
+            "CLEAR CONTEXT now;",
+            "DEFINE LABEL 'x' 'y';",
+        ] {
+            assert!(rewrite_statement("prepare_v2", sql).is_none(), "{sql}");
+            assert!(
+                parser::CustomParser::new(sql, &plugin::PLUGIN_REGISTRY)
+                    .unwrap()
+                    .parse()
+                    .is_err(),
+                "{sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_multibyte_label_and_context_values() {
+        let rewritten = rewrite_sql("DEFINE LABEL 'team=🚀';").unwrap();
+        assert_eq!(rewritten, "SELECT sec_define_label('team=🚀');");
+
+        let sql = "SET CONTEXT team = '🚀ü', role = 'ß';\nSELECT 1;";
+        let (rewrite, consumed) = rewrite_statement("prepare_v2", sql).unwrap();
+        let rewritten = rewrite.sql;
+        assert!(
+            rewritten.contains("sec_set_attr('team', '🚀ü')"),
+            "{rewritten}"
+        );
+        assert!(
+            rewritten.contains("sec_set_attr('role', 'ß')"),
+            "{rewritten}"
+        );
+        assert_eq!(&sql[consumed..], "\nSELECT 1;");
+
+        // Multibyte characters ahead of the keywords must not panic either.
+        assert!(rewrite_sql("🚀 SET CONTEXT role = 'x';").is_none());
+        assert!(rewrite_sql("SET 🚀 CONTEXT role = 'x';").is_none());
+    }
+
+    /// Fuzz-style sweep: random mixes of plugin keywords, quotes and
+    /// multibyte characters must never make the parser panic.
+    #[test]
+    fn test_parser_never_panics_on_random_utf8() {
+        const FRAGMENTS: &[&str] = &[
+            "SET", "CONTEXT", "DEFINE", "LABEL", "LEVEL", "REGISTER", "SECURE", "TABLE", "CREATE",
+            "POLICY", "ON", "USING", "FROM", "WITH", "ROW", "AS", "SELECT", " ", "\n", "\r", "\t",
+            "'", "\"", "(", ")", ",", ";", "=", "--", "/*", "*/", "🚀", "ü", "ß", "日本",
+            "\u{200d}", "\u{fffd}", "é", "x", "1", "'🚀'",
+        ];
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..20_000 {
+            let len = next() % 16;
+            let sql: String = (0..len)
+                .map(|_| match next() % 4 {
+                    0 => char::from_u32((next() % 0x11_0000) as u32)
+                        .unwrap_or('\u{fffd}')
+                        .to_string(),
+                    _ => FRAGMENTS[(next() as usize) % FRAGMENTS.len()].to_string(),
+                })
+                .collect();
+            let _ = parser::parse(&sql);
+            if let Some((_, consumed)) = parser::parse_rewrite_statement(&sql) {
+                assert!(
+                    sql.is_char_boundary(consumed),
+                    "{sql:?} split at {consumed}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_refresh_views_accepts_both_spellings() {
+        for sql in ["REFRESH SECURE VIEWS;", "REFRESH SECURITY VIEWS;"] {
+            assert!(matches!(
+                parser::parse(sql),
+                Some(statement::CustomStatement::RefreshSecureViews)
+            ));
+            assert_eq!(
+                rewrite_sql(sql).as_deref(),
+                Some("SELECT sec_refresh_views();")
+            );
+            let (rewrite, _) = rewrite_statement("prepare_v2", sql).unwrap();
+            assert_eq!(rewrite.sql, "SELECT sec_refresh_views();");
+        }
+    }
+
+    #[test]
+    fn test_show_context_reads_sec_context() {
+        let rewritten = rewrite_sql("SHOW CONTEXT;").unwrap();
+        assert_eq!(rewritten, "SELECT key, value, level FROM sec_context;");
+    }
+
+    #[test]
+    fn test_list_secure_tables_reads_sec_tables_info() {
+        assert_eq!(
+            rewrite_sql("LIST SECURE TABLES;").unwrap(),
+            "SELECT logical_name, physical_name, row_label_col, table_label_id, \
+             insert_label_id, allow_implicit_label FROM sec_tables_info;"
+        );
+    }
+
+    #[test]
+    fn test_show_levels_lists_levels_in_order() {
+        assert_eq!(
+            rewrite_sql("SHOW LEVELS;").unwrap(),
+            "SELECT attr_name, level_name, level_value FROM sec_levels \
+             ORDER BY attr_name, level_value;"
+        );
+        assert_eq!(
+            rewrite_sql("SHOW LEVELS clearance;").unwrap(),
+            "SELECT attr_name, level_name, level_value FROM sec_levels \
+             WHERE attr_name = 'clearance' ORDER BY attr_name, level_value;"
+        );
+    }
+
+    #[test]
+    fn test_parse_create_tenant_table() {
+        let sql = "CREATE TENANT TABLE notes (id INTEGER PRIMARY KEY, body TEXT NOT NULL, UNIQUE (body));";
+        let stmt = parser::parse(sql).unwrap();
+        match stmt {
+            statement::CustomStatement::CreateTenantTable(s) => {
+                assert_eq!(s.name, "notes");
+                assert_eq!(s.columns, vec!["id", "body"]);
+                assert_eq!(s.key_columns, vec!["id"]);
+                assert_eq!(
+                    s.definitions,
+                    vec!["\"id\" INTEGER", "\"body\" TEXT NOT NULL"]
+                );
+                assert_eq!(
+                    s.constraints,
+                    vec![
+                        "PRIMARY KEY (\"tenant_id\", \"id\")",
+                        "UNIQUE (\"tenant_id\", body )"
+                    ]
+                );
+            }
+            _ => panic!("Expected CreateTenantTable"),
+        }
+
+        assert!(parser::parse("CREATE TENANT TABLE notes (body TEXT);").is_none());
+        assert!(
+            parser::parse("CREATE TENANT TABLE notes (id INTEGER PRIMARY KEY AUTOINCREMENT);")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_rewrite_create_tenant_table() {
+        let sql = "CREATE TENANT TABLE notes (id INTEGER, body TEXT CONSTRAINT one UNIQUE ON CONFLICT IGNORE, PRIMARY KEY (id));";
+        let rewritten = rewrite_sql(sql).unwrap();
+        assert!(rewritten.contains("CREATE TABLE \"__tenant_notes\""));
+        assert!(rewritten.contains(
+            "\"tenant_id\" TEXT NOT NULL ON CONFLICT REPLACE DEFAULT (sec_get_attr('tenant'))"
+        ));
+        assert!(
+            rewritten
+                .contains("CONSTRAINT one UNIQUE (\"tenant_id\", \"body\") ON CONFLICT IGNORE")
+        );
+        assert!(rewritten.contains("PRIMARY KEY (\"tenant_id\", id )"));
+        assert!(rewritten.contains("sec_define_label('tenant=' || NEW.\"tenant_id\")"));
+        assert!(rewritten.contains(
+            "SELECT sec_register_table('notes', '__tenant_notes', 'tenant_label', NULL, NULL);"
+        ));
+    }
+
+    #[test]
+    fn test_rewrite_create_tenant_table_quotes_identifiers() {
+        let rewritten =
+            rewrite_sql(r#"CREATE TENANT TABLE "it's ""x""" ("a b" TEXT PRIMARY KEY);"#).unwrap();
+        assert!(rewritten.contains(r#"CREATE TABLE "__tenant_it's ""x""" ("#));
+        assert!(rewritten.contains(r#""a b" TEXT,"#));
+        assert!(rewritten.contains(r#"PRIMARY KEY ("tenant_id", "a b")"#));
+        assert!(rewritten.contains(r#"sec_register_table('it''s "x"', '__tenant_it''s "x"', "#));
+    }
+
+    #[test]
+    fn test_parse_import_tenant() {
+        let Some(CustomStatement::ImportTenant(stmt)) = parser::parse(
+            "IMPORT TENANT 'acme' FROM 'old.db' INTO notes (id, body), tasks (id) ON CONFLICT replace;",
+        ) else {
+            panic!("expected IMPORT TENANT");
+        };
+        assert_eq!(stmt.tenant, "acme");
+        assert_eq!(stmt.path, "old.db");
+        assert_eq!(
+            stmt.tables,
+            vec![
+                ImportTable {
+                    name: "notes".into(),
+                    columns: vec!["id".into(), "body".into()],
+                },
+                ImportTable {
+                    name: "tasks".into(),
+                    columns: vec!["id".into()],
+                },
+            ]
+        );
+        assert_eq!(stmt.on_conflict, ImportConflict::Replace);
+
+        let Some(CustomStatement::ImportTenant(stmt)) =
+            parser::parse("IMPORT TENANT 'acme' FROM 'old.db' INTO notes (id)")
+        else {
+            panic!("expected IMPORT TENANT");
+        };
+        assert_eq!(stmt.on_conflict, ImportConflict::Error);
+
+        assert!(
+            parser::parse("IMPORT TENANT 'acme' FROM 'old.db' INTO notes (id) ON CONFLICT MERGE;")
+                .is_none()
+        );
+        assert!(parser::parse("IMPORT TENANT 'acme' FROM 'old.db' INTO notes;").is_none());
+        assert!(parser::parse("IMPORT TENANT 'acme' FROM 'old.db';").is_none());
+    }
+
+    #[test]
+    fn test_rewrite_import_tenant_per_strategy() {
+        for (strategy, conflict) in [
+            ("SKIP", Some("ON CONFLICT DO NOTHING;")),
+            (
+                "REPLACE",
+                Some(
+                    "ON CONFLICT DO UPDATE SET \"id\" = excluded.\"id\", \"body\" = excluded.\"body\"\n\
+                     WHERE \"tenant_id\" = 'o''brien';",
+                ),
+            ),
+            ("ERROR", None),
+        ] {
+            let rewritten = rewrite_sql(&format!(
+                "IMPORT TENANT 'o''brien' FROM 'old.db' INTO notes (id, body) ON CONFLICT {strategy};"
+            ))
+            .unwrap();
+            assert!(rewritten.contains("ATTACH DATABASE 'old.db' AS __sqlshim_import;"));
+            assert!(rewritten.contains(
+                "SELECT \"id\", \"body\", \"tenant_id\" FROM __sqlshim_import.\"__tenant_notes\"\n\
+                 WHERE \"tenant_id\" = 'o''brien';"
+            ));
+            let insert = "INSERT OR ROLLBACK INTO main.\"__tenant_notes\" (\"id\", \"body\", \"tenant_id\")\n\
+                          SELECT \"id\", \"body\", \"tenant_id\" FROM temp.\"__sqlshim_import_notes\"";
+            assert!(rewritten.contains(insert));
+            // The source is detached before the first insert can fail, and
+            // the copies are one savepoint.
+            let detach = rewritten.find("DETACH").unwrap();
+            let savepoint = rewritten.find("SAVEPOINT __sqlshim_import;").unwrap();
+            let release = rewritten.find("RELEASE __sqlshim_import;").unwrap();
+            assert!(detach < savepoint && savepoint < rewritten.find(insert).unwrap());
+            assert!(rewritten.rfind(insert).unwrap() < release);
+            // Only SKIP and REPLACE look for keys another tenant uses.
+            assert_eq!(
+                rewritten.contains("ROLLBACK TO __sqlshim_import_probe;"),
+                conflict.is_some()
+            );
+            if let Some(conflict) = conflict {
+                assert!(rewritten.contains(&format!("{insert} WHERE true\n{conflict}")));
+            }
+        }
+    }
+
+    #[test]
+    fn test_import_tenant_restores_the_callers_tenant() {
+        let sql = "IMPORT TENANT 'acme' FROM 'old.db' INTO notes (id);";
+        let (rewrite, _) = rewrite_statement("prepare_v2", sql).unwrap();
+        let rewritten = &rewrite.sql;
+        // The tenant is switched in a context of its own, popped once the
+        // copy is released...
+        let push = rewritten
+            .find("SELECT sec_push_context('__sqlshim_import');")
+            .unwrap();
+        let set = rewritten
+            .find("SELECT sec_set_attr('tenant', 'acme');")
+            .unwrap();
+        let release = rewritten.find("RELEASE __sqlshim_import;").unwrap();
+        let pop = rewritten
+            .find("SELECT sec_pop_context('__sqlshim_import');")
+            .unwrap();
+        assert!(push < set && set < release && release < pop);
+        // ...or by the undo, when a conflict rolls the import back first.
+        assert_eq!(
+            rewrite.undo.as_deref(),
+            Some("SELECT sec_pop_context('__sqlshim_import');")
+        );
+        let (rewrite, _) = rewrite_statement("prepare_v2", "SET TENANT = 'acme';").unwrap();
+        assert_eq!(rewrite.undo, None);
+    }
+
+    #[test]
+    fn test_parse_unregister_secure_table() {
+        let stmt = parser::parse("UNREGISTER SECURE TABLE employees;").unwrap();
+        match stmt {
+            statement::CustomStatement::UnregisterSecureTable(s) => {
+                assert_eq!(s.logical_name, "employees");
+            }
+            _ => panic!("Expected UnregisterSecureTable"),
+        }
+    }
+
+    #[test]
+    fn test_register_secure_table_allow_implicit_label() {
+        let stmt = parser::parse(
+            "REGISTER SECURE TABLE docs ON __docs WITH ROW LABEL lbl \
+             INSERT LABEL 'role=editor' ALLOW IMPLICIT LABEL;",
+        )
+        .unwrap();
+        match stmt {
+            statement::CustomStatement::RegisterSecureTable(s) => {
+                assert!(s.allow_implicit_label);
+                assert_eq!(s.insert_label, Some(LabelRef::Expr("role=editor".into())));
+            }
+            _ => panic!("Expected RegisterSecureTable"),
+        }
+
+        let allowed = rewrite_sql(
+            "REGISTER SECURE TABLE docs ON __docs WITH ROW LABEL lbl ALLOW IMPLICIT LABEL;",
+        )
+        .unwrap();
+        assert!(allowed.contains("sec_register_table('docs', '__docs', 'lbl', NULL, NULL, 1)"));
+
+        let disallowed =
+            rewrite_sql("REGISTER SECURE TABLE docs ON __docs WITH ROW LABEL lbl;").unwrap();
+        assert!(disallowed.contains("sec_register_table('docs', '__docs', 'lbl', NULL, NULL, 0)"));
+    }
+
+    #[test]
+    fn test_register_secure_table_composite_row_label() {
+        let rewritten = rewrite_sql(
+            "REGISTER SECURE TABLE staff ON __staff WITH ROW LABEL (dept_label, clearance_label);",
+        )
+        .unwrap();
+        assert!(rewritten.contains(
+            "sec_register_table('staff', '__staff', 'dept_label,clearance_label', NULL, NULL, 0)"
+        ));
+        let rewritten = rewrite_sql(
+            r#"REGISTER SECURE TABLE staff ON __staff WITH ROW LABEL ("dept,label", clearance_label);"#,
+        )
+        .unwrap();
+        assert!(rewritten.contains(
+            r#"sec_register_table('staff', '__staff', '"dept,label",clearance_label', NULL, NULL, 0)"#
+        ));
+
+        let unclosed = "REGISTER SECURE TABLE staff ON __staff WITH ROW LABEL (dept_label;";
+        assert!(
+            parser::CustomParser::new(unclosed, &plugin::PLUGIN_REGISTRY)
+                .unwrap()
+                .parse()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_rewrite_unregister_secure_table() {
+        let rewritten = rewrite_sql("UNREGISTER SECURE TABLE employees;").unwrap();
+        assert!(rewritten.contains("DELETE FROM sec_columns WHERE logical_table = 'employees'"));
+        assert!(rewritten.contains("DELETE FROM sec_tables WHERE logical_name = 'employees'"));
+        for suffix in ["ins", "upd", "del"] {
+            assert!(rewritten.contains(&format!(
+                r#"DROP TRIGGER IF EXISTS temp."employees_sec_{suffix}""#
+            )));
+        }
+        assert!(rewritten.contains(r#"DROP VIEW IF EXISTS temp."employees""#));
+    }
+
+    #[test]
+    fn test_rewrite_drop_secure_view() {
+        let rewritten = rewrite_sql("DROP SECURE VIEW employee_view;").unwrap();
+        assert_eq!(rewritten.trim(), r#"DROP VIEW IF EXISTS "employee_view";"#);
+    }
+
+    #[test]
+    fn test_rewrite_create_secure_view_trailing_semicolon() {
+        let rewritten = rewrite_sql(
+            "CREATE SECURE VIEW finance AS SELECT id, name FROM employees -- trailing\n;",
+        )
+        .unwrap();
+        assert!(rewritten.contains(r#"CREATE VIEW "finance" AS"#));
+        assert!(rewritten.contains("FROM (SELECT id, name FROM employees) AS t"));
+        assert!(!rewritten.contains("trailing"));
+    }
+
+    #[test]
+    fn test_rewrite_create_secure_view_order_by() {
+        let rewritten = rewrite_sql(
+            "CREATE SECURE VIEW top_paid AS SELECT name FROM employees ORDER BY salary DESC LIMIT 5;",
+        )
+        .unwrap();
+        assert!(
+            rewritten
+                .contains("FROM (SELECT name FROM employees ORDER BY salary DESC LIMIT 5) AS t\n")
+        );
+        assert!(rewritten.contains("WHERE sec_assert_fresh();"));
+    }
+
+    #[test]
+    fn test_parse_create_secure_view_rejects_non_select() {
+        for sql in [
+            "CREATE SECURE VIEW v AS VALUES (1, 2);",
+            "CREATE SECURE VIEW v AS SELECT 1 FROM t garbage here;",
+        ] {
+            let result = parser::CustomParser::new(sql, &plugin::PLUGIN_REGISTRY)
+                .unwrap()
+                .parse();
+            assert!(result.is_err(), "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_escape_sql_ident_doubles_quotes() {
+        assert_eq!(rewriter::escape_sql_ident("plain"), r#""plain""#);
+        assert_eq!(
+            rewriter::escape_sql_ident(r#"a" ; DROP TABLE t; --"#),
+            r#""a"" ; DROP TABLE t; --""#
+        );
+    }
+
+    #[test]
+    fn test_rewrite_unregister_quotes_adversarial_identifier() {
+        let rewritten =
+            rewrite_sql(r#"UNREGISTER SECURE TABLE "x"" ; DROP TABLE t; --";"#).unwrap();
+        assert!(rewritten.contains(r#"DROP VIEW IF EXISTS temp."x"" ; DROP TABLE t; --";"#));
+        assert!(
+            rewritten.contains(r#"DROP TRIGGER IF EXISTS temp."x"" ; DROP TABLE t; --_sec_ins";"#)
+        );
+        assert!(rewritten.contains(r#"WHERE logical_table = 'x" ; DROP TABLE t; --';"#));
+    }
+
+    #[test]
+    fn test_rewrite_set_tenant_replaces_attr() {
+        let rewritten = rewrite_sql("SET TENANT = 'o''brien';").unwrap();
+        assert!(rewritten.contains("sec_clear_attr('tenant')"));
+        assert!(rewritten.contains("sec_set_attr('tenant', 'o''brien')"));
+        assert!(rewritten.ends_with("SELECT sec_refresh_views();\n"));
+    }
+
+    #[test]
+    fn test_rewrite_log_file_records_rewrites() {
+        let path = std::env::temp_dir().join(format!("sqlshim-log-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (rewrite, _) = parser::parse_rewrite_statement("REFRESH SECURE VIEWS;").unwrap();
+        let rewritten = rewrite.sql.clone();
+        log::rewrite_to(
+            Some(path.as_os_str()),
+            "rewrite_sql",
+            &rewrite.plugin,
+            "REFRESH SECURE VIEWS;",
+            &rewrite.sql,
+        );
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let record = log
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .find(|record| record["original"] == "REFRESH SECURE VIEWS;")
+            .expect("rewrite recorded");
+        assert_eq!(record["plugin"], "REFRESH SECURE VIEWS");
+        assert_eq!(record["via"], "rewrite_sql");
+        assert_eq!(record["rewritten"], rewritten.trim());
+        assert!(record["timestamp_ms"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_every_rewrite_passes_validation() {
+        for sql in [
+            "DEFINE LABEL 'role=admin' AS admins;",
+            "DEFINE LEVEL clearance 'secret' = 2;",
+            "SHOW LEVELS clearance;",
+            "SHOW CONTEXT;",
+            "LIST SECURE TABLES;",
+            "CREATE POLICY p ON t FOR SELECT, UPDATE USING (owner = sec_get_attr('user'));",
+            "CREATE POLICY IF NOT EXISTS p ON t USING (1);",
+            "DROP POLICY p ON t;",
+            "SET CONTEXT role = 'admin', team = 'x';",
+            "SET CONTEXT FROM '{\"role\": \"admin\"}';",
+            "CLEAR CONTEXT;",
+            "PUSH CONTEXT;",
+            "POP CONTEXT;",
+            "REFRESH SECURE VIEWS;",
+            "CREATE SECURE VIEW v AS SELECT id FROM e WHERE d = 'f' ORDER BY id LIMIT 5;",
+            "DROP SECURE VIEW v;",
+            "REGISTER SECURE TABLE e ON __e WITH ROW LABEL (a, b) TABLE LABEL admins \
+             INSERT LABEL 'role=x' ALLOW IMPLICIT LABEL;",
+            "UNREGISTER SECURE TABLE e;",
+            "SET COLUMN SECURITY e.s READ 'role=hr' UPDATE NONE;",
+            "SET TENANT = 'acme';",
+            "CREATE TENANT TABLE notes (id INTEGER, body TEXT, PRIMARY KEY (id));",
+            "IMPORT TENANT 'acme' FROM '/tmp/a.db' INTO notes (id, body), tasks (id) ON CONFLICT SKIP;",
+            "IMPORT TENANT 'acme' FROM '/tmp/a.db' INTO notes (id, body) ON CONFLICT REPLACE;",
+            "ENABLE AUDIT ON t FOR INSERT, UPDATE;",
+            "CREATE CHANGEFEED f ON orders (id, status) WHERE status = 'open';",
+            "DROP CHANGEFEED f KEEP OUTBOX;",
+        ] {
+            let (rewrite, _) = parser::parse_rewrite_statement(sql).expect(sql);
+            if let Err(e) = parser::validate_rewrite(&rewrite.sql) {
+                panic!("{sql}\n{}\n{e}", rewrite.sql);
+            }
+        }
+    }
+
+    #[test]
+    fn test_validation_accepts_sqlite_only_spellings() {
+        parser::validate_rewrite(
+            "CREATE TRIGGER IF NOT EXISTS g INSTEAD OF UPDATE ON v BEGIN \
+             UPDATE t SET body = 'it''s' WHERE id IS OLD.id AND body IS NOT NULL; END;",
+        )
+        .unwrap();
+        assert!(parser::validate_rewrite("SELECT 1 WHERE a IS;").is_err());
+    }
+
+    #[test]
+    fn test_validation_falls_back_on_a_broken_rewrite() {
+        let broken = || Rewrite {
+            plugin: "DEFINE LABEL".into(),
+            // An unbalanced quote, as a bad escape of a label would leave.
+            sql: "SELECT sec_define_label('role=o'brien');".into(),
+            undo: None,
+        };
+        let original = "DEFINE LABEL 'role=o''brien';";
+
+        let accept =
+            |rewrite, validate| accept_rewrite("test", original, rewrite, validate).map(|r| r.sql);
+        assert_eq!(accept(broken(), true), None);
+        assert_eq!(
+            accept(broken(), false).as_deref(),
+            Some("SELECT sec_define_label('role=o'brien');")
+        );
+
+        let good = rewrite_sql(original).unwrap();
+        let (rewrite, _) = parser::parse_rewrite_statement(original).unwrap();
+        assert_eq!(accept(rewrite, true), Some(good));
+    }
+
+    #[test]
+    fn test_allowlist_matches_statement_kinds() {
+        assert!(is_allowed(None, "DEFINE LABEL"));
+        assert!(is_allowed(
+            Some("SET CONTEXT, create  policy"),
+            "CREATE POLICY"
+        ));
+        assert!(is_allowed(Some("SET CONTEXT,CREATE POLICY"), "SET CONTEXT"));
+        assert!(!is_allowed(Some("SET CONTEXT"), "SET CONTEXT FROM"));
+        assert!(!is_allowed(Some("SET CONTEXT"), "DEFINE LABEL"));
+        assert!(!is_allowed(Some(""), "SET CONTEXT"));
+    }
 }
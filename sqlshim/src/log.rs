@@ -0,0 +1,90 @@
+//! Single sink for rewrite diagnostics.
+//!
+//! With `SQLSHIM_LOG_FILE` set, every rewrite is appended to that file as one
+//! JSON object per line. Otherwise `SQLSHIM_DEBUG` prints rewrites and
+//! passthroughs to stderr.
+
+use std::{
+    ffi::OsStr,
+    fs::OpenOptions,
+    io::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{Value, json};
+
+const LOG_FILE_VAR: &str = "SQLSHIM_LOG_FILE";
+const DEBUG_VAR: &str = "SQLSHIM_DEBUG";
+
+fn debug() -> bool {
+    std::env::var_os(DEBUG_VAR).is_some()
+}
+
+fn timestamp_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis())
+}
+
+fn append(path: &OsStr, record: Value) {
+    // Never let a logging failure disturb the host program's query.
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = file.write_all(format!("{record}\n").as_bytes());
+    }
+}
+
+/// Record that `original` was rewritten to `rewritten` by `plugin`.
+pub(crate) fn rewrite(via: &str, plugin: &str, original: &str, rewritten: &str) {
+    let log_file = std::env::var_os(LOG_FILE_VAR);
+    rewrite_to(log_file.as_deref(), via, plugin, original, rewritten);
+}
+
+/// [`rewrite`] with the `SQLSHIM_LOG_FILE` value given as `log_file`.
+pub(crate) fn rewrite_to(
+    log_file: Option<&OsStr>,
+    via: &str,
+    plugin: &str,
+    original: &str,
+    rewritten: &str,
+) {
+    if let Some(path) = log_file {
+        append(
+            path,
+            json!({
+                "timestamp_ms": timestamp_ms(),
+                "via": via,
+                "plugin": plugin,
+                "original": original.trim(),
+                "rewritten": rewritten.trim(),
+            }),
+        );
+    } else if debug() {
+        eprintln!("sqlshim: {via} rewrite ({plugin})");
+        eprintln!("  original: {}", original.trim());
+        eprintln!("  rewritten: {}", rewritten.trim());
+    }
+}
+
+/// Record that `sql` was passed through to SQLite unchanged.
+pub(crate) fn passthrough(via: &str, sql: &str) {
+    if std::env::var_os(LOG_FILE_VAR).is_none() && debug() {
+        eprintln!("sqlshim: {via} passthrough: {}", sql.trim());
+    }
+}
+
+/// Record that a rewrite was thrown away and the original SQL handed to
+/// SQLite instead.
+pub(crate) fn discarded(via: &str, reason: &str) {
+    if let Some(path) = std::env::var_os(LOG_FILE_VAR) {
+        append(
+            &path,
+            json!({
+                "timestamp_ms": timestamp_ms(),
+                "via": via,
+                "discarded": reason,
+            }),
+        );
+    } else if debug() {
+        eprintln!("sqlshim: {via} rewrite discarded: {reason}");
+    }
+}
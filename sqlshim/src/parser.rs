@@ -1,12 +1,13 @@
 use sqlparser::{
     ast::Ident,
-    dialect::{Dialect, GenericDialect},
+    dialect::{Dialect, GenericDialect, SQLiteDialect},
+    keywords::Keyword,
     parser::{Parser, ParserError},
-    tokenizer::{Token, TokenWithSpan},
+    tokenizer::{Location, Token, TokenWithSpan, Tokenizer},
 };
 
 use crate::{
-    plugin::{PLUGIN_REGISTRY, PluginRegistry},
+    plugin::{CustomPlugin, PLUGIN_REGISTRY, PluginRegistry},
     statement::*,
 };
 
@@ -26,6 +27,16 @@ impl Dialect for CustomDialect {
     }
 }
 
+/// A custom statement rewritten into plain SQL
+pub struct Rewrite {
+    /// Keyword prefix of the plugin that matched, e.g. `CREATE SECURE VIEW`
+    pub plugin: String,
+    pub sql: String,
+    /// SQL undoing what the rewrite changed in the session, should it fail
+    /// part way; see [`CustomPlugin::undo`].
+    pub undo: Option<String>,
+}
+
 /// Wraps sqlparser's Parser for custom statement parsing
 pub struct CustomParser {
     parser: Parser<'static>,
@@ -54,6 +65,29 @@ fn consume_prefix(parser: &mut Parser<'_>, words: &[&str]) -> Result<(), ParserE
     Ok(())
 }
 
+/// Parse the statement `plugin` matched. It must end at `;` or the end of
+/// the SQL: anything left over is an error, never the start of the next
+/// statement.
+fn parse_plugin_statement(
+    parser: &mut Parser<'_>,
+    plugin: &(dyn CustomPlugin + Send + Sync),
+) -> Result<CustomStatement, ParserError> {
+    consume_prefix(parser, plugin.prefix())?;
+    let stmt = plugin.parse(parser)?;
+    if !parser.is_statement_end() {
+        let token = parser.peek_token();
+        return Err(error_at_span(
+            &format!(
+                "Expected end of statement after {}, got '{}'",
+                plugin.prefix().join(" "),
+                token.token
+            ),
+            &token,
+        ));
+    }
+    Ok(stmt)
+}
+
 impl CustomParser {
     pub fn new(sql: &str, registry: &'static PluginRegistry) -> Result<Self, ParserError> {
         let parser = Parser::new(&CUSTOM_DIALECT).try_with_sql(sql)?;
@@ -64,9 +98,7 @@ impl CustomParser {
     pub fn parse(&mut self) -> Result<Option<CustomStatement>, ParserError> {
         let Self { parser, registry } = self;
         if let Some(plugin) = registry.find_match(parser) {
-            consume_prefix(parser, plugin.prefix())?;
-            let stmt = plugin.parse(parser)?;
-            return Ok(Some(stmt));
+            return parse_plugin_statement(parser, plugin).map(Some);
         }
 
         // Fall back to standard SQL parsing
@@ -74,18 +106,49 @@ impl CustomParser {
     }
 
     /// Parse and rewrite a single statement
-    pub fn parse_rewrite(&mut self) -> Result<Option<String>, ParserError> {
+    pub fn parse_rewrite(&mut self) -> Result<Option<Rewrite>, ParserError> {
         let Self { parser, registry } = self;
         if let Some(plugin) = registry.find_match(parser) {
-            consume_prefix(parser, plugin.prefix())?;
-            let stmt = plugin.parse(parser)?;
-            let rewritten = plugin.rewrite(stmt);
-            return Ok(Some(rewritten));
+            let stmt = parse_plugin_statement(parser, plugin)?;
+            return Ok(Some(Rewrite {
+                plugin: plugin.prefix().join(" "),
+                undo: plugin.undo(&stmt),
+                sql: plugin.rewrite(stmt),
+            }));
         }
 
         // Standard SQL should pass through unchanged.
         Ok(None)
     }
+
+    /// Byte offset in `sql` just past the statement parsed so far, including
+    /// its terminating `;`. Parsing has checked that one, or the end of the
+    /// SQL, follows.
+    pub fn consumed_len(&mut self, sql: &str) -> usize {
+        let token = self.parser.peek_token();
+        match token.token {
+            Token::SemiColon => byte_offset(sql, token.span.end),
+            _ => sql.len(),
+        }
+    }
+}
+
+/// Convert a tokenizer location (1-based line and character column) into a
+/// byte offset in `sql`.
+fn byte_offset(sql: &str, location: Location) -> usize {
+    let (mut line, mut column) = (1, 1);
+    for (i, ch) in sql.char_indices() {
+        if line == location.line && column == location.column {
+            return i;
+        }
+        if ch == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    sql.len()
 }
 
 // --- Helper methods for parsing identifiers, literals, and keywords ---
@@ -94,6 +157,7 @@ pub trait ParserExt {
     fn parse_identifier(&mut self) -> Result<Ident, ParserError>;
     fn parse_literal_string(&mut self) -> Result<String, ParserError>;
     fn parse_literal_int(&mut self) -> Result<i64, ParserError>;
+    fn parse_label_ref(&mut self) -> Result<LabelRef, ParserError>;
     fn expect_word(&mut self, word: &str) -> Result<(), ParserError>;
     fn parse_keyword_seq(&mut self, keywords: &[&str]) -> bool;
     fn parse_policy_operation(&mut self) -> Result<PolicyOperation, ParserError>;
@@ -125,6 +189,14 @@ impl ParserExt for Parser<'_> {
         }
     }
 
+    fn parse_label_ref(&mut self) -> Result<LabelRef, ParserError> {
+        if matches!(self.peek_token().token, Token::Word(_)) {
+            Ok(LabelRef::Named(self.parse_identifier()?.value))
+        } else {
+            Ok(LabelRef::Expr(self.parse_literal_string()?))
+        }
+    }
+
     fn expect_word(&mut self, word: &str) -> Result<(), ParserError> {
         let token = self.next_token();
         match &token.token {
@@ -223,8 +295,73 @@ impl ParserExt for Parser<'_> {
 
 /// Convenience function matching original API
 pub fn parse_rewrite(sql: &str) -> Option<String> {
+    parse_rewrite_statement(sql).map(|(rewritten, _)| rewritten.sql)
+}
+
+/// Rewrite the leading statement of `sql`, returning the rewritten SQL and
+/// the number of bytes of `sql` the original statement spanned.
+pub fn parse_rewrite_statement(sql: &str) -> Option<(Rewrite, usize)> {
     let mut parser = CustomParser::new(sql, &PLUGIN_REGISTRY).ok()?;
-    parser.parse_rewrite().ok().flatten()
+    let rewritten = parser.parse_rewrite().ok().flatten()?;
+    Some((rewritten, parser.consumed_len(sql)))
+}
+
+/// Check that rewritten SQL parses as SQLite, for `SQLSHIM_VALIDATE`.
+/// sqlparser only knows some SQLite statements, e.g. `DETACH`, in its
+/// generic dialect, so that is tried too.
+pub fn validate_rewrite(sql: &str) -> Result<(), ParserError> {
+    let sql = respell_sqlite(sql)?;
+    Parser::parse_sql(&SQLiteDialect {}, &sql)
+        .or_else(|e| Parser::parse_sql(&GenericDialect {}, &sql).map_err(|_| e))
+        .map(|_| ())
+}
+
+/// Respell SQLite syntax that sqlparser does not parse as equivalents it
+/// does, so the rest of a rewrite is still checked: `CREATE TRIGGER IF NOT
+/// EXISTS` drops its `IF NOT EXISTS`, and `a IS b` (SQLite's null-safe
+/// equality) becomes `a IS NOT DISTINCT FROM b`.
+fn respell_sqlite(sql: &str) -> Result<String, ParserError> {
+    let tokens: Vec<TokenWithSpan> = Tokenizer::new(&SQLiteDialect {}, sql)
+        .tokenize_with_location()?
+        .into_iter()
+        .filter(|t| !matches!(t.token, Token::Whitespace(_)))
+        .collect();
+    let keyword = |i: usize| match tokens.get(i).map(|t| &t.token) {
+        Some(Token::Word(w)) if w.quote_style.is_none() => w.keyword,
+        _ => Keyword::NoKeyword,
+    };
+
+    let mut respelled = String::with_capacity(sql.len());
+    let mut copied = 0;
+    for i in 0..tokens.len() {
+        let (last, text) = match keyword(i) {
+            Keyword::TRIGGER
+                if [keyword(i + 1), keyword(i + 2), keyword(i + 3)]
+                    == [Keyword::IF, Keyword::NOT, Keyword::EXISTS] =>
+            {
+                (i + 3, "TRIGGER")
+            }
+            Keyword::IS
+                if !matches!(
+                    keyword(i + 1),
+                    Keyword::NULL
+                        | Keyword::NOT
+                        | Keyword::TRUE
+                        | Keyword::FALSE
+                        | Keyword::DISTINCT
+                        | Keyword::UNKNOWN
+                ) =>
+            {
+                (i, "IS NOT DISTINCT FROM")
+            }
+            _ => continue,
+        };
+        respelled.push_str(&sql[copied..byte_offset(sql, tokens[i].span.start)]);
+        respelled.push_str(text);
+        copied = byte_offset(sql, tokens[last].span.end);
+    }
+    respelled.push_str(&sql[copied..]);
+    Ok(respelled)
 }
 
 /// Convenience function matching original API
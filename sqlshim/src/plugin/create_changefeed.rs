@@ -0,0 +1,204 @@
+use sqlparser::{
+    dialect::GenericDialect,
+    keywords::Keyword,
+    parser::{Parser, ParserError},
+    tokenizer::{Token, Tokenizer},
+};
+
+use crate::{
+    parser::ParserExt,
+    plugin::CustomPlugin,
+    rewriter::{escape_sql_ident, escape_sql_string},
+    statement::{CreateChangefeedStmt, CustomStatement},
+};
+
+/// Words in a filter expression that are never column references.
+const FILTER_KEYWORDS: &[&str] = &[
+    "AND",
+    "BETWEEN",
+    "CASE",
+    "CURRENT_DATE",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "DISTINCT",
+    "ELSE",
+    "END",
+    "ESCAPE",
+    "EXISTS",
+    "FALSE",
+    "GLOB",
+    "IN",
+    "IS",
+    "ISNULL",
+    "LIKE",
+    "MATCH",
+    "NEW",
+    "NOT",
+    "NOTNULL",
+    "NULL",
+    "OLD",
+    "OR",
+    "REGEXP",
+    "THEN",
+    "TRUE",
+    "WHEN",
+];
+
+pub struct CreateChangefeedPlugin;
+
+/// Qualify bare column references in `filter` with `row` (`NEW` or `OLD`)
+/// so the expression can be used in a trigger's WHEN clause.
+fn qualify_filter(filter: &str, row: &str) -> String {
+    let dialect = GenericDialect {};
+    let Ok(tokens) = Tokenizer::new(&dialect, filter).tokenize() else {
+        return filter.to_string();
+    };
+    let tokens: Vec<Token> = tokens
+        .into_iter()
+        .filter(|t| !matches!(t, Token::Whitespace(_)))
+        .collect();
+
+    tokens
+        .iter()
+        .enumerate()
+        .map(|(i, token)| {
+            let prev = i.checked_sub(1).and_then(|i| tokens.get(i));
+            let next = tokens.get(i + 1);
+            match token {
+                Token::Word(w) if is_column_ref(w, prev, next) => format!("{row}.{w}"),
+                _ => token.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_column_ref(
+    word: &sqlparser::tokenizer::Word,
+    prev: Option<&Token>,
+    next: Option<&Token>,
+) -> bool {
+    if matches!(prev, Some(Token::Period)) || matches!(next, Some(Token::Period | Token::LParen)) {
+        return false;
+    }
+    // CAST(x AS type), x COLLATE name
+    if let Some(Token::Word(p)) = prev
+        && p.quote_style.is_none()
+        && matches!(p.value.to_uppercase().as_str(), "AS" | "COLLATE")
+    {
+        return false;
+    }
+    word.quote_style.is_some() || !FILTER_KEYWORDS.contains(&word.value.to_uppercase().as_str())
+}
+
+fn payload_expr(columns: &[String], row: &str) -> String {
+    if columns.is_empty() {
+        return "NULL".to_string();
+    }
+    let pairs = columns
+        .iter()
+        .map(|c| format!("'{}', {row}.{}", escape_sql_string(c), escape_sql_ident(c)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("json_object({pairs})")
+}
+
+impl CustomPlugin for CreateChangefeedPlugin {
+    fn prefix(&self) -> &'static [&'static str] {
+        &["CREATE", "CHANGEFEED"]
+    }
+
+    fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        let name = parser.parse_identifier()?.value;
+
+        parser.expect_keyword(Keyword::ON)?;
+        let table = parser.parse_identifier()?.value;
+
+        let mut columns = vec![];
+        if parser.consume_token(&Token::LParen) {
+            columns.push(parser.parse_identifier()?.value);
+            while parser.consume_token(&Token::Comma) {
+                columns.push(parser.parse_identifier()?.value);
+            }
+            parser.expect_token(&Token::RParen)?;
+        }
+
+        let filter = if parser.parse_keyword(Keyword::WHERE) {
+            Some(parser.parse_until_statement_end()?)
+        } else {
+            None
+        };
+
+        Ok(CustomStatement::CreateChangefeed(CreateChangefeedStmt {
+            name,
+            table,
+            columns,
+            filter,
+        }))
+    }
+
+    fn rewrite(&self, stmt: CustomStatement) -> String {
+        match stmt {
+            CustomStatement::CreateChangefeed(stmt) => {
+                let outbox = escape_sql_ident(&format!("{}_outbox", stmt.name));
+                let [on_insert, on_update, on_delete] = ["insert", "update", "delete"]
+                    .map(|op| escape_sql_ident(&format!("{}_changefeed_{op}", stmt.name)));
+                let table = escape_sql_ident(&stmt.table);
+
+                let (when_new, when_old, when_either) = match &stmt.filter {
+                    Some(filter) => {
+                        let new = qualify_filter(filter, "NEW");
+                        let old = qualify_filter(filter, "OLD");
+                        (
+                            format!("WHEN {new}"),
+                            format!("WHEN {old}"),
+                            format!("WHEN ({new}) OR ({old})"),
+                        )
+                    }
+                    None => Default::default(),
+                };
+                let payload_new = payload_expr(&stmt.columns, "NEW");
+                let payload_old = payload_expr(&stmt.columns, "OLD");
+
+                // The outbox survives a re-CREATE so undrained events are kept;
+                // the triggers are replaced so a changed filter takes effect.
+                format!(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS {outbox} (
+                        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                        op TEXT NOT NULL,
+                        row_id INTEGER,
+                        payload TEXT,
+                        created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+                    );
+                    DROP TRIGGER IF EXISTS {on_insert};
+                    DROP TRIGGER IF EXISTS {on_update};
+                    DROP TRIGGER IF EXISTS {on_delete};
+                    CREATE TRIGGER {on_insert}
+                    AFTER INSERT ON {table}
+                    {when_new}
+                    BEGIN
+                        INSERT INTO {outbox} (op, row_id, payload)
+                        VALUES ('INSERT', NEW.rowid, {payload_new});
+                    END;
+                    CREATE TRIGGER {on_update}
+                    AFTER UPDATE ON {table}
+                    {when_either}
+                    BEGIN
+                        INSERT INTO {outbox} (op, row_id, payload)
+                        VALUES ('UPDATE', NEW.rowid, {payload_new});
+                    END;
+                    CREATE TRIGGER {on_delete}
+                    AFTER DELETE ON {table}
+                    {when_old}
+                    BEGIN
+                        INSERT INTO {outbox} (op, row_id, payload)
+                        VALUES ('DELETE', OLD.rowid, {payload_old});
+                    END;
+                    "#
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+}
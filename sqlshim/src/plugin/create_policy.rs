@@ -13,32 +13,64 @@ use crate::{
 
 pub struct CreatePolicyPlugin;
 
+/// Columns of `__sqlshim_policies`: one row per operation of a policy. A
+/// row for the `DUPLICATE` operation is how a plain `CREATE POLICY` on an
+/// existing name fails, with the constraint's name as the error.
+const POLICY_COLUMNS: &str = r#"
+                        name TEXT NOT NULL,
+                        table_name TEXT NOT NULL,
+                        operation TEXT NOT NULL,
+                        label_id INTEGER,
+                        expr TEXT NOT NULL,
+                        PRIMARY KEY (name, table_name, operation),
+                        CONSTRAINT "policy already exists; DROP POLICY first or use CREATE POLICY IF NOT EXISTS"
+                            CHECK (operation <> 'DUPLICATE')
+                    "#;
+
 impl CustomPlugin for CreatePolicyPlugin {
     fn prefix(&self) -> &'static [&'static str] {
         &["CREATE", "POLICY"]
     }
 
     fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        let if_not_exists = parser.parse_keywords(&[Keyword::IF, Keyword::NOT, Keyword::EXISTS]);
         let name = parser.parse_identifier()?.value;
 
         parser.expect_keyword(Keyword::ON)?;
         let table = parser.parse_identifier()?.value;
 
-        let operation = if parser.parse_keyword(Keyword::FOR) {
-            Some(parser.parse_policy_operation()?)
+        let mut operations = if parser.parse_keyword(Keyword::FOR) {
+            parser.parse_operation_list()?
         } else {
-            None
+            vec![PolicyOperation::All]
         };
+        // One metadata row per operation, so repeats would collide.
+        let mut seen = vec![];
+        operations.retain(|op| {
+            let first = !seen.contains(op);
+            seen.push(*op);
+            first
+        });
 
         parser.expect_word("USING")?;
         parser.expect_token(&Token::LParen)?;
+        // Nested parens are tracked, so this stops at the matching `)`.
         let using_expr = parser.parse_until_token(&Token::RParen)?;
         parser.expect_token(&Token::RParen)?;
 
+        if !parser.is_statement_end() {
+            return Err(ParserError::ParserError(
+                "Expected end of statement after CREATE POLICY ... USING (...); \
+                 wrap the whole expression in parentheses"
+                    .to_string(),
+            ));
+        }
+
         Ok(CustomStatement::CreatePolicy(CreatePolicyStmt {
+            if_not_exists,
             name,
             table,
-            operation,
+            operations,
             using_expr,
         }))
     }
@@ -50,26 +82,60 @@ impl CustomPlugin for CreatePolicyPlugin {
                 let escaped_name = escape_sql_string(&stmt.name);
                 let escaped_table = escape_sql_string(&stmt.table);
 
-                let op_str = match stmt.operation {
-                    Some(PolicyOperation::Select) => "SELECT",
-                    Some(PolicyOperation::Insert) => "INSERT",
-                    Some(PolicyOperation::Update) => "UPDATE",
-                    Some(PolicyOperation::Delete) => "DELETE",
-                    Some(PolicyOperation::All) | None => "ALL",
+                let rows = stmt
+                    .operations
+                    .iter()
+                    .map(|op| {
+                        format!(
+                            "('{escaped_name}', '{escaped_table}', '{}', NULL, '{escaped_expr}')",
+                            op.as_sql()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",\n                        ");
+
+                // The first definition of a policy wins: IF NOT EXISTS skips
+                // the insert, a plain CREATE POLICY fails the table's check.
+                let insert = if stmt.if_not_exists {
+                    format!(
+                        r#"
+                    INSERT OR IGNORE INTO __sqlshim_policies (name, table_name, operation, label_id, expr)
+                    SELECT * FROM (VALUES {rows})
+                    WHERE NOT EXISTS (
+                        SELECT 1 FROM __sqlshim_policies
+                        WHERE name = '{escaped_name}' AND table_name = '{escaped_table}'
+                    );"#
+                    )
+                } else {
+                    format!(
+                        r#"
+                    INSERT INTO __sqlshim_policies (name, table_name, operation, label_id, expr)
+                    SELECT name, table_name, 'DUPLICATE', NULL, '' FROM __sqlshim_policies
+                    WHERE name = '{escaped_name}' AND table_name = '{escaped_table}'
+                    LIMIT 1;
+                    INSERT INTO __sqlshim_policies (name, table_name, operation, label_id, expr)
+                    VALUES {rows};"#
+                    )
                 };
 
+                // Tables from before operation lists are keyed by (name,
+                // table_name) alone, which refuses a policy's second
+                // operation. SQL can't branch on the schema, so the table is
+                // rebuilt under the current key every time; it holds a row
+                // per policy operation. The guard view earlier versions kept
+                // over it would stop the rename, so it goes.
                 format!(
                     r#"
-                    CREATE TABLE IF NOT EXISTS __sqlshim_policies (
-                        name TEXT NOT NULL,
-                        table_name TEXT NOT NULL,
-                        operation TEXT NOT NULL,
-                        label_id INTEGER,
-                        expr TEXT NOT NULL,
-                        PRIMARY KEY (name, table_name)
-                    );
-                    INSERT OR REPLACE INTO __sqlshim_policies (name, table_name, operation, label_id, expr)
-                    VALUES ('{escaped_name}', '{escaped_table}', '{op_str}', NULL, '{escaped_expr}');
+                    CREATE TABLE IF NOT EXISTS __sqlshim_policies ({POLICY_COLUMNS});
+                    SAVEPOINT __sqlshim_policies_migrate;
+                    DROP VIEW IF EXISTS __sqlshim_policy_names;
+                    DROP TABLE IF EXISTS __sqlshim_policies_new;
+                    CREATE TABLE __sqlshim_policies_new ({POLICY_COLUMNS});
+                    INSERT INTO __sqlshim_policies_new (name, table_name, operation, label_id, expr)
+                    SELECT name, table_name, operation, label_id, expr FROM __sqlshim_policies;
+                    DROP TABLE __sqlshim_policies;
+                    ALTER TABLE __sqlshim_policies_new RENAME TO __sqlshim_policies;
+                    RELEASE __sqlshim_policies_migrate;{insert}
                     "#
                 )
             }
@@ -1,11 +1,13 @@
 use sqlparser::{
+    ast::SetExpr,
     keywords::Keyword,
     parser::{Parser, ParserError},
 };
 
 use crate::{
+    parser::ParserExt,
     plugin::CustomPlugin,
-    rewriter::escape_sql_string,
+    rewriter::escape_sql_ident,
     statement::{CreateSecureViewStmt, CustomStatement},
 };
 
@@ -20,7 +22,24 @@ impl CustomPlugin for CreateSecureViewPlugin {
         let name = parser.parse_identifier()?.value;
 
         parser.expect_keyword(Keyword::AS)?;
-        let query = parser.parse_query()?.to_string();
+        let query = parser.parse_query()?;
+        if !matches!(
+            *query.body,
+            SetExpr::Select(_) | SetExpr::SetOperation { .. } | SetExpr::Query(_)
+        ) {
+            return Err(ParserError::ParserError(
+                "CREATE SECURE VIEW expects a SELECT query".to_string(),
+            ));
+        }
+        if !parser.is_statement_end() {
+            return Err(ParserError::ParserError(
+                "Expected end of statement after CREATE SECURE VIEW query".to_string(),
+            ));
+        }
+
+        // Re-serialize from the AST so comments and the statement's own `;`
+        // never end up inside the wrapping subquery.
+        let query = query.to_string().trim_end_matches(';').to_string();
 
         Ok(CustomStatement::CreateSecureView(CreateSecureViewStmt {
             name,
@@ -31,15 +50,15 @@ impl CustomPlugin for CreateSecureViewPlugin {
     fn rewrite(&self, stmt: CustomStatement) -> String {
         match stmt {
             CustomStatement::CreateSecureView(stmt) => {
-                let escaped_name = escape_sql_string(&stmt.name);
+                let name = escape_sql_ident(&stmt.name);
+                let query = &stmt.query;
                 format!(
                     r#"
-                    CREATE VIEW {} AS
+                    CREATE VIEW {name} AS
                     SELECT *
-                    FROM ({})
+                    FROM ({query}) AS t
                     WHERE sec_assert_fresh();
-                    "#,
-                    escaped_name, stmt.query
+                    "#
                 )
             }
             _ => unreachable!(),
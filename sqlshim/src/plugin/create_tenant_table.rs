@@ -0,0 +1,265 @@
+use sqlparser::{
+    parser::{Parser, ParserError},
+    tokenizer::Token,
+};
+
+use crate::{
+    parser::ParserExt,
+    plugin::CustomPlugin,
+    rewriter::{escape_sql_ident, escape_sql_string},
+    statement::{CreateTenantTableStmt, CustomStatement},
+};
+
+const TABLE_CONSTRAINTS: &[&str] = &["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"];
+
+/// Column of the physical table naming the tenant that owns each row.
+const TENANT_ID: &str = "tenant_id";
+
+/// Row label column of the physical table, holding the label `tenant=<id>`.
+const TENANT_LABEL: &str = "tenant_label";
+
+pub struct CreateTenantTablePlugin;
+
+fn is_word(token: Option<&Token>, word: &str) -> bool {
+    matches!(token, Some(Token::Word(w)) if w.value.eq_ignore_ascii_case(word))
+}
+
+fn has_primary_key(tokens: &[Token]) -> Option<usize> {
+    (0..tokens.len())
+        .find(|&i| is_word(tokens.get(i), "PRIMARY") && is_word(tokens.get(i + 1), "KEY"))
+}
+
+fn join(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(Token::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Read one column definition or table constraint, up to the `,` or `)`
+/// that ends it.
+fn parse_definition(parser: &mut Parser<'_>) -> Result<Vec<Token>, ParserError> {
+    let mut tokens = vec![];
+    let mut depth = 0;
+    loop {
+        let token = parser.peek_token();
+        match &token.token {
+            Token::Comma | Token::RParen if depth == 0 => break,
+            Token::LParen => depth += 1,
+            Token::RParen => depth -= 1,
+            Token::EOF => {
+                return Err(ParserError::ParserError(
+                    "Unexpected end of input in CREATE TENANT TABLE".to_string(),
+                ));
+            }
+            _ => {}
+        }
+        tokens.push(parser.next_token().token);
+    }
+    Ok(tokens)
+}
+
+/// Split a column's `PRIMARY KEY` and `UNIQUE` constraints out of its
+/// definition, returning the rest of the definition and the constraints
+/// as table constraints scoped by tenant.
+fn scope_column(column: &str, tokens: &[Token]) -> Result<(String, Vec<String>), ParserError> {
+    let mut rest = vec![];
+    let mut constraints = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let named = is_word(tokens.get(i), "CONSTRAINT")
+            && (is_word(tokens.get(i + 2), "PRIMARY") || is_word(tokens.get(i + 2), "UNIQUE"));
+        let start = if named { i + 2 } else { i };
+        let kind = if is_word(tokens.get(start), "PRIMARY") && is_word(tokens.get(start + 1), "KEY")
+        {
+            "PRIMARY KEY"
+        } else if is_word(tokens.get(start), "UNIQUE") {
+            "UNIQUE"
+        } else {
+            rest.push(tokens[i].clone());
+            i += 1;
+            continue;
+        };
+
+        let mut end = start + kind.split(' ').count();
+        if is_word(tokens.get(end), "ASC") || is_word(tokens.get(end), "DESC") {
+            end += 1;
+        }
+        if is_word(tokens.get(end), "AUTOINCREMENT") {
+            return Err(ParserError::ParserError(
+                "AUTOINCREMENT is not supported in CREATE TENANT TABLE: keys are scoped by tenant"
+                    .to_string(),
+            ));
+        }
+        let mut on_conflict = String::new();
+        if is_word(tokens.get(end), "ON") && is_word(tokens.get(end + 1), "CONFLICT") {
+            on_conflict = format!(" {}", join(&tokens[end..(end + 3).min(tokens.len())]));
+            end += 3;
+        }
+
+        let name = if named {
+            format!("{} ", join(&tokens[i..i + 2]))
+        } else {
+            String::new()
+        };
+        constraints.push(format!(
+            "{name}{kind} ({}, {}){on_conflict}",
+            escape_sql_ident(TENANT_ID),
+            escape_sql_ident(column)
+        ));
+        i = end;
+    }
+    Ok((join(&rest), constraints))
+}
+
+/// Scope a table-level `PRIMARY KEY (...)` or `UNIQUE (...)` by tenant.
+fn scope_table_constraint(tokens: &[Token]) -> String {
+    let key = (0..tokens.len()).find(|&i| {
+        (is_word(tokens.get(i), "KEY") && is_word(tokens.get(i.wrapping_sub(1)), "PRIMARY"))
+            || is_word(tokens.get(i), "UNIQUE")
+    });
+    match key {
+        Some(i) if tokens.get(i + 1) == Some(&Token::LParen) => format!(
+            "{} ({}, {}",
+            join(&tokens[..=i]),
+            escape_sql_ident(TENANT_ID),
+            join(&tokens[i + 2..])
+        ),
+        _ => join(tokens),
+    }
+}
+
+impl CustomPlugin for CreateTenantTablePlugin {
+    fn prefix(&self) -> &'static [&'static str] {
+        &["CREATE", "TENANT", "TABLE"]
+    }
+
+    fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        let name = parser.parse_identifier()?.value;
+        parser.expect_token(&Token::LParen)?;
+
+        let mut definitions = vec![];
+        let mut constraints = vec![];
+        let mut columns = vec![];
+        let mut key_columns = vec![];
+        loop {
+            let tokens = parse_definition(parser)?;
+            let first = match tokens.first() {
+                Some(Token::Word(w)) => w,
+                _ => {
+                    return Err(ParserError::ParserError(
+                        "Expected a column definition in CREATE TENANT TABLE".to_string(),
+                    ));
+                }
+            };
+
+            if first.quote_style.is_none()
+                && TABLE_CONSTRAINTS.contains(&first.value.to_uppercase().as_str())
+            {
+                // PRIMARY KEY (a, b)
+                if let Some(pos) = has_primary_key(&tokens) {
+                    key_columns.extend(tokens[pos + 2..].iter().filter_map(|t| match t {
+                        Token::Word(w) => Some(w.value.clone()),
+                        _ => None,
+                    }));
+                }
+                constraints.push(scope_table_constraint(&tokens));
+            } else {
+                let column = first.value.clone();
+                if has_primary_key(&tokens).is_some() {
+                    key_columns.push(column.clone());
+                }
+                let (rest, scoped) = scope_column(&column, &tokens[1..])?;
+                definitions.push(format!("{} {rest}", escape_sql_ident(&column)));
+                constraints.extend(scoped);
+                columns.push(column);
+            }
+
+            if !parser.consume_token(&Token::Comma) {
+                break;
+            }
+        }
+        parser.expect_token(&Token::RParen)?;
+
+        if !parser.is_statement_end() {
+            return Err(ParserError::ParserError(
+                "Expected end of statement after CREATE TENANT TABLE".to_string(),
+            ));
+        }
+        // Secure tables find rows to update and delete by primary key
+        if key_columns.is_empty() {
+            return Err(ParserError::ParserError(
+                "CREATE TENANT TABLE requires a PRIMARY KEY".to_string(),
+            ));
+        }
+
+        Ok(CustomStatement::CreateTenantTable(CreateTenantTableStmt {
+            name,
+            definitions,
+            constraints,
+            columns,
+            key_columns,
+        }))
+    }
+
+    fn rewrite(&self, stmt: CustomStatement) -> String {
+        match stmt {
+            CustomStatement::CreateTenantTable(stmt) => {
+                let physical_name = format!("__tenant_{}", stmt.name);
+                let physical = escape_sql_ident(&physical_name);
+                let guard = escape_sql_ident(&format!("{physical_name}_guard"));
+                let label = escape_sql_ident(&format!("{physical_name}_label"));
+                let tenant_id = escape_sql_ident(TENANT_ID);
+                let tenant_label = escape_sql_ident(TENANT_LABEL);
+                // Tenant columns go between the column definitions and the
+                // table constraints, which must come last.
+                let tenant_columns = [
+                    format!(
+                        "{tenant_id} TEXT NOT NULL ON CONFLICT REPLACE DEFAULT (sec_get_attr('tenant'))"
+                    ),
+                    format!("{tenant_label} INTEGER"),
+                ];
+                let definitions = stmt
+                    .definitions
+                    .iter()
+                    .chain(&tenant_columns)
+                    .chain(&stmt.constraints)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(",\n                        ");
+
+                // A row is labelled with its tenant once inserted, so the
+                // secure view shows each context only its own tenant's rows.
+                // The default fills in the tenant for inserts through it.
+                format!(
+                    r#"
+                    CREATE TABLE {physical} (
+                        {definitions}
+                    );
+                    CREATE TRIGGER {guard}
+                    BEFORE INSERT ON {physical}
+                    BEGIN
+                        SELECT RAISE(ABORT, 'no tenant set')
+                        WHERE sec_get_attr('tenant') IS NULL;
+                        SELECT RAISE(ABORT, 'tenant_id must be the current tenant')
+                        WHERE NEW.{tenant_id} <> sec_get_attr('tenant');
+                    END;
+                    CREATE TRIGGER {label}
+                    AFTER INSERT ON {physical}
+                    BEGIN
+                        UPDATE {physical}
+                        SET {tenant_label} = sec_define_label('tenant=' || NEW.{tenant_id})
+                        WHERE rowid = NEW.rowid;
+                    END;
+                    SELECT sec_register_table('{logical}', '{physical_string}', '{TENANT_LABEL}', NULL, NULL);
+                    SELECT sec_refresh_views();
+                    "#,
+                    logical = escape_sql_string(&stmt.name),
+                    physical_string = escape_sql_string(&physical_name),
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use sqlparser::parser::{Parser, ParserError};
 
 use crate::{
+    parser::ParserExt,
     plugin::CustomPlugin,
     rewriter::escape_sql_string,
     statement::{CustomStatement, DefineLabelStmt},
@@ -15,15 +16,26 @@ impl CustomPlugin for DefineLabelPlugin {
 
     fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
         let expr = parser.parse_literal_string()?;
+        let name = if parser.parse_keyword_seq(&["AS"]) {
+            Some(parser.parse_identifier()?.value)
+        } else {
+            None
+        };
 
-        Ok(CustomStatement::DefineLabel(DefineLabelStmt { expr }))
+        Ok(CustomStatement::DefineLabel(DefineLabelStmt { expr, name }))
     }
 
     fn rewrite(&self, stmt: CustomStatement) -> String {
         match stmt {
             CustomStatement::DefineLabel(stmt) => {
                 let escaped = escape_sql_string(&stmt.expr);
-                format!("SELECT sec_define_label('{escaped}');")
+                match stmt.name {
+                    Some(name) => format!(
+                        "SELECT sec_define_named_label('{}', '{escaped}');",
+                        escape_sql_string(&name)
+                    ),
+                    None => format!("SELECT sec_define_label('{escaped}');"),
+                }
             }
             _ => unreachable!(),
         }
@@ -0,0 +1,51 @@
+use sqlparser::parser::{Parser, ParserError};
+
+use crate::{
+    parser::ParserExt,
+    plugin::CustomPlugin,
+    rewriter::escape_sql_ident,
+    statement::{CustomStatement, DropChangefeedStmt},
+};
+
+pub struct DropChangefeedPlugin;
+
+impl CustomPlugin for DropChangefeedPlugin {
+    fn prefix(&self) -> &'static [&'static str] {
+        &["DROP", "CHANGEFEED"]
+    }
+
+    fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        let name = parser.parse_identifier()?.value;
+        let keep_outbox = parser.parse_keyword_seq(&["KEEP", "OUTBOX"]);
+
+        Ok(CustomStatement::DropChangefeed(DropChangefeedStmt {
+            name,
+            keep_outbox,
+        }))
+    }
+
+    fn rewrite(&self, stmt: CustomStatement) -> String {
+        match stmt {
+            CustomStatement::DropChangefeed(stmt) => {
+                let outbox = escape_sql_ident(&format!("{}_outbox", stmt.name));
+                let [on_insert, on_update, on_delete] = ["insert", "update", "delete"]
+                    .map(|op| escape_sql_ident(&format!("{}_changefeed_{op}", stmt.name)));
+                let drop_outbox = if stmt.keep_outbox {
+                    String::new()
+                } else {
+                    format!("DROP TABLE IF EXISTS {outbox};")
+                };
+
+                format!(
+                    r#"
+                    DROP TRIGGER IF EXISTS {on_insert};
+                    DROP TRIGGER IF EXISTS {on_update};
+                    DROP TRIGGER IF EXISTS {on_delete};
+                    {drop_outbox}
+                    "#
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+}
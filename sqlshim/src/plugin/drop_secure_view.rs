@@ -0,0 +1,30 @@
+use sqlparser::parser::{Parser, ParserError};
+
+use crate::{
+    plugin::CustomPlugin,
+    rewriter::escape_sql_ident,
+    statement::{CustomStatement, DropSecureViewStmt},
+};
+
+pub struct DropSecureViewPlugin;
+
+impl CustomPlugin for DropSecureViewPlugin {
+    fn prefix(&self) -> &'static [&'static str] {
+        &["DROP", "SECURE", "VIEW"]
+    }
+
+    fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        let name = parser.parse_identifier()?.value;
+
+        Ok(CustomStatement::DropSecureView(DropSecureViewStmt { name }))
+    }
+
+    fn rewrite(&self, stmt: CustomStatement) -> String {
+        match stmt {
+            CustomStatement::DropSecureView(stmt) => {
+                format!("DROP VIEW IF EXISTS {};", escape_sql_ident(&stmt.name))
+            }
+            _ => unreachable!(),
+        }
+    }
+}
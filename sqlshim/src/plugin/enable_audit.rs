@@ -40,13 +40,7 @@ impl CustomPlugin for EnableAuditPlugin {
                 let ops_str = stmt
                     .operations
                     .iter()
-                    .map(|op| match op {
-                        PolicyOperation::Select => "SELECT",
-                        PolicyOperation::Insert => "INSERT",
-                        PolicyOperation::Update => "UPDATE",
-                        PolicyOperation::Delete => "DELETE",
-                        PolicyOperation::All => "ALL",
-                    })
+                    .map(PolicyOperation::as_sql)
                     .collect::<Vec<_>>()
                     .join(", ");
 
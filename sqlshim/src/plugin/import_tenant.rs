@@ -0,0 +1,201 @@
+use sqlparser::{
+    parser::{Parser, ParserError},
+    tokenizer::Token,
+};
+
+use crate::{
+    parser::ParserExt,
+    plugin::CustomPlugin,
+    rewriter::{escape_sql_ident, escape_sql_string},
+    statement::{CustomStatement, ImportConflict, ImportTable, ImportTenantStmt},
+};
+
+pub struct ImportTenantPlugin;
+
+/// Name of the context the import pushes to switch tenant in.
+const IMPORT_CONTEXT: &str = "__sqlshim_import";
+
+fn parse_conflict(parser: &mut Parser<'_>) -> Result<ImportConflict, ParserError> {
+    let word = parser.parse_identifier()?.value;
+    match word.to_uppercase().as_str() {
+        "SKIP" => Ok(ImportConflict::Skip),
+        "REPLACE" => Ok(ImportConflict::Replace),
+        "ERROR" => Ok(ImportConflict::Error),
+        _ => Err(ParserError::ParserError(format!(
+            "Expected SKIP, REPLACE or ERROR after ON CONFLICT, got '{word}'"
+        ))),
+    }
+}
+
+/// `name (col, ...)`: a tenant table and the columns to copy.
+fn parse_table(parser: &mut Parser<'_>) -> Result<ImportTable, ParserError> {
+    let name = parser.parse_identifier()?.value;
+    parser.expect_token(&Token::LParen)?;
+    let mut columns = vec![parser.parse_identifier()?.value];
+    while parser.consume_token(&Token::Comma) {
+        columns.push(parser.parse_identifier()?.value);
+    }
+    parser.expect_token(&Token::RParen)?;
+    Ok(ImportTable { name, columns })
+}
+
+/// Copy `staged` into `physical` by `strategy`. A plain insert fails on a
+/// key the tenant already uses; SKIP and REPLACE first try one in place of
+/// the tenant's own rows, which only fails on a key another tenant uses.
+/// Every failure rolls back the transaction, and so the whole import.
+fn copy_sql(
+    physical: &str,
+    staged: &str,
+    columns: &[String],
+    tenant: &str,
+    strategy: ImportConflict,
+) -> String {
+    let tenant_id = escape_sql_ident("tenant_id");
+    let names = columns
+        .iter()
+        .map(|c| escape_sql_ident(c))
+        .chain([tenant_id.clone()])
+        .collect::<Vec<_>>()
+        .join(", ");
+    let insert = format!(
+        "INSERT OR ROLLBACK INTO main.{physical} ({names})\n\
+         SELECT {names} FROM temp.{staged}"
+    );
+    let probe = format!(
+        "SAVEPOINT __sqlshim_import_probe;\n\
+         DELETE FROM main.{physical} WHERE {tenant_id} = '{tenant}';\n\
+         {insert};\n\
+         ROLLBACK TO __sqlshim_import_probe;\n\
+         RELEASE __sqlshim_import_probe;\n"
+    );
+    match strategy {
+        ImportConflict::Error => format!("{insert};\n"),
+        ImportConflict::Skip => format!("{probe}{insert} WHERE true\nON CONFLICT DO NOTHING;\n"),
+        ImportConflict::Replace => {
+            let sets = columns
+                .iter()
+                .map(|c| {
+                    let c = escape_sql_ident(c);
+                    format!("{c} = excluded.{c}")
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "{probe}{insert} WHERE true\n\
+                 ON CONFLICT DO UPDATE SET {sets}\n\
+                 WHERE {tenant_id} = '{tenant}';\n"
+            )
+        }
+    }
+}
+
+impl CustomPlugin for ImportTenantPlugin {
+    fn prefix(&self) -> &'static [&'static str] {
+        &["IMPORT", "TENANT"]
+    }
+
+    fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        let tenant = parser.parse_literal_string()?;
+        parser.expect_word("FROM")?;
+        let path = parser.parse_literal_string()?;
+        parser.expect_word("INTO")?;
+        let mut tables = vec![parse_table(parser)?];
+        while parser.consume_token(&Token::Comma) {
+            tables.push(parse_table(parser)?);
+        }
+        let on_conflict = if parser.parse_keyword_seq(&["ON", "CONFLICT"]) {
+            parse_conflict(parser)?
+        } else {
+            ImportConflict::Error
+        };
+
+        if !parser.is_statement_end() {
+            return Err(ParserError::ParserError(
+                "Expected end of statement after IMPORT TENANT".to_string(),
+            ));
+        }
+
+        Ok(CustomStatement::ImportTenant(ImportTenantStmt {
+            tenant,
+            path,
+            tables,
+            on_conflict,
+        }))
+    }
+
+    fn rewrite(&self, stmt: CustomStatement) -> String {
+        match stmt {
+            CustomStatement::ImportTenant(stmt) => {
+                let tenant = escape_sql_string(&stmt.tenant);
+                let path = escape_sql_string(&stmt.path);
+                // (physical table, staging copy, columns) for each tenant table
+                let tables = stmt
+                    .tables
+                    .iter()
+                    .map(|t| {
+                        (
+                            escape_sql_ident(&format!("__tenant_{}", t.name)),
+                            escape_sql_ident(&format!("__sqlshim_import_{}", t.name)),
+                            &t.columns,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                // The tenant's rows are staged in temp tables so the source
+                // is detached before the copy, which ATTACH can't be part of.
+                let drop_staged = tables
+                    .iter()
+                    .map(|(_, staged, _)| format!("DROP TABLE IF EXISTS temp.{staged};"))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let stage = tables
+                    .iter()
+                    .map(|(physical, staged, columns)| {
+                        let names = columns
+                            .iter()
+                            .map(|c| escape_sql_ident(c))
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        format!(
+                            "CREATE TEMP TABLE {staged} AS\n\
+                             SELECT {names}, \"tenant_id\" FROM __sqlshim_import.{physical}\n\
+                             WHERE \"tenant_id\" = '{tenant}';"
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let copy: String = tables
+                    .iter()
+                    .map(|(physical, staged, columns)| {
+                        copy_sql(physical, staged, columns, &tenant, stmt.on_conflict)
+                    })
+                    .collect();
+
+                // Tenant tables only take the current tenant's rows, so the
+                // import switches to the tenant as SET TENANT would, in a
+                // context of its own that is popped once it is done.
+                format!(
+                    "SELECT sec_push_context('{IMPORT_CONTEXT}');\n\
+                     SELECT sec_clear_attr('tenant');\n\
+                     SELECT sec_set_attr('tenant', '{tenant}');\n\
+                     {drop_staged}\n\
+                     ATTACH DATABASE '{path}' AS __sqlshim_import;\n\
+                     {stage}\n\
+                     DETACH DATABASE __sqlshim_import;\n\
+                     SAVEPOINT __sqlshim_import;\n\
+                     {copy}\
+                     RELEASE __sqlshim_import;\n\
+                     SELECT sec_pop_context('{IMPORT_CONTEXT}');\n\
+                     {drop_staged}\n"
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// A failed import, e.g. one rolled back on a conflict, never reaches
+    /// its pop, so the caller's tenant is restored here instead.
+    fn undo(&self, _stmt: &CustomStatement) -> Option<String> {
+        Some(format!("SELECT sec_pop_context('{IMPORT_CONTEXT}');"))
+    }
+}
@@ -0,0 +1,26 @@
+use sqlparser::parser::{Parser, ParserError};
+
+use crate::{plugin::CustomPlugin, statement::CustomStatement};
+
+pub struct ListSecureTablesPlugin;
+
+impl CustomPlugin for ListSecureTablesPlugin {
+    fn prefix(&self) -> &'static [&'static str] {
+        &["LIST", "SECURE", "TABLES"]
+    }
+
+    fn parse(&self, _parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        Ok(CustomStatement::ListSecureTables)
+    }
+
+    fn rewrite(&self, stmt: CustomStatement) -> String {
+        match stmt {
+            CustomStatement::ListSecureTables => {
+                "SELECT logical_name, physical_name, row_label_col, table_label_id, \
+                 insert_label_id, allow_implicit_label FROM sec_tables_info;"
+                    .to_string()
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -1,17 +1,28 @@
 mod clear_context;
+mod create_changefeed;
 mod create_policy;
 mod create_secure_view;
+mod create_tenant_table;
 mod define_label;
 mod define_level;
+mod drop_changefeed;
 mod drop_policy;
+mod drop_secure_view;
 mod enable_audit;
 mod explain_policy;
+mod import_tenant;
+mod list_secure_tables;
 mod pop_context;
 mod push_context;
 mod refresh_secure_views;
 mod register_secure_table;
 mod set_column_security;
 mod set_context;
+mod set_context_from_json;
+mod set_tenant;
+mod show_context;
+mod show_levels;
+mod unregister_secure_table;
 
 use std::sync::LazyLock;
 
@@ -30,20 +41,32 @@ pub static PLUGIN_REGISTRY: LazyLock<PluginRegistry> = LazyLock::new(|| {
         Box::new(clear_context::ClearContextPlugin),
         Box::new(create_policy::CreatePolicyPlugin),
         Box::new(create_secure_view::CreateSecureViewPlugin),
+        Box::new(create_tenant_table::CreateTenantTablePlugin),
         Box::new(define_label::DefineLabelPlugin),
         Box::new(define_level::DefineLevelPlugin),
         Box::new(drop_policy::DropPolicyPlugin),
+        Box::new(drop_secure_view::DropSecureViewPlugin),
         Box::new(explain_policy::ExplainPolicyPlugin),
+        Box::new(import_tenant::ImportTenantPlugin),
+        Box::new(list_secure_tables::ListSecureTablesPlugin),
         Box::new(pop_context::PopContextPlugin),
         Box::new(push_context::PushContextPlugin),
         Box::new(refresh_secure_views::RefreshSecureViewsPlugin),
+        Box::new(refresh_secure_views::RefreshSecurityViewsPlugin),
         Box::new(register_secure_table::RegisterSecureTablePlugin),
         Box::new(set_column_security::SetColumnSecurityPlugin),
         Box::new(set_context::SetContextPlugin),
+        Box::new(set_context_from_json::SetContextFromJsonPlugin),
+        Box::new(set_tenant::SetTenantPlugin),
+        Box::new(show_context::ShowContextPlugin),
+        Box::new(show_levels::ShowLevelsPlugin),
+        Box::new(unregister_secure_table::UnregisterSecureTablePlugin),
     ]);
     
     #[cfg(feature = "sqlaudit")]
     plugins.extend::<Vec<Box<dyn CustomPlugin + Send + Sync + 'static>>>(vec![
+        Box::new(create_changefeed::CreateChangefeedPlugin),
+        Box::new(drop_changefeed::DropChangefeedPlugin),
         Box::new(enable_audit::EnableAuditPlugin),
     ]);
 
@@ -92,4 +115,11 @@ pub trait CustomPlugin {
 
     /// Rewrite into SQL
     fn rewrite(&self, stmt: CustomStatement) -> String;
+
+    /// SQL undoing what the rewrite changes in the session, for the shim to
+    /// run before the connection's next statement if the rewrite fails part
+    /// way. Most rewrites leave nothing behind.
+    fn undo(&self, _stmt: &CustomStatement) -> Option<String> {
+        None
+    }
 }
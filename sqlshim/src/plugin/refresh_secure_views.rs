@@ -20,3 +20,21 @@ impl CustomPlugin for RefreshSecureViewsPlugin {
         }
     }
 }
+
+/// Deprecated `REFRESH SECURITY VIEWS` spelling, kept as an alias of
+/// [`RefreshSecureViewsPlugin`] until callers have moved over.
+pub struct RefreshSecurityViewsPlugin;
+
+impl CustomPlugin for RefreshSecurityViewsPlugin {
+    fn prefix(&self) -> &'static [&'static str] {
+        &["REFRESH", "SECURITY", "VIEWS"]
+    }
+
+    fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        RefreshSecureViewsPlugin.parse(parser)
+    }
+
+    fn rewrite(&self, stmt: CustomStatement) -> String {
+        RefreshSecureViewsPlugin.rewrite(stmt)
+    }
+}
@@ -1,12 +1,13 @@
 use sqlparser::{
     keywords::Keyword,
     parser::{Parser, ParserError},
+    tokenizer::Token,
 };
 
 use crate::{
     parser::ParserExt,
     plugin::CustomPlugin,
-    rewriter::escape_sql_string,
+    rewriter::{escape_sql_ident, escape_sql_string, label_id_sql},
     statement::{CustomStatement, RegisterSecureTableStmt},
 };
 
@@ -27,16 +28,26 @@ impl CustomPlugin for RegisterSecureTablePlugin {
         parser.expect_word("ROW")?;
         parser.expect_word("LABEL")?;
 
-        let row_label_column = parser.parse_identifier()?.value;
+        // A single column, or `(a, b, ...)` for a composite row label.
+        let row_label_columns = if parser.consume_token(&Token::LParen) {
+            let cols = parser.parse_comma_separated(|p| Ok(p.parse_identifier()?.value))?;
+            parser.expect_token(&Token::RParen)?;
+            cols
+        } else {
+            vec![parser.parse_identifier()?.value]
+        };
 
         let mut table_label = None;
         let mut insert_label = None;
+        let mut allow_implicit_label = false;
 
         while !parser.is_statement_end() {
             if parser.parse_keyword_seq(&["TABLE", "LABEL"]) {
-                table_label = Some(parser.parse_literal_string()?);
+                table_label = Some(parser.parse_label_ref()?);
             } else if parser.parse_keyword_seq(&["INSERT", "LABEL"]) {
-                insert_label = Some(parser.parse_literal_string()?);
+                insert_label = Some(parser.parse_label_ref()?);
+            } else if parser.parse_keyword_seq(&["ALLOW", "IMPLICIT", "LABEL"]) {
+                allow_implicit_label = true;
             } else {
                 break;
             }
@@ -46,9 +57,10 @@ impl CustomPlugin for RegisterSecureTablePlugin {
             RegisterSecureTableStmt {
                 logical_name,
                 physical_name,
-                row_label_column,
+                row_label_columns,
                 table_label,
                 insert_label,
+                allow_implicit_label,
             },
         ))
     }
@@ -58,20 +70,35 @@ impl CustomPlugin for RegisterSecureTablePlugin {
             CustomStatement::RegisterSecureTable(stmt) => {
                 let escaped_logical = escape_sql_string(&stmt.logical_name);
                 let escaped_physical = escape_sql_string(&stmt.physical_name);
-                let escaped_row_col = escape_sql_string(&stmt.row_label_column);
+                // sqlsec splits the list at `,` outside double quotes.
+                let row_cols = stmt
+                    .row_label_columns
+                    .iter()
+                    .map(|c| {
+                        if c.contains([',', '"']) {
+                            escape_sql_ident(c)
+                        } else {
+                            c.clone()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let escaped_row_col = escape_sql_string(&row_cols);
 
                 let table_label = stmt
                     .table_label
-                    .map(|l| format!("sec_define_label('{}')", escape_sql_string(&l)))
+                    .map(|l| label_id_sql(&l))
                     .unwrap_or_else(|| "NULL".to_string());
 
                 let insert_label = stmt
                     .insert_label
-                    .map(|l| format!("sec_define_label('{}')", escape_sql_string(&l)))
+                    .map(|l| label_id_sql(&l))
                     .unwrap_or_else(|| "NULL".to_string());
 
+                let allow_implicit_label = i32::from(stmt.allow_implicit_label);
+
                 format!(
-                    "SELECT sec_register_table('{escaped_logical}', '{escaped_physical}', '{escaped_row_col}', {table_label}, {insert_label});"
+                    "SELECT sec_register_table('{escaped_logical}', '{escaped_physical}', '{escaped_row_col}', {table_label}, {insert_label}, {allow_implicit_label});"
                 )
             }
             _ => unreachable!(),
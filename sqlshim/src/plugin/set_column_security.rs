@@ -4,11 +4,29 @@ use sqlparser::{
 };
 
 use crate::{
-    parser::ParserExt, plugin::CustomPlugin, rewriter::escape_sql_string, statement::{CustomStatement, SetColumnSecurityStmt}
+    parser::ParserExt,
+    plugin::CustomPlugin,
+    rewriter::{escape_sql_string, label_id_sql},
+    statement::{CustomStatement, LabelChange, SetColumnSecurityStmt},
 };
 
 pub struct SetColumnSecurityPlugin;
 
+fn parse_label_change(parser: &mut Parser<'_>) -> Result<LabelChange, ParserError> {
+    if parser.parse_keyword_seq(&["NONE"]) {
+        Ok(LabelChange::Clear)
+    } else {
+        Ok(LabelChange::Set(parser.parse_label_ref()?))
+    }
+}
+
+fn label_id_expr(change: &LabelChange) -> String {
+    match change {
+        LabelChange::Set(label) => label_id_sql(label),
+        LabelChange::Clear => "NULL".to_string(),
+    }
+}
+
 impl CustomPlugin for SetColumnSecurityPlugin {
     fn prefix(&self) -> &'static [&'static str] {
         &["SET", "COLUMN", "SECURITY"]
@@ -24,9 +42,9 @@ impl CustomPlugin for SetColumnSecurityPlugin {
 
         while !parser.is_statement_end() {
             if parser.parse_keyword_seq(&["READ"]) {
-                read_label = Some(parser.parse_literal_string()?);
+                read_label = Some(parse_label_change(parser)?);
             } else if parser.parse_keyword_seq(&["UPDATE"]) {
-                update_label = Some(parser.parse_literal_string()?);
+                update_label = Some(parse_label_change(parser)?);
             } else {
                 break;
             }
@@ -49,11 +67,11 @@ impl CustomPlugin for SetColumnSecurityPlugin {
                 let mut stmts = Vec::new();
 
                 if let Some(read_label) = stmt.read_label {
-                    let escaped = escape_sql_string(&read_label);
+                    let label_id = label_id_expr(&read_label);
                     stmts.push(format!(
                         r#"
                         UPDATE sec_columns
-                        SET read_label_id = sec_define_label('{escaped}')
+                        SET read_label_id = {label_id}
                         WHERE logical_table = '{escaped_table}'
                           AND column_name = '{escaped_column}';
                         "#
@@ -61,11 +79,11 @@ impl CustomPlugin for SetColumnSecurityPlugin {
                 }
 
                 if let Some(update_label) = stmt.update_label {
-                    let escaped = escape_sql_string(&update_label);
+                    let label_id = label_id_expr(&update_label);
                     stmts.push(format!(
                         r#"
                         UPDATE sec_columns
-                        SET update_label_id = sec_define_label('{escaped}')
+                        SET update_label_id = {label_id}
                         WHERE logical_table = '{escaped_table}'
                           AND column_name = '{escaped_column}';
                         "#
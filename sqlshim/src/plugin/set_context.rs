@@ -1,4 +1,7 @@
-use sqlparser::{parser::{Parser, ParserError}, tokenizer::Token};
+use sqlparser::{
+    parser::{Parser, ParserError},
+    tokenizer::Token,
+};
 
 use crate::{plugin::CustomPlugin, rewriter::escape_sql_string, statement::CustomStatement};
 
@@ -10,24 +13,38 @@ impl CustomPlugin for SetContextPlugin {
     }
 
     fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
-        let key = parser.parse_identifier()?.value;
-        parser.expect_token(&Token::Eq)?;
-        let value = parser.parse_literal_string()?;
+        let mut attrs = vec![];
+        loop {
+            let key = parser.parse_identifier()?.value;
+            parser.expect_token(&Token::Eq)?;
+            let value = parser.parse_literal_string()?;
+            attrs.push((key, value));
 
-        Ok(CustomStatement::SetContext(crate::statement::SetContextStmt { key, value }))
+            if !parser.consume_token(&Token::Comma) {
+                break;
+            }
+        }
+
+        Ok(CustomStatement::SetContext(
+            crate::statement::SetContextStmt { attrs },
+        ))
     }
 
     fn rewrite(&self, stmt: CustomStatement) -> String {
         match stmt {
             CustomStatement::SetContext(stmt) => {
-                let escaped_key = escape_sql_string(&stmt.key);
-                let escaped_value = escape_sql_string(&stmt.value);
-                format!(
-                    r#"
-                    SELECT sec_set_attr('{escaped_key}', '{escaped_value}');
-                    SELECT sec_refresh_views();
-                    "#
-                )
+                let set_attrs: String = stmt
+                    .attrs
+                    .iter()
+                    .map(|(key, value)| {
+                        let escaped_key = escape_sql_string(key);
+                        let escaped_value = escape_sql_string(value);
+                        format!("SELECT sec_set_attr('{escaped_key}', '{escaped_value}');\n")
+                    })
+                    .collect();
+                // Reads would refresh lazily, but writes through stale views
+                // are refused, so refresh once for all the attrs.
+                format!("{set_attrs}SELECT sec_refresh_views();\n")
             }
             _ => unreachable!(),
         }
@@ -0,0 +1,66 @@
+use serde_json::Value;
+use sqlparser::parser::{Parser, ParserError};
+
+use crate::{
+    plugin::CustomPlugin,
+    rewriter::escape_sql_string,
+    statement::{CustomStatement, SetContextStmt},
+};
+
+pub struct SetContextFromJsonPlugin;
+
+/// Flatten a JSON object of scalar claims into `(key, value)` attrs.
+fn parse_claims(json: &str) -> Result<Vec<(String, String)>, ParserError> {
+    let err = |msg: String| ParserError::ParserError(format!("SET CONTEXT FROM: {msg}"));
+
+    let value: Value = serde_json::from_str(json).map_err(|e| err(e.to_string()))?;
+    let Value::Object(claims) = value else {
+        return Err(err("expected a JSON object".to_string()));
+    };
+
+    claims
+        .into_iter()
+        .map(|(key, value)| match value {
+            Value::String(s) => Ok((key, s)),
+            Value::Number(n) => Ok((key, n.to_string())),
+            Value::Bool(b) => Ok((key, b.to_string())),
+            _ => Err(err(format!(
+                "value for '{key}' must be a string, number or boolean"
+            ))),
+        })
+        .collect()
+}
+
+impl CustomPlugin for SetContextFromJsonPlugin {
+    fn prefix(&self) -> &'static [&'static str] {
+        &["SET", "CONTEXT", "FROM"]
+    }
+
+    fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        let json = parser.parse_literal_string()?;
+        let attrs = parse_claims(&json)?;
+
+        Ok(CustomStatement::SetContextFromJson(SetContextStmt {
+            attrs,
+        }))
+    }
+
+    fn rewrite(&self, stmt: CustomStatement) -> String {
+        match stmt {
+            CustomStatement::SetContextFromJson(stmt) => {
+                let mut sql: String = stmt
+                    .attrs
+                    .iter()
+                    .map(|(key, value)| {
+                        let escaped_key = escape_sql_string(key);
+                        let escaped_value = escape_sql_string(value);
+                        format!("SELECT sec_set_attr('{escaped_key}', '{escaped_value}');\n")
+                    })
+                    .collect();
+                sql.push_str("SELECT sec_refresh_views();\n");
+                sql
+            }
+            _ => unreachable!(),
+        }
+    }
+}
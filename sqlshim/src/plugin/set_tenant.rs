@@ -0,0 +1,41 @@
+use sqlparser::{
+    parser::{Parser, ParserError},
+    tokenizer::Token,
+};
+
+use crate::{
+    plugin::CustomPlugin,
+    rewriter::escape_sql_string,
+    statement::{CustomStatement, SetTenantStmt},
+};
+
+pub struct SetTenantPlugin;
+
+impl CustomPlugin for SetTenantPlugin {
+    fn prefix(&self) -> &'static [&'static str] {
+        &["SET", "TENANT"]
+    }
+
+    fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        parser.expect_token(&Token::Eq)?;
+        let tenant = parser.parse_literal_string()?;
+
+        Ok(CustomStatement::SetTenant(SetTenantStmt { tenant }))
+    }
+
+    fn rewrite(&self, stmt: CustomStatement) -> String {
+        match stmt {
+            CustomStatement::SetTenant(stmt) => {
+                // Attributes are multi-valued; a tenant replaces the previous
+                // one. Refreshed like SET CONTEXT, for writes that follow.
+                let escaped_tenant = escape_sql_string(&stmt.tenant);
+                format!(
+                    "SELECT sec_clear_attr('tenant');\n\
+                     SELECT sec_set_attr('tenant', '{escaped_tenant}');\n\
+                     SELECT sec_refresh_views();\n"
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+}
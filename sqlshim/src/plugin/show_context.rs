@@ -0,0 +1,24 @@
+use sqlparser::parser::{Parser, ParserError};
+
+use crate::{plugin::CustomPlugin, statement::CustomStatement};
+
+pub struct ShowContextPlugin;
+
+impl CustomPlugin for ShowContextPlugin {
+    fn prefix(&self) -> &'static [&'static str] {
+        &["SHOW", "CONTEXT"]
+    }
+
+    fn parse(&self, _parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        Ok(CustomStatement::ShowContext)
+    }
+
+    fn rewrite(&self, stmt: CustomStatement) -> String {
+        match stmt {
+            CustomStatement::ShowContext => {
+                "SELECT key, value, level FROM sec_context;".to_string()
+            }
+            _ => unreachable!(),
+        }
+    }
+}
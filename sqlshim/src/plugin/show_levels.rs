@@ -0,0 +1,42 @@
+use sqlparser::parser::{Parser, ParserError};
+
+use crate::{
+    parser::ParserExt,
+    plugin::CustomPlugin,
+    rewriter::escape_sql_string,
+    statement::{CustomStatement, ShowLevelsStmt},
+};
+
+pub struct ShowLevelsPlugin;
+
+impl CustomPlugin for ShowLevelsPlugin {
+    fn prefix(&self) -> &'static [&'static str] {
+        &["SHOW", "LEVELS"]
+    }
+
+    fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        let attribute = if parser.is_statement_end() {
+            None
+        } else {
+            Some(parser.parse_identifier()?.value)
+        };
+
+        Ok(CustomStatement::ShowLevels(ShowLevelsStmt { attribute }))
+    }
+
+    fn rewrite(&self, stmt: CustomStatement) -> String {
+        match stmt {
+            CustomStatement::ShowLevels(stmt) => {
+                let filter = stmt
+                    .attribute
+                    .map(|attr| format!(" WHERE attr_name = '{}'", escape_sql_string(&attr)))
+                    .unwrap_or_default();
+                format!(
+                    "SELECT attr_name, level_name, level_value FROM sec_levels{filter} \
+                     ORDER BY attr_name, level_value;"
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+}
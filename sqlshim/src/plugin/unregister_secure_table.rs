@@ -0,0 +1,49 @@
+use sqlparser::parser::{Parser, ParserError};
+
+use crate::{
+    plugin::CustomPlugin,
+    rewriter::{escape_sql_ident, escape_sql_string},
+    statement::{CustomStatement, UnregisterSecureTableStmt},
+};
+
+pub struct UnregisterSecureTablePlugin;
+
+impl CustomPlugin for UnregisterSecureTablePlugin {
+    fn prefix(&self) -> &'static [&'static str] {
+        &["UNREGISTER", "SECURE", "TABLE"]
+    }
+
+    fn parse(&self, parser: &mut Parser<'_>) -> Result<CustomStatement, ParserError> {
+        let logical_name = parser.parse_identifier()?.value;
+
+        Ok(CustomStatement::UnregisterSecureTable(
+            UnregisterSecureTableStmt { logical_name },
+        ))
+    }
+
+    fn rewrite(&self, stmt: CustomStatement) -> String {
+        match stmt {
+            CustomStatement::UnregisterSecureTable(stmt) => {
+                let logical = &stmt.logical_name;
+                let escaped_logical = escape_sql_string(logical);
+                let view = escape_sql_ident(logical);
+                let [ins, upd, del] = ["ins", "upd", "del"]
+                    .map(|op| escape_sql_ident(&format!("{logical}_sec_{op}")));
+
+                // The view and its INSTEAD OF triggers are TEMP objects
+                // created by sec_refresh_views.
+                format!(
+                    r#"
+                    DELETE FROM sec_columns WHERE logical_table = '{escaped_logical}';
+                    DELETE FROM sec_tables WHERE logical_name = '{escaped_logical}';
+                    DROP TRIGGER IF EXISTS temp.{ins};
+                    DROP TRIGGER IF EXISTS temp.{upd};
+                    DROP TRIGGER IF EXISTS temp.{del};
+                    DROP VIEW IF EXISTS temp.{view};
+                    "#
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -1,3 +1,18 @@
+use crate::statement::LabelRef;
+
 pub(crate) fn escape_sql_string(s: &str) -> String {
     s.replace('\'', "''")
 }
+
+pub(crate) fn escape_sql_ident(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+/// SQL evaluating to a label's id: defined on the fly from an expression,
+/// or looked up by name, failing when no such label was defined.
+pub(crate) fn label_id_sql(label: &LabelRef) -> String {
+    match label {
+        LabelRef::Expr(expr) => format!("sec_define_label('{}')", escape_sql_string(expr)),
+        LabelRef::Named(name) => format!("sec_label_id('{}')", escape_sql_string(name)),
+    }
+}
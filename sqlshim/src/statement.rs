@@ -4,15 +4,18 @@ pub enum CustomStatement {
     // =========================================
     // sqlsec: Row-Level & Column-Level Security
     // =========================================
-    /// CREATE POLICY name ON table [FOR operation] USING (expr)
+    /// CREATE POLICY [IF NOT EXISTS] name ON table [FOR operation] USING (expr)
     CreatePolicy(CreatePolicyStmt),
 
     /// DROP POLICY name ON table
     DropPolicy(DropPolicyStmt),
 
-    /// SET CONTEXT key = 'value'
+    /// SET CONTEXT key = 'value' [, key = 'value' ...]
     SetContext(SetContextStmt),
 
+    /// SET CONTEXT FROM '{"key": "value", ...}'
+    SetContextFromJson(SetContextStmt),
+
     /// CLEAR CONTEXT
     ClearContext,
 
@@ -22,25 +25,50 @@ pub enum CustomStatement {
     /// POP CONTEXT
     PopContext,
 
-    /// REFRESH SECURITY VIEWS
+    /// SHOW CONTEXT
+    ShowContext,
+
+    /// SHOW LEVELS [attr]
+    ShowLevels(ShowLevelsStmt),
+
+    /// LIST SECURE TABLES
+    ListSecureTables,
+
+    /// REFRESH SECURE VIEWS (or the deprecated REFRESH SECURITY VIEWS)
     RefreshSecureViews,
 
     /// CREATE SECURE VIEW name AS SELECT ... (with automatic policy injection)
     CreateSecureView(CreateSecureViewStmt),
 
+    /// DROP SECURE VIEW name
+    DropSecureView(DropSecureViewStmt),
+
     /// REGISTER SECURE TABLE logical ON physical WITH ROW LABEL column
-    ///     [TABLE LABEL label_expr] [INSERT LABEL label_expr]
+    ///     [TABLE LABEL label] [INSERT LABEL label]
     RegisterSecureTable(RegisterSecureTableStmt),
 
-    /// DEFINE LABEL 'expr'
+    /// UNREGISTER SECURE TABLE logical
+    UnregisterSecureTable(UnregisterSecureTableStmt),
+
+    /// DEFINE LABEL 'expr' [AS name]
     DefineLabel(DefineLabelStmt),
 
     /// DEFINE LEVEL attr 'name' = value
     DefineLevelStmt(DefineLevelStmt),
 
-    /// SET COLUMN SECURITY table.column READ 'label_expr' [UPDATE 'label_expr']
+    /// SET COLUMN SECURITY table.column READ {label | NONE} [UPDATE {label | NONE}]
     SetColumnSecurity(SetColumnSecurityStmt),
 
+    /// SET TENANT = 'id'
+    SetTenant(SetTenantStmt),
+
+    /// CREATE TENANT TABLE name (column_def, ...)
+    CreateTenantTable(CreateTenantTableStmt),
+
+    /// IMPORT TENANT 'id' FROM 'path' INTO table [, ...]
+    ///     [ON CONFLICT {SKIP | REPLACE | ERROR}]
+    ImportTenant(ImportTenantStmt),
+
     // ===============
     // Auditing (STUB)
     // ===============
@@ -51,13 +79,24 @@ pub enum CustomStatement {
     /// EXPLAIN POLICY ON table FOR USER = 'name'
     /// Expected: Show which rows/columns would be visible
     ExplainPolicy(ExplainPolicyStmt),
+
+    // =====================
+    // Change data capture
+    // =====================
+    /// CREATE CHANGEFEED name ON table [(column, ...)] [WHERE expr]
+    CreateChangefeed(CreateChangefeedStmt),
+
+    /// DROP CHANGEFEED name [KEEP OUTBOX]
+    DropChangefeed(DropChangefeedStmt),
 }
 
 #[derive(Debug, Clone)]
 pub struct CreatePolicyStmt {
+    /// Keep an existing policy of the same name instead of failing.
+    pub if_not_exists: bool,
     pub name: String,
     pub table: String,
-    pub operation: Option<PolicyOperation>,
+    pub operations: Vec<PolicyOperation>,
     pub using_expr: String,
 }
 
@@ -70,6 +109,18 @@ pub enum PolicyOperation {
     All,
 }
 
+impl PolicyOperation {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            PolicyOperation::Select => "SELECT",
+            PolicyOperation::Insert => "INSERT",
+            PolicyOperation::Update => "UPDATE",
+            PolicyOperation::Delete => "DELETE",
+            PolicyOperation::All => "ALL",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DropPolicyStmt {
     pub name: String,
@@ -78,8 +129,7 @@ pub struct DropPolicyStmt {
 
 #[derive(Debug, Clone)]
 pub struct SetContextStmt {
-    pub key: String,
-    pub value: String,
+    pub attrs: Vec<(String, String)>,
 }
 
 #[derive(Debug, Clone)]
@@ -88,18 +138,31 @@ pub struct CreateSecureViewStmt {
     pub query: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct DropSecureViewStmt {
+    pub name: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct RegisterSecureTableStmt {
     pub logical_name: String,
     pub physical_name: String,
-    pub row_label_column: String,
-    pub table_label: Option<String>,
-    pub insert_label: Option<String>,
+    pub row_label_columns: Vec<String>,
+    pub table_label: Option<LabelRef>,
+    pub insert_label: Option<LabelRef>,
+    pub allow_implicit_label: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct UnregisterSecureTableStmt {
+    pub logical_name: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct DefineLabelStmt {
     pub expr: String,
+    /// `AS name`: bind the label to a name later statements can use.
+    pub name: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -109,12 +172,84 @@ pub struct DefineLevelStmt {
     pub value: i64,
 }
 
+#[derive(Debug, Clone)]
+pub struct ShowLevelsStmt {
+    /// Only list this attribute's levels.
+    pub attribute: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct SetColumnSecurityStmt {
     pub table: String,
     pub column: String,
-    pub read_label: Option<String>,
-    pub update_label: Option<String>,
+    pub read_label: Option<LabelChange>,
+    pub update_label: Option<LabelChange>,
+}
+
+/// New value for a column label; `None` on the statement leaves it as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelChange {
+    /// `'label_expr'` or a label name
+    Set(LabelRef),
+    /// `NONE`: make the column unrestricted again.
+    Clear,
+}
+
+/// A label given inline or by the name from `DEFINE LABEL ... AS name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelRef {
+    /// `'label_expr'`
+    Expr(String),
+    /// A bare name
+    Named(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct SetTenantStmt {
+    pub tenant: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateTenantTableStmt {
+    pub name: String,
+    /// Column definitions, less any `PRIMARY KEY` or `UNIQUE` constraint.
+    pub definitions: Vec<String>,
+    /// Table constraints, with every `PRIMARY KEY` and `UNIQUE` scoped by
+    /// `tenant_id` so that tenants may reuse each other's keys.
+    pub constraints: Vec<String>,
+    pub columns: Vec<String>,
+    /// Primary key columns, before scoping by tenant.
+    pub key_columns: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportTenantStmt {
+    pub tenant: String,
+    /// Database file holding the rows, in tenant tables of the same names.
+    pub path: String,
+    /// Tenant tables to import.
+    pub tables: Vec<ImportTable>,
+    pub on_conflict: ImportConflict,
+}
+
+/// A tenant table to import, by logical name, and the columns to copy
+/// besides `tenant_id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportTable {
+    pub name: String,
+    pub columns: Vec<String>,
+}
+
+/// What IMPORT TENANT does with a row whose key the tenant already uses. A
+/// key another tenant uses fails the import whatever the strategy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportConflict {
+    /// Keep the existing row.
+    Skip,
+    /// Overwrite the existing row.
+    Replace,
+    /// Fail the import (the default).
+    Error,
 }
 
 #[derive(Debug, Clone)]
@@ -128,3 +263,18 @@ pub struct ExplainPolicyStmt {
     pub table: String,
     pub user: String,
 }
+
+#[derive(Debug, Clone)]
+pub struct CreateChangefeedStmt {
+    pub name: String,
+    pub table: String,
+    /// Columns captured into the event payload; empty records no payload.
+    pub columns: Vec<String>,
+    pub filter: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DropChangefeedStmt {
+    pub name: String,
+    pub keep_outbox: bool,
+}